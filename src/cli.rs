@@ -1,6 +1,10 @@
-use crate::parser::common::{DotplotMode, DotplotoutFormat, FileFormat};
-use clap::ArgAction;
-use clap::{command, Parser, Subcommand};
+use crate::log::LogLevel;
+use crate::parser::common::{
+    CigarStyle, DotplotMode, DotplotoutFormat, FileFormat, IdentityMode, PrimaryBy,
+};
+use clap::builder::RangedU64ValueParser;
+use clap::{ArgAction, ArgMatches, ColorChoice, CommandFactory, FromArgMatches};
+use clap::{Parser, Subcommand};
 use clap_complete::Shell;
 
 #[derive(Parser)]
@@ -23,12 +27,33 @@ pub struct Cli {
     /// Bool, if rewrite output file [default: false]
     #[arg(long, short, global = true, default_value = "false", help_heading = Some("GLOBAL"))]
     pub rewrite: bool,
-    /// Threads, default 1
-    #[arg(long, short, global = true, default_value = "1", help_heading = Some("GLOBAL"))]
+    /// Threads, default 1, all parallel sections run in a pool of this size
+    #[arg(long, short, global = true, default_value = "1", help_heading = Some("GLOBAL"),
+          value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
     pub threads: usize,
+    /// Bool, do not decompress gzip/bgzip/bzip2/xz input automatically [default: false]
+    #[arg(long, global = true, default_value = "false", help_heading = Some("GLOBAL"))]
+    pub no_decompress: bool,
+    /// Bool, end MAF output with a UCSC `##eof maf` line [default: false]
+    #[arg(long, global = true, default_value = "false", help_heading = Some("GLOBAL"))]
+    pub maf_eof: bool,
+    /// Bool, do not show progress of reading input on stderr [default: false]
+    #[arg(long, global = true, default_value = "false", help_heading = Some("GLOBAL"))]
+    pub quiet: bool,
+    /// Max records read of each input, reading stops after them, e.g. to preview output
+    /// of a large file
+    #[arg(long, global = true, help_heading = Some("GLOBAL"),
+          value_parser = RangedU64ValueParser::<u64>::new().range(1..))]
+    pub max_records: Option<u64>,
     /// Logging level [-v: Info, -vv: Debug, -vvv: Trace, defalut: Warn].
     #[arg(short, long, global = true, action = ArgAction::Count, help_heading = Some("GLOBAL"))]
     pub verbose: u8,
+    /// Logging level, preferred to `-v` if set, logs are always written to stderr
+    #[arg(long, global = true, value_enum, help_heading = Some("GLOBAL"))]
+    pub log_level: Option<LogLevel>,
+    /// Color of error messages on stderr, `auto` for a terminal only, `-vv` for error details
+    #[arg(long, global = true, value_enum, default_value_t = ColorChoice::Auto, help_heading = Some("GLOBAL"))]
+    pub color: ColorChoice,
     /// Subcommands
     #[command(subcommand)]
     pub command: Commands,
@@ -42,6 +67,48 @@ pub enum Commands {
         /// Input MAF File, None for STDIN
         #[arg(required = false)]
         input: Option<String>,
+        /// Sort output by target name and start, default: false
+        #[arg(required = false, long, default_value = "false")]
+        sort: bool,
+        /// Only output reference vs this species, matched by `species` or `species.contig`
+        #[arg(required = false, long)]
+        species: Option<String>,
+        /// S-line of this name or species as reference instead of the first one,
+        /// paired with all others, it should be in every block
+        #[arg(required = false, long)]
+        target_name: Option<String>,
+        /// Style of `cg:Z:` CIGAR, `extended` with `=`/`X` or `match` with `M` only
+        #[arg(required = false, long, value_enum, default_value_t = CigarStyle::Extended)]
+        cigar_style: CigarStyle,
+        /// Compare stats re-derived from each PAF CIGAR with the MAF block, fail on any difference
+        #[arg(required = false, long, default_value = "false")]
+        verify: bool,
+        /// Rebuild gapped sequences from each PAF CIGAR, fail if any differs from the MAF block
+        #[arg(required = false, long, default_value = "false")]
+        check: bool,
+        /// Add divergence tags: gap-compressed `de:f:` and per-base `dv:f:`, default: false
+        #[arg(required = false, long, default_value = "false")]
+        with_divergence: bool,
+        /// Tag the best alignment of each query `tp:A:P` and others `tp:A:S`,
+        /// all records are buffered and grouped by query, default: false
+        #[arg(required = false, long, default_value = "false")]
+        tag_primary: bool,
+        /// Key to select the best alignment for `--tag-primary`
+        #[arg(required = false, long, value_enum, default_value_t = PrimaryBy::Identity)]
+        primary_by: PrimaryBy,
+    },
+    /// Convert MAF format to MUMmer delta format, reference vs each other s-line
+    #[command(visible_alias = "m2d", name = "maf2delta")]
+    Maf2Delta {
+        /// Input MAF File, None for STDIN
+        #[arg(required = false)]
+        input: Option<String>,
+        /// Target FASTA path in the first line of delta, it's not read
+        #[arg(required = false, long, default_value = "target.fa")]
+        target: String,
+        /// Query FASTA path in the first line of delta, it's not read
+        #[arg(required = false, long, default_value = "query.fa")]
+        query: String,
     },
     /// Convert MAF format to Chain format
     #[command(visible_alias = "m2c", name = "maf2chain")]
@@ -50,6 +117,25 @@ pub enum Commands {
         #[arg(required = false)]
         input: Option<String>,
     },
+    /// Convert MAF blocks to gapped multiple-sequence FASTA
+    #[command(visible_alias = "m2f", name = "maf2fasta")]
+    Maf2Fasta {
+        /// Input MAF File, None for STDIN
+        #[arg(required = false)]
+        input: Option<String>,
+        /// Only output the N-th block (0-based)
+        #[arg(required = false, long, short = 'b')]
+        block: Option<usize>,
+        /// Only output s-lines of these names or species, split by ','
+        #[arg(required = false, long, short = 'n', value_delimiter = ',')]
+        names: Option<Vec<String>>,
+        /// Output a consensus row by majority vote of each column, default: false
+        #[arg(required = false, long, default_value = "false")]
+        consensus: bool,
+        /// Remove columns which are all gaps in output s-lines, default: false
+        #[arg(required = false, long, default_value = "false")]
+        remove_empty_columns: bool,
+    },
     /// Convert PAF format to MAF format
     #[command(visible_alias = "p2m", name = "paf2maf")]
     Paf2Maf {
@@ -62,6 +148,15 @@ pub enum Commands {
         /// Input query FASTA File, required
         #[arg(required = true, long, short)]
         query: String,
+        /// Check sequence lengths and end coordinates of records against FASTA, default: false
+        #[arg(required = false, long, default_value = "false")]
+        coordinate_check: bool,
+        /// Fail on records without `cg:Z:` CIGAR tag instead of skipping them, default: false
+        #[arg(required = false, long, default_value = "false")]
+        require_cigar: bool,
+        /// Uppercase bases fetched from FASTA, soft-masked case is kept by default
+        #[arg(required = false, long, default_value = "false")]
+        uppercase: bool,
     },
     /// Convert PAF format to Chain format
     #[command(visible_alias = "p2c", name = "paf2chain")]
@@ -69,6 +164,51 @@ pub enum Commands {
         /// Input PAF File, None for STDIN
         #[arg(required = false)]
         input: Option<String>,
+        /// Fail on records without `cg:Z:` CIGAR tag instead of skipping them, default: false
+        #[arg(required = false, long, default_value = "false")]
+        require_cigar: bool,
+    },
+    /// Convert PAF format to MUMmer delta format
+    #[command(visible_alias = "p2d", name = "paf2delta")]
+    Paf2Delta {
+        /// Input PAF File, None for STDIN
+        #[arg(required = false)]
+        input: Option<String>,
+        /// Target FASTA path in the first line of delta, it's not read
+        #[arg(required = false, long, default_value = "target.fa")]
+        target: String,
+        /// Query FASTA path in the first line of delta, it's not read
+        #[arg(required = false, long, default_value = "query.fa")]
+        query: String,
+        /// Fail on records without `cg:Z:` CIGAR tag instead of skipping them, default: false
+        #[arg(required = false, long, default_value = "false")]
+        require_cigar: bool,
+    },
+    /// Convert PAF format to SAM format, target as reference and query as read
+    #[command(visible_alias = "p2s", name = "paf2sam")]
+    Paf2Sam {
+        /// Input PAF File, None for STDIN
+        #[arg(required = false)]
+        input: Option<String>,
+        /// Fail on records without `cg:Z:` CIGAR tag instead of skipping them, default: false
+        #[arg(required = false, long, default_value = "false")]
+        require_cigar: bool,
+        /// Soft clip unaligned query instead of hard clip, default: false
+        #[arg(required = false, long, default_value = "false")]
+        soft_clip: bool,
+    },
+    /// Convert PAF format to BAM format, target as reference and query as read
+    #[command(visible_alias = "p2b", name = "paf2bam")]
+    Paf2Bam {
+        /// Input PAF File, None for STDIN
+        #[arg(required = false)]
+        input: Option<String>,
+        /// Fail on records without `cg:Z:` CIGAR tag instead of skipping them, default: false
+        #[arg(required = false, long, default_value = "false")]
+        require_cigar: bool,
+        /// Soft clip unaligned query instead of hard clip, default: false
+        #[arg(required = false, long, default_value = "false")]
+        soft_clip: bool,
     },
     /// Convert Chain format to MAF format
     #[command(visible_alias = "c2m", name = "chain2maf")]
@@ -77,11 +217,14 @@ pub enum Commands {
         #[arg(required = false)]
         input: Option<String>,
         /// Input target FASTA File, required
-        #[arg(required = true, long, short)]
+        #[arg(required = true, long, short = 'g')]
         target: String,
         /// Input query FASTA File, required
         #[arg(required = true, long, short)]
         query: String,
+        /// Uppercase bases fetched from FASTA, soft-masked case is kept by default
+        #[arg(required = false, long, default_value = "false")]
+        uppercase: bool,
     },
     /// Convert Chain format to PAF format
     #[command(visible_alias = "c2p", name = "chain2paf")]
@@ -89,6 +232,47 @@ pub enum Commands {
         /// Input Chain File, None for STDIN
         #[arg(required = false)]
         input: Option<String>,
+        /// Sort output by target name and start, default: false
+        #[arg(required = false, long, default_value = "false")]
+        sort: bool,
+    },
+    /// Convert alignment file to another format, input format is detected automatically
+    #[command(visible_alias = "cv", name = "convert")]
+    Convert {
+        /// Input Alignment File, None for STDIN
+        #[arg(required = false)]
+        input: Option<String>,
+        /// Input File format, by extension or content if not set
+        #[arg(required = false, long, short = 'f')]
+        input_format: Option<FileFormat>,
+        /// Output format
+        #[arg(required = true, long)]
+        to: FileFormat,
+        /// Input target FASTA File, required when convert to MAF except from delta,
+        /// and written in the header of delta
+        #[arg(required = false, long, short = 'g')]
+        target: Option<String>,
+        /// Input query FASTA File, same as `--target`
+        #[arg(required = false, long, short)]
+        query: Option<String>,
+        /// Sort output by target name and start when convert to PAF, default: false
+        #[arg(required = false, long, default_value = "false")]
+        sort: bool,
+        /// Write each record into its own numbered file in this dir, instead of `--outfile`
+        #[arg(required = false, long)]
+        per_block_dir: Option<String>,
+        /// Drop self alignments before converting, see `filter --drop-self-alignments`,
+        /// default: false
+        #[arg(required = false, long, default_value = "false")]
+        drop_self_alignments: bool,
+        /// Self alignments less than this bp off the diagonal are dropped as well
+        #[arg(
+            required = false,
+            long,
+            default_value = "0",
+            requires = "drop_self_alignments"
+        )]
+        keep_diagonal_within: u64,
     },
     /// Build index for MAF file
     #[command(visible_alias = "mi", name = "maf-index")]
@@ -96,6 +280,13 @@ pub enum Commands {
         /// Input MAF File
         #[arg(required = true)]
         input: String,
+        /// Check each s-line's non-gap count equal to its align size, default: false
+        #[arg(required = false, long, default_value = "false")]
+        validate: bool,
+        /// Write the index in a compact binary format instead of JSON, it's much smaller and
+        /// faster to load by `maf-ext`, both formats are detected when loading, default: false
+        #[arg(required = false, long, default_value = "false")]
+        binary_index: bool,
     },
     /// Extract specific region from MAF file with index
     #[command(visible_alias = "me", name = "maf-ext")]
@@ -103,13 +294,59 @@ pub enum Commands {
         /// Input MAF File, None for STDIN
         #[arg(required = false)]
         input: Option<String>,
-        /// Input regions
-        #[arg(required = false, long, short, value_delimiter = ',')]
+        /// Input regions, `-r` is taken by the global rewrite flag
+        #[arg(required = false, long, short = 'g', value_delimiter = ',')]
+        regions: Option<Vec<String>>,
+        /// Input regions file
+        #[arg(required = false, long, short)]
+        file: Option<String>,
+    },
+    /// Build index of target byte ranges for PAF file, records should be grouped by target
+    #[command(visible_alias = "pi", name = "paf-index")]
+    PafIndex {
+        /// Input PAF File, plain text
+        #[arg(required = true)]
+        input: String,
+    },
+    /// Extract PAF records overlapping target regions with index
+    #[command(visible_alias = "pe", name = "paf-ext")]
+    PafExtract {
+        /// Input PAF File, indexed by `paf-index`
+        #[arg(required = true)]
+        input: String,
+        /// Input regions of target, `-r` is taken by the global rewrite flag
+        #[arg(required = false, long, short = 'g', value_delimiter = ',')]
         regions: Option<Vec<String>>,
         /// Input regions file
         #[arg(required = false, long, short)]
         file: Option<String>,
     },
+    /// Slice MAF blocks into a region of a reference sequence, without index
+    #[command(visible_alias = "ms", name = "maf-slice")]
+    MafSlice {
+        /// Input MAF File, None for STDIN
+        #[arg(required = false)]
+        input: Option<String>,
+        /// Region to slice, format: `chr:start-end`
+        #[arg(required = true, long)]
+        region: String,
+        /// Name of s-line as reference, default: the name in region
+        #[arg(required = false, long, short)]
+        seq: Option<String>,
+    },
+    /// Mask aligned bases of MAF in BED regions of reference (first s-line) with N
+    #[command(visible_alias = "mm", name = "maf-mask")]
+    MafMask {
+        /// Input MAF File, None for STDIN
+        #[arg(required = false)]
+        input: Option<String>,
+        /// BED file of regions to mask, on forward strand of reference
+        #[arg(required = true, long, short)]
+        bed: String,
+        /// Cut masked columns out and split blocks, instead of N, default: false
+        #[arg(required = false, long, default_value = "false")]
+        as_gap: bool,
+    },
     /// Chunk MAF file by length
     #[command(visible_alias = "ch", name = "chunk")]
     Chunk {
@@ -120,6 +357,87 @@ pub enum Commands {
         #[arg(required = true, long, short = 'l')]
         length: u64,
     },
+    /// Split MAF file into chunk files by blocks count or size, never cut a block
+    #[command(visible_alias = "mc", name = "maf-chunk")]
+    MafChunk {
+        /// Input MAF File, None for STDIN
+        #[arg(required = false)]
+        input: Option<String>,
+        /// Max blocks in each chunk
+        #[arg(required = false, long, short = 'b', required_unless_present = "size")]
+        blocks: Option<u64>,
+        /// Approximate max size of each chunk, e.g. `500K`, `100M`, `1G`
+        #[arg(required = false, long, short = 's')]
+        size: Option<String>,
+        /// Prefix of chunk files, output as `{prefix}.000.maf`
+        #[arg(required = false, long, short = 'p', default_value = "chunk")]
+        prefix: String,
+    },
+    /// Normalize MAF file by merging adjacent blocks
+    #[command(visible_alias = "mn", name = "maf-normalize")]
+    MafNormalize {
+        /// Input MAF File, None for STDIN
+        #[arg(required = false)]
+        input: Option<String>,
+        /// Remove leading and trailing columns of all gaps in blocks, default: false
+        #[arg(required = false, long, default_value = "false")]
+        trim_gaps: bool,
+        /// Reverse complement blocks whose target (first s-line) is on '-' strand, default: false
+        #[arg(required = false, long, default_value = "false")]
+        flip_to_target_plus: bool,
+    },
+    /// Join two MAFs sharing the reference (first s-line) into multiple alignment blocks
+    #[command(visible_alias = "mj", name = "maf-join")]
+    MafJoin {
+        /// First MAF File
+        #[arg(required = true)]
+        first: String,
+        /// Second MAF File, aligned to the same reference
+        #[arg(required = true)]
+        second: String,
+    },
+    /// Keep or drop MAF blocks by names of their s-lines
+    #[command(visible_alias = "mfn", name = "maf-filter-by-name")]
+    MafFilterByName {
+        /// Input MAF File, None for STDIN
+        #[arg(required = false)]
+        input: Option<String>,
+        /// Keep blocks containing these names or species, split by ','
+        #[arg(required = false, long, short = 'i', value_delimiter = ',')]
+        include: Option<Vec<String>>,
+        /// Drop blocks containing any of these names or species, split by ','
+        #[arg(required = false, long, short = 'e', value_delimiter = ',')]
+        exclude: Option<Vec<String>>,
+        /// File of names to include, one per line
+        #[arg(required = false, long)]
+        include_file: Option<String>,
+        /// File of names to exclude, one per line
+        #[arg(required = false, long)]
+        exclude_file: Option<String>,
+        /// Block should contain all included names, instead of any, default: false
+        #[arg(required = false, long, default_value = "false")]
+        require_all: bool,
+    },
+    /// Sort MAF blocks by target name and start, external sort for files over memory
+    #[command(visible_alias = "mst", name = "maf-sort")]
+    MafSort {
+        /// Input MAF Files, sorted into one MAF with header of the first, None for STDIN
+        #[arg(required = false)]
+        input: Vec<String>,
+        /// Approximate memory of blocks before spilling a sorted run, e.g. `500M`, `1G`
+        #[arg(required = false, long, default_value = "1G")]
+        buffer_size: String,
+    },
+    /// Trim ragged edges of MAF blocks until enough species aligned
+    #[command(visible_alias = "mte", name = "maf-trim-edges")]
+    MafTrimEdges {
+        /// Input MAF File, None for STDIN
+        #[arg(required = false)]
+        input: Option<String>,
+        /// Min species with non-gap bases in edge columns
+        #[arg(required = false, long, short = 'k', default_value = "2")]
+        min_species: usize,
+    },
     /// Call Variants from MAF file
     #[command(visible_alias = "c", name = "call")]
     Call {
@@ -157,12 +475,153 @@ pub enum Commands {
         /// Input Alignment File, None for STDIN
         #[arg(required = false)]
         input: Option<String>,
-        /// Input File format,
-        #[arg(required = false, long, short, default_value = "maf")]
-        format: FileFormat,
+        /// Input File format, by extension or content if not set
+        #[arg(required = false, long, short, visible_alias = "input-format")]
+        format: Option<FileFormat>,
         /// Show each block's statistics, default: false
         #[arg(required = false, long, short, default_value = "false")]
         each: bool,
+        /// Show statistics for each target sequence, default: false
+        #[arg(
+            required = false,
+            long,
+            default_value = "false",
+            conflicts_with = "each"
+        )]
+        by_target: bool,
+        /// Show summary of whole file and coverage of each target, default: false
+        #[arg(
+            required = false,
+            long,
+            default_value = "false",
+            conflicts_with_all = ["each", "by_target"]
+        )]
+        summary: bool,
+        /// Show blocks, aligned bases, identity and span of each target and query, default: false
+        #[arg(
+            required = false,
+            long,
+            default_value = "false",
+            conflicts_with_all = ["each", "by_target", "summary"]
+        )]
+        per_sequence: bool,
+        /// Output summary as machine-readable TSV, default: false
+        #[arg(required = false, long, default_value = "false", requires = "summary")]
+        tsv: bool,
+        /// Show GC content of each species over aligned bases, only for MAF, default: false
+        #[arg(
+            required = false,
+            long,
+            default_value = "false",
+            conflicts_with_all = ["each", "by_target", "summary", "per_sequence"]
+        )]
+        gc: bool,
+        /// Denominator of identity: target (M+X+D), blast (M+X+I+D) or gap-excluded (M+X)
+        #[arg(required = false, long, value_enum, default_value_t = IdentityMode::Target)]
+        identity_mode: IdentityMode,
+        /// Count records, aligned bases and coverage only, without CIGAR of records,
+        /// identity and gap metrics are omitted, for `--summary` and `--by-target`, default: false
+        #[arg(required = false, long, default_value = "false", conflicts_with_all = ["each", "gc"])]
+        fast: bool,
+    },
+    /// Statistics for Chain file: scores, blocks per chain, aligned bases and
+    /// a TSV histogram of block sizes
+    #[command(visible_alias = "cs", name = "chain-stat")]
+    ChainStat {
+        /// Input Chain File, None for STDIN
+        #[arg(required = false)]
+        input: Option<String>,
+        /// Width of bins of block size histogram
+        #[arg(required = false, long, default_value = "100",
+              value_parser = RangedU64ValueParser::<u64>::new().range(1..))]
+        bin_width: u64,
+    },
+    /// Output alignments as CIGAR BED: tname tstart tend qname qstart qend strand cigar
+    #[command(visible_alias = "cb", name = "cigar-bed")]
+    CigarBed {
+        /// Input Alignment File, None for STDIN
+        #[arg(required = false)]
+        input: Option<String>,
+        /// Input File format, by extension or content if not set
+        #[arg(required = false, long, short, visible_alias = "input-format")]
+        format: Option<FileFormat>,
+        /// Write a `#` header line of columns, default: false
+        #[arg(required = false, long, default_value = "false")]
+        header: bool,
+    },
+    /// Split PAF records at long insertions or deletions into separate records
+    #[command(visible_alias = "psp", name = "paf-split")]
+    PafSplit {
+        /// Input Alignment File, None for STDIN
+        #[arg(required = false)]
+        input: Option<String>,
+        /// Input File format, by extension or content if not set
+        #[arg(required = false, long, short, visible_alias = "input-format")]
+        format: Option<FileFormat>,
+        /// Split at a single insertion or deletion longer than this
+        #[arg(required = true, long)]
+        max_gap: u64,
+    },
+    /// Output BED of aligned target intervals for Alignment file
+    #[command(visible_alias = "cov", name = "coverage")]
+    Coverage {
+        /// Input Alignment File, None for STDIN
+        #[arg(required = false)]
+        input: Option<String>,
+        /// Input File format, by extension or content if not set
+        #[arg(required = false, long, short, visible_alias = "input-format")]
+        format: Option<FileFormat>,
+        /// Merge overlapping intervals, default: false
+        #[arg(required = false, long, short, default_value = "false")]
+        merge: bool,
+        /// Add a depth column of alignments covering each interval, default: false
+        #[arg(required = false, long, short, default_value = "false")]
+        depth: bool,
+    },
+    /// Output bedGraph of MAF blocks covering each reference base
+    #[command(visible_alias = "md", name = "maf-depth")]
+    MafDepth {
+        /// Input MAF File, None for STDIN
+        #[arg(required = false)]
+        input: Option<String>,
+        /// Only output regions covered by at least this many blocks
+        #[arg(required = false, long, default_value = "1")]
+        min_depth: usize,
+    },
+    /// Remove duplicate records of Alignment file, the first one is kept
+    #[command(visible_alias = "dd", name = "dedup")]
+    Dedup {
+        /// Input Alignment File, None for STDIN
+        #[arg(required = false)]
+        input: Option<String>,
+        /// Input File format, by extension or content if not set
+        #[arg(required = false, long, short, visible_alias = "input-format")]
+        format: Option<FileFormat>,
+        /// Duplicates by coordinates only, ignore sequences or CIGAR, default: false
+        #[arg(required = false, long, default_value = "false")]
+        by_coords: bool,
+        /// Keep the record of highest identity among coordinate-duplicates, default: false
+        #[arg(required = false, long, default_value = "false")]
+        keep_best: bool,
+    },
+    /// Keep the best alignment of each query, the others are secondary
+    #[command(visible_alias = "pri", name = "primary")]
+    Primary {
+        /// Input Alignment File, None for STDIN
+        #[arg(required = false)]
+        input: Option<String>,
+        /// Input File format, by extension or content if not set
+        #[arg(required = false, long, short, visible_alias = "input-format")]
+        format: Option<FileFormat>,
+        /// Key to select the best alignment
+        #[arg(required = false, long, value_enum, default_value_t = PrimaryBy::Identity)]
+        by: PrimaryBy,
+        /// Keep secondary PAF records with `tp:A:S`, primary ones get `tp:A:P`, default: false
+        #[arg(required = false, long, default_value = "false")]
+        mark_secondary: bool,
+        /// Buffer all records for input not sorted by query name, default: false
+        #[arg(required = false, long, default_value = "false")]
+        unsorted: bool,
     },
     /// Plot dotplot for Alignment file
     #[command(visible_alias = "dp", name = "dotplot")]
@@ -173,7 +632,7 @@ pub enum Commands {
         /// Input File format,
         #[arg(required = false, long, short, default_value = "maf")]
         format: FileFormat,
-        /// Output format
+        /// Output format, tsv for line segments of other visualization tools
         #[arg(required = false, long, default_value = "html")]
         out_format: DotplotoutFormat,
         /// Plot mode, BaseLevel or Overview
@@ -185,6 +644,15 @@ pub enum Commands {
         /// Skip segment with length less than cutoff in BaseLevel mode, default: 0
         #[arg(required = false, long, short = 'l')]
         length: Option<usize>,
+        /// Skip records with target aligned length less than cutoff, default: 0
+        #[arg(required = false, long, default_value = "0")]
+        min_length: u64,
+        /// Only plot records of this target name
+        #[arg(required = false, long)]
+        target: Option<String>,
+        /// Only plot records of this query name
+        #[arg(required = false, long)]
+        query: Option<String>,
     },
     /// Filter records for Alignment file
     #[command(visible_alias = "fl", name = "filter")]
@@ -192,36 +660,109 @@ pub enum Commands {
         /// Input Alignment File, None for STDIN
         #[arg(required = false)]
         input: Option<String>,
-        /// Input File format,
-        #[arg(required = false, long, short, default_value = "maf")]
-        format: FileFormat,
+        /// Input File format, by extension or content if not set
+        #[arg(required = false, long, short, visible_alias = "input-format")]
+        format: Option<FileFormat>,
         /// Min block size
-        #[arg(required = false, long, short = 'b', default_value = "0")]
+        #[arg(
+            required = false,
+            long,
+            short = 'b',
+            default_value = "0",
+            visible_alias = "min-block-length"
+        )]
         min_block_size: u64,
         /// Min query size, usually for contigs
         #[arg(required = false, long, short = 'q', default_value = "0")]
         min_query_size: u64,
+        /// Min identity in [0, 1], matched / aligned size of target by default, same as `stat`
+        #[arg(required = false, long, short = 'i')]
+        min_identity: Option<f64>,
+        /// Denominator of identity: target (M+X+D), blast (M+X+I+D) or gap-excluded (M+X)
+        #[arg(required = false, long, value_enum, default_value_t = IdentityMode::Target)]
+        identity_mode: IdentityMode,
+        /// Min MAPQ, only for PAF, ignored for MAF and Chain
+        #[arg(required = false, long)]
+        min_mapq: Option<u64>,
+        /// Max length of a single insertion or deletion
+        #[arg(required = false, long)]
+        max_gap: Option<u64>,
         /// Min align size for query-target pair, only for all-to-all alignment paf
         #[arg(required = false, long, short = 'a', default_value = None)]
         min_align_size: Option<u64>,
+        /// Drop self alignments of a genome to itself, a region aligned to itself on the same
+        /// strand, inversions and off-diagonal paralogs are kept, default: false
+        #[arg(required = false, long, default_value = "false")]
+        drop_self_alignments: bool,
+        /// Self alignments less than this bp off the diagonal are dropped as well
+        #[arg(
+            required = false,
+            long,
+            default_value = "0",
+            requires = "drop_self_alignments"
+        )]
+        keep_diagonal_within: u64,
     },
     /// Rename MAF records with prefix
     #[command(visible_alias = "rn", name = "rename")]
     Rename {
-        /// Input MAF File, None for STDIN
+        /// Input Alignment File, None for STDIN, only MAF for `--prefixs`
         #[arg(required = false)]
         input: Option<String>,
         /// prefix for rename, split by ',' ordered by input
-        #[arg(required = true, long, short, value_delimiter = ',')]
-        prefixs: Vec<String>,
+        #[arg(
+            required = false,
+            long,
+            short,
+            value_delimiter = ',',
+            required_unless_present_any = ["map", "pansn_sep"],
+            conflicts_with_all = ["map", "pansn_sep"]
+        )]
+        prefixs: Option<Vec<String>>,
+        /// Two-column TSV of old and new names, for MAF/PAF/Chain
+        #[arg(required = false, long, short)]
+        map: Option<String>,
+        /// Error on names not in `--map` instead of keeping them, default: false
+        #[arg(required = false, long, default_value = "false", requires = "map")]
+        strict: bool,
+        /// Input File format for `--map` and `--pansn-sep`, by extension or content if not set
+        #[arg(required = false, long, short, visible_alias = "input-format")]
+        format: Option<FileFormat>,
+        /// Separator of PanSN names like `sample#hap#contig`, for MAF/PAF/Chain
+        #[arg(required = false, long, conflicts_with = "map")]
+        pansn_sep: Option<String>,
+        /// Strip the PanSN prefix before the last `--pansn-sep`, default: false
+        #[arg(
+            required = false,
+            long,
+            default_value = "false",
+            requires = "pansn_sep"
+        )]
+        strip_prefix: bool,
+        /// Add a PanSN prefix to target names, after `--strip-prefix`
+        #[arg(required = false, long, requires = "pansn_sep")]
+        target_prefix: Option<String>,
+        /// Add a PanSN prefix to query names, after `--strip-prefix`
+        #[arg(required = false, long, requires = "pansn_sep")]
+        query_prefix: Option<String>,
+        /// Write `new\told` names for reversing by `--map`
+        #[arg(required = false, long, requires = "pansn_sep")]
+        map_out: Option<String>,
     },
-    /// TEST: maf2sam
+    /// Convert MAF format to SAM format, target as reference
     #[command(visible_alias = "m2s", name = "maf2sam")]
     Maf2Sam {
         /// Input MAF File, None for STDIN
         #[arg(required = false)]
         input: Option<String>,
     },
+    /// Convert MAF format to VCF of SNPs and indels of each query relative to the reference
+    #[command(visible_alias = "m2v", name = "maf2vcf")]
+    Maf2Vcf {
+        /// Input MAF File, None for STDIN
+        #[arg(required = false)]
+        input: Option<String>,
+    },
     /// TEST: pafcov
     #[command(visible_alias = "pc", name = "pafcov")]
     PafCov {
@@ -242,13 +783,17 @@ pub enum Commands {
         #[arg(required = false, long, short = 'g')]
         target: Option<String>,
     },
-    // /// TEST: trim overlap for paf
-    // #[command(visible_alias = "tr", name = "trimovp")]
-    // TrimOvp {
-    //     /// Input PAF File, None for STDIN
-    //     #[arg(required = false)]
-    //     input: Option<String>,
-    // },
+    /// Trim overlaps of PAF records on target, records of higher score (`AS:i:` or MAPQ),
+    /// then longer target span, then earlier in input are kept as is
+    #[command(visible_alias = "tr", name = "trim-overlaps")]
+    TrimOverlaps {
+        /// Input PAF File, None for STDIN
+        #[arg(required = false)]
+        input: Option<String>,
+        /// Drop trimmed pieces shorter than this on target
+        #[arg(required = false, long, default_value = "1")]
+        min_length: u64,
+    },
     /// Generate completion script for shell
     #[command(visible_alias = "gc", name = "gen-completion")]
     GenCompletion {
@@ -258,6 +803,22 @@ pub enum Commands {
     },
 }
 
-pub fn make_cli_parse() -> Cli {
-    Cli::parse()
+/// Parse the command line, matches are kept for the input files of error messages
+pub fn make_cli_parse() -> (Cli, ArgMatches) {
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    (cli, matches)
+}
+
+/// Input files of the subcommand, STDIN is not included
+pub fn cli_inputs(matches: &ArgMatches) -> Vec<String> {
+    matches
+        .subcommand()
+        .and_then(|(_, sub)| sub.try_get_raw("input").ok().flatten())
+        .map(|inputs| {
+            inputs
+                .map(|input| input.to_string_lossy().to_string())
+                .collect()
+        })
+        .unwrap_or_default()
 }
@@ -1,15 +1,25 @@
-use crate::errors::WGAError;
+use crate::errors::{ParseChainErrKind, WGAError};
 use crate::parser::chain::{ChainHeader, ChainReader, ChainRecord};
 use crate::parser::cigar::{
-    parse_cigar_to_blocks, parse_cigar_to_chain, parse_cigar_to_insert, parse_maf_seq_to_chain,
+    cigar_to_gapped_seqs, parse_cigar_to_blocks, parse_cigar_to_chain, parse_cigar_to_insert,
+    parse_maf_seq_to_chain, parse_maf_seq_to_cigar,
 };
-use crate::parser::common::{AlignRecord, Strand};
-use crate::parser::maf::{MAFReader, MAFRecord, MAFSLine, MAFWriter};
-use crate::parser::paf::PAFReader;
-use crate::utils::reverse_complement;
+use crate::parser::common::{groups_buffered, AlignRecord, CigarStyle, GroupBy, PrimaryBy, Strand};
+use crate::parser::delta::DeltaReader;
+use crate::parser::fasta::{FastaIndex, SharedFasta};
+use crate::parser::maf::{is_species, MAFReader, MAFRecord, MAFSLine, MAFWriter};
+use crate::parser::paf::{PAFReader, PafRecord};
+use crate::parser::writer::{DeltaWriter, RecordWriter};
+use crate::tools::primary::primary;
+use crate::tools::sort::{PafSorter, SORT_BUFFER_SIZE};
+use crate::utils::TraceExt;
+use log::{debug, error, warn};
+use noodles::bam;
+use noodles::core::Position;
 use noodles::sam::header::record::value::map;
-use noodles::sam::header::record::value::map::header::SortOrder;
-use noodles::sam::record::ReadName;
+use noodles::sam::header::record::value::map::header::{SortOrder, Version};
+use noodles::sam::record::data::field::{tag, Value};
+use noodles::sam::record::{Cigar, Data, Flags, MappingQuality, ReadName, Sequence};
 use noodles::sam::{
     self as sam,
     header::record::value::{
@@ -18,14 +28,52 @@ use noodles::sam::{
     },
 };
 use rayon::prelude::*;
-use rust_htslib::faidx;
+use std::collections::HashSet;
 use std::io::{Read, Write};
 use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-/// Convert a MAF Reader to output a PAF file
+// records count per thread in a batch for parallel converting
+const BATCH_SIZE: usize = 256;
+
+/// Project a MAF Reader into PAF records of reference vs `species`,
+/// blocks lacking that species are skipped
+pub fn project_species<'a, R: Read + Send>(
+    mafreader: &'a mut MAFReader<R>,
+    species: &'a str,
+) -> impl Iterator<Item = Result<PafRecord, WGAError>> + 'a {
+    mafreader
+        .records()
+        .traced()
+        .filter_map(move |record| match record {
+            Ok(mafrecord) => mafrecord
+                .project(species)
+                .map(|pairwise| pairwise.convert2paf()),
+            Err(e) => Some(Err(e)),
+        })
+}
+
+/// Convert a MAF Reader to output a PAF file, sorted by target if `sort`,
+/// one record for reference vs each other s-line of a block, or only reference vs
+/// `species` if set, CIGAR written in `style`. Reference is the first s-line, or the
+/// s-line of `target_name` if set, which should be in every block. If `verify`, stats re-derived from each
+/// PAF CIGAR are compared with the MAF block, and any difference fails the conversion.
+/// If `check`, gapped sequences rebuilt from each PAF CIGAR should be the same as
+/// the MAF block. `de:f:` and `dv:f:` divergence tags are added if `with_divergence`.
+/// If `tag_primary` is set, all records are buffered and grouped by query in order of
+/// first occurrence, the best one of each query by the key is `tp:A:P`, others `tp:A:S`
+#[allow(clippy::too_many_arguments)]
 pub fn maf2paf<R: Read + Send>(
     mafreader: &mut MAFReader<R>,
     writer: &mut dyn Write,
+    sort: bool,
+    species: Option<&str>,
+    target_name: Option<&str>,
+    style: CigarStyle,
+    verify: bool,
+    check: bool,
+    with_divergence: bool,
+    tag_primary: Option<PrimaryBy>,
 ) -> Result<(), WGAError> {
     // init csv writer for deserializing
     let mut wtr = csv::WriterBuilder::new()
@@ -33,83 +81,562 @@ pub fn maf2paf<R: Read + Send>(
         .has_headers(false)
         .from_writer(writer);
 
-    // multi-threading
-    let pafrecords = mafreader
-        .records()
-        .par_bridge()
-        .map(|record| -> Result<_, WGAError> {
-            let mafrecord = record?;
-            mafrecord.convert2paf()
-        })
-        .collect::<Result<Vec<_>, WGAError>>()?;
-    for pafrec in pafrecords {
-        wtr.serialize(pafrec)?;
+    // multi-threading by batches, `par_iter` over a Vec keeps the input order
+    let batch_size = BATCH_SIZE * rayon::current_num_threads();
+    let mut sorter = sort.then(|| PafSorter::new(SORT_BUFFER_SIZE));
+    let mut tagged = tag_primary.map(|_| Vec::new());
+    let mut records = mafreader.records().traced();
+    let failed = AtomicUsize::new(0);
+    let check_failed = AtomicUsize::new(0);
+    let convert = |pairwise: MAFRecord| {
+        let mut pafrec = pairwise.convert2paf()?;
+        if with_divergence {
+            pafrec.add_divergence(&pairwise.get_stat()?);
+        }
+        if verify {
+            if let Some(diff) = verify_paf(&pairwise, &pafrec)? {
+                error!("verify failed: {}", diff);
+                failed.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        if check {
+            if let Some(diff) = check_roundtrip(&pairwise, &pafrec)? {
+                error!("roundtrip check failed: {}", diff);
+                check_failed.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        Ok(pafrec)
+    };
+    loop {
+        let batch = records
+            .by_ref()
+            .take(batch_size)
+            .collect::<Result<Vec<_>, WGAError>>()?;
+        if batch.is_empty() {
+            break;
+        }
+        let pafrecords = batch
+            .par_iter()
+            .flat_map_iter(|mafrecord| {
+                let reordered;
+                let mafrecord = match target_name {
+                    Some(name) => match mafrecord.with_target(name) {
+                        Ok(rec) => {
+                            reordered = rec;
+                            &reordered
+                        }
+                        Err(e) => return vec![Err(e)],
+                    },
+                    None => mafrecord,
+                };
+                match species {
+                    Some(species) => {
+                        let pairwise = mafrecord.project(species);
+                        if pairwise.is_none() {
+                            debug!(
+                                "block {}:{} lacks `{}`, skipped",
+                                mafrecord.target_name(),
+                                mafrecord.target_start(),
+                                species
+                            );
+                        }
+                        pairwise.map(convert).into_iter().collect::<Vec<_>>()
+                    }
+                    None => match mafrecord.check_pairwise() {
+                        Ok(()) => mafrecord.pairwise().map(convert).collect(),
+                        Err(e) => vec![Err(e)],
+                    },
+                }
+            })
+            .collect::<Result<Vec<_>, WGAError>>()?;
+        for mut pafrec in pafrecords {
+            pafrec.set_cigar_style(style);
+            match tagged.as_mut() {
+                Some(tagged) => tagged.push(pafrec),
+                None => emit_paf(&mut sorter, &mut wtr, pafrec)?,
+            }
+        }
+    }
+    if let (Some(by), Some(tagged)) = (tag_primary, tagged) {
+        let groups = groups_buffered(tagged.into_iter().map(Ok), GroupBy::Query)?;
+        primary(groups.into_iter().map(Ok), by, |mut pafrec, is_primary| {
+            pafrec.set_primary(is_primary);
+            emit_paf(&mut sorter, &mut wtr, pafrec)
+        })?;
+    }
+    if let Some(sorter) = sorter {
+        sorter.write_sorted(|rec| Ok(wtr.serialize(rec)?))?;
     }
     wtr.flush()?;
+    match (failed.into_inner(), check_failed.into_inner()) {
+        (0, 0) => Ok(()),
+        (0, failed) => Err(WGAError::CheckFailed(failed)),
+        (failed, _) => Err(WGAError::VerifyFailed(failed)),
+    }
+}
+
+// push a record to the sorter if sorting, otherwise write it directly
+fn emit_paf<W: Write>(
+    sorter: &mut Option<PafSorter>,
+    wtr: &mut csv::Writer<W>,
+    pafrec: PafRecord,
+) -> Result<(), WGAError> {
+    match sorter.as_mut() {
+        Some(sorter) => sorter.push(pafrec)?,
+        None => wtr.serialize(pafrec)?,
+    }
     Ok(())
 }
 
+// gapped sequences rebuilt from the PAF CIGAR and ungapped MAF sequences should be
+// the same as the pairwise MAF block without all-gap columns, return the difference if not
+fn check_roundtrip(mafrec: &MAFRecord, pafrec: &PafRecord) -> Result<Option<String>, WGAError> {
+    let (target, query) = (
+        mafrec.target_seq().as_bytes(),
+        mafrec.query_seq().as_bytes(),
+    );
+    let (expect_target, expect_query): (Vec<u8>, Vec<u8>) = target
+        .iter()
+        .zip(query)
+        .filter(|(t, q)| **t != b'-' || **q != b'-')
+        .unzip();
+    let ungapped = |seq: &[u8]| {
+        seq.iter()
+            .filter(|b| **b != b'-')
+            .copied()
+            .collect::<Vec<_>>()
+    };
+    let describe = || {
+        format!(
+            "block {}:{}-{} vs {}:{}-{}",
+            mafrec.target_name(),
+            mafrec.target_start(),
+            mafrec.target_end(),
+            mafrec.query_name(),
+            mafrec.query_start(),
+            mafrec.query_end(),
+        )
+    };
+    let cigar = pafrec.get_cigar_str()?;
+    let (rebuilt_target, rebuilt_query) =
+        match cigar_to_gapped_seqs(&cigar, &ungapped(target), &ungapped(query)) {
+            Ok(seqs) => seqs,
+            Err(e) => return Ok(Some(format!("{}, {}", describe(), e))),
+        };
+    let column = expect_target
+        .iter()
+        .zip(&expect_query)
+        .zip(rebuilt_target.bytes().zip(rebuilt_query.bytes()))
+        .position(|((t, q), (rt, rq))| (*t, *q) != (rt, rq));
+    match column {
+        None if rebuilt_target.len() == expect_target.len() => Ok(None),
+        column => Ok(Some(format!(
+            "{}, first differs at column {}",
+            describe(),
+            column.unwrap_or(rebuilt_target.len().min(expect_target.len()))
+        ))),
+    }
+}
+
+// stats of a PAF record re-derived from its CIGAR and columns should be the same as
+// the pairwise MAF block, return the difference if not
+fn verify_paf(mafrec: &MAFRecord, pafrec: &PafRecord) -> Result<Option<String>, WGAError> {
+    let maf_stat = mafrec.get_stat()?;
+    let paf_stat = pafrec.get_stat()?;
+    // block length of the PAF line should be the same recomputed from its `cg:Z:`
+    if maf_stat == paf_stat
+        && pafrec.matches == maf_stat.matched as u64
+        && pafrec.block_length == paf_stat.block_length()
+    {
+        return Ok(None);
+    }
+    Ok(Some(format!(
+        "block {}:{}-{} vs {}:{}-{}, MAF {:?}, PAF {:?} with matches {} block length {}",
+        mafrec.target_name(),
+        mafrec.target_start(),
+        mafrec.target_end(),
+        mafrec.query_name(),
+        mafrec.query_start(),
+        mafrec.query_end(),
+        maf_stat,
+        paf_stat,
+        pafrec.matches,
+        pafrec.block_length
+    )))
+}
+
 /// Convert a MAF Reader to output a Chain file
 pub fn maf2chain<R: Read + Send>(
     mafreader: &mut MAFReader<R>,
     writer: &mut Box<dyn Write>,
 ) -> Result<(), WGAError> {
-    // iterate over records and give a self-increasing chain-id
-    for (id, record) in mafreader.records().enumerate() {
-        let record = record?;
+    // multi-threading by batches as maf2paf, chain-ids are self-increasing in input order
+    let batch_size = BATCH_SIZE * rayon::current_num_threads();
+    let mut records = mafreader.records().traced();
+    let mut id = 0;
+    loop {
+        let batch = records
+            .by_ref()
+            .take(batch_size)
+            .collect::<Result<Vec<_>, WGAError>>()?;
+        if batch.is_empty() {
+            break;
+        }
+        let chains = batch
+            .par_iter()
+            .enumerate()
+            .map(|(idx, record)| {
+                // transform record to Chain Header
+                let mut header = ChainHeader::try_from(record)?;
 
-        // transform record to Chain Header
-        let mut header = ChainHeader::try_from(&record)?;
+                // set chain id
+                header.chain_id = id + idx;
 
-        // set chain id
-        header.chain_id = id;
+                // write header without newline
+                let mut chain = format!("{}", header).into_bytes();
 
-        // write header without newline
-        writer.write_all(format!("{}", header).as_bytes())?;
+                // nom the cigar string and write to buffer
+                parse_maf_seq_to_chain(record, &mut chain)?;
 
-        // nom the cigar string and write to file
-        parse_maf_seq_to_chain(&record, writer)?;
+                // additional newline for standard chain format
+                chain.extend_from_slice(b"\n\n");
+                Ok(chain)
+            })
+            .collect::<Result<Vec<_>, WGAError>>()?;
+        for chain in chains {
+            writer.write_all(&chain)?;
+        }
+        id += batch.len();
+    }
+    writer.flush()?;
+    Ok(())
+}
 
-        // additional newline for standard chain format
-        writer.write_all(b"\n\n")?;
+/// Convert MAF blocks to gapped multiple-sequence FASTA, one record per s-line
+// >A block=0 0-5 +
+// AC-GTT
+// >B block=0 5-10 +
+// ACTGT-
+// >consensus block=0
+// ACNGTT
+pub fn maf2fasta<R: Read + Send>(
+    mafreader: &mut MAFReader<R>,
+    writer: &mut dyn Write,
+    block: Option<usize>,
+    names: Option<&[String]>,
+    consensus: bool,
+    remove_empty_columns: bool,
+) -> Result<(), WGAError> {
+    let mut found = false;
+    for (idx, record) in mafreader.records().traced().enumerate() {
+        if block.is_some_and(|block| idx < block) {
+            continue;
+        }
+        let record = record?;
+        // select s-lines by exact name or species
+        let slines = record
+            .slines
+            .iter()
+            .filter(|sline| {
+                names.is_none_or(|names| names.iter().any(|name| is_species(&sline.name, name)))
+            })
+            .collect::<Vec<_>>();
+        // keep columns with any base of selected s-lines
+        let keep = match remove_empty_columns {
+            true => Some(non_gap_columns(
+                slines.iter().map(|sline| sline.seq.as_bytes()),
+            )),
+            false => None,
+        };
+        let col_seq = |seq: &str| -> Vec<u8> {
+            match &keep {
+                Some(keep) => seq
+                    .bytes()
+                    .zip(keep.iter())
+                    .filter(|(_, keep)| **keep)
+                    .map(|(c, _)| c)
+                    .collect(),
+                None => seq.as_bytes().to_vec(),
+            }
+        };
+
+        let seqs = slines
+            .iter()
+            .map(|sline| col_seq(&sline.seq))
+            .collect::<Vec<_>>();
+        for (sline, seq) in slines.iter().zip(seqs.iter()) {
+            writeln!(
+                writer,
+                ">{} block={} {}-{} {}",
+                sline.name,
+                idx,
+                sline.start,
+                sline.start + sline.align_size,
+                sline.strand
+            )?;
+            writer.write_all(seq)?;
+            writeln!(writer)?;
+        }
+        if consensus && !seqs.is_empty() {
+            writeln!(writer, ">consensus block={}", idx)?;
+            writer.write_all(&consensus_seq(&seqs))?;
+            writeln!(writer)?;
+        }
+
+        if block.is_some() {
+            found = true;
+            break;
+        }
+    }
+    if let (Some(block), false) = (block, found) {
+        return Err(WGAError::Other(anyhow::anyhow!(
+            "block `{}` not found in MAF",
+            block
+        )));
     }
     writer.flush()?;
     Ok(())
 }
 
+// mask of columns which are not all gaps
+fn non_gap_columns<'a, I: Iterator<Item = &'a [u8]>>(seqs: I) -> Vec<bool> {
+    let mut keep: Vec<bool> = Vec::new();
+    for seq in seqs {
+        if keep.len() < seq.len() {
+            keep.resize(seq.len(), false);
+        }
+        for (keep, c) in keep.iter_mut().zip(seq) {
+            *keep |= *c != b'-';
+        }
+    }
+    keep
+}
+
+// majority vote of bases in each column ignoring case and gaps,
+// all-gap column is `-` and a tie is `N`
+fn consensus_seq(seqs: &[Vec<u8>]) -> Vec<u8> {
+    let len = seqs.iter().map(|seq| seq.len()).max().unwrap_or(0);
+    (0..len)
+        .map(|col| {
+            let mut counts = [0usize; 256];
+            for seq in seqs {
+                match seq.get(col) {
+                    Some(b'-') | None => {}
+                    Some(c) => counts[c.to_ascii_uppercase() as usize] += 1,
+                }
+            }
+            let max = *counts.iter().max().unwrap_or(&0);
+            if max == 0 {
+                return b'-';
+            }
+            let mut bases = (0..=255u8).filter(|c| counts[*c as usize] == max);
+            match (bases.next(), bases.next()) {
+                (Some(c), None) => c,
+                _ => b'N',
+            }
+        })
+        .collect()
+}
+
+/// Convert a MAF Reader to output a SAM file, target as reference and query as read,
+/// records are loaded first to collect `@SQ` lines of all targets
 pub fn maf2sam<R: Read + Send>(
-    _mafreader: &mut MAFReader<R>,
+    mafreader: &mut MAFReader<R>,
     writer: &mut Box<dyn Write>,
 ) -> Result<(), WGAError> {
-    let mut sam_writer = sam::Writer::new(writer);
-    let mut header = Map::<map::Header>::default();
-    *header.sort_order_mut() = Some(SortOrder::Unsorted);
-    let header = sam::Header::builder()
-        .set_header(header)
-        .add_reference_sequence(
-            "sq0".parse()?,
-            Map::<ReferenceSequence>::new(NonZeroUsize::try_from(8)?),
-        )
-        .add_reference_sequence(
-            "sq1".parse()?,
-            Map::<ReferenceSequence>::new(NonZeroUsize::try_from(13)?),
-        )
-        .add_reference_sequence(
-            "sq2".parse()?,
-            Map::<ReferenceSequence>::new(NonZeroUsize::try_from(21)?),
-        )
-        .add_program("noodles-sam", Map::<Program>::default())
-        .add_comment("an example SAM written by noodles-sam")
-        .build();
+    let records = mafreader
+        .records()
+        .traced()
+        .collect::<Result<Vec<_>, WGAError>>()?;
+    let header = build_sam_header(&records)?;
 
+    let mut sam_writer = sam::Writer::new(writer);
     sam_writer.write_header(&header)?;
-    let mut record = sam::alignment::Record::default();
-    let read_name: ReadName = "sq2".parse()?;
-    *record.read_name_mut() = Some(read_name);
-    sam_writer.write_record(&header, &record)?;
+    // CIGAR of blocks in parallel by batches, written in input order
+    let batch_size = BATCH_SIZE * rayon::current_num_threads();
+    for batch in records.chunks(batch_size) {
+        let sam_records = batch
+            .par_iter()
+            .map(|rec| maf_rec_to_sam(rec, &header))
+            .collect::<Result<Vec<_>, WGAError>>()?;
+        for record in &sam_records {
+            sam_writer.write_record(&header, record)?;
+        }
+    }
+    Ok(())
+}
+
+// @HD VN:1.6 SO:unsorted, @SQ of targets in order of appearance and @PG of wgatools
+fn build_sam_header<T: AlignRecord>(records: &[T]) -> Result<sam::Header, WGAError> {
+    let mut hd = Map::<map::Header>::new(Version::new(1, 6));
+    *hd.sort_order_mut() = Some(SortOrder::Unsorted);
+    let mut builder = sam::Header::builder().set_header(hd);
+    let mut seen = HashSet::new();
+    for rec in records {
+        if seen.insert(rec.target_name()) {
+            builder = builder.add_reference_sequence(
+                rec.target_name().parse()?,
+                Map::<ReferenceSequence>::new(
+                    NonZeroUsize::try_from(rec.target_length() as usize)?,
+                ),
+            );
+        }
+    }
+    let program = Map::<Program>::builder()
+        .set_name("wgatools")
+        .set_version(env!("CARGO_PKG_VERSION"))
+        .build()?;
+    Ok(builder.add_program("wgatools", program).build())
+}
+
+// add clips of query outside alignment to CIGAR on the target forward strand
+fn clip_cigar<T: AlignRecord>(rec: &T, cigar: &str, clip_op: char) -> String {
+    let (head_clip, tail_clip) = match rec.query_strand() {
+        Strand::Positive => (rec.query_start(), rec.query_length() - rec.query_end()),
+        Strand::Negative => (rec.query_length() - rec.query_end(), rec.query_start()),
+    };
+    let mut clipped = String::new();
+    if head_clip > 0 {
+        clipped.push_str(&format!("{}{}", head_clip, clip_op));
+    }
+    clipped.push_str(cigar);
+    if tail_clip > 0 {
+        clipped.push_str(&format!("{}{}", tail_clip, clip_op));
+    }
+    clipped
+}
+
+// SAM record builder of fields shared by MAF and PAF: QNAME FLAG RNAME POS CIGAR
+fn sam_builder<T: AlignRecord>(
+    rec: &T,
+    header: &sam::Header,
+    cigar: &str,
+    mut flags: Flags,
+) -> Result<sam::alignment::record::Builder, WGAError> {
+    if rec.query_strand() == Strand::Negative {
+        flags.insert(Flags::REVERSE_COMPLEMENTED);
+    }
+    let reference_sequence_id = header
+        .reference_sequences()
+        .get_index_of(rec.target_name())
+        .ok_or(WGAError::SeqNotFound(rec.target_name().to_string()))?;
+    let alignment_start =
+        Position::try_from(rec.target_start() as usize + 1).map_err(anyhow::Error::from)?;
+
+    Ok(sam::alignment::Record::builder()
+        .set_read_name(rec.query_name().parse::<ReadName>()?)
+        .set_flags(flags)
+        .set_reference_sequence_id(reference_sequence_id)
+        .set_alignment_start(alignment_start)
+        .set_cigar(cigar.parse::<Cigar>()?))
+}
+
+// one SAM record of a MAF record, query outside alignment is hard clipped
+// t: ACG-TT
+// q: AC-ATT  =>  CIGAR: {clip}H2=1D1I2={clip}H, SEQ: ACATT
+fn maf_rec_to_sam(
+    rec: &MAFRecord,
+    header: &sam::Header,
+) -> Result<sam::alignment::Record, WGAError> {
+    rec.check_pairwise()?;
+    let cigar = clip_cigar(rec, &parse_maf_seq_to_cigar(rec, false).cigar_string, 'H');
+    let mut builder = sam_builder(rec, header, &cigar, Flags::empty())?;
+    // query seq is already on the target forward strand
+    let seq = rec
+        .query_seq()
+        .chars()
+        .filter(|c| *c != '-')
+        .map(|c| c.to_ascii_uppercase())
+        .collect::<String>();
+    if !seq.is_empty() {
+        builder = builder.set_sequence(seq.parse::<Sequence>()?);
+    }
+    Ok(builder.build())
+}
+
+/// Convert a PAF Reader to output a SAM file, or BAM if `bam`, target as reference and
+/// query as read, records are loaded first to collect `@SQ` lines of all targets
+pub fn paf2sam<R: Read + Send>(
+    pafreader: &mut PAFReader<R>,
+    writer: &mut Box<dyn Write>,
+    require_cigar: bool,
+    soft_clip: bool,
+    bam: bool,
+) -> Result<(), WGAError> {
+    let mut skipped = 0;
+    let records = pafreader
+        .records()
+        .traced()
+        .filter(|rec| keep_cigar_rec(rec, require_cigar, &mut skipped))
+        .collect::<Result<Vec<_>, WGAError>>()?;
+    warn_skipped_cigar(skipped);
+    let header = build_sam_header(&records)?;
+
+    let clip_op = if soft_clip { 'S' } else { 'H' };
+    if bam {
+        let mut bam_writer = bam::Writer::new(writer);
+        bam_writer.write_header(&header)?;
+        for rec in &records {
+            let record = paf_rec_to_sam(rec, &header, clip_op)?;
+            bam_writer.write_record(&header, &record)?;
+        }
+        bam_writer.try_finish()?;
+    } else {
+        let mut sam_writer = sam::Writer::new(writer);
+        sam_writer.write_header(&header)?;
+        for rec in &records {
+            let record = paf_rec_to_sam(rec, &header, clip_op)?;
+            sam_writer.write_record(&header, &record)?;
+        }
+    }
     Ok(())
 }
 
+// one SAM record of a PAF record, SEQ is `*` as PAF has no sequence,
+// MAPQ 255 is missing and secondary alignments (tp:A:S) are flagged
+fn paf_rec_to_sam(
+    rec: &PafRecord,
+    header: &sam::Header,
+    clip_op: char,
+) -> Result<sam::alignment::Record, WGAError> {
+    let cigar = rec.get_cigar_str()?;
+    let cigar = cigar.trim_start_matches("cg:Z:");
+    let flags = match rec.tags.iter().any(|t| t == "tp:A:S") {
+        true => Flags::SECONDARY,
+        false => Flags::empty(),
+    };
+    let nm = i32::try_from(edit_distance(rec, cigar)).map_err(anyhow::Error::from)?;
+    let data = [(tag::EDIT_DISTANCE, Value::from(nm))]
+        .into_iter()
+        .collect::<Data>();
+
+    let mut builder =
+        sam_builder(rec, header, &clip_cigar(rec, cigar, clip_op), flags)?.set_data(data);
+    if let Some(mapq) = u8::try_from(rec.mapq).ok().and_then(MappingQuality::new) {
+        builder = builder.set_mapping_quality(mapq);
+    }
+    Ok(builder.build())
+}
+
+// edit distance by CIGAR: X + I + D, mismatches of `M` are unknown,
+// then it falls back to PAF: block_length - matches
+fn edit_distance(rec: &PafRecord, cigar: &str) -> u64 {
+    let mut nm = 0;
+    let mut len = 0;
+    for c in cigar.chars() {
+        match c {
+            '0'..='9' => len = len * 10 + c as u64 - '0' as u64,
+            'M' => return rec.block_length.saturating_sub(rec.matches),
+            'X' | 'I' | 'D' => {
+                nm += len;
+                len = 0;
+            }
+            _ => len = 0,
+        }
+    }
+    nm
+}
+
 /// Convert a PAF Reader to output a Blocks file
 pub fn paf2blocks<R: Read + Send>(
     pafreader: &mut PAFReader<R>,
@@ -121,7 +648,7 @@ pub fn paf2blocks<R: Read + Send>(
         .from_writer(writer);
 
     // iterate over records
-    for record in pafreader.records() {
+    for record in pafreader.records().traced() {
         let record = record?;
         // nom the cigar string and write to file
         parse_cigar_to_blocks(&record, &mut wtr)?;
@@ -134,9 +661,15 @@ pub fn paf2blocks<R: Read + Send>(
 pub fn paf2chain<R: Read + Send>(
     pafreader: &mut PAFReader<R>,
     writer: &mut Box<dyn Write>,
+    require_cigar: bool,
 ) -> Result<(), WGAError> {
+    let mut skipped = 0;
     // iterate over records and give a self-increasing chain-id
-    for (id, record) in pafreader.records().enumerate() {
+    let records = pafreader
+        .records()
+        .traced()
+        .filter(|rec| keep_cigar_rec(rec, require_cigar, &mut skipped));
+    for (id, record) in records.enumerate() {
         let record = record?;
 
         // transform record to Chain Header
@@ -154,111 +687,275 @@ pub fn paf2chain<R: Read + Send>(
         // additional newline for standard chain format
         writer.write_all(b"\n\n")?;
     }
+    warn_skipped_cigar(skipped);
     writer.flush()?;
     Ok(())
 }
 
-/// Convert a PAF Reader to output a MAF file
+/// Convert a PAF Reader to output a MUMmer delta file, `target_path` and `query_path`
+/// are written in the first line as FASTA paths of nucmer
+pub fn paf2delta<R: Read + Send>(
+    pafreader: &mut PAFReader<R>,
+    writer: &mut dyn Write,
+    target_path: &str,
+    query_path: &str,
+    require_cigar: bool,
+) -> Result<(), WGAError> {
+    let mut skipped = 0;
+    let mut deltawtr = DeltaWriter::new(writer, target_path, query_path);
+    let records = pafreader
+        .records()
+        .traced()
+        .filter(|rec| keep_cigar_rec(rec, require_cigar, &mut skipped));
+    for record in records {
+        deltawtr.write_record(&record?)?;
+    }
+    deltawtr.flush()?;
+    warn_skipped_cigar(skipped);
+    Ok(())
+}
+
+/// Convert a MAF Reader to output a MUMmer delta file, one alignment for reference vs
+/// each other s-line of a block as `maf2paf`
+pub fn maf2delta<R: Read + Send>(
+    mafreader: &mut MAFReader<R>,
+    writer: &mut dyn Write,
+    target_path: &str,
+    query_path: &str,
+) -> Result<(), WGAError> {
+    let mut deltawtr = DeltaWriter::new(writer, target_path, query_path);
+    for record in mafreader.records().traced() {
+        let record = record?;
+        record.check_pairwise()?;
+        for pairwise in record.pairwise() {
+            deltawtr.write_record(&pairwise)?;
+        }
+    }
+    deltawtr.flush()?;
+    Ok(())
+}
+
+// records without CIGAR are skipped and counted unless `require_cigar`,
+// then they go through and fail with the naming error of `get_cigar_str`
+fn keep_cigar_rec(
+    rec: &Result<PafRecord, WGAError>,
+    require_cigar: bool,
+    skipped: &mut usize,
+) -> bool {
+    match rec {
+        Ok(rec) if !require_cigar && !rec.has_cigar() => {
+            *skipped += 1;
+            false
+        }
+        _ => true,
+    }
+}
+
+fn warn_skipped_cigar(skipped: usize) {
+    if skipped > 0 {
+        warn!(
+            "skipped {} records without `cg:Z:` CIGAR tag, use `--require-cigar` to fail on them",
+            skipped
+        );
+    }
+}
+
+/// Convert a PAF Reader to output a MAF file, case of FASTA (e.g. soft-masked repeats)
+/// is kept unless `uppercase`
 pub fn paf2maf<R: Read + Send>(
     pafreader: &mut PAFReader<R>,
     writer: &mut dyn Write,
     t_fa_path: &str,
     q_fa_path: &str,
+    coordinate_check: bool,
+    require_cigar: bool,
+    uppercase: bool,
 ) -> Result<(), WGAError> {
-    // get the target and query fasta reader
-    let t_reader = faidx::Reader::from_path(t_fa_path)?;
-    let q_reader = faidx::Reader::from_path(q_fa_path)?;
+    let mut skipped = 0;
+    let records = pafreader
+        .records()
+        .traced()
+        .filter(|rec| keep_cigar_rec(rec, require_cigar, &mut skipped));
+    let source = MafSource {
+        format: "paf",
+        t_fa_path,
+        q_fa_path,
+    };
+    paf_records2maf(records, writer, &source, coordinate_check, uppercase)?;
+    warn_skipped_cigar(skipped);
+    Ok(())
+}
+
+/// Convert a delta Reader to output a MAF file as `paf2maf`
+pub fn delta2maf<R: Read + Send>(
+    deltareader: &mut DeltaReader<R>,
+    writer: &mut dyn Write,
+    t_fa_path: &str,
+    q_fa_path: &str,
+    uppercase: bool,
+) -> Result<(), WGAError> {
+    let records = deltareader.records().traced().map(|rec| rec?.convert2paf());
+    let source = MafSource {
+        format: "delta",
+        t_fa_path,
+        q_fa_path,
+    };
+    paf_records2maf(records, writer, &source, false, uppercase)
+}
+
+// input format and FASTA paths in the header of MAF converted from PAF-like records
+struct MafSource<'a> {
+    format: &'a str,
+    t_fa_path: &'a str,
+    q_fa_path: &'a str,
+}
+
+// PAF records with CIGAR into pairwise MAF blocks by bases of FASTA,
+// multi-threading by batches as maf2paf, FASTA is shared by threads with a cache
+fn paf_records2maf<I>(
+    mut records: I,
+    writer: &mut dyn Write,
+    source: &MafSource,
+    coordinate_check: bool,
+    uppercase: bool,
+) -> Result<(), WGAError>
+where
+    I: Iterator<Item = Result<PafRecord, WGAError>>,
+{
+    // get the target and query fasta index
+    let t_reader = SharedFasta::from_path(source.t_fa_path)?;
+    let q_reader = SharedFasta::from_path(source.q_fa_path)?;
 
     // init a MAFWriter
     let mut mafwtr = MAFWriter::new(writer);
 
     // write header
     let header = format!(
-        "#maf version=1.6 convert_from=paf t_seq_path={} q_seq_path={}",
-        t_fa_path, q_fa_path
+        "#maf version=1.6 convert_from={} t_seq_path={} q_seq_path={}",
+        source.format, source.t_fa_path, source.q_fa_path
     );
-    mafwtr.write_header(header)?;
+    mafwtr.write_header(&[header])?;
 
-    for pafrec in pafreader.records() {
-        let pafrec = pafrec?;
-        // get mapq as score
-        let score = pafrec.mapq;
-        // get target info
-        let t_name = &pafrec.target_name;
-        let t_start = pafrec.target_start;
-        let t_end = pafrec.target_end - 1;
-        let t_strand = pafrec.target_strand();
-        let t_alilen = pafrec.target_end - pafrec.target_start;
-        let t_size = pafrec.target_length;
-        // get query info
-        let q_name = &pafrec.query_name;
-        let q_strand = pafrec.query_strand();
-        let q_size = pafrec.query_length;
-        let q_alilen = pafrec.query_end - pafrec.query_start;
-        // NOTE: if negative strand, we should convert the start position
-        let q_start = match q_strand {
-            Strand::Positive => pafrec.query_start,
-            Strand::Negative => q_size - pafrec.query_end,
-        };
-
-        // get seqs from indexed fasta files
-        let mut whole_t_seq =
-            t_reader.fetch_seq_string(t_name, t_start as usize, t_end as usize)?;
-        let mut whole_q_seq = q_reader.fetch_seq_string(
-            q_name,
-            pafrec.query_start as usize,
-            (pafrec.query_end - 1) as usize,
-        )?;
-
-        // reverse complement the query sequence if it is on the negative strand
-        match q_strand {
-            Strand::Positive => {}
-            Strand::Negative => {
-                whole_q_seq = reverse_complement(&whole_q_seq)?;
-            }
+    let batch_size = BATCH_SIZE * rayon::current_num_threads();
+    loop {
+        let batch = records
+            .by_ref()
+            .take(batch_size)
+            .collect::<Result<Vec<_>, WGAError>>()?;
+        if batch.is_empty() {
+            break;
+        }
+        let mafrecs = batch
+            .par_iter()
+            .map(|pafrec| {
+                if coordinate_check {
+                    t_reader.check_coordinate(
+                        &pafrec.target_name,
+                        pafrec.target_length,
+                        pafrec.target_end,
+                    )?;
+                    q_reader.check_coordinate(
+                        &pafrec.query_name,
+                        pafrec.query_length,
+                        pafrec.query_end,
+                    )?;
+                }
+                paf_rec2maf(pafrec, &t_reader, &q_reader, uppercase)
+            })
+            .collect::<Result<Vec<_>, WGAError>>()?;
+        for mafrec in mafrecs {
+            mafwtr.write_record(&mafrec)?;
         }
-        // nom the cigar string and insert the `-` to sequence
-        parse_cigar_to_insert(&pafrec, &mut whole_t_seq, &mut whole_q_seq)?;
-        // get s-lines
-        let t_sline = MAFSLine {
-            mode: 's',
-            name: t_name.to_string(),
-            start: t_start,
-            align_size: t_alilen,
-            strand: t_strand,
-            size: t_size,
-            seq: whole_t_seq,
-        };
-        let q_sline = MAFSLine {
-            mode: 's',
-            name: q_name.to_string(),
-            start: q_start,
-            align_size: q_alilen,
-            strand: q_strand,
-            size: q_size,
-            seq: whole_q_seq,
-        };
-        // get maf record
-        let mafrec = MAFRecord {
-            score,
-            slines: vec![t_sline, q_sline],
-        };
-        // write maf record
-        mafwtr.write_record(&mafrec)?;
     }
+    mafwtr.finish()?;
+    let (t_hits, t_misses) = t_reader.cache_stats();
+    let (q_hits, q_misses) = q_reader.cache_stats();
+    debug!(
+        "FASTA cache hits/misses: target {}/{}, query {}/{}",
+        t_hits, t_misses, q_hits, q_misses
+    );
     Ok(())
 }
 
-/// Convert a Chain Reader to output a MAF file
+// a PAF record into a pairwise MAF block
+fn paf_rec2maf(
+    pafrec: &PafRecord,
+    t_reader: &SharedFasta,
+    q_reader: &SharedFasta,
+    uppercase: bool,
+) -> Result<MAFRecord, WGAError> {
+    // get mapq as score
+    let score = pafrec.mapq;
+    // get target info
+    let t_name = &pafrec.target_name;
+    let t_start = pafrec.target_start;
+    let t_strand = pafrec.target_strand();
+    let t_alilen = pafrec.target_end - pafrec.target_start;
+    let t_size = pafrec.target_length;
+    // get query info
+    let q_name = &pafrec.query_name;
+    let q_strand = pafrec.query_strand();
+    let q_size = pafrec.query_length;
+    let q_alilen = pafrec.query_end - pafrec.query_start;
+    // NOTE: if negative strand, we should convert the start position
+    let q_start = match q_strand {
+        Strand::Positive => pafrec.query_start,
+        Strand::Negative => q_size - pafrec.query_end,
+    };
+
+    // get seqs from indexed fasta files,
+    // query sequence is reverse complemented if it is on the negative strand
+    let mut whole_t_seq =
+        seq_string(t_reader.fetch(t_name, pafrec.target_start, pafrec.target_end, t_strand)?);
+    let mut whole_q_seq =
+        seq_string(q_reader.fetch(q_name, pafrec.query_start, pafrec.query_end, q_strand)?);
+    if uppercase {
+        whole_t_seq.make_ascii_uppercase();
+        whole_q_seq.make_ascii_uppercase();
+    }
+    // nom the cigar string and insert the `-` to sequence
+    parse_cigar_to_insert(pafrec, &mut whole_t_seq, &mut whole_q_seq)?;
+    // get s-lines
+    let t_sline = MAFSLine {
+        mode: 's',
+        name: t_name.to_string(),
+        start: t_start,
+        align_size: t_alilen,
+        strand: t_strand,
+        size: t_size,
+        seq: whole_t_seq,
+    };
+    let q_sline = MAFSLine {
+        mode: 's',
+        name: q_name.to_string(),
+        start: q_start,
+        align_size: q_alilen,
+        strand: q_strand,
+        size: q_size,
+        seq: whole_q_seq,
+    };
+    Ok(MAFRecord {
+        score,
+        slines: vec![t_sline, q_sline],
+    })
+}
+
+// bases fetched from FASTA into a String
+fn seq_string(seq: Vec<u8>) -> String {
+    String::from_utf8_lossy(&seq).into_owned()
+}
+
+/// Convert a Chain Reader to output a MAF file, case of FASTA is kept unless `uppercase`
 pub fn chain2maf<R: Read + Send>(
     chainreader: &mut ChainReader<R>,
     writer: &mut dyn Write,
     t_fa_path: &str,
     q_fa_path: &str,
+    uppercase: bool,
 ) -> Result<(), WGAError> {
-    // get the target and query fasta reader
-    let t_reader = faidx::Reader::from_path(t_fa_path)?;
-    let q_reader = faidx::Reader::from_path(q_fa_path)?;
+    // get the target and query fasta index
+    let mut t_reader = FastaIndex::from_path(t_fa_path)?;
+    let mut q_reader = FastaIndex::from_path(q_fa_path)?;
 
     // init a MAFWriter
     let mut mafwtr = MAFWriter::new(writer);
@@ -268,16 +965,15 @@ pub fn chain2maf<R: Read + Send>(
         "#maf version=1.6 convert_from=chain t_seq_path={} q_seq_path={}",
         t_fa_path, q_fa_path
     );
-    mafwtr.write_header(header)?;
+    mafwtr.write_header(&[header])?;
 
-    for chainrec in chainreader.records()? {
+    for chainrec in chainreader.records()?.traced() {
         let chainrec = chainrec?;
-        // 255 as score
-        let score = 255;
+        // chain score is float, MAF score is rounded
+        let score = chainrec.header.score().round().max(0.0) as u64;
         // get target info
         let t_name = chainrec.target_name();
         let t_start = chainrec.target_start();
-        let t_end = chainrec.target_end() - 1;
         let t_strand = chainrec.target_strand();
         let t_alilen = chainrec.target_end() - chainrec.target_start();
         let t_size = chainrec.target_length();
@@ -286,30 +982,20 @@ pub fn chain2maf<R: Read + Send>(
         let q_strand = chainrec.query_strand();
         let q_size = chainrec.query_length();
         let q_alilen = chainrec.query_end() - chainrec.query_start();
-        // NOTE: if negative strand, we should convert the start position
-        let q_start = match q_strand {
-            Strand::Positive => chainrec.query_start(),
-            Strand::Negative => q_size - chainrec.query_end(),
-        };
+        // NOTE: negative strand query coordinates of chain are on the reverse complement,
+        // same as MAF
+        let q_start = chainrec.query_start();
 
-        // get seqs from indexed fasta files
-        let mut whole_t_seq =
-            t_reader.fetch_seq_string(t_name, t_start as usize, t_end as usize)?;
-        let mut whole_q_seq = q_reader.fetch_seq_string(
-            q_name,
-            chainrec.query_start() as usize,
-            (chainrec.query_end() - 1) as usize,
-        )?;
-
-        // reverse complement the query sequence if it is on the negative strand
-        match q_strand {
-            Strand::Positive => {}
-            Strand::Negative => {
-                whole_q_seq = reverse_complement(&whole_q_seq)?;
-            }
+        // get seqs from indexed fasta files,
+        // query sequence is reverse complemented if it is on the negative strand
+        let mut t_seq = seq_string(chainrec.target_seq_from_fasta(&mut t_reader)?);
+        let mut q_seq = seq_string(chainrec.query_seq_from_fasta(&mut q_reader)?);
+        if uppercase {
+            t_seq.make_ascii_uppercase();
+            q_seq.make_ascii_uppercase();
         }
-        // read chain dataline and insert the `-` to sequence
-        parse_chain_to_insert(&chainrec, &mut whole_t_seq, &mut whole_q_seq)?;
+        // walk chain data lines and insert the `-` to sequences
+        let (whole_t_seq, whole_q_seq) = chain_to_gapped_seqs(&chainrec, &t_seq, &q_seq)?;
         // get s-lines
         let t_sline = MAFSLine {
             mode: 's',
@@ -337,44 +1023,66 @@ pub fn chain2maf<R: Read + Send>(
         // write maf record
         mafwtr.write_record(&mafrec)?;
     }
+    mafwtr.finish()?;
     Ok(())
 }
 
-/// Parse the Chain Data Lines to insert the `-` to sequence
-fn parse_chain_to_insert(
+/// Walk the Chain Data Lines to build gapped sequences of equal length,
+/// `target_diff` is the gap in target (`I`) and `query_diff` is the gap in query (`D`)
+// size=3 query_diff=2 target_diff=1, then size=2:
+// t: ACG - TT AC
+// q: ACG G -- AC
+fn chain_to_gapped_seqs(
     rec: &ChainRecord,
-    t_seq: &mut String,
-    q_seq: &mut String,
-) -> Result<(), WGAError> {
-    let mut current_offset = 0;
-    for dataline in &rec.lines {
-        let ins_len = dataline.target_diff;
-        let del_len = dataline.query_diff;
-        current_offset += dataline.size;
-        match ins_len {
-            0 => {}
-            _ => {
-                let ins_str = "-".repeat(ins_len as usize);
-                t_seq.insert_str(current_offset as usize, &ins_str);
-                current_offset += ins_len;
-            }
-        }
-        match del_len {
-            0 => {}
-            _ => {
-                let del_str = "-".repeat(del_len as usize);
-                q_seq.insert_str(current_offset as usize, &del_str);
-                current_offset += del_len;
-            }
+    t_seq: &str,
+    q_seq: &str,
+) -> Result<(String, String), WGAError> {
+    let (t_seq, q_seq) = (t_seq.as_bytes(), q_seq.as_bytes());
+    // data lines should cover the whole ranges in header
+    let t_total: u64 = rec.lines.iter().map(|l| l.size + l.query_diff).sum();
+    let q_total: u64 = rec.lines.iter().map(|l| l.size + l.target_diff).sum();
+    for (kind, total, len) in [
+        ("target", t_total, t_seq.len()),
+        ("query", q_total, q_seq.len()),
+    ] {
+        if total != len as u64 {
+            return Err(WGAError::ParseChain(ParseChainErrKind::BlockSizeNotMatch(
+                rec.header.chain_id.to_string(),
+                kind.to_string(),
+                total,
+                len as u64,
+            )));
         }
     }
-    Ok(())
+
+    let mut t_gapped = Vec::with_capacity(t_seq.len());
+    let mut q_gapped = Vec::with_capacity(q_seq.len());
+    let (mut t_pos, mut q_pos) = (0, 0);
+    for dataline in &rec.lines {
+        let size = dataline.size as usize;
+        let ins_len = dataline.target_diff as usize;
+        let del_len = dataline.query_diff as usize;
+        // matched block, then insertion, then deletion
+        t_gapped.extend_from_slice(&t_seq[t_pos..t_pos + size]);
+        t_gapped.resize(t_gapped.len() + ins_len, b'-');
+        t_gapped.extend_from_slice(&t_seq[t_pos + size..t_pos + size + del_len]);
+        q_gapped.extend_from_slice(&q_seq[q_pos..q_pos + size + ins_len]);
+        q_gapped.resize(q_gapped.len() + del_len, b'-');
+        t_pos += size + del_len;
+        q_pos += size + ins_len;
+    }
+    // only ASCII bases and `-` are pushed
+    Ok((
+        String::from_utf8_lossy(&t_gapped).into_owned(),
+        String::from_utf8_lossy(&q_gapped).into_owned(),
+    ))
 }
 
-/// Convert a Chain Reader to output a PAF file
+/// Convert a Chain Reader to output a PAF file, sorted by target if `sort`
 pub fn chain2paf<R: Read + Send>(
     chainreader: &mut ChainReader<R>,
     writer: &mut dyn Write,
+    sort: bool,
 ) -> Result<(), WGAError> {
     // init csv writer for deserializing
     let mut wtr = csv::WriterBuilder::new()
@@ -385,15 +1093,23 @@ pub fn chain2paf<R: Read + Send>(
     // multi-threading
     let pafrecords = chainreader
         .records()?
+        .traced()
         .par_bridge()
         .map(|record| -> Result<_, WGAError> {
             let chainrecord = record?;
             chainrecord.convert2paf()
         })
         .collect::<Result<Vec<_>, WGAError>>()?;
-    // if we should sort pafrecords?
-    for pafrec in pafrecords {
-        wtr.serialize(pafrec)?;
+    if sort {
+        let mut sorter = PafSorter::new(SORT_BUFFER_SIZE);
+        for pafrec in pafrecords {
+            sorter.push(pafrec)?;
+        }
+        sorter.write_sorted(|rec| Ok(wtr.serialize(rec)?))?;
+    } else {
+        for pafrec in pafrecords {
+            wtr.serialize(pafrec)?;
+        }
     }
     wtr.flush()?;
     Ok(())
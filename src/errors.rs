@@ -1,6 +1,11 @@
 //! The error kinds when process whole genome alignments(wga)
 
+#[cfg(feature = "std")]
+use crate::parser::common::FileFormat;
+#[cfg(feature = "std")]
 use crate::tools::mafextra::GenomeRegion;
+#[cfg(feature = "std")]
+use crossterm::style::Stylize;
 use thiserror::Error;
 
 // define Error types
@@ -17,15 +22,21 @@ pub enum WGAError {
     NotDir(std::path::PathBuf),
     // Parse MAF Error
     #[error("Parse MAF error by: {0}")]
-    ParseMaf(ParseMafErrKind),
+    ParseMaf(#[source] ParseMafErrKind),
+    #[cfg(feature = "std")]
     #[error("CSV deserialize error by: {0}")]
     CsvDeserialize(#[from] csv::Error),
     #[error("Empty stdin, please add `-h` for help")]
     EmptyStdin,
     #[error("File `{0}` already exists, please add `-r` to rewrite it.")]
     FileReWrite(String),
+    #[cfg(feature = "std")]
+    #[error("Temp file of external sort error by: {0}")]
+    TempSerialize(#[from] bincode::Error),
+    #[cfg(feature = "std")]
     #[error("json dese error")]
     SerdeDeserialize(#[from] serde_json::Error),
+    #[cfg(feature = "std")]
     #[error("ThreadPoolBuildError error")]
     ThreadPoolBuildError(#[from] rayon::ThreadPoolBuildError),
     #[error("Empty record")]
@@ -38,51 +49,165 @@ pub enum WGAError {
     StdoutNotAllowed,
     #[error("Parse Genome Region Error By: {0}")]
     ParseGenomeRegion(ParseGenomeRegionErrKind),
+    #[error("Region `{0}:{1}-{2}` is out of block `{0}:{3}-{4}`")]
+    RegionOutOfBlock(String, u64, u64, u64, u64),
+    #[error("MAF index `{0}` is corrupted: {1}, please rebuild it by `maf-index`")]
+    IndexCorrupted(std::path::PathBuf, String),
+    #[error("PAF `{0}` is compressed, only a plain PAF file can be indexed")]
+    CompressedPaf(std::path::PathBuf),
+    #[cfg(feature = "std")]
     #[error("Failed region: {0}")]
     FailedRegion(GenomeRegion),
     #[error("Duplicate name `{0}` in a record not allowed, please check or use `rename`")]
     DuplicateName(String),
+    #[error("Name `{0}` is not in the name map, remove `--strict` to keep it unchanged")]
+    NameNotMapped(String),
+    #[cfg(feature = "std")]
     #[error("Format {0} Parse Error by rust::nom, please check")]
     NomErr(#[from] nom::error::Error<String>),
+    #[error("Parse delta line {0} error: {1}")]
+    ParseDelta(u64, String),
+    #[error("PROMER delta of amino acid alignments is not supported, only NUCMER")]
+    DeltaPromer,
     #[error("Parse Chain Error By: {0}")]
     ParseChain(ParseChainErrKind),
-    #[error("Parse Strand `{0}` Error")]
-    ParseStrand(String),
+    #[error("Invalid strand `{0}`, expect `+`/`-` or `1`/`-1`")]
+    InvalidStrand(String),
     #[error("Parse `{0}` Into Integer Error")]
     #[from = "ParseIntError"]
     ParseIntError(String),
+    #[error("Parse `{0}` Into Size Error, expect e.g. `500K`, `100M`, `1G`")]
+    ParseSizeError(String),
     #[error("Parse `{0}` Into Float Error")]
     #[from = "ParseFloatError"]
     ParseFloatError(String),
     #[error("CIGAR start tag not found")]
     CigarTagNotFound,
+    #[error("Record `{0}` has no `cg:Z:` CIGAR or `cs:Z:` tag, which is required, please check the aligner options")]
+    CigarRequired(String),
+    #[error("cs tag `{0}` invalid")]
+    CsInvalid(String),
     #[error("CIGAR OP `{0}` invalid")]
     CigarOpInvalid(String),
+    #[cfg(feature = "std")]
     #[error("noodles-sam parse error {0}")]
     NoodlesSamParseError(#[from] noodles::sam::record::reference_sequence_name::ParseError),
     #[error("noodlesp-sam try into num parse error {0}")]
     TryIntoNum(#[from] std::num::TryFromIntError),
+    #[cfg(feature = "std")]
     #[error("noodlesp-sam read name parse error {0}")]
     ReadNameParseError(#[from] noodles::sam::record::read_name::ParseError),
+    #[cfg(feature = "std")]
+    #[error("noodles-sam cigar parse error {0}")]
+    SamCigarParseError(#[from] noodles::sam::record::cigar::ParseError),
+    #[cfg(feature = "std")]
+    #[error("noodles-sam sequence parse error {0}")]
+    SamSequenceParseError(#[from] noodles::sam::record::sequence::ParseError),
+    #[cfg(feature = "std")]
+    #[error("noodles-sam header build error {0}")]
+    SamHeaderBuildError(#[from] noodles::sam::header::record::value::map::builder::BuildError),
+    #[cfg(feature = "std")]
     #[error("HTS library error by {0}")]
     HtsLibError(#[from] rust_htslib::errors::Error),
     #[error("Unexcepted Regex Error by: {0}")]
     UnexceptedRegexError(String),
+    #[cfg(feature = "std")]
     #[error("Regex build Error")]
     RegexBuildError(#[from] regex::Error),
+    #[error("FASTA format error: {0}")]
+    FastaFormat(String),
+    #[error("Inconsistent line width in FASTA record `{0}`, can't build index")]
+    FaiInconsistentLine(String),
+    #[error("Sequence `{0}` not found in FASTA index")]
+    SeqNotFound(String),
+    #[error(
+        "Sequence `{0}` length is {1} in alignment, but {2} in FASTA, please check the reference"
+    )]
+    SeqLengthNotMatch(String, u64, u64),
+    #[error("Fetch `{0}:{1}-{2}` out of range, sequence length is {3}")]
+    FetchOutOfRange(String, u64, u64, u64),
     #[error("Invalid Base: `{0}`")]
     InvalidBase(String),
     #[error("Ah-oh! NOT IMPLEMENTED :(")]
     NotImplemented,
+    #[error("Can't detect input format, please check the input file")]
+    UnknownFormat,
+    #[cfg(feature = "std")]
+    #[error("Convert from `{0:?}` to `{1:?}` is not supported")]
+    ConvertNotSupported(FileFormat, FileFormat),
+    #[error("`--{0}` FASTA file is required for this conversion")]
+    FastaRequired(String),
     #[error("S-line count not match")]
     SLineCountNotMatch,
+    #[error("PAF line {0} has {1} columns, at least 12 are required")]
+    PafTooFewColumns(u64, usize),
+    #[error("PAF line {0} column {1} `{2}` is invalid: `{3}`")]
+    PafInvalidColumn(u64, usize, String, String),
+    #[error("Records of `{0}` are not contiguous, input should be sorted by name")]
+    UnsortedGroup(String),
+    #[error("{0} records failed verification, stats of PAF CIGAR differ from MAF blocks")]
+    VerifyFailed(usize),
+    #[error("{0} records failed roundtrip check, sequences rebuilt from PAF CIGAR differ from MAF blocks")]
+    CheckFailed(usize),
+    #[error("CIGAR consumes {0} target and {1} query bases, sequences have {2} and {3}")]
+    CigarSeqMismatch(u64, u64, usize, usize),
+    #[cfg(feature = "std")]
     #[error("Jinja2 Error: {0}, please contact the author")]
     Jinja2Error(#[from] minijinja::Error),
     // Other error
+    #[cfg(feature = "std")]
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
 
+#[cfg(feature = "std")]
+impl WGAError {
+    /// Diagnostic of the error for stderr: the message, messages of its sources
+    /// not shown yet, and input files of the command
+    pub fn report(&self, inputs: &[String], color: bool) -> String {
+        let paint = |label: &str| match color {
+            true => label.red().bold().to_string(),
+            false => label.to_string(),
+        };
+        let mut shown = self.to_string();
+        let mut report = format!("{} {}\n", paint("error:"), shown);
+        let mut source = std::error::Error::source(self);
+        while let Some(err) = source {
+            // most variants already format their source into the message
+            let msg = err.to_string();
+            if !shown.contains(&msg) {
+                report += &format!("  {} {}\n", paint("caused by:"), msg);
+                shown = msg;
+            }
+            source = err.source();
+        }
+        // e.g. a missing file is already in the message
+        if !inputs.is_empty() && !inputs.iter().any(|input| report.contains(input.as_str())) {
+            report += &format!("  {} {}\n", paint("input:"), inputs.join(", "));
+        }
+        report
+    }
+
+    /// If the error is caused by a closed downstream pipe, e.g. `| head`,
+    /// io errors wrapped by csv or other sources are found in the source chain
+    pub fn is_broken_pipe(&self) -> bool {
+        let mut source: Option<&(dyn std::error::Error + 'static)> = Some(self);
+        while let Some(err) = source {
+            // csv doesn't expose its io error as the source
+            let io_err = match err.downcast_ref::<csv::Error>().map(|e| e.kind()) {
+                Some(csv::ErrorKind::Io(e)) => Some(e),
+                _ => err.downcast_ref::<std::io::Error>(),
+            };
+            if io_err.is_some_and(|e| e.kind() == std::io::ErrorKind::BrokenPipe) {
+                return true;
+            }
+            source = err.source();
+        }
+        false
+    }
+}
+
+#[cfg(feature = "std")]
 impl From<nom::Err<nom::error::Error<&str>>> for WGAError {
     fn from(value: nom::Err<nom::error::Error<&str>>) -> Self {
         match value {
@@ -101,12 +226,24 @@ pub enum ParseMafErrKind {
     FiledMissing(String),
     #[error("Surplus Filed > 7")]
     SurplusField,
+    #[error("S-line `{0}` align size {1} not match non-gap count {2}")]
+    AlignSizeNotMatch(String, u64, u64),
+    #[error("Target s-line `{0}` at {1} is on '-' strand, use `maf-normalize --flip-to-target-plus` to fix it")]
+    TargetMinusStrand(String, u64),
+    #[error("Block of `{0}` at {1} has only one s-line, no query to pair with")]
+    SingleSLine(String, u64),
+    #[error("Target `{0}` not found in block of `{1}` at {2}")]
+    TargetNotInBlock(String, String, u64),
+    #[error("Block of `{0}` at {1} has {2} columns, but s-line `{3}` has {4}")]
+    SeqWidthNotMatch(String, u64, usize, String, usize),
 }
 
 #[derive(Error, Debug)]
 pub enum ParseChainErrKind {
     #[error("Chain Line Field `{0}` Missing")]
     FiledMissing(String),
+    #[error("Chain data lines of `{0}` cover {2} bases of {1}, but {3} in header")]
+    BlockSizeNotMatch(String, String, u64, u64),
 }
 
 #[derive(Error, Debug)]
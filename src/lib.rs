@@ -1,7 +1,14 @@
+#[cfg(feature = "std")]
 pub mod cli;
+#[cfg(feature = "std")]
 pub mod converter;
 pub mod errors;
+#[cfg(feature = "std")]
 pub mod log;
+#[cfg(feature = "std")]
 pub mod parser;
+pub mod record;
+#[cfg(feature = "std")]
 pub mod tools;
+#[cfg(feature = "std")]
 pub mod utils;
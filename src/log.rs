@@ -1,3 +1,4 @@
+use clap::ValueEnum;
 use log::LevelFilter;
 use log4rs::{
     append::console::{ConsoleAppender, Target},
@@ -6,13 +7,41 @@ use log4rs::{
     filter::threshold::ThresholdFilter,
 };
 
-pub fn init_logger(verbose: u8) {
-    let log_level = match verbose {
-        0 => LevelFilter::Warn,
-        1 => LevelFilter::Info,
-        2 => LevelFilter::Debug,
-        _ => LevelFilter::Trace,
-    };
+/// Level of `--log-level`, messages of lower levels are dropped
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<LogLevel> for LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Error => LevelFilter::Error,
+            LogLevel::Warn => LevelFilter::Warn,
+            LogLevel::Info => LevelFilter::Info,
+            LogLevel::Debug => LevelFilter::Debug,
+            LogLevel::Trace => LevelFilter::Trace,
+        }
+    }
+}
+
+/// Level of logging, `--log-level` is preferred to the count of `-v`
+pub fn log_level(verbose: u8, log_level: Option<LogLevel>) -> LevelFilter {
+    match (log_level, verbose) {
+        (Some(level), _) => level.into(),
+        (None, 0) => LevelFilter::Warn,
+        (None, 1) => LevelFilter::Info,
+        (None, 2) => LevelFilter::Debug,
+        (None, _) => LevelFilter::Trace,
+    }
+}
+
+/// Init the logger on stderr, so output on stdout is never mixed with logs
+pub fn init_logger(log_level: LevelFilter) {
     // Build a stderr logger.
     let log_stderr = ConsoleAppender::builder()
         .target(Target::Stderr)
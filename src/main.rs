@@ -1,33 +1,65 @@
-use log::{error, info};
-use wgalib::cli::{make_cli_parse, Commands};
+use clap::ColorChoice;
+use log::{info, LevelFilter};
+use wgalib::cli::{cli_inputs, make_cli_parse, Cli, Commands};
 use wgalib::errors::WGAError;
-use wgalib::log::init_logger;
+use wgalib::log::{init_logger, log_level};
+use wgalib::parser::common::set_max_records;
+use wgalib::parser::maf::set_maf_eof;
+use wgalib::tools::dotplot::SegmentFilter;
+use wgalib::tools::filter::FilterOpts;
 use wgalib::tools::tview::tview;
 use wgalib::utils::{
-    wrap_build_index, wrap_chain2maf, wrap_chain2paf, wrap_chunk, wrap_dotplot, wrap_filter,
-    wrap_gencomp, wrap_maf2chain, wrap_maf2paf, wrap_maf2sam, wrap_maf_call, wrap_maf_extract,
-    wrap_paf2chain, wrap_paf2maf, wrap_paf_cov, wrap_paf_pesudo_maf, wrap_rename_maf, wrap_stat,
+    finish_progress, set_no_decompress, set_progress, wrap_build_index, wrap_chain2maf,
+    wrap_chain2paf, wrap_chain_stat, wrap_chunk, wrap_cigar_bed, wrap_convert, wrap_coverage,
+    wrap_dedup, wrap_dotplot, wrap_filter, wrap_gencomp, wrap_maf2chain, wrap_maf2delta,
+    wrap_maf2fasta, wrap_maf2paf, wrap_maf2sam, wrap_maf2vcf, wrap_maf_call, wrap_maf_chunk,
+    wrap_maf_depth, wrap_maf_extract, wrap_maf_filter_by_name, wrap_maf_join, wrap_maf_mask,
+    wrap_maf_slice, wrap_maf_sort, wrap_normalize, wrap_paf2chain, wrap_paf2delta, wrap_paf2maf,
+    wrap_paf2sam, wrap_paf_cov, wrap_paf_extract, wrap_paf_index, wrap_paf_pesudo_maf,
+    wrap_paf_split, wrap_primary, wrap_rename_by_map, wrap_rename_maf, wrap_rename_pansn,
+    wrap_stat, wrap_trim_edges, wrap_trim_overlaps,
 };
 
 fn main() {
-    match main_entry() {
+    let (cli, matches) = make_cli_parse();
+    let inputs = cli_inputs(&matches);
+    let level = log_level(cli.verbose, cli.log_level);
+    let color = match cli.color {
+        ColorChoice::Auto => atty::is(atty::Stream::Stderr),
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+    };
+    let result = main_entry(cli);
+    finish_progress();
+    match result {
         Ok(_) => {}
+        // downstream closed early, e.g. `| head`, not an error
+        Err(e) if e.is_broken_pipe() => {}
         Err(e) => {
-            error!("{}", e);
+            eprint!("{}", e.report(&inputs, color));
+            // variants and fields of the whole error chain
+            if level >= LevelFilter::Debug {
+                eprintln!("{:#?}", e);
+            }
             std::process::exit(1);
         }
     }
 }
 
-fn main_entry() -> Result<(), WGAError> {
-    let cli = make_cli_parse();
-    let verbose = cli.verbose;
-
-    init_logger(verbose);
+fn main_entry(cli: Cli) -> Result<(), WGAError> {
+    init_logger(log_level(cli.verbose, cli.log_level));
 
+    // all rayon parallel iterators will run in this global pool
     rayon::ThreadPoolBuilder::new()
         .num_threads(cli.threads)
         .build_global()?;
+    info!("Threads: {}", rayon::current_num_threads());
+
+    set_no_decompress(cli.no_decompress);
+    set_maf_eof(cli.maf_eof);
+    set_max_records(cli.max_records);
+    // progress bar would break the terminal UI of tview
+    set_progress(!cli.quiet && !matches!(cli.command, Commands::Tview { .. }));
 
     let outfile = cli.outfile;
     let rewrite = cli.rewrite;
@@ -36,32 +68,141 @@ fn main_entry() -> Result<(), WGAError> {
     info!("Command: {:?}", &cli.command);
 
     match &cli.command {
-        Commands::Maf2Paf { input } => {
-            wrap_maf2paf(input, &outfile, rewrite)?;
+        Commands::Maf2Paf {
+            input,
+            sort,
+            species,
+            target_name,
+            cigar_style,
+            verify,
+            check,
+            with_divergence,
+            tag_primary,
+            primary_by,
+        } => {
+            wrap_maf2paf(
+                input,
+                &outfile,
+                rewrite,
+                *sort,
+                species,
+                target_name,
+                *cigar_style,
+                *verify,
+                *check,
+                *with_divergence,
+                tag_primary.then_some(*primary_by),
+            )?;
         }
         Commands::Paf2Maf {
             input,
             target,
             query,
+            coordinate_check,
+            require_cigar,
+            uppercase,
+        } => {
+            wrap_paf2maf(
+                input,
+                &outfile,
+                target,
+                query,
+                rewrite,
+                *coordinate_check,
+                *require_cigar,
+                *uppercase,
+            )?;
+        }
+        Commands::Paf2Delta {
+            input,
+            target,
+            query,
+            require_cigar,
+        } => {
+            wrap_paf2delta(input, &outfile, rewrite, target, query, *require_cigar)?;
+        }
+        Commands::Maf2Delta {
+            input,
+            target,
+            query,
+        } => {
+            wrap_maf2delta(input, &outfile, rewrite, target, query)?;
+        }
+        Commands::Paf2Chain {
+            input,
+            require_cigar,
+        } => {
+            wrap_paf2chain(input, &outfile, rewrite, *require_cigar)?;
+        }
+        Commands::Paf2Sam {
+            input,
+            require_cigar,
+            soft_clip,
         } => {
-            wrap_paf2maf(input, &outfile, target, query, rewrite)?;
+            wrap_paf2sam(input, &outfile, rewrite, *require_cigar, *soft_clip, false)?;
         }
-        Commands::Paf2Chain { input } => {
-            wrap_paf2chain(input, &outfile, rewrite)?;
+        Commands::Paf2Bam {
+            input,
+            require_cigar,
+            soft_clip,
+        } => {
+            wrap_paf2sam(input, &outfile, rewrite, *require_cigar, *soft_clip, true)?;
         }
-        Commands::Chain2Paf { input } => {
-            wrap_chain2paf(input, &outfile, rewrite)?;
+        Commands::Chain2Paf { input, sort } => {
+            wrap_chain2paf(input, &outfile, rewrite, *sort)?;
         }
         Commands::Chain2Maf {
             input,
             target,
             query,
+            uppercase,
         } => {
-            wrap_chain2maf(input, &outfile, target, query, rewrite)?;
+            wrap_chain2maf(input, &outfile, target, query, rewrite, *uppercase)?;
+        }
+        Commands::Maf2Fasta {
+            input,
+            block,
+            names,
+            consensus,
+            remove_empty_columns,
+        } => {
+            wrap_maf2fasta(
+                input,
+                &outfile,
+                rewrite,
+                *block,
+                names,
+                *consensus,
+                *remove_empty_columns,
+            )?;
         }
         Commands::Maf2Chain { input } => {
             wrap_maf2chain(input, &outfile, rewrite)?;
         }
+        Commands::Convert {
+            input,
+            input_format,
+            to,
+            target,
+            query,
+            sort,
+            per_block_dir,
+            drop_self_alignments,
+            keep_diagonal_within,
+        } => {
+            wrap_convert(
+                input,
+                &outfile,
+                rewrite,
+                *input_format,
+                *to,
+                target,
+                query,
+                *sort,
+                per_block_dir,
+                drop_self_alignments.then_some(*keep_diagonal_within),
+            )?;
+        }
         Commands::MafExtract {
             input,
             regions,
@@ -69,6 +210,16 @@ fn main_entry() -> Result<(), WGAError> {
         } => {
             wrap_maf_extract(input, regions, file, &outfile, rewrite)?;
         }
+        Commands::PafIndex { input } => {
+            wrap_paf_index(input, &outfile)?;
+        }
+        Commands::PafExtract {
+            input,
+            regions,
+            file,
+        } => {
+            wrap_paf_extract(input, regions, file, &outfile, rewrite)?;
+        }
         Commands::Call {
             input,
             sample,
@@ -88,8 +239,15 @@ fn main_entry() -> Result<(), WGAError> {
         Commands::Maf2Sam { input } => {
             wrap_maf2sam(input, &outfile, rewrite)?;
         }
-        Commands::MafIndex { input } => {
-            wrap_build_index(input, &outfile)?;
+        Commands::Maf2Vcf { input } => {
+            wrap_maf2vcf(input, &outfile, rewrite)?;
+        }
+        Commands::MafIndex {
+            input,
+            validate,
+            binary_index,
+        } => {
+            wrap_build_index(input, &outfile, *validate, cli.threads, *binary_index)?;
         }
         Commands::Tview { input, step } => {
             tview(input, *step)?;
@@ -98,7 +256,76 @@ fn main_entry() -> Result<(), WGAError> {
             input,
             format,
             each,
-        } => wrap_stat(*format, input, &outfile, rewrite, *each)?,
+            by_target,
+            summary,
+            per_sequence,
+            tsv,
+            gc,
+            identity_mode,
+            fast,
+        } => wrap_stat(
+            *format,
+            input,
+            &outfile,
+            rewrite,
+            *each,
+            *by_target,
+            *summary,
+            *per_sequence,
+            *tsv,
+            *gc,
+            *identity_mode,
+            *fast,
+        )?,
+        Commands::ChainStat { input, bin_width } => {
+            wrap_chain_stat(input, &outfile, rewrite, *bin_width)?
+        }
+        Commands::CigarBed {
+            input,
+            format,
+            header,
+        } => wrap_cigar_bed(*format, input, &outfile, rewrite, *header)?,
+        Commands::PafSplit {
+            input,
+            format,
+            max_gap,
+        } => wrap_paf_split(*format, input, &outfile, rewrite, *max_gap)?,
+        Commands::Coverage {
+            input,
+            format,
+            merge,
+            depth,
+        } => {
+            wrap_coverage(*format, input, &outfile, rewrite, *merge, *depth)?;
+        }
+        Commands::MafDepth { input, min_depth } => {
+            wrap_maf_depth(input, &outfile, rewrite, *min_depth)?;
+        }
+        Commands::Dedup {
+            input,
+            format,
+            by_coords,
+            keep_best,
+        } => {
+            wrap_dedup(*format, input, &outfile, rewrite, *by_coords, *keep_best)?;
+        }
+        Commands::Primary {
+            input,
+            format,
+            by,
+            mark_secondary,
+            unsorted,
+        } => {
+            wrap_primary(
+                *format,
+                input,
+                &outfile,
+                rewrite,
+                *by,
+                *mark_secondary,
+                *unsorted,
+            )?;
+        }
         Commands::Dotplot {
             input,
             format,
@@ -106,7 +333,15 @@ fn main_entry() -> Result<(), WGAError> {
             no_identity,
             length,
             mode,
+            min_length,
+            target,
+            query,
         } => {
+            let filter = SegmentFilter {
+                min_length: *min_length,
+                target: target.clone(),
+                query: query.clone(),
+            };
             wrap_dotplot(
                 input,
                 *format,
@@ -114,6 +349,7 @@ fn main_entry() -> Result<(), WGAError> {
                 *mode,
                 *no_identity,
                 *length,
+                &filter,
                 &outfile,
                 rewrite,
             )?;
@@ -123,21 +359,55 @@ fn main_entry() -> Result<(), WGAError> {
             format,
             min_block_size,
             min_query_size,
+            min_identity,
+            identity_mode,
+            min_mapq,
+            max_gap,
             min_align_size,
+            drop_self_alignments,
+            keep_diagonal_within,
         } => {
-            wrap_filter(
+            let opts = FilterOpts {
+                min_block_size: *min_block_size,
+                min_query_size: *min_query_size,
+                min_identity: *min_identity,
+                identity_mode: *identity_mode,
+                min_mapq: *min_mapq,
+                max_gap: *max_gap,
+                drop_self: drop_self_alignments.then_some(*keep_diagonal_within),
+            };
+            wrap_filter(*format, input, &outfile, rewrite, &opts, *min_align_size)?;
+        }
+        Commands::Rename {
+            input,
+            prefixs,
+            map,
+            strict,
+            format,
+            pansn_sep,
+            strip_prefix,
+            target_prefix,
+            query_prefix,
+            map_out,
+        } => match (prefixs, map, pansn_sep) {
+            (Some(prefixs), _, _) => wrap_rename_maf(input, &outfile, rewrite, prefixs)?,
+            (None, Some(map), _) => {
+                wrap_rename_by_map(*format, input, &outfile, rewrite, map, *strict)?
+            }
+            (None, None, Some(sep)) => wrap_rename_pansn(
                 *format,
                 input,
                 &outfile,
                 rewrite,
-                *min_block_size,
-                *min_query_size,
-                *min_align_size,
-            )?;
-        }
-        Commands::Rename { input, prefixs } => {
-            wrap_rename_maf(input, &outfile, rewrite, prefixs)?;
-        }
+                sep,
+                *strip_prefix,
+                target_prefix,
+                query_prefix,
+                map_out,
+            )?,
+            // guaranteed by clap
+            (None, None, None) => unreachable!(),
+        },
         Commands::PafCov { input } => {
             wrap_paf_cov(input, &outfile, rewrite)?;
         }
@@ -147,12 +417,62 @@ fn main_entry() -> Result<(), WGAError> {
             target,
         } => {
             wrap_paf_pesudo_maf(input, &outfile, rewrite, fasta, target)?;
-        } // Commands::TrimOvp { input } => {
-        //     wrap_paf_trim_overlap(input, &outfile, rewrite)?;
-        // }
+        }
+        Commands::TrimOverlaps { input, min_length } => {
+            wrap_trim_overlaps(input, &outfile, rewrite, *min_length)?;
+        }
         Commands::Chunk { input, length } => {
             wrap_chunk(input, &outfile, rewrite, *length)?;
         }
+        Commands::MafSlice { input, region, seq } => {
+            wrap_maf_slice(input, &outfile, rewrite, region, seq)?;
+        }
+        Commands::MafMask { input, bed, as_gap } => {
+            wrap_maf_mask(input, &outfile, rewrite, bed, *as_gap)?;
+        }
+        Commands::MafChunk {
+            input,
+            blocks,
+            size,
+            prefix,
+        } => {
+            wrap_maf_chunk(input, rewrite, *blocks, size, prefix)?;
+        }
+        Commands::MafNormalize {
+            input,
+            trim_gaps,
+            flip_to_target_plus,
+        } => {
+            wrap_normalize(input, &outfile, rewrite, *trim_gaps, *flip_to_target_plus)?;
+        }
+        Commands::MafJoin { first, second } => {
+            wrap_maf_join(first, second, &outfile, rewrite)?;
+        }
+        Commands::MafFilterByName {
+            input,
+            include,
+            exclude,
+            include_file,
+            exclude_file,
+            require_all,
+        } => {
+            wrap_maf_filter_by_name(
+                input,
+                &outfile,
+                rewrite,
+                include,
+                exclude,
+                include_file,
+                exclude_file,
+                *require_all,
+            )?;
+        }
+        Commands::MafSort { input, buffer_size } => {
+            wrap_maf_sort(input, &outfile, rewrite, buffer_size)?;
+        }
+        Commands::MafTrimEdges { input, min_species } => {
+            wrap_trim_edges(input, &outfile, rewrite, *min_species)?;
+        }
         Commands::GenCompletion { shell } => {
             wrap_gencomp(*shell, &outfile, rewrite)?;
         }
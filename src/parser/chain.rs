@@ -1,16 +1,17 @@
 use crate::errors::{ParseChainErrKind, WGAError};
 use crate::parser::cigar::{parse_chain_to_cigar, parse_cigar_to_trim, parse_maf_seq_to_trim};
-use crate::parser::common::{AlignRecord, SeqInfo, Strand};
+use crate::parser::common::{AlignRecord, RecStat, RecordLimit, SeqInfo, Strand};
+use crate::parser::fasta::FastaIndex;
 use crate::parser::maf::MAFRecord;
 use crate::parser::paf::PafRecord;
-use crate::utils::{parse_str2f64, parse_str2u64};
+use crate::utils::{get_input_reader, parse_str2f64, parse_str2u64};
 use nom::bytes::complete::{is_not, tag, take_while};
 use nom::character::complete::{line_ending, not_line_ending};
 use nom::multi::fold_many1;
 use nom::sequence::terminated;
 use nom::IResult;
 use std::fs::File;
-use std::io::{BufReader, Read};
+use std::io::{BufRead, BufReader, Read, Seek};
 use std::{fmt, io};
 
 /// Reader for MAF file format
@@ -33,13 +34,31 @@ where
     pub fn records(&mut self) -> Result<ChainRecords, WGAError> {
         let mut data = String::with_capacity(512);
         self.inner.read_to_string(&mut data)?;
-        Ok(ChainRecords { inner: data })
+        // the last data line must be terminated for the line parser
+        if !data.is_empty() && !data.ends_with('\n') {
+            data.push('\n');
+        }
+        Ok(ChainRecords {
+            inner: data,
+            limit: RecordLimit::default(),
+        })
+    }
+}
+
+impl ChainReader<Box<dyn BufRead + Send>> {
+    /// Create a new Chain parser from a file path, gzip/bgzip/bzip2/xz will be decompressed
+    /// transparently unless `--no-decompress` is set
+    pub fn from_path<P: AsRef<std::path::Path>>(
+        path: P,
+    ) -> Result<ChainReader<Box<dyn BufRead + Send>>, WGAError> {
+        let path = path.as_ref().to_string_lossy().to_string();
+        Ok(ChainReader::new(get_input_reader(&Some(path))?))
     }
 }
 
 impl ChainReader<File> {
-    /// Create a new PAF parser from a file path
-    pub fn from_path<P: AsRef<std::path::Path>>(path: P) -> io::Result<ChainReader<File>> {
+    /// Create a new Chain parser from a raw file path
+    pub fn from_path_raw<P: AsRef<std::path::Path>>(path: P) -> io::Result<ChainReader<File>> {
         File::open(path).map(ChainReader::new)
     }
 }
@@ -52,23 +71,57 @@ pub struct ChainRecord {
     pub lines: Vec<ChainDataLine>,
 }
 
+impl ChainRecord {
+    /// Ungapped blocks as `(size, dt, dq)` of data lines, `dt` (second column) is kept in
+    /// `query_diff` and `dq` in `target_diff`, gaps after the last block are 0
+    pub fn blocks(&self) -> impl Iterator<Item = (u64, u64, u64)> + '_ {
+        self.lines
+            .iter()
+            .map(|line| (line.size, line.query_diff, line.target_diff))
+    }
+}
+
 pub struct ChainRecords {
     inner: String,
+    limit: RecordLimit,
 }
 
 impl Iterator for ChainRecords {
     type Item = Result<ChainRecord, WGAError>;
     fn next(&mut self) -> Option<Self::Item> {
-        if self.inner.is_empty() {
+        // skip blank and `#` comment lines before a chain header
+        while self.inner.starts_with(['\n', '\r', '#']) {
+            match self.inner.find('\n') {
+                Some(i) => self.inner.drain(..=i),
+                None => self.inner.drain(..),
+            };
+        }
+        if self.inner.is_empty() || self.limit.reached() {
             return None;
         }
-        match chain_parser(&self.inner) {
+        let item = match chain_parser(&self.inner) {
             Ok((i, r)) => {
                 self.inner = i.to_string();
-                Some(Ok(r))
+                Ok(r)
             }
-            Err(e) => Some(Err(e)),
+            Err(e) => Err(e),
+        };
+        self.limit.count(Some(item))
+    }
+}
+
+/// Write a chain record in UCSC chain format, the last data line only has `size`
+/// so that parse-then-write is stable
+impl fmt::Display for ChainRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.header)?;
+        if let Some((last, lines)) = self.lines.split_last() {
+            for dataline in lines {
+                write!(f, "{}", dataline)?;
+            }
+            write!(f, "\n{}", last.size)?;
         }
+        Ok(())
     }
 }
 
@@ -81,6 +134,21 @@ pub struct ChainHeader {
     pub chain_id: usize,
 }
 
+impl ChainHeader {
+    pub fn score(&self) -> f64 {
+        self.score
+    }
+    pub fn set_score(&mut self, score: f64) {
+        self.score = score;
+    }
+    pub fn set_target_name(&mut self, name: String) {
+        self.target.name = name;
+    }
+    pub fn set_query_name(&mut self, name: String) {
+        self.query.name = name;
+    }
+}
+
 /// Define a chain data lines
 #[derive(Debug, Default)]
 pub struct ChainDataLine {
@@ -104,8 +172,9 @@ impl TryFrom<&MAFRecord> for ChainHeader {
     type Error = WGAError;
 
     fn try_from(value: &MAFRecord) -> Result<Self, Self::Error> {
+        value.check_pairwise()?;
         let mut header = ChainHeader {
-            score: 255_f64,
+            score: value.score as f64,
             target: SeqInfo {
                 name: value.target_name().to_owned(),
                 size: value.target_length(),
@@ -133,8 +202,10 @@ impl TryFrom<&MAFRecord> for ChainHeader {
             Strand::Negative => {
                 header.target.start += head_del;
                 header.target.end -= tail_del;
-                header.query.start = header.query.size - (header.query.end - head_ins);
-                header.query.end = header.query.size - (header.query.start + tail_ins);
+                // chain coordinates of negative strand are on the reverse complement
+                let (fwd_start, fwd_end) = (header.query.start, header.query.end);
+                header.query.start = header.query.size - (fwd_end - head_ins);
+                header.query.end = header.query.size - (fwd_start + tail_ins);
             }
         }
         Ok(header)
@@ -174,8 +245,10 @@ impl TryFrom<&PafRecord> for ChainHeader {
             Strand::Negative => {
                 header.target.start += head_del;
                 header.target.end -= tail_del;
-                header.query.start = header.query.size - (header.query.end - head_ins);
-                header.query.end = header.query.size - (header.query.start + tail_ins);
+                // chain coordinates of negative strand are on the reverse complement
+                let (fwd_start, fwd_end) = (header.query.start, header.query.end);
+                header.query.start = header.query.size - (fwd_end - head_ins);
+                header.query.end = header.query.size - (fwd_start + tail_ins);
             }
         }
         Ok(header)
@@ -427,18 +500,58 @@ impl AlignRecord for ChainRecord {
         self.header.target.end - self.header.target.start
     }
 
+    // query coordinates on '-' strand are on the reverse complement, fetch the forward range
+    fn query_seq_from_fasta<R: Read + Seek>(
+        &self,
+        fasta: &mut FastaIndex<R>,
+    ) -> Result<Vec<u8>, WGAError> {
+        let (start, end) = match self.query_strand() {
+            Strand::Positive => (self.query_start(), self.query_end()),
+            Strand::Negative => (
+                self.query_length() - self.query_end(),
+                self.query_length() - self.query_start(),
+            ),
+        };
+        fasta.fetch(self.query_name(), start, end, self.query_strand())
+    }
+
+    fn get_cigar_string(&self) -> String {
+        parse_chain_to_cigar(self, false).cigar_string
+    }
+
+    fn get_stat(&self) -> Result<RecStat, WGAError> {
+        // chain has no base info, all aligned columns are counted as matched
+        let cigar = parse_chain_to_cigar(self, false);
+        Ok(RecStat::from(cigar))
+    }
+
+    fn query_forward(&self) -> (u64, u64) {
+        // query of '-' strand is on the reverse complement
+        match self.query_strand() {
+            Strand::Positive => (self.query_start(), self.query_end()),
+            Strand::Negative => (
+                self.query_length() - self.query_end(),
+                self.query_length() - self.query_start(),
+            ),
+        }
+    }
+
+    fn align_score(&self) -> f64 {
+        self.header.score()
+    }
+
     fn convert2paf(&self) -> Result<PafRecord, WGAError> {
         let cigar = parse_chain_to_cigar(self, false);
-        let cigar_string = cigar.cigar_string;
-        let block_length =
-            (cigar.match_count + cigar.mismatch_count + cigar.del_count + cigar.inv_del_count)
-                as u64;
+        let cigar_string = String::from("cg:Z:") + &cigar.cigar_string;
+        let block_length = cigar.block_length();
         let matches = cigar.match_count as u64;
+        // PAF coordinates are on the forward strand of query, as `TryFrom<&PafRecord>` flips
+        let (query_start, query_end) = self.query_forward();
         Ok(PafRecord {
             query_name: self.query_name().to_string(),
             query_length: self.query_length(),
-            query_start: self.query_start(),
-            query_end: self.query_end(),
+            query_start,
+            query_end,
             strand: self.query_strand(),
             target_name: self.target_name().to_string(),
             target_length: self.target_length(),
@@ -28,6 +28,19 @@ pub struct Cigar {
     pub inv_event: usize,
 }
 
+impl Cigar {
+    /// Columns of the alignment as PAF `block_length`, i.e. sum of CIGAR lengths:
+    /// M + X + I + D, each base of target and query is counted once, gaps included
+    pub fn block_length(&self) -> u64 {
+        (self.match_count
+            + self.mismatch_count
+            + self.ins_count
+            + self.inv_ins_count
+            + self.del_count
+            + self.inv_del_count) as u64
+    }
+}
+
 /// CigarUnit is a atom operation in cigar string
 #[derive(Debug)]
 struct CigarUnit {
@@ -56,7 +69,7 @@ fn cst2cu(cst: CigarStrTuple) -> Result<CigarUnit, WGAError> {
 }
 
 /// Parse a cigar unit until the input is empty
-fn parse_cigar_str_tuple(input: &str) -> IResult<&str, CigarStrTuple> {
+fn parse_cigar_str_tuple(input: &str) -> IResult<&str, CigarStrTuple<'_>> {
     // if input is empty, return error to break infinite loop
     if input.is_empty() {
         return Err(nom::Err::Error(Error::new(
@@ -123,7 +136,7 @@ pub fn parse_cigar_to_blocks<T: AlignRecord>(
 ) -> Result<(), WGAError> {
     // get cigar bytes and tags
     let cigar = rec.get_cigar_str()?;
-    let (cigar, _tag) = tag("cg:Z:")(cigar)?;
+    let (cigar, _tag) = tag("cg:Z:")(cigar.as_ref())?;
 
     // init a original block
     let mut block = Block {
@@ -165,6 +178,7 @@ pub fn parse_maf_seq_to_trim<T: AlignRecord>(rec: &T) -> Result<(u64, u64, u64,
 
     let group_by_iter = seq1_iter
         .zip(seq2_iter)
+        .filter(|(c1, c2)| !(*c1 == '-' && *c2 == '-'))
         .group_by(|(c1, c2)| cigar_cat_ext(c1, c2));
     for (k, g) in group_by_iter.into_iter() {
         let count = g.count();
@@ -208,7 +222,7 @@ pub fn parse_cigar_to_trim<T: AlignRecord>(rec: &T) -> Result<(u64, u64, u64, u6
     let mut head_indel = true;
 
     let cigar = rec.get_cigar_str()?;
-    let (cigar, _tag) = tag("cg:Z:")(cigar)?;
+    let (cigar, _tag) = tag("cg:Z:")(cigar.as_ref())?;
 
     let (_, _) = fold_many1(
         parse_cigar_str_tuple,
@@ -250,12 +264,12 @@ pub fn parse_cigar_to_trim<T: AlignRecord>(rec: &T) -> Result<(u64, u64, u64, u6
 /// - For SamRecord: cigar's first `[0-9]+H` should represent the query start
 pub fn parse_cigar_to_chain<T: AlignRecord>(
     rec: &T,
-    wtr: &mut Box<dyn Write>,
+    wtr: &mut dyn Write,
     // ) -> Result<(&'a str, Result<(), WGAError>), WGAError> {
 ) -> Result<(), WGAError> {
     // get cigar bytes and tag
     let cigar = rec.get_cigar_str()?;
-    let (cigar, _tag) = tag("cg:Z:")(cigar)?;
+    let (cigar, _tag) = tag("cg:Z:")(cigar.as_ref())?;
 
     // init a ChainDataLine filled 0
     let mut dataline = ChainDataLine {
@@ -320,6 +334,38 @@ pub fn cigar_cat(c1: &char, c2: &char) -> char {
     }
 }
 
+/// Condense `=` and `X` operations of a CIGAR string into `M`, other operations are kept
+/// 10=5X1D2I ==> 15M1D2I
+pub fn condense_cigar(cigar: &str) -> String {
+    let mut condensed = String::with_capacity(cigar.len());
+    let mut match_len = 0;
+    let mut len = 0;
+    for c in cigar.chars() {
+        match c {
+            '0'..='9' => len = len * 10 + c.to_digit(10).unwrap_or(0) as usize,
+            '=' | 'X' | 'M' => {
+                match_len += len;
+                len = 0;
+            }
+            _ => {
+                if match_len > 0 {
+                    condensed.push_str(&match_len.to_string());
+                    condensed.push('M');
+                    match_len = 0;
+                }
+                condensed.push_str(&len.to_string());
+                condensed.push(c);
+                len = 0;
+            }
+        }
+    }
+    if match_len > 0 {
+        condensed.push_str(&match_len.to_string());
+        condensed.push('M');
+    }
+    condensed
+}
+
 /// parse MAF two seqs into Cigar
 pub fn parse_maf_seq_to_cigar<T: AlignRecord>(rec: &T, with_h: bool) -> Cigar {
     let mut cigar_string = String::new();
@@ -336,8 +382,10 @@ pub fn parse_maf_seq_to_cigar<T: AlignRecord>(rec: &T, with_h: bool) -> Cigar {
     let mut inv_del_event = 0;
     let mut inv_del_count = 0;
     let mut inv_event = 0;
+    // columns gap in both, e.g. from a multiple alignment, are not aligned
     let group_by_iter = seq1_iter
         .zip(seq2_iter)
+        .filter(|(c1, c2)| !(*c1 == '-' && *c2 == '-'))
         .group_by(|(c1, c2)| cigar_cat_ext(c1, c2));
 
     let begin = rec.query_start() as usize;
@@ -414,12 +462,14 @@ pub fn parse_maf_seq_to_cigar<T: AlignRecord>(rec: &T, with_h: bool) -> Cigar {
 /// parse MAF two seqs adn write into a chain file
 pub fn parse_maf_seq_to_chain<T: AlignRecord>(
     rec: &T,
-    wtr: &mut Box<dyn Write>,
+    wtr: &mut dyn Write,
 ) -> Result<(), WGAError> {
     let seq1_iter = rec.target_seq().chars();
     let seq2_iter = rec.query_seq().chars();
+    // columns gap in both are skipped as in `parse_maf_seq_to_cigar`
     let group_by_iter = seq1_iter
         .zip(seq2_iter)
+        .filter(|(c1, c2)| !(*c1 == '-' && *c2 == '-'))
         .group_by(|(c1, c2)| cigar_cat(c1, c2));
 
     // init a ChainDataLine filled 0
@@ -440,7 +490,7 @@ pub fn parse_maf_seq_to_chain<T: AlignRecord>(
 fn cigar_unit_chain(
     op: char,
     count: u64,
-    wtr: &mut Box<dyn Write>,
+    wtr: &mut dyn Write,
     dataline: &mut ChainDataLine,
 ) -> Result<(), WGAError> {
     match op {
@@ -506,7 +556,7 @@ pub fn parse_cigar_to_insert<T: AlignRecord>(
 ) -> Result<(), WGAError> {
     // get cigar bytes and tag
     let cigar = rec.get_cigar_str()?;
-    let (cigar, _tag) = tag("cg:Z:")(cigar)?;
+    let (cigar, _tag) = tag("cg:Z:")(cigar.as_ref())?;
 
     // fold cigar bytes into many CigarUnits[#CigarUnit]
     let mut current_offset = 0;
@@ -629,7 +679,7 @@ pub fn parse_paf_to_cigar<T: AlignRecord>(rec: &T) -> Result<Cigar, WGAError> {
     };
 
     let cigar = rec.get_cigar_str()?;
-    let (cigar, _tag) = tag("cg:Z:")(cigar)?;
+    let (cigar, _tag) = tag("cg:Z:")(cigar.as_ref())?;
     let mut current_offset = 0;
     let (_, res) = fold_many1(
         parse_cigar_str_tuple,
@@ -686,6 +736,177 @@ pub fn parse_paf_to_cigar<T: AlignRecord>(rec: &T) -> Result<Cigar, WGAError> {
     })
 }
 
+/// Convert a minimap2 difference string into CIGAR with `=` and `X`, `cs:Z:` tag is optional,
+/// both short (`:N`) and long (`=ACGT`) forms of matches are supported
+// :4*ag+cc-t=AC  =>  4=1X2I1D2=
+pub fn cs_to_cigar(cs: &str) -> Result<String, WGAError> {
+    let cs = cs.strip_prefix("cs:Z:").unwrap_or(cs);
+    let invalid = || WGAError::CsInvalid(cs.to_string());
+    let bytes = cs.as_bytes();
+    let mut units: Vec<(char, u64)> = Vec::new();
+    let mut push = |op: char, len: u64| match units.last_mut() {
+        Some((last_op, last_len)) if *last_op == op => *last_len += len,
+        _ => units.push((op, len)),
+    };
+    let mut i = 0;
+    while i < bytes.len() {
+        let op = bytes[i];
+        i += 1;
+        // length of the following run of digits or bases
+        let run = bytes[i..]
+            .iter()
+            .take_while(|b| match op {
+                b':' => b.is_ascii_digit(),
+                _ => b.is_ascii_alphabetic(),
+            })
+            .count();
+        if run == 0 {
+            return Err(invalid());
+        }
+        match op {
+            b':' => push('=', parse_str2u64(&cs[i..i + run])?),
+            b'=' => push('=', run as u64),
+            b'+' => push('I', run as u64),
+            b'-' => push('D', run as u64),
+            // one substitution, target base then query base
+            b'*' if run >= 2 => {
+                push('X', 1);
+                i += 2;
+                continue;
+            }
+            _ => return Err(invalid()),
+        }
+        i += run;
+    }
+    Ok(units
+        .iter()
+        .map(|(op, len)| format!("{}{}", len, op))
+        .collect())
+}
+
+/// Get the longest single insertion or deletion of a CIGAR, `cg:Z:` tag is optional
+pub fn max_gap_len(cigar: &str) -> Result<u64, WGAError> {
+    let cigar = cigar.strip_prefix("cg:Z:").unwrap_or(cigar);
+    let mut max_gap = 0;
+    let (_, res) = fold_many1(
+        parse_cigar_str_tuple,
+        null,
+        |res: Result<(), WGAError>, cigarunit| {
+            if res.is_ok() {
+                let cigarunit = cst2cu(cigarunit)?;
+                if matches!(cigarunit.op, 'I' | 'D') {
+                    max_gap = max_gap.max(cigarunit.len);
+                }
+            }
+            res
+        },
+    )(cigar)?;
+    res?;
+    Ok(max_gap)
+}
+
+/// Operations and lengths of a CIGAR in order, `cg:Z:` tag is optional
+pub fn cigar_units(cigar: &str) -> Result<Vec<(char, u64)>, WGAError> {
+    let cigar = cigar.strip_prefix("cg:Z:").unwrap_or(cigar);
+    let mut units = Vec::new();
+    let (_, res) = fold_many1(
+        parse_cigar_str_tuple,
+        null,
+        |res: Result<(), WGAError>, cigarunit| {
+            if res.is_ok() {
+                let cigarunit = cst2cu(cigarunit)?;
+                units.push((cigarunit.op, cigarunit.len));
+            }
+            res
+        },
+    )(cigar)?;
+    res?;
+    Ok(units)
+}
+
+/// Write the MUMmer delta gap stream of a record, one integer per line ending with `0`,
+/// `n` is an insertion in target (gap in query) after `n-1` aligned bases, `-n` in query
+// 10M2D5M1I  =>  11 1 -6 0
+pub fn parse_cigar_to_delta<T: AlignRecord>(rec: &T, wtr: &mut dyn Write) -> Result<(), WGAError> {
+    let mut run = 0;
+    for (op, len) in cigar_units(&rec.get_cigar_str()?)? {
+        match op {
+            'M' | '=' | 'X' => run += len,
+            'D' | 'I' => {
+                let sign = if op == 'D' { 1 } else { -1 };
+                for _ in 0..len {
+                    writeln!(wtr, "{}", sign * (run as i64 + 1))?;
+                    run = 0;
+                }
+            }
+            _ => return Err(WGAError::CigarOpInvalid(op.to_string())),
+        }
+    }
+    writeln!(wtr, "0")?;
+    Ok(())
+}
+
+/// Rebuild gapped target and query sequences of a pairwise alignment from CIGAR
+/// and ungapped sequences in alignment order, `cg:Z:` tag is optional
+// 2=1I1D1X, ACGT, ATAG  =>  AC-GT
+//                           ATA-G
+pub fn cigar_to_gapped_seqs(
+    cigar: &str,
+    target: &[u8],
+    query: &[u8],
+) -> Result<(String, String), WGAError> {
+    let units = cigar_units(cigar)?;
+    let (mut target_len, mut query_len) = (0, 0);
+    for &(op, len) in &units {
+        match op {
+            'M' | '=' | 'X' => {
+                target_len += len;
+                query_len += len;
+            }
+            'D' => target_len += len,
+            'I' => query_len += len,
+            _ => return Err(WGAError::CigarOpInvalid(op.to_string())),
+        }
+    }
+    if target_len != target.len() as u64 || query_len != query.len() as u64 {
+        return Err(WGAError::CigarSeqMismatch(
+            target_len,
+            query_len,
+            target.len(),
+            query.len(),
+        ));
+    }
+    let width = units.iter().map(|(_, len)| *len as usize).sum();
+    let mut gapped_target = Vec::with_capacity(width);
+    let mut gapped_query = Vec::with_capacity(width);
+    let (mut t, mut q) = (0, 0);
+    for (op, len) in units {
+        let len = len as usize;
+        match op {
+            'D' => {
+                gapped_target.extend_from_slice(&target[t..t + len]);
+                gapped_query.resize(gapped_query.len() + len, b'-');
+                t += len;
+            }
+            'I' => {
+                gapped_target.resize(gapped_target.len() + len, b'-');
+                gapped_query.extend_from_slice(&query[q..q + len]);
+                q += len;
+            }
+            _ => {
+                gapped_target.extend_from_slice(&target[t..t + len]);
+                gapped_query.extend_from_slice(&query[q..q + len]);
+                t += len;
+                q += len;
+            }
+        }
+    }
+    Ok((
+        String::from_utf8_lossy(&gapped_target).into_owned(),
+        String::from_utf8_lossy(&gapped_query).into_owned(),
+    ))
+}
+
 /// Parse CIGAR to Cigar struct and stat cov
 pub fn update_cov_vec(cov_vec: &mut [usize], cigar: &str, start: usize) -> Result<(), WGAError> {
     let (cigar, _tag) = tag("cg:Z:")(cigar)?;
@@ -746,13 +967,9 @@ pub fn gen_pesudo_maf_by_cigar(
                             }
                         }
                     }
-                    'I' | 'S' => {
-                        if base {
-                            // del from whole_q_seq
-                            raw_q_seq.drain(current_offset..(current_offset + length));
-                        } else {
-                            // do nothing
-                        }
+                    'I' | 'S' if base => {
+                        // del from whole_q_seq
+                        raw_q_seq.drain(current_offset..(current_offset + length));
                     }
                     'D' => {
                         if base {
@@ -899,7 +1116,7 @@ pub fn parse_cigar_to_base_plotdata<T: AlignRecord>(
     skip_cutoff: usize,
 ) -> Result<Vec<BasePlotdata>, WGAError> {
     let cigar = rec.get_cigar_str()?;
-    let (cigar, _tag) = tag("cg:Z:")(cigar)?;
+    let (cigar, _tag) = tag("cg:Z:")(cigar.as_ref())?;
     let ref_start = rec.target_start();
     let query_start = rec.query_start();
     let mut ref_current_offset = ref_start;
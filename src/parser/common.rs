@@ -1,22 +1,22 @@
 use crate::errors::WGAError;
 use crate::parser::cigar::Cigar;
-use crate::parser::maf::MAFRecord;
-use crate::parser::paf::PafRecord;
 use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::fmt;
-use std::str::FromStr;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+
+// core types of the record model, kept here so paths of all parsers are unchanged
+pub use crate::record::{AlignRecord, Strand};
 
 /// Enum the file types
 #[derive(Debug, PartialEq, Clone, Copy, ValueEnum)]
 pub enum FileFormat {
     Maf,
-    #[value(skip)]
     Sam,
     // Bam,
     Paf,
     Chain,
+    Delta,
     #[value(skip)]
     Bedpe,
     #[value(skip)]
@@ -25,6 +25,26 @@ pub enum FileFormat {
     Unknown,
 }
 
+impl FileFormat {
+    /// Format of alignments by extension of `path`, compression suffix is skipped,
+    /// e.g. `a.paf.gz` is PAF, None for other extensions
+    pub fn from_path(path: &str) -> Option<FileFormat> {
+        let path = path.to_ascii_lowercase();
+        let path = [".gz", ".bgz", ".bz2", ".xz"]
+            .iter()
+            .find_map(|suffix| path.strip_suffix(suffix))
+            .unwrap_or(&path);
+        match path.rsplit_once('.')?.1 {
+            "maf" => Some(FileFormat::Maf),
+            "paf" => Some(FileFormat::Paf),
+            "chain" => Some(FileFormat::Chain),
+            "delta" => Some(FileFormat::Delta),
+            "sam" => Some(FileFormat::Sam),
+            _ => None,
+        }
+    }
+}
+
 /// Represented in:
 /// - PAF 1-9 columns
 /// - CHAIN header lines
@@ -38,36 +58,6 @@ pub struct SeqInfo {
     pub end: u64,
 }
 
-#[derive(Debug, Clone, PartialEq, Copy, Serialize, Deserialize, Eq, Default)]
-pub enum Strand {
-    #[serde(rename = "+")]
-    #[default]
-    Positive,
-    #[serde(rename = "-")]
-    Negative,
-}
-
-impl FromStr for Strand {
-    type Err = WGAError;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "+" => Ok(Strand::Positive),
-            "-" => Ok(Strand::Negative),
-            _ => Err(WGAError::ParseStrand(s.to_string())),
-        }
-    }
-}
-
-impl fmt::Display for Strand {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            Strand::Positive => write!(f, "+"),
-            Strand::Negative => write!(f, "-"),
-        }
-    }
-}
-
 /// Define an alignment block
 #[derive(Debug, Copy, Clone, Serialize)]
 pub struct Block<'a> {
@@ -95,7 +85,7 @@ impl<'a> Default for Block<'a> {
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, PartialEq)]
 pub struct RecStat {
     pub aligned_size: usize,
     pub matched: usize,
@@ -110,6 +100,109 @@ pub struct RecStat {
     pub inv_del_size: usize,
     pub inv_event: usize,
     pub inv_size: f32,
+    /// gap-compressed identity, each gap counts once whatever its length:
+    /// M / (M + X + gap opens)
+    pub gap_compressed_identity: f64,
+    /// per-base identity of all columns, as BLAST identity: M / (M + X + I + D)
+    pub block_identity: f64,
+}
+
+/// Denominator of identity, numerator is always matched bases
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum IdentityMode {
+    /// aligned size of target: matched + mismatched + deleted, gaps in target excluded
+    #[default]
+    Target,
+    /// all alignment columns like BLAST: matched + mismatched + inserted + deleted
+    Blast,
+    /// columns without gaps: matched + mismatched
+    GapExcluded,
+}
+
+/// Style of `cg:Z:` CIGAR in PAF converted from MAF
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum CigarStyle {
+    /// `=` for matches and `X` for mismatches
+    #[default]
+    Extended,
+    /// `M` for both matches and mismatches
+    Match,
+}
+
+/// Name to group records by
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum GroupBy {
+    #[default]
+    Query,
+    Target,
+}
+
+/// Key to select the primary alignment of a query
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum PrimaryBy {
+    /// BLAST-like identity, see `IdentityMode::Blast`
+    #[default]
+    Identity,
+    /// aligned length on query
+    Length,
+    /// alignment score, `AS:i:` tag or MAPQ of PAF
+    Score,
+}
+
+impl RecStat {
+    /// Identity in [0, 1] by `mode`, 0 if there is no aligned column
+    pub fn identity(&self, mode: IdentityMode) -> f64 {
+        let denominator = match mode {
+            IdentityMode::Target => self.aligned_size,
+            IdentityMode::Blast => self.aligned_size + self.ins_size + self.inv_ins_size,
+            IdentityMode::GapExcluded => self.matched + self.mismatched,
+        };
+        match denominator {
+            0 => 0.0,
+            n => self.matched as f64 / n as f64,
+        }
+    }
+
+    /// Recompute `gap_compressed_identity` and `block_identity` from the counts,
+    /// e.g. after counts of records are summed
+    pub fn update_identities(&mut self) {
+        let gap_opens = self.ins_event + self.del_event + self.inv_ins_event + self.inv_del_event;
+        self.gap_compressed_identity = match self.matched + self.mismatched + gap_opens {
+            0 => 0.0,
+            n => self.matched as f64 / n as f64,
+        };
+        self.block_identity = self.identity(IdentityMode::Blast);
+    }
+
+    /// Columns of the alignment, same as `Cigar::block_length`: M + X + I + D
+    pub fn block_length(&self) -> u64 {
+        (self.matched
+            + self.mismatched
+            + self.ins_size
+            + self.inv_ins_size
+            + self.del_size
+            + self.inv_del_size) as u64
+    }
+
+    /// Gap-compressed divergence like minimap2 `de:f:`, each gap counts once whatever
+    /// its length: (X + gap opens) / (M + X + gap opens)
+    pub fn gap_compressed_divergence(&self) -> f64 {
+        let gap_opens = self.ins_event + self.del_event + self.inv_ins_event + self.inv_del_event;
+        match self.matched + self.mismatched + gap_opens {
+            0 => 0.0,
+            n => (self.mismatched + gap_opens) as f64 / n as f64,
+        }
+    }
+
+    /// Per-base divergence as `dv:f:`, each gapped base counts:
+    /// (X + I + D) / (M + X + I + D), i.e. NM / block length
+    pub fn per_base_divergence(&self) -> f64 {
+        let gap_size = self.ins_size + self.del_size + self.inv_ins_size + self.inv_del_size;
+        match self.matched + self.mismatched + gap_size {
+            0 => 0.0,
+            n => (self.mismatched + gap_size) as f64 / n as f64,
+        }
+    }
 }
 
 // Statistic for each record by CIGAR
@@ -135,44 +228,112 @@ impl From<Cigar> for RecStat {
             rec_stat.inv_size =
                 (rec_stat.aligned_size + query_align_size) as f32 / (rec_stat.inv_event + 1) as f32;
         };
+        rec_stat.update_identities();
         rec_stat
     }
 }
 
-pub trait AlignRecord {
-    fn query_name(&self) -> &str;
-    fn query_length(&self) -> u64;
-    fn query_start(&self) -> u64;
-    fn query_end(&self) -> u64;
-    fn query_strand(&self) -> Strand;
-    fn target_name(&self) -> &str;
-    fn target_length(&self) -> u64;
-    fn target_start(&self) -> u64;
-    fn target_end(&self) -> u64;
-    fn target_strand(&self) -> Strand;
-    fn target_align_size(&self) -> u64;
-    fn get_cigar_str(&self) -> Result<&str, WGAError> {
-        Ok("*")
-    }
-    fn get_cigar_string(&self) -> String {
-        "*".to_string()
-    }
-    fn convert2paf(&self) -> Result<PafRecord, WGAError> {
-        Ok(PafRecord::default())
+fn group_name<T: AlignRecord>(rec: &T, by: GroupBy) -> &str {
+    match by {
+        GroupBy::Query => rec.query_name(),
+        GroupBy::Target => rec.target_name(),
     }
-    fn convert2maf(&self) -> Result<MAFRecord, WGAError> {
-        Ok(MAFRecord::default())
-    }
-    fn convert2bam(&self, _name_id_map: &HashMap<&str, u64>) {}
-    fn query_seq(&self) -> &str {
-        ""
+}
+
+/// An iterator of `(name, records)` groups of contiguous records with the same name,
+/// only one group is buffered at a time
+pub struct Groups<I, T> {
+    inner: I,
+    by: GroupBy,
+    // first record of the next group
+    pending: Option<T>,
+    seen: HashSet<String>,
+    done: bool,
+}
+
+impl<I, T> Groups<I, T>
+where
+    I: Iterator<Item = Result<T, WGAError>>,
+    T: AlignRecord,
+{
+    /// Group records of any record iterator, input should be sorted by the name,
+    /// a name seen in an earlier group is an error
+    pub fn new(inner: I, by: GroupBy) -> Self {
+        Groups {
+            inner,
+            by,
+            pending: None,
+            seen: HashSet::new(),
+            done: false,
+        }
     }
-    fn target_seq(&self) -> &str {
-        ""
+}
+
+impl<I, T> Iterator for Groups<I, T>
+where
+    I: Iterator<Item = Result<T, WGAError>>,
+    T: AlignRecord,
+{
+    type Item = Result<(String, Vec<T>), WGAError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let first = match self.pending.take() {
+            Some(rec) => rec,
+            None => match self.inner.next()? {
+                Ok(rec) => rec,
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            },
+        };
+        let name = group_name(&first, self.by).to_string();
+        if !self.seen.insert(name.clone()) {
+            self.done = true;
+            return Some(Err(WGAError::UnsortedGroup(name)));
+        }
+        let mut group = vec![first];
+        for rec in self.inner.by_ref() {
+            let rec = match rec {
+                Ok(rec) => rec,
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            };
+            if group_name(&rec, self.by) != name {
+                self.pending = Some(rec);
+                break;
+            }
+            group.push(rec);
+        }
+        Some(Ok((name, group)))
     }
-    fn get_stat(&self) -> Result<RecStat, WGAError> {
-        Ok(RecStat::default())
+}
+
+/// Group all records by query or target name for unsorted input,
+/// groups are in order of first occurrence and records are in input order
+pub fn groups_buffered<I, T>(records: I, by: GroupBy) -> Result<Vec<(String, Vec<T>)>, WGAError>
+where
+    I: Iterator<Item = Result<T, WGAError>>,
+    T: AlignRecord,
+{
+    let mut groups: Vec<(String, Vec<T>)> = Vec::new();
+    let mut slots: HashMap<String, usize> = HashMap::new();
+    for rec in records {
+        let rec = rec?;
+        let name = group_name(&rec, by);
+        match slots.get(name) {
+            Some(&idx) => groups[idx].1.push(rec),
+            None => {
+                slots.insert(name.to_string(), groups.len());
+                groups.push((name.to_string(), vec![rec]));
+            }
+        }
     }
+    Ok(groups)
 }
 
 /// Re-count align-size from seqs
@@ -200,4 +361,41 @@ pub enum DotplotoutFormat {
     Html,
     Json,
     Csv,
+    Tsv,
+}
+
+// max records read of each input, `u64::MAX` if no limit
+static MAX_RECORDS: AtomicU64 = AtomicU64::new(u64::MAX);
+
+/// Stop reading each input after `max` records, e.g. to preview output of a large file
+pub fn set_max_records(max: Option<u64>) {
+    MAX_RECORDS.store(max.unwrap_or(u64::MAX), AtomicOrdering::Relaxed);
+}
+
+/// Limit of records read of each input set by `--max-records`
+pub fn max_records() -> Option<u64> {
+    let max = MAX_RECORDS.load(AtomicOrdering::Relaxed);
+    (max != u64::MAX).then_some(max)
+}
+
+/// Count of records read by a reader, the record iterators of all readers end early
+/// once `--max-records` are read
+#[derive(Debug, Default)]
+pub(crate) struct RecordLimit {
+    read: u64,
+}
+
+impl RecordLimit {
+    // if no more record should be read
+    pub(crate) fn reached(&self) -> bool {
+        self.read >= MAX_RECORDS.load(AtomicOrdering::Relaxed)
+    }
+
+    // count the item just read and pass it through
+    pub(crate) fn count<T>(&mut self, item: Option<T>) -> Option<T> {
+        if item.is_some() {
+            self.read += 1;
+        }
+        item
+    }
 }
@@ -0,0 +1,331 @@
+use crate::errors::WGAError;
+use crate::parser::cigar::parse_paf_to_cigar;
+use crate::parser::common::{AlignRecord, RecStat, RecordLimit, Strand};
+use crate::parser::paf::PafRecord;
+use crate::utils::{get_input_reader, parse_str2u64};
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+
+/// Reader for MUMmer delta format of nucmer, the first two lines are FASTA paths
+/// of reference and query, and the program name
+pub struct DeltaReader<R: Read> {
+    inner: BufReader<R>,
+    pub target_path: String,
+    pub query_path: String,
+    limit: RecordLimit,
+}
+
+impl<R> DeltaReader<R>
+where
+    R: Read + Send,
+{
+    /// Create a new delta reader, the header lines are read and checked
+    pub fn new(reader: R) -> Result<Self, WGAError> {
+        let mut inner = BufReader::new(reader);
+        let mut line = String::new();
+        inner.read_line(&mut line)?;
+        let mut paths = line.split_whitespace();
+        let (target_path, query_path) = match (paths.next(), paths.next()) {
+            (Some(target), Some(query)) => (target.to_string(), query.to_string()),
+            _ => {
+                return Err(WGAError::ParseDelta(
+                    1,
+                    "two FASTA paths expected".to_string(),
+                ))
+            }
+        };
+        line.clear();
+        inner.read_line(&mut line)?;
+        match line.trim() {
+            "NUCMER" => {}
+            "PROMER" => return Err(WGAError::DeltaPromer),
+            other => {
+                return Err(WGAError::ParseDelta(
+                    2,
+                    format!("`NUCMER` expected, found `{}`", other),
+                ))
+            }
+        }
+        Ok(DeltaReader {
+            inner,
+            target_path,
+            query_path,
+            limit: RecordLimit::default(),
+        })
+    }
+
+    /// Iterate over the records in the delta file
+    pub fn records(&mut self) -> DeltaRecords<'_, R> {
+        DeltaRecords {
+            inner: self.inner.by_ref(),
+            limit: &mut self.limit,
+            pair: None,
+            line_no: 2,
+        }
+    }
+}
+
+impl DeltaReader<Box<dyn BufRead + Send>> {
+    /// Create a new delta parser from a file path, gzip/bgzip/bzip2/xz will be decompressed
+    /// transparently unless `--no-decompress` is set
+    pub fn from_path<P: AsRef<std::path::Path>>(
+        path: P,
+    ) -> Result<DeltaReader<Box<dyn BufRead + Send>>, WGAError> {
+        let path = path.as_ref().to_string_lossy().to_string();
+        DeltaReader::new(get_input_reader(&Some(path))?)
+    }
+}
+
+impl DeltaReader<File> {
+    /// Create a new delta parser from a raw file path
+    pub fn from_path_raw<P: AsRef<std::path::Path>>(
+        path: P,
+    ) -> Result<DeltaReader<File>, WGAError> {
+        DeltaReader::new(File::open(path)?)
+    }
+}
+
+/// One alignment of delta, coordinates are 0-based half-open and query is on
+/// the forward strand as PAF, the CIGAR is rebuilt from the gap stream with `M`/`I`/`D`
+#[derive(Debug, Clone, Default)]
+pub struct DeltaRecord {
+    pub target_name: String,
+    pub target_length: u64,
+    pub target_start: u64,
+    pub target_end: u64,
+    pub query_name: String,
+    pub query_length: u64,
+    pub query_start: u64,
+    pub query_end: u64,
+    pub strand: Strand,
+    /// non-matching columns, mismatches and gaps
+    pub errors: u64,
+    pub similarity_errors: u64,
+    pub stop_codons: u64,
+    pub cigar: String,
+}
+
+/// An iterator struct for delta records
+pub struct DeltaRecords<'a, R: Read> {
+    inner: &'a mut BufReader<R>,
+    // names and lengths of the current `>` header
+    pair: Option<(String, String, u64, u64)>,
+    line_no: u64,
+    limit: &'a mut RecordLimit,
+}
+
+impl<R: Read> DeltaRecords<'_, R> {
+    fn read_line(&mut self, line: &mut String) -> Result<bool, WGAError> {
+        line.clear();
+        self.line_no += 1;
+        Ok(self.inner.read_line(line)? > 0)
+    }
+
+    fn error(&self, msg: impl Into<String>) -> WGAError {
+        WGAError::ParseDelta(self.line_no, msg.into())
+    }
+
+    fn integers<const N: usize>(&self, line: &str) -> Result<[u64; N], WGAError> {
+        let fields = line.split_whitespace().collect::<Vec<_>>();
+        if fields.len() != N {
+            return Err(self.error(format!("{} fields expected, found {}", N, fields.len())));
+        }
+        let mut values = [0; N];
+        for (value, field) in values.iter_mut().zip(fields) {
+            *value = parse_str2u64(field)?;
+        }
+        Ok(values)
+    }
+
+    fn next_record(&mut self) -> Result<Option<DeltaRecord>, WGAError> {
+        let mut line = String::new();
+        // skip `>` headers and blank lines until a coordinate line
+        loop {
+            if !self.read_line(&mut line)? {
+                return Ok(None);
+            }
+            let trimmed = line.trim();
+            if let Some(header) = trimmed.strip_prefix('>') {
+                let fields = header.split_whitespace().collect::<Vec<_>>();
+                if fields.len() != 4 {
+                    return Err(self.error("`>target query target_len query_len` expected"));
+                }
+                let [target_length, query_length] = self.integers(&fields[2..].join(" "))?;
+                self.pair = Some((
+                    fields[0].to_string(),
+                    fields[1].to_string(),
+                    target_length,
+                    query_length,
+                ));
+            } else if !trimmed.is_empty() {
+                break;
+            }
+        }
+        let [rs, re, qs, qe, errors, similarity_errors, stop_codons] = self.integers(&line)?;
+        let (target_name, query_name, target_length, query_length) = match &self.pair {
+            Some(pair) => pair.clone(),
+            None => return Err(self.error("alignment before any `>` header")),
+        };
+        if rs == 0 || qs == 0 || qe == 0 || rs > re {
+            return Err(self.error(format!("invalid coordinates `{}`", line.trim())));
+        }
+        let (strand, query_start, query_end) = match qs <= qe {
+            true => (Strand::Positive, qs - 1, qe),
+            false => (Strand::Negative, qe - 1, qs),
+        };
+
+        // gap stream until `0`, `n` is a gap in query and `-n` a gap in target
+        // after `|n|-1` aligned bases
+        let mut units: Vec<(char, u64)> = Vec::new();
+        let mut push = |op: char, len: u64| match units.last_mut() {
+            Some((last_op, last_len)) if *last_op == op => *last_len += len,
+            _ if len > 0 => units.push((op, len)),
+            _ => {}
+        };
+        let (target_span, query_span) = (re - rs + 1, query_end - query_start);
+        let (mut target_used, mut query_used) = (0, 0);
+        loop {
+            if !self.read_line(&mut line)? {
+                return Err(self.error("gap stream should end with `0`"));
+            }
+            let dist = line
+                .trim()
+                .parse::<i64>()
+                .map_err(|_| self.error(format!("invalid gap distance `{}`", line.trim())))?;
+            if dist == 0 {
+                break;
+            }
+            let aligned = dist.unsigned_abs() - 1;
+            push('M', aligned);
+            target_used += aligned;
+            query_used += aligned;
+            if dist > 0 {
+                push('D', 1);
+                target_used += 1;
+            } else {
+                push('I', 1);
+                query_used += 1;
+            }
+        }
+        if target_used > target_span || target_span - target_used + query_used != query_span {
+            return Err(self.error("gap stream does not match coordinates"));
+        }
+        push('M', target_span - target_used);
+        let cigar = units
+            .iter()
+            .map(|(op, len)| format!("{}{}", len, op))
+            .collect();
+
+        Ok(Some(DeltaRecord {
+            target_name,
+            target_length,
+            target_start: rs - 1,
+            target_end: re,
+            query_name,
+            query_length,
+            query_start,
+            query_end,
+            strand,
+            errors,
+            similarity_errors,
+            stop_codons,
+            cigar,
+        }))
+    }
+}
+
+impl<R: Read> Iterator for DeltaRecords<'_, R> {
+    type Item = Result<DeltaRecord, WGAError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.limit.reached() {
+            return None;
+        }
+        let item = self.next_record().transpose();
+        self.limit.count(item)
+    }
+}
+
+/// impl AlignRecord Trait for DeltaRecord
+impl AlignRecord for DeltaRecord {
+    fn query_name(&self) -> &str {
+        &self.query_name
+    }
+
+    fn query_length(&self) -> u64 {
+        self.query_length
+    }
+
+    fn query_start(&self) -> u64 {
+        self.query_start
+    }
+
+    fn query_end(&self) -> u64 {
+        self.query_end
+    }
+
+    fn query_strand(&self) -> Strand {
+        self.strand
+    }
+
+    fn target_name(&self) -> &str {
+        &self.target_name
+    }
+
+    fn target_length(&self) -> u64 {
+        self.target_length
+    }
+
+    fn target_start(&self) -> u64 {
+        self.target_start
+    }
+
+    fn target_end(&self) -> u64 {
+        self.target_end
+    }
+
+    fn target_strand(&self) -> Strand {
+        Strand::Positive
+    }
+
+    fn target_align_size(&self) -> u64 {
+        self.target_end - self.target_start
+    }
+
+    fn get_cigar_str(&self) -> Result<Cow<'_, str>, WGAError> {
+        Ok(Cow::Owned(format!("cg:Z:{}", self.cigar)))
+    }
+
+    fn get_cigar_string(&self) -> String {
+        self.cigar.clone()
+    }
+
+    // matches are aligned columns without errors, `M` of CIGAR includes mismatches
+    fn convert2paf(&self) -> Result<PafRecord, WGAError> {
+        let cigar = parse_paf_to_cigar(self)?;
+        let block_length = cigar.block_length();
+        Ok(PafRecord {
+            query_name: self.query_name.clone(),
+            query_length: self.query_length,
+            query_start: self.query_start,
+            query_end: self.query_end,
+            strand: self.strand,
+            target_name: self.target_name.clone(),
+            target_length: self.target_length,
+            target_start: self.target_start,
+            target_end: self.target_end,
+            matches: block_length.saturating_sub(self.errors),
+            block_length,
+            mapq: 255,
+            tags: vec![
+                format!("NM:i:{}", self.errors),
+                format!("cg:Z:{}", self.cigar),
+            ],
+        })
+    }
+
+    fn get_stat(&self) -> Result<RecStat, WGAError> {
+        Ok(RecStat::from(parse_paf_to_cigar(self)?))
+    }
+}
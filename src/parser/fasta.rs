@@ -0,0 +1,453 @@
+use crate::errors::WGAError;
+use crate::parser::common::Strand;
+use crate::utils::parse_str2u64;
+use log::{info, warn};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// A samtools compatible `.fai` record refer to https://www.htslib.org/doc/faidx.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FaiRecord {
+    pub name: String,
+    pub length: u64,
+    pub offset: u64,
+    pub linebases: u64,
+    pub linewidth: u64,
+}
+
+/// Build `.fai` records from a FASTA stream, all lines of a record except the last
+/// one must have the same width, otherwise the `.fai` math is broken, the last line
+/// of file may have no line break
+pub fn build_fai<R: BufRead>(mut reader: R) -> Result<Vec<FaiRecord>, WGAError> {
+    let mut records: Vec<FaiRecord> = Vec::new();
+    let mut line = Vec::new();
+    let mut pos = 0;
+    // if a line shorter than linebases is met, the record must end
+    let mut short_line = false;
+    loop {
+        line.clear();
+        let n = reader.read_until(b'\n', &mut line)? as u64;
+        if n == 0 {
+            break;
+        }
+        pos += n;
+        if line[0] == b'>' {
+            let name = String::from_utf8_lossy(&line[1..]);
+            let name = name.split_whitespace().next().unwrap_or_default();
+            records.push(FaiRecord {
+                name: name.to_string(),
+                length: 0,
+                offset: pos,
+                linebases: 0,
+                linewidth: 0,
+            });
+            short_line = false;
+            continue;
+        }
+
+        let rec = match records.last_mut() {
+            Some(rec) => rec,
+            None => return Err(WGAError::FastaFormat("sequence before header".to_string())),
+        };
+        let bases = line
+            .iter()
+            .rev()
+            .skip_while(|c| **c == b'\n' || **c == b'\r')
+            .count() as u64;
+        if bases == 0 {
+            // blank line is only allowed at the end of a record
+            short_line = true;
+            continue;
+        }
+        if rec.linebases == 0 {
+            rec.linebases = bases;
+            rec.linewidth = n;
+        } else if short_line
+            || bases > rec.linebases
+            || (bases == rec.linebases && line.ends_with(b"\n") && n != rec.linewidth)
+        {
+            return Err(WGAError::FaiInconsistentLine(rec.name.clone()));
+        }
+        if bases < rec.linebases {
+            short_line = true;
+        }
+        rec.length += bases;
+    }
+    Ok(records)
+}
+
+/// Read `.fai` records
+pub fn read_fai<R: BufRead>(reader: R) -> Result<Vec<FaiRecord>, WGAError> {
+    let mut records = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        let fields = line.split('\t').collect::<Vec<&str>>();
+        if fields.len() < 5 {
+            return Err(WGAError::FastaFormat(format!(
+                "invalid fai line `{}`",
+                line
+            )));
+        }
+        records.push(FaiRecord {
+            name: fields[0].to_string(),
+            length: parse_str2u64(fields[1])?,
+            offset: parse_str2u64(fields[2])?,
+            linebases: parse_str2u64(fields[3])?,
+            linewidth: parse_str2u64(fields[4])?,
+        });
+    }
+    Ok(records)
+}
+
+/// Write `.fai` records
+pub fn write_fai<W: Write>(records: &[FaiRecord], mut writer: W) -> Result<(), WGAError> {
+    for rec in records {
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}\t{}",
+            rec.name, rec.length, rec.offset, rec.linebases, rec.linewidth
+        )?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+// `.fai` records by name, shared by `FastaIndex` and `SharedFasta`
+struct FaiMap(HashMap<String, FaiRecord>);
+
+impl FaiMap {
+    fn new(records: Vec<FaiRecord>) -> Self {
+        FaiMap(
+            records
+                .into_iter()
+                .map(|rec| (rec.name.clone(), rec))
+                .collect(),
+        )
+    }
+
+    fn seq_len(&self, name: &str) -> Option<u64> {
+        self.0.get(name).map(|rec| rec.length)
+    }
+
+    fn check_coordinate(&self, name: &str, length: u64, end: u64) -> Result<(), WGAError> {
+        let fa_length = self
+            .seq_len(name)
+            .ok_or(WGAError::SeqNotFound(name.to_string()))?;
+        if length != fa_length {
+            return Err(WGAError::SeqLengthNotMatch(
+                name.to_string(),
+                length,
+                fa_length,
+            ));
+        }
+        if end > fa_length {
+            return Err(WGAError::FetchOutOfRange(
+                name.to_string(),
+                0,
+                end,
+                fa_length,
+            ));
+        }
+        Ok(())
+    }
+
+    // record of `name` and `[start, end)` checked in range of it
+    fn get(&self, name: &str, start: u64, end: u64) -> Result<&FaiRecord, WGAError> {
+        let rec = self
+            .0
+            .get(name)
+            .ok_or(WGAError::SeqNotFound(name.to_string()))?;
+        if start > end || end > rec.length {
+            return Err(WGAError::FetchOutOfRange(
+                name.to_string(),
+                start,
+                end,
+                rec.length,
+            ));
+        }
+        Ok(rec)
+    }
+}
+
+// file offsets of bases `[start, end)`, line breaks inside are included
+fn byte_range(rec: &FaiRecord, start: u64, end: u64) -> (u64, u64) {
+    let file_offset =
+        |pos: u64| rec.offset + pos / rec.linebases * rec.linewidth + pos % rec.linebases;
+    (file_offset(start), file_offset(end - 1) + 1)
+}
+
+// read `.fai` of FASTA at `path` if exists, or build and try to save it
+fn open_with_fai(path: &Path) -> Result<(File, Vec<FaiRecord>), WGAError> {
+    let file = File::open(path).map_err(|_| WGAError::FileNotExist(path.to_path_buf()))?;
+    let fai_path = format!("{}.fai", path.display());
+    let records = if Path::new(&fai_path).exists() {
+        read_fai(BufReader::new(File::open(&fai_path)?))?
+    } else {
+        info!("build fasta index `{}`", fai_path);
+        let records = build_fai(BufReader::new(&file))?;
+        match File::create(&fai_path) {
+            Ok(fai) => write_fai(&records, BufWriter::new(fai))?,
+            Err(e) => warn!("failed to save fasta index `{}`: {}", fai_path, e),
+        }
+        records
+    };
+    Ok((file, records))
+}
+
+/// Random access FASTA with `.fai` records in memory, bases are read by `seek`
+/// so whole sequences are never loaded, case of bases (soft-mask) is preserved
+pub struct FastaIndex<R: Read + Seek> {
+    inner: R,
+    index: FaiMap,
+}
+
+impl<R> FastaIndex<R>
+where
+    R: Read + Seek,
+{
+    /// Create a new FASTA index with `.fai` records
+    pub fn new(inner: R, records: Vec<FaiRecord>) -> Self {
+        FastaIndex {
+            inner,
+            index: FaiMap::new(records),
+        }
+    }
+
+    /// Get length of a sequence
+    pub fn seq_len(&self, name: &str) -> Option<u64> {
+        self.index.seq_len(name)
+    }
+
+    /// Check the declared length and end coordinate of an alignment against FASTA
+    pub fn check_coordinate(&self, name: &str, length: u64, end: u64) -> Result<(), WGAError> {
+        self.index.check_coordinate(name, length, end)
+    }
+
+    /// Fetch sub sequence of `[start, end)` in 0-based forward strand coordinates,
+    /// reverse complemented if `strand` is negative
+    pub fn fetch(
+        &mut self,
+        name: &str,
+        start: u64,
+        end: u64,
+        strand: Strand,
+    ) -> Result<Vec<u8>, WGAError> {
+        let seq = self.fetch_forward(name, start, end)?;
+        match strand {
+            Strand::Positive => Ok(seq),
+            Strand::Negative => Ok(Strand::complement_seq(&seq)),
+        }
+    }
+
+    /// Same as `fetch` but return a String
+    pub fn fetch_string(
+        &mut self,
+        name: &str,
+        start: u64,
+        end: u64,
+        strand: Strand,
+    ) -> Result<String, WGAError> {
+        let seq = self.fetch(name, start, end, strand)?;
+        Ok(String::from_utf8_lossy(&seq).into_owned())
+    }
+
+    // fetch bases of forward strand
+    fn fetch_forward(&mut self, name: &str, start: u64, end: u64) -> Result<Vec<u8>, WGAError> {
+        let rec = self.index.get(name, start, end)?;
+        if start == end {
+            return Ok(Vec::new());
+        }
+        let (begin, stop) = byte_range(rec, start, end);
+        let mut buf = vec![0; (stop - begin) as usize];
+        self.inner.seek(SeekFrom::Start(begin))?;
+        self.inner.read_exact(&mut buf)?;
+        buf.retain(|c| *c != b'\n' && *c != b'\r');
+        Ok(buf)
+    }
+}
+
+impl FastaIndex<File> {
+    /// Create a new FASTA index from a file path, read `{path}.fai` if exists,
+    /// or build and try to save it
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<FastaIndex<File>, WGAError> {
+        let (file, records) = open_with_fai(path.as_ref())?;
+        Ok(FastaIndex::new(file, records))
+    }
+}
+
+// bases of a window in cache, windows are aligned to multiples of `WINDOW_SIZE`
+const WINDOW_SIZE: u64 = 1 << 16;
+// at most 16 MiB of bases are cached
+const CACHE_WINDOWS: usize = 256;
+
+// name of sequence and window index
+type WindowKey = (String, u64);
+
+// LRU cache of windows, with the tick of last use
+#[derive(Default)]
+struct WindowCache {
+    windows: HashMap<WindowKey, (Arc<Vec<u8>>, u64)>,
+    tick: u64,
+    hits: u64,
+    misses: u64,
+}
+
+impl WindowCache {
+    fn get(&mut self, key: &WindowKey) -> Option<Arc<Vec<u8>>> {
+        self.tick += 1;
+        match self.windows.get_mut(key) {
+            Some((window, used)) => {
+                *used = self.tick;
+                self.hits += 1;
+                Some(window.clone())
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    fn insert(&mut self, key: WindowKey, window: Arc<Vec<u8>>) {
+        if self.windows.len() >= CACHE_WINDOWS && !self.windows.contains_key(&key) {
+            // the least recently used one is evicted, the cache is small to scan
+            let lru = self
+                .windows
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(key, _)| key.clone());
+            if let Some(lru) = lru {
+                self.windows.remove(&lru);
+            }
+        }
+        self.windows.insert(key, (window, self.tick));
+    }
+}
+
+/// FASTA shared by threads, `fetch` takes `&self` as bases are read by positioned reads
+/// without a shared cursor, recently fetched windows of sequences are kept in a LRU cache
+/// so alignments hitting the same region don't read the disk again
+pub struct SharedFasta {
+    file: File,
+    index: FaiMap,
+    cache: Mutex<WindowCache>,
+}
+
+impl SharedFasta {
+    /// Create a shared FASTA from a file path, `.fai` is handled as `FastaIndex::from_path`
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<SharedFasta, WGAError> {
+        let (file, records) = open_with_fai(path.as_ref())?;
+        Ok(SharedFasta {
+            file,
+            index: FaiMap::new(records),
+            cache: Mutex::new(WindowCache::default()),
+        })
+    }
+
+    /// Get length of a sequence
+    pub fn seq_len(&self, name: &str) -> Option<u64> {
+        self.index.seq_len(name)
+    }
+
+    /// Same as `FastaIndex::check_coordinate`
+    pub fn check_coordinate(&self, name: &str, length: u64, end: u64) -> Result<(), WGAError> {
+        self.index.check_coordinate(name, length, end)
+    }
+
+    /// Same as `FastaIndex::fetch`, safe to call from many threads
+    pub fn fetch(
+        &self,
+        name: &str,
+        start: u64,
+        end: u64,
+        strand: Strand,
+    ) -> Result<Vec<u8>, WGAError> {
+        let seq = self.fetch_forward(name, start, end)?;
+        match strand {
+            Strand::Positive => Ok(seq),
+            Strand::Negative => Ok(Strand::complement_seq(&seq)),
+        }
+    }
+
+    /// Hits and misses of cached windows
+    pub fn cache_stats(&self) -> (u64, u64) {
+        let cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        (cache.hits, cache.misses)
+    }
+
+    fn fetch_forward(&self, name: &str, start: u64, end: u64) -> Result<Vec<u8>, WGAError> {
+        let rec = self.index.get(name, start, end)?;
+        if start == end {
+            return Ok(Vec::new());
+        }
+        let first = start / WINDOW_SIZE;
+        let last = (end - 1) / WINDOW_SIZE;
+        // a long fetch would flush the cache, so it's read directly
+        if (last - first + 1) as usize > CACHE_WINDOWS / 4 {
+            return self.read_bases(rec, start, end);
+        }
+        let mut seq = Vec::with_capacity((end - start) as usize);
+        for idx in first..=last {
+            let window = self.window(rec, idx)?;
+            let window_start = idx * WINDOW_SIZE;
+            let from = start.max(window_start) - window_start;
+            let to = end.min(window_start + window.len() as u64) - window_start;
+            seq.extend_from_slice(&window[from as usize..to as usize]);
+        }
+        Ok(seq)
+    }
+
+    // bases of the `idx`-th window, read outside the lock on a miss
+    fn window(&self, rec: &FaiRecord, idx: u64) -> Result<Arc<Vec<u8>>, WGAError> {
+        let key = (rec.name.clone(), idx);
+        let cached = self
+            .cache
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&key);
+        if let Some(window) = cached {
+            return Ok(window);
+        }
+        let start = idx * WINDOW_SIZE;
+        let end = (start + WINDOW_SIZE).min(rec.length);
+        let window = Arc::new(self.read_bases(rec, start, end)?);
+        self.cache
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(key, window.clone());
+        Ok(window)
+    }
+
+    fn read_bases(&self, rec: &FaiRecord, start: u64, end: u64) -> Result<Vec<u8>, WGAError> {
+        let (begin, stop) = byte_range(rec, start, end);
+        let mut buf = vec![0; (stop - begin) as usize];
+        read_exact_at(&self.file, &mut buf, begin)?;
+        buf.retain(|c| *c != b'\n' && *c != b'\r');
+        Ok(buf)
+    }
+}
+
+#[cfg(unix)]
+fn read_exact_at(file: &File, buf: &mut [u8], offset: u64) -> std::io::Result<()> {
+    use std::os::unix::fs::FileExt;
+    file.read_exact_at(buf, offset)
+}
+
+#[cfg(windows)]
+fn read_exact_at(file: &File, buf: &mut [u8], offset: u64) -> std::io::Result<()> {
+    use std::os::windows::fs::FileExt;
+    let mut done = 0;
+    while done < buf.len() {
+        match file.seek_read(&mut buf[done..], offset + done as u64)? {
+            0 => return Err(std::io::ErrorKind::UnexpectedEof.into()),
+            n => done += n,
+        }
+    }
+    Ok(())
+}
@@ -1,95 +1,112 @@
 use crate::errors::{ParseMafErrKind, WGAError};
-use crate::parser::cigar::parse_maf_seq_to_cigar;
-use crate::parser::common::{AlignRecord, RecStat, Strand};
-use crate::parser::paf::PafRecord;
-use crate::utils::parse_str2u64;
-use anyhow::anyhow;
+use crate::parser::common::{recount_align_size, RecordLimit, Strand};
+use crate::utils::get_input_reader;
 use log::warn;
-use std::cmp::Ordering;
 use std::fs::File;
 use std::io::Write;
-use std::io::{BufRead, BufReader, Read};
+use std::io::{BufRead, BufReader, BufWriter, Read};
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+
+// blocks are parsed by the core record model, so they can be used without readers
+pub use crate::record::maf::{
+    next_record, parse_records, skip_to_block, MAFRecord, MAFSLine, DEFAULT_SCORE,
+};
 
 /// Parser for MAF file format
 pub struct MAFReader<R: Read> {
     pub inner: BufReader<R>,
-    pub header: String,
+    /// leading `#` header, comment and UCSC `track` lines, without line endings
+    pub header: Vec<String>,
+    limit: RecordLimit,
 }
 
 impl<R> MAFReader<R>
 where
     R: Read + Send,
 {
-    /// Create a new MAF parser
+    /// Create a new MAF parser, a failed header read (e.g. non-UTF8 input) is returned as error
     pub fn new(reader: R) -> Result<Self, WGAError> {
         let mut buf_reader = BufReader::new(reader);
-        let mut header = String::new();
-        buf_reader.read_line(&mut header)?;
-        if !header.starts_with('#') {
+        // take all contiguous `#` lines
+        let header = read_header(&mut buf_reader)?;
+        // empty input is allowed, it just has no records
+        if header.is_empty() && !buf_reader.fill_buf()?.is_empty() {
             warn!("MAF Header is not start with `#`")
         }
         Ok(MAFReader {
             inner: buf_reader,
             header,
+            limit: RecordLimit::default(),
         })
     }
 
+    /// Header lines of input followed by a provenance line of current command,
+    /// the provenance line is a `#` comment so the original version line is kept
+    pub fn header_with(&self, line: String) -> Vec<String> {
+        let mut header = self.header.clone();
+        header.push(line);
+        header
+    }
+
     /// Iterate over the records in the MAF file
-    pub fn records(&mut self) -> MAFRecords<R> {
+    pub fn records(&mut self) -> MAFRecords<'_, R> {
         MAFRecords {
             inner: self.inner.by_ref(),
+            limit: &mut self.limit,
         }
     }
 }
 
+impl MAFReader<Box<dyn BufRead + Send>> {
+    /// Create a new MAF parser from a file path, gzip/bgzip/bzip2/xz will be decompressed
+    /// transparently unless `--no-decompress` is set
+    pub fn from_path<P: AsRef<std::path::Path>>(
+        path: P,
+    ) -> Result<MAFReader<Box<dyn BufRead + Send>>, WGAError> {
+        let path = path.as_ref().to_string_lossy().to_string();
+        MAFReader::new(get_input_reader(&Some(path))?)
+    }
+}
+
 impl MAFReader<File> {
-    /// Create a new PAF parser from a file path
-    pub fn from_path<P: AsRef<std::path::Path>>(path: P) -> Result<MAFReader<File>, WGAError> {
+    /// Create a new MAF parser from a raw file path, keep the file seekable for index
+    pub fn from_path_raw<P: AsRef<std::path::Path>>(path: P) -> Result<MAFReader<File>, WGAError> {
         match File::open(path.as_ref()) {
-            Ok(file) => Ok(MAFReader::new(file)?),
-            Err(_) => Err(WGAError::FileNotExist(path.as_ref().to_path_buf())),
+            Ok(file) => MAFReader::new(file),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                Err(WGAError::FileNotExist(path.as_ref().to_path_buf()))
+            }
+            Err(e) => Err(WGAError::Io(e)),
         }
     }
 }
 
-/// A MAF s-line refer to https://genome.ucsc.edu/FAQ/FAQformat.html#format5
-// a score=111
-// s ref    100 10 + 100000 ---AGC-CAT-CATT
-// s contig 0   10 + 10     ---AGC-CAT-CATT
-//
-// a score=222
-// s ref    100 12 + 100000 ---AGC-CAT-CATTTT
-// s contig 0   12 + 12     ---AGC-CAT-CATTTT
-#[derive(Debug, PartialEq, Eq)]
-pub struct MAFSLine {
-    pub mode: char,
-    pub name: String,
-    pub start: u64,
-    pub align_size: u64,
-    pub strand: Strand,
-    pub size: u64,
-    pub seq: String,
-}
-
 // impl mut for MAFSLine
 impl MAFSLine {
-    fn get_col_coord(&self, pos: u64) -> u64 {
-        let mut col_coord = 0;
-        let mut flag = 0;
+    /// Column of the `pos`-th (0-based) non-gap base in `seq`, None if out of range
+    pub fn get_col_coord(&self, pos: u64) -> Option<u64> {
         // skip '-'
-        for (i, c) in self.seq.chars().enumerate() {
-            if c == '-' {
-                continue;
-            } else {
-                flag += 1;
-                if flag == pos + 1 {
-                    col_coord = i as u64;
-                    break;
-                }
-            }
-        }
+        self.seq
+            .bytes()
+            .enumerate()
+            .filter(|(_, c)| *c != b'-')
+            .nth(pos as usize)
+            .map(|(i, _)| i as u64)
+    }
 
-        col_coord
+    /// Count of non-gap bases in `seq`
+    pub fn ungapped_len(&self) -> u64 {
+        recount_align_size(&self.seq).0
+    }
+
+    /// Count of gaps in `seq`
+    pub fn gap_count(&self) -> u64 {
+        recount_align_size(&self.seq).1
+    }
+
+    /// Gapped length of `seq`, i.e. columns of the block
+    pub fn aligned_len(&self) -> u64 {
+        self.seq.len() as u64
     }
 
     pub fn set_start(&mut self, start: u64) {
@@ -109,116 +126,221 @@ impl MAFSLine {
     }
 }
 
-// main parse function for s-line
-fn parse_sline(line: String) -> Result<MAFSLine, WGAError> {
-    let mut iter = line.split_whitespace();
-    let mode = match iter.next() {
-        Some(mode) => mode
-            .chars()
-            .next()
-            .ok_or(WGAError::Other(anyhow!("mode is empty"))),
-        None => {
-            return Err(WGAError::ParseMaf(ParseMafErrKind::FiledMissing(
-                "mode".to_string(),
-            )))
+/// If s-line name belongs to species, `species` or `species.contig`
+pub fn is_species(name: &str, species: &str) -> bool {
+    name.strip_prefix(species)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+}
+
+impl MAFRecord {
+    /// A pairwise record needs a query (second s-line), error names the block if not
+    pub fn check_pairwise(&self) -> Result<(), WGAError> {
+        match self.slines.len() {
+            0 => Err(WGAError::SLineCountNotMatch),
+            1 => Err(WGAError::ParseMaf(ParseMafErrKind::SingleSLine(
+                self.slines[0].name.clone(),
+                self.slines[0].start,
+            ))),
+            _ => Ok(()),
         }
-    }?;
-    let name = match iter.next() {
-        Some(name) => name.to_string(),
-        None => {
-            return Err(WGAError::ParseMaf(ParseMafErrKind::FiledMissing(
-                "name".to_string(),
-            )))
+    }
+
+    /// Validate that the target (first s-line) is on '+' strand and
+    /// the non-gap count of each s-line's seq equal to its align size
+    pub fn validate(&self) -> Result<(), WGAError> {
+        if let Some(target) = self.slines.first() {
+            if target.strand == Strand::Negative {
+                return Err(WGAError::ParseMaf(ParseMafErrKind::TargetMinusStrand(
+                    target.name.clone(),
+                    target.start,
+                )));
+            }
         }
-    };
-    let start = match iter.next() {
-        Some(start) => parse_str2u64(start)?,
-        None => {
-            return Err(WGAError::ParseMaf(ParseMafErrKind::FiledMissing(
-                "start".to_string(),
-            )))
+        for sline in &self.slines {
+            let non_gap = sline.seq.chars().filter(|c| *c != '-').count() as u64;
+            if non_gap != sline.align_size {
+                return Err(WGAError::ParseMaf(ParseMafErrKind::AlignSizeNotMatch(
+                    sline.name.clone(),
+                    sline.align_size,
+                    non_gap,
+                )));
+            }
         }
-    };
-    let align_size = match iter.next() {
-        Some(align_size) => parse_str2u64(align_size)?,
-        None => {
-            return Err(WGAError::ParseMaf(ParseMafErrKind::FiledMissing(
-                "align_size".to_string(),
-            )))
+        Ok(())
+    }
+
+    /// All s-lines have the same columns as the target (first s-line),
+    /// error names the block and the first s-line of another width
+    pub fn check_width(&self) -> Result<(), WGAError> {
+        let Some(target) = self.slines.first() else {
+            return Ok(());
+        };
+        match self.slines.iter().find(|s| s.seq.len() != target.seq.len()) {
+            Some(sline) => Err(WGAError::ParseMaf(ParseMafErrKind::SeqWidthNotMatch(
+                target.name.clone(),
+                target.start,
+                target.seq.len(),
+                sline.name.clone(),
+                sline.seq.len(),
+            ))),
+            None => Ok(()),
         }
-    };
-    let strand = match iter.next() {
-        Some(strand) => strand.parse::<Strand>()?,
-        None => {
-            return Err(WGAError::ParseMaf(ParseMafErrKind::FiledMissing(
-                "strand".to_string(),
-            )))
+    }
+
+    /// Slice the block into `[cut_start, cut_end)` of the `ord`-th s-line's coordinates,
+    /// other s-lines are clipped to the same columns
+    pub fn slice_block(
+        &mut self,
+        cut_start: u64,
+        cut_end: u64,
+        ord: usize,
+    ) -> Result<(), WGAError> {
+        let sline = self.slines.get(ord).ok_or(WGAError::SLineCountNotMatch)?;
+        let non_gap = sline.ungapped_len();
+        if non_gap != sline.align_size {
+            return Err(WGAError::ParseMaf(ParseMafErrKind::AlignSizeNotMatch(
+                sline.name.clone(),
+                sline.align_size,
+                non_gap,
+            )));
         }
-    };
-    let size = match iter.next() {
-        Some(size) => parse_str2u64(size)?,
-        None => {
-            return Err(WGAError::ParseMaf(ParseMafErrKind::FiledMissing(
-                "size".to_string(),
-            )))
+        let sline_end = sline.start + sline.align_size;
+        let out_of_block = || {
+            WGAError::RegionOutOfBlock(
+                sline.name.clone(),
+                cut_start,
+                cut_end,
+                sline.start,
+                sline_end,
+            )
+        };
+        if cut_start < sline.start || cut_end > sline_end || cut_start >= cut_end {
+            return Err(out_of_block());
         }
-    };
-    let seq = match iter.next() {
-        Some(seq) => seq.to_string(),
-        None => {
-            return Err(WGAError::ParseMaf(ParseMafErrKind::FiledMissing(
-                "seq".to_string(),
-            )))
+
+        // columns of the first and the last base, [start_coord, end_coord)
+        let start_coord = sline
+            .get_col_coord(cut_start - sline.start)
+            .ok_or_else(out_of_block)? as usize;
+        let end_coord = sline
+            .get_col_coord(cut_end - sline.start - 1)
+            .ok_or_else(out_of_block)? as usize
+            + 1;
+
+        for sline in self.slines.iter_mut() {
+            let (head_size, _) = recount_align_size(&sline.seq[..start_coord]);
+            let new_seq = sline.seq[start_coord..end_coord].to_string();
+            let (align_size, _) = recount_align_size(&new_seq);
+            sline.set_start(sline.start + head_size);
+            sline.set_align_size(align_size);
+            sline.seq = new_seq;
         }
-    };
-    if iter.next().is_some() {
-        return Err(WGAError::ParseMaf(ParseMafErrKind::SurplusField));
-    };
-    Ok(MAFSLine {
-        mode,
-        name,
-        start,
-        align_size,
-        strand,
-        size,
-        seq,
-    })
-}
+        Ok(())
+    }
 
-/// A MAF alignment record refer to https://genome.ucsc.edu/FAQ/FAQformat.html#format5
-/// a pair of a-lines should be a align record
-#[derive(Debug, PartialEq, Eq)]
-pub struct MAFRecord {
-    pub score: u64,
-    pub slines: Vec<MAFSLine>,
-}
+    /// Reverse complement the whole block if the target (first s-line) is on '-' strand,
+    /// so target becomes '+', strands and starts of all s-lines are flipped
+    // s t 2 3 - 10 AC-G    flip    s t 5 3 + 10 C-GT
+    // s q 0 4 + 8  ACTG    ===>    s q 4 4 - 8  CAGT
+    pub fn flip_to_target_plus(&mut self) {
+        if self
+            .slines
+            .first()
+            .is_none_or(|t| t.strand == Strand::Positive)
+        {
+            return;
+        }
+        for sline in self.slines.iter_mut() {
+            let seq = Strand::complement_seq(sline.seq.as_bytes());
+            sline.seq = String::from_utf8_lossy(&seq).into_owned();
+            sline.set_start(sline.size - sline.start - sline.align_size);
+            sline.set_strand(sline.strand.flip());
+        }
+    }
 
-impl MAFRecord {
-    pub fn slice_block(&mut self, cut_start: u64, cut_end: u64, ord: usize) {
-        let sline = &mut self.slines[ord];
-
-        let cut_start_index = cut_start - sline.start;
-        let cut_end_index = cut_end - sline.start;
-
-        sline.set_start(cut_start);
-        sline.set_align_size(cut_end - cut_start);
-
-        let start_coord = sline.get_col_coord(cut_start_index);
-        let end_coord = sline.get_col_coord(cut_end_index);
-        sline.seq = sline.seq[start_coord as usize..end_coord as usize].to_string();
-
-        let mut sline_idx_vec = (0..self.slines.len()).collect::<Vec<usize>>();
-        sline_idx_vec.remove(ord);
-        for sline in sline_idx_vec.iter() {
-            let sline = &mut self.slines[*sline];
-            let new_s_start = sline.start + cut_start_index;
-            sline.set_start(new_s_start);
-            let new_seq = sline.seq[start_coord as usize..end_coord as usize].to_string();
-            let pre_align_size = end_coord - start_coord;
-            let gap_size = new_seq.matches('-').count() as u64;
-            sline.set_align_size(pre_align_size - gap_size);
-            sline.seq = new_seq;
+    /// Remove leading and trailing columns which are gaps in all s-lines,
+    /// start and align size are unaffected by gap-only columns
+    pub fn trim_gap_columns(&mut self) {
+        let width = self.slines.iter().map(|s| s.seq.len()).max().unwrap_or(0);
+        let is_gap_col = |col: usize| {
+            self.slines
+                .iter()
+                .all(|s| s.seq.as_bytes().get(col) == Some(&b'-'))
+        };
+        let head = (0..width).take_while(|col| is_gap_col(*col)).count();
+        if head == width {
+            return;
+        }
+        let tail = (head..width)
+            .rev()
+            .take_while(|col| is_gap_col(*col))
+            .count();
+        if head == 0 && tail == 0 {
+            return;
         }
+        for sline in self.slines.iter_mut() {
+            sline.seq = sline.seq[head..sline.seq.len() - tail].to_string();
+        }
+    }
+
+    /// Project the record into a pairwise record of reference(first s-line) and `species`,
+    /// columns gap in both are dropped, return None if block lacks that species
+    pub fn project(&self, species: &str) -> Option<MAFRecord> {
+        let idx = self
+            .slines
+            .iter()
+            .skip(1)
+            .position(|sline| is_species(&sline.name, species))?;
+        self.pair_with(idx + 1)
+    }
+
+    /// Block with the s-line of `name` as reference (first s-line), matched as `project`,
+    /// other s-lines keep their order, the block is flipped if it's on '-' strand
+    pub fn with_target(&self, name: &str) -> Result<MAFRecord, WGAError> {
+        let idx = self
+            .slines
+            .iter()
+            .position(|sline| is_species(&sline.name, name))
+            .ok_or_else(|| {
+                let first = self.slines.first();
+                WGAError::ParseMaf(ParseMafErrKind::TargetNotInBlock(
+                    name.to_string(),
+                    first.map_or_else(String::new, |s| s.name.clone()),
+                    first.map_or(0, |s| s.start),
+                ))
+            })?;
+        let mut rec = self.clone();
+        let target = rec.slines.remove(idx);
+        rec.slines.insert(0, target);
+        rec.flip_to_target_plus();
+        Ok(rec)
+    }
+
+    /// Pairwise records of reference(first s-line) and each other s-line, in block order
+    pub fn pairwise(&self) -> impl Iterator<Item = MAFRecord> + '_ {
+        (1..self.slines.len()).filter_map(|idx| self.pair_with(idx))
+    }
+
+    /// Pairwise record of reference(first s-line) and the `idx`-th s-line,
+    /// columns gap in both are dropped, e.g. gaps opened by other sequences of the block
+    pub fn pair_with(&self, idx: usize) -> Option<MAFRecord> {
+        let target = self.slines.first()?;
+        let query = self.slines.get(idx).filter(|_| idx > 0)?;
+
+        let (t_seq, q_seq): (String, String) = target
+            .seq
+            .chars()
+            .zip(query.seq.chars())
+            .filter(|(t, q)| !(*t == '-' && *q == '-'))
+            .unzip();
+        let mut target = target.clone();
+        let mut query = query.clone();
+        target.seq = t_seq;
+        query.seq = q_seq;
+        Some(MAFRecord {
+            score: self.score,
+            slines: vec![target, query],
+        })
     }
 
     pub fn rename(&mut self, prefixs: &[&str]) -> Result<(), WGAError> {
@@ -235,43 +357,11 @@ impl MAFRecord {
     }
 }
 
-// impl PartialEq for MAFRecord
-impl PartialOrd<Self> for MAFRecord {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
-    }
-}
-
-// impl Ord for MAFRecord
-impl Ord for MAFRecord {
-    // natural order
-    fn cmp(&self, other: &Self) -> Ordering {
-        let t1_name = self.target_name();
-        let t1_start = self.target_start();
-        let t2_name = other.target_name();
-        let t2_start = other.target_start();
-        if t1_name == t2_name {
-            t1_start.cmp(&t2_start)
-        } else {
-            natord::compare(t1_name, t2_name)
-        }
-    }
-}
-
-/// impl Default trait for MAFRecord
-impl Default for MAFRecord {
-    fn default() -> Self {
-        MAFRecord {
-            score: 255,
-            slines: Vec::new(),
-        }
-    }
-}
-
 /// A MAF record iterator
 /// two s-lines should be a record
 pub struct MAFRecords<'a, R: Read + Send> {
     inner: &'a mut BufReader<R>,
+    limit: &'a mut RecordLimit,
 }
 
 /// impl Iterator trait for MAFRecords
@@ -279,163 +369,52 @@ impl<R: Read + Send> Iterator for MAFRecords<'_, R> {
     type Item = Result<MAFRecord, WGAError>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let score = 255;
-        match self.inner.lines().next() {
-            Some(Ok(line)) => {
-                if !line.starts_with('s') {
-                    self.next() // skip empty line
-                } else {
-                    // start read multi s-lines
-                    // init a maf-record
-                    let mut mafrecord = MAFRecord {
-                        score,
-                        slines: Vec::new(),
-                    };
-                    let sline = match parse_sline(line) {
-                        Ok(sline) => sline,
-                        // if catch error, return error
-                        Err(e) => return Some(Err(e)),
-                    };
-                    mafrecord.slines.push(sline); // push first s-line
-                                                  // start read next sequential s-lines
-                    for line in self.inner.lines() {
-                        match line {
-                            Ok(line) => {
-                                if line.starts_with('s') {
-                                    let sline = match parse_sline(line) {
-                                        Ok(sline) => sline,
-                                        Err(e) => return Some(Err(e)),
-                                    };
-                                    mafrecord.slines.push(sline);
-                                } else {
-                                    // if s-line is over, break
-                                    break;
-                                }
-                            }
-                            _ => {
-                                // if line is empty, break
-                                break;
-                            }
-                        }
-                    }
-                    Some(Ok(mafrecord))
-                }
-            }
-            _ => None, // if line is empty, iterator over
+        if self.limit.reached() {
+            return None;
         }
+        self.limit.count(next_record(self.inner))
     }
 }
 
-/// impl AlignRecord Trait for PafRecord
-impl AlignRecord for MAFRecord {
-    fn query_name(&self) -> &str {
-        self.slines[1].name.as_str()
-    }
-
-    fn query_length(&self) -> u64 {
-        self.slines[1].size
-    }
-
-    fn query_start(&self) -> u64 {
-        // self.slines[1].start
-        match self.query_strand() {
-            Strand::Positive => self.slines[1].start,
-            Strand::Negative => {
-                self.slines[1].size - self.slines[1].start - self.slines[1].align_size
-            }
+/// Read leading `#` header and comment lines, e.g. `##maf version=1 scoring=...` and `# generator`,
+/// UCSC `track name=...` lines before or among them are kept in order
+pub fn read_header<B: BufRead>(inner: &mut B) -> Result<Vec<String>, WGAError> {
+    let mut header = Vec::new();
+    // UTF-8 BOM written by some Windows editors
+    if inner.fill_buf()?.starts_with(UTF8_BOM) {
+        inner.consume(UTF8_BOM.len());
+    }
+    loop {
+        let buf = inner.fill_buf()?;
+        if !(buf.starts_with(b"#") || buf.starts_with(b"track")) {
+            break;
         }
+        let mut line = String::new();
+        inner.read_line(&mut line)?;
+        header.push(line.trim_end_matches(['\n', '\r']).to_string());
     }
+    Ok(header)
+}
 
-    fn query_end(&self) -> u64 {
-        match self.query_strand() {
-            Strand::Positive => self.slines[1].start + self.slines[1].align_size,
-            Strand::Negative => self.slines[1].size - self.slines[1].start,
-        }
-    }
-
-    fn query_strand(&self) -> Strand {
-        self.slines[1].strand
-    }
-
-    fn target_name(&self) -> &str {
-        self.slines[0].name.as_str()
-    }
-
-    fn target_length(&self) -> u64 {
-        self.slines[0].size
-    }
-
-    fn target_start(&self) -> u64 {
-        self.slines[0].start
-    }
-
-    fn target_end(&self) -> u64 {
-        self.slines[0].start + self.slines[0].align_size
-    }
-
-    fn target_strand(&self) -> Strand {
-        self.slines[0].strand
-    }
-
-    fn target_align_size(&self) -> u64 {
-        self.slines[0].align_size
-    }
-
-    fn get_cigar_string(&self) -> String {
-        parse_maf_seq_to_cigar(self, false).cigar_string
-    }
-
-    fn convert2paf(&self) -> Result<PafRecord, WGAError> {
-        let cigar = parse_maf_seq_to_cigar(self, false);
-        let cigar_string = String::from("cg:Z:") + &cigar.cigar_string;
-        let matches = cigar.match_count as u64;
-        let block_length = (cigar.match_count
-            + cigar.mismatch_count
-            + cigar.ins_count
-            + cigar.inv_ins_count
-            + cigar.del_count
-            + cigar.inv_del_count) as u64;
-        let edit_dist = block_length - matches;
-        let nm_tag = String::from("NM:i:") + &*edit_dist.to_string();
-
-        Ok(PafRecord {
-            query_name: self.query_name().to_string(),
-            query_length: self.query_length(),
-            query_start: self.query_start(),
-            query_end: self.query_end(),
-            strand: self.query_strand(),
-            target_name: self.target_name().to_string(),
-            target_length: self.target_length(),
-            target_start: self.target_start(),
-            target_end: self.target_end(),
-            matches,
-            block_length,
-            mapq: 255,
-            tags: vec![nm_tag, cigar_string],
-        })
-    }
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
 
-    fn query_seq(&self) -> &str {
-        &self.slines[1].seq
-    }
+// global switch of `--maf-eof`, end MAF output with `##eof maf` as UCSC
+static MAF_EOF: AtomicBool = AtomicBool::new(false);
 
-    fn target_seq(&self) -> &str {
-        &self.slines[0].seq
-    }
-
-    fn get_stat(&self) -> Result<RecStat, WGAError> {
-        // just convert cigar to stat
-        let cigar = parse_maf_seq_to_cigar(self, false);
-        Ok(RecStat::from(cigar))
-    }
+/// End the output of all MAF writers with a `##eof maf` line
+pub fn set_maf_eof(eof: bool) {
+    MAF_EOF.store(eof, AtomicOrdering::Relaxed);
 }
 
-/// A MAF Writer
+/// A buffered MAF Writer, errors (e.g. broken pipe) are returned instead of panic,
+/// call `finish` at the end to catch errors of the last buffered records
 pub struct MAFWriter<W>
 where
     W: Write,
 {
-    inner: W,
+    inner: BufWriter<W>,
+    // `##eof maf` is written by `finish` of the final writer
+    eof: bool,
 }
 
 impl<W> MAFWriter<W>
@@ -444,27 +423,54 @@ where
 {
     /// Create a new MAF writer
     pub fn new(inner: W) -> Self {
-        Self { inner }
+        Self {
+            inner: BufWriter::new(inner),
+            eof: MAF_EOF.load(AtomicOrdering::Relaxed),
+        }
+    }
+
+    /// If a `##eof maf` line is written at the end, default from `--maf-eof`
+    pub fn with_eof(mut self, eof: bool) -> Self {
+        self.eof = eof;
+        self
+    }
+
+    /// Flush buffered records into the inner writer
+    pub fn flush(&mut self) -> Result<(), WGAError> {
+        self.inner.flush()?;
+        Ok(())
+    }
+
+    /// End the output: write `##eof maf` if it's enabled and flush, only the
+    /// final writer of an output calls it, every block is already terminated
+    /// by a blank line
+    pub fn finish(&mut self) -> Result<(), WGAError> {
+        if self.eof {
+            writeln!(self.inner, "##eof maf")?;
+            self.eof = false;
+        }
+        self.flush()
     }
 
-    /// write header
-    pub fn write_header(&mut self, header: String) -> Result<(), WGAError> {
-        writeln!(self.inner, "{}", header)?;
+    /// write header lines
+    pub fn write_header<S: AsRef<str>>(&mut self, header: &[S]) -> Result<(), WGAError> {
+        for line in header {
+            writeln!(self.inner, "{}", line.as_ref())?;
+        }
         Ok(())
     }
 
     /// write records
     pub fn write_record(&mut self, record: &MAFRecord) -> Result<(), WGAError> {
         // write a-line
-        let a_line = format!("a score={}\n", record.score);
-        write!(self.inner, "{}", a_line)?;
+        writeln!(self.inner, "a score={}", record.score)?;
         for sline in record.slines.iter() {
             // write s-line
-            let s_line = format!(
+            writeln!(
+                self.inner,
                 "s\t{}\t{}\t{}\t{}\t{}\t{}",
                 sline.name, sline.start, sline.align_size, sline.strand, sline.size, sline.seq
-            );
-            writeln!(self.inner, "{}", s_line)?;
+            )?;
         }
         // write a empty line
         writeln!(self.inner)?;
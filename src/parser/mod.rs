@@ -1,5 +1,8 @@
 pub mod chain;
 pub mod cigar;
 pub mod common;
+pub mod delta;
+pub mod fasta;
 pub mod maf;
 pub mod paf;
+pub mod writer;
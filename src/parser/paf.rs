@@ -1,138 +1,191 @@
 use crate::errors::WGAError;
-use crate::parser::cigar::parse_paf_to_cigar;
-use crate::parser::common::{AlignRecord, RecStat, Strand};
-use csv::{DeserializeRecordsIter, ReaderBuilder};
-use serde::{Deserialize, Serialize};
+use crate::parser::cigar::condense_cigar;
+use crate::parser::common::{groups_buffered, CigarStyle, GroupBy, Groups, RecStat, RecordLimit};
+use crate::utils::get_input_reader;
+use csv::{ReaderBuilder, StringRecordsIter};
 use std::fs::File;
 use std::io;
-use std::str;
+
+// the record is parsed by the core record model, so it can be used without readers
+pub use crate::record::paf::{parse_records, PafRecord};
 
 /// Parser for PAF format files
 pub struct PAFReader<R: io::Read> {
     inner: csv::Reader<R>,
+    limit: RecordLimit,
 }
 
-impl<R> PAFReader<R>
-where
-    R: io::Read + Send,
-{
-    /// Create a new PAF parser
-    pub fn new(reader: R) -> Self {
+/// Builder of PAF parser, e.g. for space-delimited PAF-like output of some tools
+#[derive(Debug, Clone)]
+pub struct PAFReaderBuilder {
+    delimiter: u8,
+}
+
+impl Default for PAFReaderBuilder {
+    fn default() -> Self {
+        Self { delimiter: b'\t' }
+    }
+}
+
+impl PAFReaderBuilder {
+    /// Create a new builder, delimiter is `\t` by default
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the field delimiter
+    pub fn delimiter(&mut self, delimiter: u8) -> &mut Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Build a PAF parser from a reader
+    pub fn from_reader<R: io::Read + Send>(&self, reader: R) -> PAFReader<R> {
         PAFReader {
             inner: ReaderBuilder::new()
                 .flexible(true)
-                .delimiter(b'\t')
+                .delimiter(self.delimiter)
                 .has_headers(false)
                 .comment(Some(b'#'))
                 .from_reader(reader),
+            limit: RecordLimit::default(),
         }
     }
+}
+
+impl<R> PAFReader<R>
+where
+    R: io::Read + Send,
+{
+    /// Create a new PAF parser
+    pub fn new(reader: R) -> Self {
+        PAFReaderBuilder::new().from_reader(reader)
+    }
 
     /// Iterate over the records in the PAF file
     pub fn records(&mut self) -> Records<'_, R> {
         Records {
-            inner: self.inner.deserialize(),
+            inner: self.inner.records(),
+            limit: &mut self.limit,
         }
     }
 }
 
-impl PAFReader<File> {
-    /// Create a new PAF parser from a file path
-    pub fn from_path<P: AsRef<std::path::Path>>(path: P) -> io::Result<PAFReader<File>> {
-        File::open(path).map(PAFReader::new)
+impl PAFReader<Box<dyn io::BufRead + Send>> {
+    /// Create a new PAF parser from a file path, gzip/bgzip/bzip2/xz will be decompressed
+    /// transparently unless `--no-decompress` is set
+    pub fn from_path<P: AsRef<std::path::Path>>(
+        path: P,
+    ) -> Result<PAFReader<Box<dyn io::BufRead + Send>>, WGAError> {
+        let path = path.as_ref().to_string_lossy().to_string();
+        Ok(PAFReader::new(get_input_reader(&Some(path))?))
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Default)]
-/// A PAF record refer to https://github.com/lh3/miniasm/blob/master/PAF.md
-pub struct PafRecord {
-    pub query_name: String,
-    pub query_length: u64,
-    pub query_start: u64,
-    pub query_end: u64,
-    pub strand: Strand,
-    pub target_name: String,
-    pub target_length: u64,
-    pub target_start: u64,
-    pub target_end: u64,
-    pub matches: u64,
-    pub block_length: u64,
-    pub mapq: u64,
-    #[serde(default)]
-    pub tags: Vec<String>,
-}
-
-/// An iterator struct for PAF records
-pub struct Records<'a, R: io::Read> {
-    inner: DeserializeRecordsIter<'a, R, PafRecord>,
-}
-
-/// impl Iterator for Records
-impl<'a, R: io::Read> Iterator for Records<'a, R> {
-    type Item = csv::Result<PafRecord>;
-    fn next(&mut self) -> Option<csv::Result<PafRecord>> {
-        self.inner.next()
+impl PAFReader<File> {
+    /// Create a new PAF parser from a raw file path
+    pub fn from_path_raw<P: AsRef<std::path::Path>>(path: P) -> io::Result<PAFReader<File>> {
+        File::open(path).map(PAFReader::new)
     }
 }
 
-/// impl AlignRecord Trait for PafRecord
-impl AlignRecord for PafRecord {
-    fn query_name(&self) -> &str {
-        &self.query_name
-    }
-
-    fn query_length(&self) -> u64 {
-        self.query_length
-    }
-
-    fn query_start(&self) -> u64 {
-        self.query_start
-    }
-
-    fn query_end(&self) -> u64 {
-        self.query_end
-    }
-
-    fn query_strand(&self) -> Strand {
-        self.strand
+impl PafRecord {
+    /// If the record has a `cg:Z:` CIGAR or a `cs:Z:` difference string
+    pub fn has_cigar(&self) -> bool {
+        self.tags
+            .iter()
+            .any(|x| x.starts_with("cg:Z:") || x.starts_with("cs:Z:"))
     }
 
-    fn target_name(&self) -> &str {
-        &self.target_name
+    /// Append `de:f:` and `dv:f:` divergence tags of `stat` before the CIGAR tag
+    pub fn add_divergence(&mut self, stat: &RecStat) {
+        let tags = [
+            format!("de:f:{:.4}", stat.gap_compressed_divergence()),
+            format!("dv:f:{:.4}", stat.per_base_divergence()),
+        ];
+        let at = self
+            .tags
+            .iter()
+            .position(|tag| tag.starts_with("cg:Z:"))
+            .unwrap_or(self.tags.len());
+        self.tags.splice(at..at, tags);
     }
 
-    fn target_length(&self) -> u64 {
-        self.target_length
+    /// Rewrite the `cg:Z:` CIGAR tag in `style`, NM and block length are kept
+    pub fn set_cigar_style(&mut self, style: CigarStyle) {
+        if style == CigarStyle::Extended {
+            return;
+        }
+        for tag in self.tags.iter_mut() {
+            if let Some(cigar) = tag.strip_prefix("cg:Z:") {
+                *tag = String::from("cg:Z:") + &condense_cigar(cigar);
+            }
+        }
     }
 
-    fn target_start(&self) -> u64 {
-        self.target_start
+    /// Set the `tp:A:` type tag, `P` for primary and `S` for secondary,
+    /// an existing one is replaced, otherwise it's inserted before the CIGAR tag
+    pub fn set_primary(&mut self, primary: bool) {
+        let tag = format!("tp:A:{}", if primary { 'P' } else { 'S' });
+        match self.tags.iter_mut().find(|tag| tag.starts_with("tp:A:")) {
+            Some(old) => *old = tag,
+            None => {
+                let at = self
+                    .tags
+                    .iter()
+                    .position(|tag| tag.starts_with("cg:Z:"))
+                    .unwrap_or(self.tags.len());
+                self.tags.insert(at, tag);
+            }
+        }
     }
 
-    fn target_end(&self) -> u64 {
-        self.target_end
+    // short description of the record for error messages
+    pub(crate) fn describe(&self) -> String {
+        format!(
+            "{}:{}-{} {} {}:{}-{}",
+            self.query_name,
+            self.query_start,
+            self.query_end,
+            self.strand,
+            self.target_name,
+            self.target_start,
+            self.target_end
+        )
     }
+}
 
-    fn target_strand(&self) -> Strand {
-        Strand::Positive
-    }
+/// An iterator struct for PAF records
+pub struct Records<'a, R: io::Read> {
+    inner: StringRecordsIter<'a, R>,
+    limit: &'a mut RecordLimit,
+}
 
-    fn get_cigar_str(&self) -> Result<&str, WGAError> {
-        self.tags
-            .iter()
-            .find(|x| x.starts_with("cg:Z:"))
-            .ok_or(WGAError::CigarTagNotFound)
-            .map(|x| x.as_str())
+/// impl Iterator for Records, csv errors are wrapped into WGAError
+impl<'a, R: io::Read> Iterator for Records<'a, R> {
+    type Item = Result<PafRecord, WGAError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.limit.reached() {
+            return None;
+        }
+        let item = self.inner.next().map(|rec| {
+            let rec = rec?;
+            let line = rec.position().map_or(0, |pos| pos.line());
+            PafRecord::from_fields(line, &rec.iter().collect::<Vec<_>>())
+        });
+        self.limit.count(item)
     }
+}
 
-    fn target_align_size(&self) -> u64 {
-        // self.block_length
-        self.target_end - self.target_start
+impl<'a, R: io::Read> Records<'a, R> {
+    /// Group records lazily by query or target name, input should be sorted by the name,
+    /// a name seen in an earlier group is an error
+    pub fn groups(self, by: GroupBy) -> Groups<Self, PafRecord> {
+        Groups::new(self, by)
     }
 
-    fn get_stat(&self) -> Result<RecStat, WGAError> {
-        // just convert cigar to stat
-        let cigar = parse_paf_to_cigar(self)?;
-        Ok(RecStat::from(cigar))
+    /// Group all records by query or target name for unsorted input, see `groups_buffered`
+    pub fn groups_buffered(self, by: GroupBy) -> Result<Vec<(String, Vec<PafRecord>)>, WGAError> {
+        groups_buffered(self, by)
     }
 }
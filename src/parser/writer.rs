@@ -0,0 +1,178 @@
+use crate::errors::WGAError;
+use crate::parser::chain::ChainHeader;
+use crate::parser::cigar::{parse_cigar_to_chain, parse_cigar_to_delta};
+use crate::parser::common::{AlignRecord, Strand};
+use crate::parser::maf::MAFWriter;
+use std::io::{BufWriter, Write};
+
+/// Writer of any AlignRecord in one output format, records are converted by
+/// `convert2maf` or `convert2paf` of the input format
+pub trait RecordWriter {
+    fn write_record(&mut self, rec: &impl AlignRecord) -> Result<(), WGAError>;
+    /// Flush buffered records, call it at the end to catch errors of the last records
+    fn flush(&mut self) -> Result<(), WGAError>;
+    /// End the output with format trailers if any, then flush
+    fn finish(&mut self) -> Result<(), WGAError> {
+        self.flush()
+    }
+}
+
+// MAF needs base sequences, so only MAF input can be written
+impl<W: Write> RecordWriter for MAFWriter<W> {
+    fn write_record(&mut self, rec: &impl AlignRecord) -> Result<(), WGAError> {
+        MAFWriter::write_record(self, &rec.convert2maf()?)
+    }
+
+    fn flush(&mut self) -> Result<(), WGAError> {
+        MAFWriter::flush(self)
+    }
+
+    fn finish(&mut self) -> Result<(), WGAError> {
+        MAFWriter::finish(self)
+    }
+}
+
+/// A PAF writer of tab-delimited records without header
+pub struct PafWriter<W: Write> {
+    inner: csv::Writer<W>,
+}
+
+impl<W: Write> PafWriter<W> {
+    /// Create a new PAF writer
+    pub fn new(inner: W) -> Self {
+        PafWriter {
+            inner: csv::WriterBuilder::new()
+                .flexible(true)
+                .delimiter(b'\t')
+                .has_headers(false)
+                .from_writer(inner),
+        }
+    }
+}
+
+impl<W: Write> RecordWriter for PafWriter<W> {
+    fn write_record(&mut self, rec: &impl AlignRecord) -> Result<(), WGAError> {
+        self.inner.serialize(rec.convert2paf()?)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), WGAError> {
+        self.inner.flush()?;
+        Ok(())
+    }
+}
+
+/// A chain writer, chain ids are given in order from 0 and scores are
+/// from `align_score` of records
+pub struct ChainWriter<W: Write> {
+    inner: BufWriter<W>,
+    chain_id: usize,
+}
+
+impl<W: Write> ChainWriter<W> {
+    /// Create a new chain writer
+    pub fn new(inner: W) -> Self {
+        ChainWriter {
+            inner: BufWriter::new(inner),
+            chain_id: 0,
+        }
+    }
+}
+
+impl<W: Write> RecordWriter for ChainWriter<W> {
+    fn write_record(&mut self, rec: &impl AlignRecord) -> Result<(), WGAError> {
+        // data lines from the CIGAR of PAF, query of PAF is on the forward strand
+        let mut paf = rec.convert2paf()?;
+        (paf.query_start, paf.query_end) = rec.query_forward();
+        let mut header = ChainHeader::try_from(&paf)?;
+        header.set_score(rec.align_score());
+        header.chain_id = self.chain_id;
+        self.chain_id += 1;
+        write!(self.inner, "{}", header)?;
+        parse_cigar_to_chain(&paf, &mut self.inner)?;
+        // additional newline for standard chain format
+        self.inner.write_all(b"\n\n")?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), WGAError> {
+        self.inner.flush()?;
+        Ok(())
+    }
+}
+
+/// A MUMmer delta writer, a `>` header line is written whenever the pair of target and
+/// query changes, so adjacent records of a pair share one header as nucmer output
+pub struct DeltaWriter<W: Write> {
+    inner: BufWriter<W>,
+    // FASTA paths of the first line, written before the first record
+    paths: Option<(String, String)>,
+    pair: Option<(String, String)>,
+}
+
+impl<W: Write> DeltaWriter<W> {
+    /// Create a new delta writer, paths of target and query FASTA are in the first line
+    pub fn new(inner: W, target_path: &str, query_path: &str) -> Self {
+        DeltaWriter {
+            inner: BufWriter::new(inner),
+            paths: Some((target_path.to_string(), query_path.to_string())),
+            pair: None,
+        }
+    }
+
+    fn write_paths(&mut self) -> Result<(), WGAError> {
+        if let Some((target_path, query_path)) = self.paths.take() {
+            writeln!(self.inner, "{} {}\nNUCMER", target_path, query_path)?;
+        }
+        Ok(())
+    }
+}
+
+// t1 0 30 q1 50 80 - 10M1D5M1I14M  ==>  >t1 q1 100 80
+//                                        1 30 80 51 2 2 0
+//                                        11
+//                                        -6
+//                                        0
+// 1-based coordinates, query from end to start on '-' strand,
+// errors and similarity errors are non-matching columns
+impl<W: Write> RecordWriter for DeltaWriter<W> {
+    fn write_record(&mut self, rec: &impl AlignRecord) -> Result<(), WGAError> {
+        self.write_paths()?;
+        // query of PAF is on the forward strand
+        let mut paf = rec.convert2paf()?;
+        (paf.query_start, paf.query_end) = rec.query_forward();
+        let pair = (paf.target_name.clone(), paf.query_name.clone());
+        if self.pair.as_ref() != Some(&pair) {
+            writeln!(
+                self.inner,
+                ">{} {} {} {}",
+                pair.0, pair.1, paf.target_length, paf.query_length
+            )?;
+            self.pair = Some(pair);
+        }
+        let (query_start, query_end) = match paf.strand {
+            Strand::Positive => (paf.query_start + 1, paf.query_end),
+            Strand::Negative => (paf.query_end, paf.query_start + 1),
+        };
+        let errors = paf.block_length.saturating_sub(paf.matches);
+        writeln!(
+            self.inner,
+            "{} {} {} {} {} {} 0",
+            paf.target_start + 1,
+            paf.target_end,
+            query_start,
+            query_end,
+            errors,
+            errors
+        )?;
+        parse_cigar_to_delta(&paf, &mut self.inner)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), WGAError> {
+        // an empty delta still has the header
+        self.write_paths()?;
+        self.inner.flush()?;
+        Ok(())
+    }
+}
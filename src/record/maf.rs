@@ -0,0 +1,414 @@
+use crate::errors::{ParseMafErrKind, WGAError};
+#[cfg(feature = "std")]
+use crate::parser::{cigar::parse_maf_seq_to_cigar, common::RecStat, fasta::FastaIndex};
+#[cfg(feature = "std")]
+use crate::record::paf::PafRecord;
+use crate::record::{parse_str2f64, parse_str2u64, AlignRecord, Strand};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::io::BufRead;
+#[cfg(feature = "std")]
+use std::io::{Read, Seek};
+use std::str::FromStr;
+
+/// A MAF s-line refer to https://genome.ucsc.edu/FAQ/FAQformat.html#format5
+// a score=111
+// s ref    100 10 + 100000 ---AGC-CAT-CATT
+// s contig 0   10 + 10     ---AGC-CAT-CATT
+//
+// a score=222
+// s ref    100 12 + 100000 ---AGC-CAT-CATTTT
+// s contig 0   12 + 12     ---AGC-CAT-CATTTT
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct MAFSLine {
+    pub mode: char,
+    pub name: String,
+    pub start: u64,
+    pub align_size: u64,
+    pub strand: Strand,
+    pub size: u64,
+    pub seq: String,
+}
+
+/// A MAF alignment record refer to https://genome.ucsc.edu/FAQ/FAQformat.html#format5
+/// a pair of a-lines should be a align record
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct MAFRecord {
+    pub score: u64,
+    pub slines: Vec<MAFSLine>,
+}
+
+// query of a block with a single s-line, so accessors of query don't panic
+static EMPTY_SLINE: MAFSLine = MAFSLine {
+    mode: 's',
+    name: String::new(),
+    start: 0,
+    align_size: 0,
+    strand: Strand::Positive,
+    size: 0,
+    seq: String::new(),
+};
+
+impl MAFRecord {
+    // query (second s-line) of the block, empty for a single s-line block
+    pub(crate) fn query_sline(&self) -> &MAFSLine {
+        self.slines.get(1).unwrap_or(&EMPTY_SLINE)
+    }
+}
+
+/// Parse one s-line from a buffer, e.g. `s chr1 0 10 + 100 ACGT-ACGTAC`
+impl FromStr for MAFSLine {
+    type Err = WGAError;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        parse_sline(line.to_string())
+    }
+}
+
+/// Parse the first block of a buffer, lines before the first s-line are skipped as `MAFReader`
+impl FromStr for MAFRecord {
+    type Err = WGAError;
+
+    fn from_str(block: &str) -> Result<Self, Self::Err> {
+        next_record(&mut block.as_bytes()).unwrap_or(Err(WGAError::EmptyRecord))
+    }
+}
+
+/// Parse all blocks of a MAF buffer, header, comment and other lines out of blocks
+/// are skipped as `MAFReader`
+pub fn parse_records(buf: &str) -> impl Iterator<Item = Result<MAFRecord, WGAError>> + '_ {
+    let mut buf = buf.as_bytes();
+    std::iter::from_fn(move || next_record(&mut buf))
+}
+
+// main parse function for s-line
+fn parse_sline(line: String) -> Result<MAFSLine, WGAError> {
+    let mut iter = line.split_whitespace();
+    let mode = match iter.next() {
+        // a token of `split_whitespace` is never empty
+        Some(mode) => mode.chars().next().unwrap_or('s'),
+        None => {
+            return Err(WGAError::ParseMaf(ParseMafErrKind::FiledMissing(
+                "mode".to_string(),
+            )))
+        }
+    };
+    let name = match iter.next() {
+        Some(name) => name.to_string(),
+        None => {
+            return Err(WGAError::ParseMaf(ParseMafErrKind::FiledMissing(
+                "name".to_string(),
+            )))
+        }
+    };
+    let start = match iter.next() {
+        Some(start) => parse_str2u64(start)?,
+        None => {
+            return Err(WGAError::ParseMaf(ParseMafErrKind::FiledMissing(
+                "start".to_string(),
+            )))
+        }
+    };
+    let align_size = match iter.next() {
+        Some(align_size) => parse_str2u64(align_size)?,
+        None => {
+            return Err(WGAError::ParseMaf(ParseMafErrKind::FiledMissing(
+                "align_size".to_string(),
+            )))
+        }
+    };
+    let strand = match iter.next() {
+        Some(strand) => Strand::try_from(strand)?,
+        None => {
+            return Err(WGAError::ParseMaf(ParseMafErrKind::FiledMissing(
+                "strand".to_string(),
+            )))
+        }
+    };
+    let size = match iter.next() {
+        Some(size) => parse_str2u64(size)?,
+        None => {
+            return Err(WGAError::ParseMaf(ParseMafErrKind::FiledMissing(
+                "size".to_string(),
+            )))
+        }
+    };
+    let seq = match iter.next() {
+        Some(seq) => seq.to_string(),
+        None => {
+            return Err(WGAError::ParseMaf(ParseMafErrKind::FiledMissing(
+                "seq".to_string(),
+            )))
+        }
+    };
+    if iter.next().is_some() {
+        return Err(WGAError::ParseMaf(ParseMafErrKind::SurplusField));
+    };
+    Ok(MAFSLine {
+        mode,
+        name,
+        start,
+        align_size,
+        strand,
+        size,
+        seq,
+    })
+}
+
+// impl PartialEq for MAFRecord
+impl PartialOrd<Self> for MAFRecord {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// impl Ord for MAFRecord, see `AlignRecord::align_cmp`
+impl Ord for MAFRecord {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.align_cmp(other)
+    }
+}
+
+/// Score of a block without `a score=`
+pub const DEFAULT_SCORE: u64 = 255;
+
+/// impl Default trait for MAFRecord
+impl Default for MAFRecord {
+    fn default() -> Self {
+        MAFRecord {
+            score: DEFAULT_SCORE,
+            slines: Vec::new(),
+        }
+    }
+}
+
+/// Read the next MAF record from any buffered reader, lines before the first s-line
+/// (a-line, empty lines) are skipped, the block ends before the first line which is not
+/// an s-line or `i`/`e`/`q` line of it, so the line is left in the reader for the next block
+pub fn next_record<B: BufRead>(inner: &mut B) -> Option<Result<MAFRecord, WGAError>> {
+    read_record(inner).transpose()
+}
+
+// each line is peeked before consumed, so a line out of the block is left in the reader
+fn read_record<B: BufRead>(inner: &mut B) -> Result<Option<MAFRecord>, WGAError> {
+    let mut line = String::new();
+    let mut score = DEFAULT_SCORE;
+    // skip lines until the first s-line, the score is from the last a-line
+    loop {
+        match peek_line_kind(inner)? {
+            Some(b's') => break,
+            None => return Ok(None),
+            kind => {
+                line.clear();
+                inner.read_line(&mut line)?;
+                if kind == Some(b'a') {
+                    score = parse_score(&line)?;
+                }
+            }
+        }
+    }
+    let mut mafrecord = MAFRecord {
+        score,
+        slines: Vec::new(),
+    };
+    loop {
+        match peek_line_kind(inner)? {
+            Some(b's') => {
+                line.clear();
+                inner.read_line(&mut line)?;
+                // `\r` of CRLF is stripped with the line ending
+                let sline = line.trim_end_matches(['\n', '\r']).to_string();
+                mafrecord.slines.push(parse_sline(sline)?);
+            }
+            // info lines of s-lines are not kept
+            Some(b'i' | b'e' | b'q') => {
+                line.clear();
+                inner.read_line(&mut line)?;
+            }
+            // an empty line, the next a-line or EOF
+            _ => return Ok(Some(mafrecord)),
+        }
+    }
+}
+
+// first byte of the next line without consuming the line, leading spaces and tabs are consumed
+fn peek_line_kind<B: BufRead>(inner: &mut B) -> Result<Option<u8>, WGAError> {
+    loop {
+        let buf = inner.fill_buf()?;
+        match buf.iter().position(|c| !matches!(c, b' ' | b'\t')) {
+            Some(i) => {
+                let kind = buf[i];
+                inner.consume(i);
+                return Ok(Some(kind));
+            }
+            None if buf.is_empty() => return Ok(None),
+            None => {
+                let len = buf.len();
+                inner.consume(len);
+            }
+        }
+    }
+}
+
+/// Skip empty, comment and `track` lines before a block, so the reader is at its a-line
+/// (or its first s-line if it has none), e.g. to record offsets of blocks in an index
+pub fn skip_to_block<B: BufRead>(inner: &mut B) -> Result<(), WGAError> {
+    let mut line = String::new();
+    while !matches!(peek_line_kind(inner)?, Some(b'a' | b's') | None) {
+        line.clear();
+        inner.read_line(&mut line)?;
+    }
+    Ok(())
+}
+
+// score of a-line like `a score=3400.5 pass=2`, float score is rounded
+fn parse_score(line: &str) -> Result<u64, WGAError> {
+    match line
+        .split_whitespace()
+        .find_map(|field| field.strip_prefix("score="))
+    {
+        Some(score) => Ok(parse_str2f64(score)?.round().max(0.0) as u64),
+        None => Ok(DEFAULT_SCORE),
+    }
+}
+
+/// impl AlignRecord Trait for PafRecord
+impl AlignRecord for MAFRecord {
+    fn query_name(&self) -> &str {
+        self.query_sline().name.as_str()
+    }
+
+    fn query_length(&self) -> u64 {
+        self.query_sline().size
+    }
+
+    // MAF coordinates on '-' strand count from the end of the reverse complement,
+    // PAF always wants forward-strand coordinates with start < end:
+    // e.g. size=50, start=20, align_size=8, '-' => [50-20-8, 50-20) = [22, 30)
+    fn query_start(&self) -> u64 {
+        match self.query_strand() {
+            Strand::Positive => self.query_sline().start,
+            Strand::Negative => {
+                let query = self.query_sline();
+                query.size - query.start - query.align_size
+            }
+        }
+    }
+
+    fn query_end(&self) -> u64 {
+        match self.query_strand() {
+            Strand::Positive => self.query_sline().start + self.query_sline().align_size,
+            Strand::Negative => self.query_sline().size - self.query_sline().start,
+        }
+    }
+
+    fn query_strand(&self) -> Strand {
+        self.query_sline().strand
+    }
+
+    fn target_name(&self) -> &str {
+        self.slines[0].name.as_str()
+    }
+
+    fn target_length(&self) -> u64 {
+        self.slines[0].size
+    }
+
+    fn target_start(&self) -> u64 {
+        self.slines[0].start
+    }
+
+    fn target_end(&self) -> u64 {
+        self.slines[0].start + self.slines[0].align_size
+    }
+
+    fn target_strand(&self) -> Strand {
+        self.slines[0].strand
+    }
+
+    fn target_align_size(&self) -> u64 {
+        self.slines[0].align_size
+    }
+
+    #[cfg(feature = "std")]
+    fn get_cigar_string(&self) -> String {
+        parse_maf_seq_to_cigar(self, false).cigar_string
+    }
+
+    #[cfg(feature = "std")]
+    fn convert2maf(&self) -> Result<MAFRecord, WGAError> {
+        Ok(self.clone())
+    }
+
+    #[cfg(feature = "std")]
+    fn convert2paf(&self) -> Result<PafRecord, WGAError> {
+        self.check_pairwise()?;
+        // PAF target is always '+', flip the block by `maf-normalize --flip-to-target-plus`
+        debug_assert_eq!(
+            self.target_strand(),
+            Strand::Positive,
+            "target `{}` is on '-' strand",
+            self.target_name()
+        );
+        let cigar = parse_maf_seq_to_cigar(self, false);
+        let cigar_string = String::from("cg:Z:") + &cigar.cigar_string;
+        let matches = cigar.match_count as u64;
+        let block_length = cigar.block_length();
+        let edit_dist = block_length - matches;
+        let nm_tag = String::from("NM:i:") + &*edit_dist.to_string();
+
+        Ok(PafRecord {
+            query_name: self.query_name().to_string(),
+            query_length: self.query_length(),
+            query_start: self.query_start(),
+            query_end: self.query_end(),
+            strand: self.query_strand(),
+            target_name: self.target_name().to_string(),
+            target_length: self.target_length(),
+            target_start: self.target_start(),
+            target_end: self.target_end(),
+            matches,
+            block_length,
+            mapq: 255,
+            tags: vec![nm_tag, cigar_string],
+        })
+    }
+
+    fn query_seq(&self) -> &str {
+        &self.query_sline().seq
+    }
+
+    fn target_seq(&self) -> &str {
+        &self.slines[0].seq
+    }
+
+    // sequences are inline, FASTA is not needed
+    #[cfg(feature = "std")]
+    fn query_seq_from_fasta<R: Read + Seek>(
+        &self,
+        _fasta: &mut FastaIndex<R>,
+    ) -> Result<Vec<u8>, WGAError> {
+        self.check_pairwise()?;
+        Ok(self.query_seq().bytes().filter(|c| *c != b'-').collect())
+    }
+
+    #[cfg(feature = "std")]
+    fn target_seq_from_fasta<R: Read + Seek>(
+        &self,
+        _fasta: &mut FastaIndex<R>,
+    ) -> Result<Vec<u8>, WGAError> {
+        Ok(self.target_seq().bytes().filter(|c| *c != b'-').collect())
+    }
+
+    #[cfg(feature = "std")]
+    fn get_stat(&self) -> Result<RecStat, WGAError> {
+        self.check_pairwise()?;
+        // just convert cigar to stat
+        let cigar = parse_maf_seq_to_cigar(self, false);
+        Ok(RecStat::from(cigar))
+    }
+
+    fn align_score(&self) -> f64 {
+        self.score as f64
+    }
+}
@@ -0,0 +1,279 @@
+//! Record model of alignments shared by all formats, `PafRecord` and `MAFRecord` can be
+//! parsed from in-memory buffers, file-backed readers and conversions between formats
+//! are behind the `std` feature, e.g. to reuse records in WASM
+
+pub mod maf;
+pub mod paf;
+
+use crate::errors::WGAError;
+#[cfg(feature = "std")]
+use crate::parser::{
+    common::{IdentityMode, RecStat},
+    fasta::FastaIndex,
+};
+#[cfg(feature = "std")]
+use crate::record::{maf::MAFRecord, paf::PafRecord};
+#[cfg(feature = "std")]
+use crate::tools::interval::Interval;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+#[cfg(feature = "std")]
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    io::{Read, Seek},
+};
+
+pub fn parse_str2u64(s: &str) -> Result<u64, WGAError> {
+    match s.parse::<u64>() {
+        Ok(n) => Ok(n),
+        Err(_) => Err(WGAError::ParseIntError(s.to_string())),
+    }
+}
+
+pub fn parse_str2f64(s: &str) -> Result<f64, WGAError> {
+    match s.parse::<f64>() {
+        Ok(n) => Ok(n),
+        Err(_) => Err(WGAError::ParseFloatError(s.to_string())),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Copy, Eq, Hash, Default)]
+pub enum Strand {
+    #[default]
+    Positive,
+    Negative,
+}
+
+/// Serialized as `+`/`-` in all formats, same as PAF and MAF files
+impl Serialize for Strand {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(match self {
+            Strand::Positive => "+",
+            Strand::Negative => "-",
+        })
+    }
+}
+
+/// Tokens of `Strand::try_from` and variant names `Positive`/`Negative` are accepted,
+/// so an index JSON written with variant names is still readable
+impl<'de> Deserialize<'de> for Strand {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct StrandVisitor;
+
+        impl de::Visitor<'_> for StrandVisitor {
+            type Value = Strand;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a strand `+` or `-`")
+            }
+
+            fn visit_str<E: de::Error>(self, s: &str) -> Result<Strand, E> {
+                match s {
+                    "Positive" => Ok(Strand::Positive),
+                    "Negative" => Ok(Strand::Negative),
+                    _ => Strand::try_from(s)
+                        .map_err(|_| E::invalid_value(de::Unexpected::Str(s), &self)),
+                }
+            }
+        }
+
+        deserializer.deserialize_str(StrandVisitor)
+    }
+}
+
+/// Parse strand token, `+`/`-` or `1`/`-1` used by some MAF variants,
+/// others (e.g. `0` for unknown strand) are invalid rather than a default
+impl TryFrom<&str> for Strand {
+    type Error = WGAError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        match s {
+            "+" | "1" => Ok(Strand::Positive),
+            "-" | "-1" => Ok(Strand::Negative),
+            _ => Err(WGAError::InvalidStrand(s.to_string())),
+        }
+    }
+}
+
+impl FromStr for Strand {
+    type Err = WGAError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Strand::try_from(s)
+    }
+}
+
+impl Strand {
+    /// Get the opposite strand
+    pub fn flip(self) -> Strand {
+        match self {
+            Strand::Positive => Strand::Negative,
+            Strand::Negative => Strand::Positive,
+        }
+    }
+
+    /// Reverse complement a sequence, case and IUPAC codes are kept,
+    /// other bytes like `-` are reversed only
+    pub fn complement_seq(seq: &[u8]) -> Vec<u8> {
+        seq.iter()
+            .rev()
+            .map(|&c| {
+                let comp = match c.to_ascii_uppercase() {
+                    b'A' => b'T',
+                    b'T' | b'U' => b'A',
+                    b'C' => b'G',
+                    b'G' => b'C',
+                    b'R' => b'Y',
+                    b'Y' => b'R',
+                    b'K' => b'M',
+                    b'M' => b'K',
+                    b'B' => b'V',
+                    b'V' => b'B',
+                    b'D' => b'H',
+                    b'H' => b'D',
+                    // N, S, W and others are self-complementary
+                    other => other,
+                };
+                if c.is_ascii_lowercase() {
+                    comp.to_ascii_lowercase()
+                } else {
+                    comp
+                }
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for Strand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Strand::Positive => write!(f, "+"),
+            Strand::Negative => write!(f, "-"),
+        }
+    }
+}
+
+/// Accessors of an alignment in any format, methods of CIGAR, FASTA and conversions
+/// between formats need the `std` feature
+pub trait AlignRecord {
+    fn query_name(&self) -> &str;
+    fn query_length(&self) -> u64;
+    fn query_start(&self) -> u64;
+    fn query_end(&self) -> u64;
+    fn query_strand(&self) -> Strand;
+    fn target_name(&self) -> &str;
+    fn target_length(&self) -> u64;
+    fn target_start(&self) -> u64;
+    fn target_end(&self) -> u64;
+    fn target_strand(&self) -> Strand;
+    fn target_align_size(&self) -> u64;
+    #[cfg(feature = "std")]
+    fn get_cigar_str(&self) -> Result<Cow<'_, str>, WGAError> {
+        Ok(Cow::Borrowed("*"))
+    }
+    #[cfg(feature = "std")]
+    fn get_cigar_string(&self) -> String {
+        "*".to_string()
+    }
+    #[cfg(feature = "std")]
+    fn convert2paf(&self) -> Result<PafRecord, WGAError> {
+        Ok(PafRecord::default())
+    }
+    /// MAF block of the record, formats without base sequences are not supported
+    #[cfg(feature = "std")]
+    fn convert2maf(&self) -> Result<MAFRecord, WGAError> {
+        Err(WGAError::NotImplemented)
+    }
+    #[cfg(feature = "std")]
+    fn convert2bam(&self, _name_id_map: &HashMap<&str, u64>) {}
+    fn query_seq(&self) -> &str {
+        ""
+    }
+    fn target_seq(&self) -> &str {
+        ""
+    }
+    /// Aligned query bases from FASTA by forward coordinates,
+    /// reverse complemented on '-' strand, for records without inline sequences
+    #[cfg(feature = "std")]
+    fn query_seq_from_fasta<R: Read + Seek>(
+        &self,
+        fasta: &mut FastaIndex<R>,
+    ) -> Result<Vec<u8>, WGAError> {
+        fasta.fetch(
+            self.query_name(),
+            self.query_start(),
+            self.query_end(),
+            self.query_strand(),
+        )
+    }
+    /// Aligned target bases from FASTA, same as `query_seq_from_fasta`
+    #[cfg(feature = "std")]
+    fn target_seq_from_fasta<R: Read + Seek>(
+        &self,
+        fasta: &mut FastaIndex<R>,
+    ) -> Result<Vec<u8>, WGAError> {
+        fasta.fetch(
+            self.target_name(),
+            self.target_start(),
+            self.target_end(),
+            self.target_strand(),
+        )
+    }
+    #[cfg(feature = "std")]
+    fn get_stat(&self) -> Result<RecStat, WGAError> {
+        Ok(RecStat::default())
+    }
+    /// Columns of the alignment recomputed from the CIGAR, see `Cigar::block_length`,
+    /// PAF `block_length` written by any format should be equal to this
+    #[cfg(feature = "std")]
+    fn block_length(&self) -> Result<u64, WGAError> {
+        Ok(self.get_stat()?.block_length())
+    }
+    /// Query `[start, end)` on the forward strand, as PAF and MAF accessors
+    fn query_forward(&self) -> (u64, u64) {
+        (self.query_start(), self.query_end())
+    }
+    /// Target `[start, end)` of the record
+    #[cfg(feature = "std")]
+    fn target_interval(&self) -> Interval {
+        Interval::new(self.target_start(), self.target_end())
+    }
+    /// Query `[start, end)` on the forward strand, see `query_forward`
+    #[cfg(feature = "std")]
+    fn query_interval(&self) -> Interval {
+        self.query_forward().into()
+    }
+    /// If the records overlap on the same target sequence
+    #[cfg(feature = "std")]
+    fn overlaps<T: AlignRecord>(&self, other: &T) -> bool
+    where
+        Self: Sized,
+    {
+        self.target_name() == other.target_name()
+            && self.target_interval().overlaps(&other.target_interval())
+    }
+    /// Score of the alignment, 0 if the format has no score
+    fn align_score(&self) -> f64 {
+        0.0
+    }
+    /// Identity of the record by `mode`, see `IdentityMode`
+    #[cfg(feature = "std")]
+    fn identity(&self, mode: IdentityMode) -> Result<f64, WGAError> {
+        Ok(self.get_stat()?.identity(mode))
+    }
+    /// Order of records for sorting in any format, ties are broken in order:
+    /// 1. target name, natural order, e.g. chr2 < chr10
+    /// 2. target start
+    /// 3. query name, natural order
+    fn align_cmp(&self, other: &Self) -> Ordering
+    where
+        Self: Sized,
+    {
+        natord::compare(self.target_name(), other.target_name())
+            .then_with(|| self.target_start().cmp(&other.target_start()))
+            .then_with(|| natord::compare(self.query_name(), other.query_name()))
+    }
+}
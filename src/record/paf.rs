@@ -0,0 +1,242 @@
+use crate::errors::WGAError;
+#[cfg(feature = "std")]
+use crate::parser::cigar::{cs_to_cigar, parse_paf_to_cigar};
+#[cfg(feature = "std")]
+use crate::parser::common::{IdentityMode, RecStat};
+use crate::record::{AlignRecord, Strand};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "std")]
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
+/// A PAF record refer to https://github.com/lh3/miniasm/blob/master/PAF.md
+pub struct PafRecord {
+    pub query_name: String,
+    pub query_length: u64,
+    pub query_start: u64,
+    pub query_end: u64,
+    pub strand: Strand,
+    pub target_name: String,
+    pub target_length: u64,
+    pub target_start: u64,
+    pub target_end: u64,
+    pub matches: u64,
+    pub block_length: u64,
+    pub mapq: u64,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+// impl PartialOrd for PafRecord
+impl PartialOrd<Self> for PafRecord {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// impl Ord for PafRecord, same as MAFRecord, see `AlignRecord::align_cmp`
+impl Ord for PafRecord {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.align_cmp(other)
+    }
+}
+
+/// Parse one PAF line from a buffer, e.g. a line of a file fetched by a browser,
+/// columns are checked as `Records`
+impl FromStr for PafRecord {
+    type Err = WGAError;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let line = line.trim_end_matches(['\n', '\r']);
+        PafRecord::from_fields(0, &line.split('\t').collect::<Vec<_>>())
+    }
+}
+
+impl PafRecord {
+    /// Parse a record from its columns, `line` (1-based, 0 if unknown) is named in errors
+    pub fn from_fields(line: u64, fields: &[&str]) -> Result<Self, WGAError> {
+        check_columns(line, fields)?;
+        // numbers are already checked
+        let num = |idx: usize| fields[idx].parse::<u64>().unwrap_or_default();
+        Ok(PafRecord {
+            query_name: fields[0].to_string(),
+            query_length: num(1),
+            query_start: num(2),
+            query_end: num(3),
+            strand: Strand::try_from(fields[4])?,
+            target_name: fields[5].to_string(),
+            target_length: num(6),
+            target_start: num(7),
+            target_end: num(8),
+            matches: num(9),
+            block_length: num(10),
+            mapq: num(11),
+            tags: fields[PAF_COLUMNS.len()..]
+                .iter()
+                .map(|tag| tag.to_string())
+                .collect(),
+        })
+    }
+}
+
+/// Parse all records of a PAF buffer, empty lines and `#` comment lines are skipped
+/// as `PAFReader`
+pub fn parse_records(buf: &str) -> impl Iterator<Item = Result<PafRecord, WGAError>> + '_ {
+    buf.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(idx, line)| {
+            PafRecord::from_fields(idx as u64 + 1, &line.split('\t').collect::<Vec<_>>())
+        })
+}
+
+// names of the 12 mandatory columns of PAF
+const PAF_COLUMNS: [&str; 12] = [
+    "query_name",
+    "query_length",
+    "query_start",
+    "query_end",
+    "strand",
+    "target_name",
+    "target_length",
+    "target_start",
+    "target_end",
+    "matches",
+    "block_length",
+    "mapq",
+];
+
+// check mandatory columns and SAM-like `TT:T:VALUE` tags before parsing,
+// so the error names the bad column
+fn check_columns(line: u64, fields: &[&str]) -> Result<(), WGAError> {
+    if fields.len() < PAF_COLUMNS.len() {
+        return Err(WGAError::PafTooFewColumns(line, fields.len()));
+    }
+    let invalid = |idx: usize, name: &str, value: &str| {
+        WGAError::PafInvalidColumn(line, idx + 1, name.to_string(), value.to_string())
+    };
+    for (idx, (name, value)) in PAF_COLUMNS.iter().zip(fields).enumerate() {
+        let valid = match idx {
+            0 | 5 => !value.is_empty(),
+            4 => *value == "+" || *value == "-",
+            _ => value.parse::<u64>().is_ok(),
+        };
+        if !valid {
+            return Err(invalid(idx, name, value));
+        }
+    }
+    for (idx, tag) in fields.iter().enumerate().skip(PAF_COLUMNS.len()) {
+        let bytes = tag.as_bytes();
+        let valid = bytes.len() >= 5
+            && bytes[0].is_ascii_alphabetic()
+            && bytes[1].is_ascii_alphanumeric()
+            && bytes[2] == b':'
+            && bytes[4] == b':';
+        if !valid {
+            return Err(invalid(idx, "tag", tag));
+        }
+    }
+    Ok(())
+}
+
+/// impl AlignRecord Trait for PafRecord
+impl AlignRecord for PafRecord {
+    fn query_name(&self) -> &str {
+        &self.query_name
+    }
+
+    fn query_length(&self) -> u64 {
+        self.query_length
+    }
+
+    fn query_start(&self) -> u64 {
+        self.query_start
+    }
+
+    fn query_end(&self) -> u64 {
+        self.query_end
+    }
+
+    fn query_strand(&self) -> Strand {
+        self.strand
+    }
+
+    fn target_name(&self) -> &str {
+        &self.target_name
+    }
+
+    fn target_length(&self) -> u64 {
+        self.target_length
+    }
+
+    fn target_start(&self) -> u64 {
+        self.target_start
+    }
+
+    fn target_end(&self) -> u64 {
+        self.target_end
+    }
+
+    fn target_strand(&self) -> Strand {
+        Strand::Positive
+    }
+
+    // `cg:Z:` is preferred, `cs:Z:` is converted into a `cg:Z:` CIGAR
+    #[cfg(feature = "std")]
+    fn get_cigar_str(&self) -> Result<Cow<'_, str>, WGAError> {
+        if let Some(cg) = self.tags.iter().find(|x| x.starts_with("cg:Z:")) {
+            return Ok(Cow::Borrowed(cg.as_str()));
+        }
+        match self.tags.iter().find(|x| x.starts_with("cs:Z:")) {
+            Some(cs) => Ok(Cow::Owned(format!("cg:Z:{}", cs_to_cigar(cs)?))),
+            None => Err(WGAError::CigarRequired(self.describe())),
+        }
+    }
+
+    fn target_align_size(&self) -> u64 {
+        // self.block_length
+        self.target_end - self.target_start
+    }
+
+    #[cfg(feature = "std")]
+    fn get_cigar_string(&self) -> String {
+        match self.get_cigar_str() {
+            Ok(cigar) => cigar.trim_start_matches("cg:Z:").to_string(),
+            Err(_) => "*".to_string(),
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn convert2paf(&self) -> Result<PafRecord, WGAError> {
+        Ok(self.clone())
+    }
+
+    #[cfg(feature = "std")]
+    fn get_stat(&self) -> Result<RecStat, WGAError> {
+        // just convert cigar to stat
+        let cigar = parse_paf_to_cigar(self)?;
+        Ok(RecStat::from(cigar))
+    }
+
+    #[cfg(feature = "std")]
+    fn identity(&self, mode: IdentityMode) -> Result<f64, WGAError> {
+        // column 10/11 is BLAST-like identity, no need to parse CIGAR
+        match mode {
+            IdentityMode::Blast if self.block_length > 0 => {
+                Ok(self.matches as f64 / self.block_length as f64)
+            }
+            _ => Ok(self.get_stat()?.identity(mode)),
+        }
+    }
+
+    fn align_score(&self) -> f64 {
+        // DP score of minimap2, MAPQ if absent
+        self.tags
+            .iter()
+            .find_map(|tag| tag.strip_prefix("AS:i:"))
+            .and_then(|score| score.parse().ok())
+            .unwrap_or(self.mapq as f64)
+    }
+}
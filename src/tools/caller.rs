@@ -3,7 +3,9 @@ use crate::parser::cigar::cigar_cat_ext;
 use crate::parser::common::{AlignRecord, Strand};
 use crate::parser::maf::{MAFReader, MAFRecord};
 use crate::tools::index::MafIndex;
+use crate::tools::stat::species_of;
 use itertools::Itertools;
+use log::warn;
 use noodles::vcf;
 use noodles::vcf::{
     header::{
@@ -14,12 +16,15 @@ use noodles::vcf::{
         Number,
     },
     record::{
-        genotypes::keys::key as gtkey, info::field::key as infokey, Info as recinfo, Position,
+        genotypes::{keys::key as gtkey, sample::Value as GtValue, Keys},
+        info::field::key as infokey,
+        Genotypes, Info as recinfo, Position,
     },
     Header, Record,
 };
 use rayon::iter::ParallelIterator;
 use rayon::prelude::*;
+use std::collections::BTreeMap;
 use std::io::{Read, Write};
 
 ///
@@ -29,7 +34,7 @@ use std::io::{Read, Write};
 /// AC--TGCTAACTGGGACG
 /// within alignment: snp | ins | del | tandem expansion | tandem contraction | Repeat expansion | Repeat contraction
 /// between alignment: INS | DEL | Repeat expansion | Repeat contraction
-
+///
 // main function, it return a Result<(), WGAErr>
 // NOTE: but other functions took anyhow, bucause noodles::vcf's error' organization is too complex
 // and it will not be error in 99.9% cases
@@ -145,10 +150,7 @@ fn get_variant_rec(
     };
 
     let infos: recinfo = match info {
-        Some(info) => match info.parse() {
-            Ok(infos) => infos,
-            Err(_) => recinfo::default(),
-        },
+        Some(info) => info.parse().unwrap_or_default(),
         None => recinfo::default(),
     };
     Ok(Record::builder()
@@ -378,3 +380,208 @@ fn call_within_var(
     }
     Ok(var_recs)
 }
+
+// a small variant of one block, alleles are merged across queries by (pos, ref)
+struct BlockVariant {
+    alts: Vec<String>,
+    // allele index of each sample, None for samples absent in the block
+    gts: Vec<Option<usize>>,
+}
+
+/// Convert MAF to VCF of SNPs and indels of each query s-line relative to the
+/// reference (first s-line), one haploid sample per query species (`species_of`)
+///
+/// Multi-allelic: variants of different queries with the same POS and REF in one block
+/// are merged into one record with multiple ALT, samples present in the block without
+/// that variant are `0` and absent samples are `.`. Indels are left-anchored on the
+/// previous reference base, so indels at the start of a block are skipped. SNPs with
+/// non-ACGT bases are skipped. A species with more than one s-line in a block keeps
+/// the first one.
+// ref: ACG-TTA
+// A  : ATGCT-A   =>  2 C T  GT:1 (SNP)  3 G GC GT:1 (INS)  4 TT T GT:1 (DEL)
+pub fn maf2vcf<R: Read + Send>(
+    mafreader: &mut MAFReader<R>,
+    writer: &mut dyn Write,
+) -> Result<(), WGAError> {
+    let mut mafrecords = mafreader.records().collect::<Result<Vec<_>, WGAError>>()?;
+    mafrecords.sort();
+
+    // contigs of reference and samples of query species in order of appearance
+    let mut contigs: Vec<(String, u64)> = Vec::new();
+    let mut samples: Vec<String> = Vec::new();
+    for rec in &mafrecords {
+        // columns of all s-lines are walked together, checked before any output
+        rec.check_width()?;
+        if !contigs.iter().any(|(name, _)| name == rec.target_name()) {
+            contigs.push((rec.target_name().to_string(), rec.target_length()));
+        }
+        for sline in rec.slines.iter().skip(1) {
+            let species = species_of(&sline.name);
+            if !samples.iter().any(|s| s == species) {
+                samples.push(species.to_string());
+            }
+        }
+    }
+    let mut header =
+        Header::builder().add_format(gtkey::GENOTYPE, Map::<Format>::from(&gtkey::GENOTYPE));
+    for sample in &samples {
+        header = header.add_sample_name(sample.clone());
+    }
+    let mut header = header.build();
+    for (name, size) in contigs {
+        let mut contigmap = Map::<Contig>::new();
+        *contigmap.length_mut() = Some(size as usize);
+        header
+            .contigs_mut()
+            .insert(name.parse().map_err(anyhow::Error::from)?, contigmap);
+    }
+
+    let mut vcf_wtr = vcf::Writer::new(writer);
+    vcf_wtr.write_header(&header)?;
+    for rec in &mafrecords {
+        if rec.target_strand() == Strand::Negative {
+            warn!(
+                "reference {}:{} is on '-' strand, skip the block",
+                rec.target_name(),
+                rec.target_start()
+            );
+            continue;
+        }
+        for ((pos, ref_allele), variant) in block_variants(rec, &samples) {
+            let record = get_haploid_rec(rec.target_name(), pos as usize, &ref_allele, &variant)?;
+            vcf_wtr.write_record(&header, &record)?;
+        }
+    }
+    Ok(())
+}
+
+// a VCF record with haploid GT of all samples, absent samples are `.`
+fn get_haploid_rec(
+    chro: &str,
+    pos: usize,
+    ref_base: &str,
+    variant: &BlockVariant,
+) -> anyhow::Result<Record> {
+    let keys = Keys::try_from(vec![gtkey::GENOTYPE])?;
+    let values = variant
+        .gts
+        .iter()
+        .map(|gt| vec![gt.map(|gt| GtValue::String(gt.to_string()))])
+        .collect();
+    Ok(Record::builder()
+        .set_chromosome(chro.parse()?)
+        .set_position(Position::from(pos))
+        .set_reference_bases(ref_base.parse()?)
+        .set_alternate_bases(variant.alts.join(",").parse()?)
+        .set_genotypes(Genotypes::new(keys, values))
+        .build()?)
+}
+
+// variants of all queries in a block, keyed and sorted by (1-based pos, ref allele)
+fn block_variants(rec: &MAFRecord, samples: &[String]) -> BTreeMap<(u64, String), BlockVariant> {
+    let mut variants: BTreeMap<(u64, String), BlockVariant> = BTreeMap::new();
+    let t_seq = rec.target_seq().as_bytes();
+    // 1-based reference position of each column, the last base if column is a gap
+    let mut ref_pos = Vec::with_capacity(t_seq.len());
+    let mut pos = rec.target_start();
+    for &c in t_seq {
+        if c != b'-' {
+            pos += 1;
+        }
+        ref_pos.push(pos);
+    }
+
+    // samples present in this block, the first s-line of a species is used
+    let mut present = vec![None; samples.len()];
+    for (ord, sline) in rec.slines.iter().enumerate().skip(1) {
+        let species = species_of(&sline.name);
+        if let Some(idx) = samples.iter().position(|s| s == species) {
+            present[idx].get_or_insert(ord);
+        }
+    }
+
+    for (idx, ord) in present.iter().enumerate() {
+        let Some(ord) = ord else { continue };
+        let q_seq = rec.slines[*ord].seq.as_bytes();
+        for (pos, ref_allele, alt) in query_variants(t_seq, q_seq, &ref_pos) {
+            let variant = variants
+                .entry((pos, ref_allele))
+                .or_insert_with(|| BlockVariant {
+                    alts: Vec::new(),
+                    gts: present.iter().map(|p| p.map(|_| 0)).collect(),
+                });
+            let allele = match variant.alts.iter().position(|a| *a == alt) {
+                Some(i) => i + 1,
+                None => {
+                    variant.alts.push(alt);
+                    variant.alts.len()
+                }
+            };
+            variant.gts[idx] = Some(allele);
+        }
+    }
+    variants
+}
+
+// walk columns of a reference and a query, return (1-based pos, ref, alt) of variants
+fn query_variants(t_seq: &[u8], q_seq: &[u8], ref_pos: &[u64]) -> Vec<(u64, String, String)> {
+    let base = |c: u8| match c.to_ascii_uppercase() {
+        c @ (b'A' | b'C' | b'G' | b'T') => c as char,
+        _ => 'N',
+    };
+    let mut vars = Vec::new();
+    // column of the last reference base as anchor of indels
+    let mut anchor: Option<usize> = None;
+    let mut col = 0;
+    while col < t_seq.len() {
+        match (t_seq[col] == b'-', q_seq[col] == b'-') {
+            (true, true) => col += 1,
+            (false, false) => {
+                let (t, q) = (base(t_seq[col]), base(q_seq[col]));
+                if t != q && t != 'N' && q != 'N' {
+                    vars.push((ref_pos[col], t.to_string(), q.to_string()));
+                }
+                anchor = Some(col);
+                col += 1;
+            }
+            (true, false) => {
+                // insertion: run of reference gaps
+                let end = (col..t_seq.len())
+                    .find(|&c| t_seq[c] != b'-')
+                    .unwrap_or(t_seq.len());
+                if let Some(anchor) = anchor {
+                    let anchor_base = base(t_seq[anchor]).to_string();
+                    let inserted = q_seq[col..end]
+                        .iter()
+                        .filter(|&&c| c != b'-')
+                        .map(|&c| base(c))
+                        .collect::<String>();
+                    vars.push((
+                        ref_pos[anchor],
+                        anchor_base.clone(),
+                        anchor_base + &inserted,
+                    ));
+                }
+                col = end;
+            }
+            (false, true) => {
+                // deletion: run of query gaps
+                let end = (col..q_seq.len())
+                    .find(|&c| q_seq[c] != b'-')
+                    .unwrap_or(q_seq.len());
+                let deleted = t_seq[col..end]
+                    .iter()
+                    .filter(|&&c| c != b'-')
+                    .map(|&c| base(c))
+                    .collect::<String>();
+                if let Some(anchor) = anchor {
+                    let anchor_base = base(t_seq[anchor]).to_string();
+                    vars.push((ref_pos[anchor], anchor_base.clone() + &deleted, anchor_base));
+                }
+                anchor = (col..end).rev().find(|&c| t_seq[c] != b'-');
+                col = end;
+            }
+        }
+    }
+    vars
+}
@@ -0,0 +1,80 @@
+use crate::{errors::WGAError, parser::chain::ChainRecord};
+use log::warn;
+use std::{collections::BTreeMap, io::Write};
+
+// min, median, mean and max of values, None if empty
+fn distribution(mut values: Vec<f64>) -> Option<[f64; 4]> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_unstable_by(f64::total_cmp);
+    let n = values.len();
+    let median = (values[(n - 1) / 2] + values[n / 2]) / 2.0;
+    let mean = values.iter().sum::<f64>() / n as f64;
+    Some([values[0], median, mean, values[n - 1]])
+}
+
+// main function of chain stat, a summary of chains followed by a histogram of block sizes,
+// bins are `[start, end)` of `bin_width`, only non-empty bins are written
+// #metric  value            #bin_start  bin_end  blocks
+// chains   2          ==>   0           100      3
+// ...                       200         300      1
+pub fn chain_stat<I>(records: I, writer: &mut dyn Write, bin_width: u64) -> Result<(), WGAError>
+where
+    I: Iterator<Item = Result<ChainRecord, WGAError>>,
+{
+    let mut scores = Vec::new();
+    let mut blocks_per_chain = Vec::new();
+    let mut aligned_target_bases = 0;
+    let mut histogram: BTreeMap<u64, u64> = BTreeMap::new();
+    for rec in records {
+        let rec = rec?;
+        scores.push(rec.header.score());
+        blocks_per_chain.push(rec.lines.len() as f64);
+        for (size, _, _) in rec.blocks() {
+            aligned_target_bases += size;
+            *histogram.entry(size / bin_width).or_default() += 1;
+        }
+    }
+    if scores.is_empty() {
+        warn!("no chains in input");
+    }
+
+    let mut summary = vec![
+        ("chains".to_string(), scores.len().to_string()),
+        (
+            "blocks".to_string(),
+            histogram.values().sum::<u64>().to_string(),
+        ),
+        (
+            "aligned_target_bases".to_string(),
+            aligned_target_bases.to_string(),
+        ),
+    ];
+    for (name, values) in [("score", scores), ("blocks_per_chain", blocks_per_chain)] {
+        let stats = distribution(values);
+        for (i, stat) in ["min", "median", "mean", "max"].iter().enumerate() {
+            let value = match stats {
+                Some(stats) => format!("{:.2}", stats[i]),
+                None => "NA".to_string(),
+            };
+            summary.push((format!("{}_{}", name, stat), value));
+        }
+    }
+    writeln!(writer, "#metric\tvalue")?;
+    for (k, v) in summary.iter() {
+        writeln!(writer, "{}\t{}", k, v)?;
+    }
+    writeln!(writer, "#bin_start\tbin_end\tblocks")?;
+    for (bin, count) in histogram {
+        writeln!(
+            writer,
+            "{}\t{}\t{}",
+            bin * bin_width,
+            (bin + 1) * bin_width,
+            count
+        )?;
+    }
+    writer.flush()?;
+    Ok(())
+}
@@ -5,6 +5,7 @@ use crate::{
         maf::{MAFReader, MAFRecord, MAFSLine, MAFWriter},
     },
 };
+use log::info;
 use std::io::{Read, Write};
 
 // mian function of chunk maf
@@ -27,7 +28,7 @@ pub fn chunk_maf<R: Read + Send>(
     let mut mafwtr = MAFWriter::new(writer);
     // write header
     let header = format!("#maf version=1.6 split_length={}", chunk_length);
-    mafwtr.write_header(header)?;
+    mafwtr.write_header(&reader.header_with(header))?;
 
     // chunk each block
     for rec in reader.records() {
@@ -56,6 +57,7 @@ pub fn chunk_maf<R: Read + Send>(
         mafwtr.write_record(&new_rec)?;
     }
 
+    mafwtr.finish()?;
     Ok(())
 }
 
@@ -87,3 +89,74 @@ fn emit_new_maf_rec(
     }
     Ok(new_rec)
 }
+
+// main function of split maf into files, boundaries always fall between blocks
+// a block is never cut, so a chunk may exceed `max_size` when a single block does
+// blocks=2
+// a1 a2 a3 a4 a5 => [a1 a2] [a3 a4] [a5]
+pub fn split_maf<R, F>(
+    mut reader: MAFReader<R>,
+    max_blocks: Option<u64>,
+    max_size: Option<u64>,
+    mut new_writer: F,
+) -> Result<usize, WGAError>
+where
+    R: Read + Send,
+    F: FnMut(usize) -> Result<Box<dyn Write>, WGAError>,
+{
+    // the original header is written to each chunk
+    let header = match reader.header.is_empty() {
+        true => vec!["##maf version=1".to_string()],
+        false => reader.header.clone(),
+    };
+
+    let mut chunk_idx = 0;
+    let mut writer: Option<Box<dyn Write>> = None;
+    let mut blocks = 0;
+    let mut bytes = 0;
+    let mut buf = Vec::new();
+    for rec in reader.records() {
+        let rec = rec?;
+        // serialize first to know the size of block
+        buf.clear();
+        let mut mafwtr = MAFWriter::new(&mut buf).with_eof(false);
+        mafwtr.write_record(&rec)?;
+        mafwtr.flush()?;
+        drop(mafwtr);
+
+        let full = blocks > 0
+            && (max_blocks.is_some_and(|n| blocks >= n)
+                || max_size.is_some_and(|n| bytes + buf.len() as u64 > n));
+        if full {
+            if let Some(mut wtr) = writer.take() {
+                MAFWriter::new(&mut wtr).finish()?;
+            }
+            chunk_idx += 1;
+        }
+        if writer.is_none() {
+            let mut wtr = new_writer(chunk_idx)?;
+            let mut mafwtr = MAFWriter::new(&mut wtr).with_eof(false);
+            mafwtr.write_header(&header)?;
+            mafwtr.flush()?;
+            drop(mafwtr);
+            writer = Some(wtr);
+            blocks = 0;
+            bytes = 0;
+        }
+        if let Some(wtr) = writer.as_mut() {
+            wtr.write_all(&buf)?;
+        }
+        blocks += 1;
+        bytes += buf.len() as u64;
+    }
+
+    let chunks = match writer {
+        Some(mut wtr) => {
+            MAFWriter::new(&mut wtr).finish()?;
+            chunk_idx + 1
+        }
+        None => 0,
+    };
+    info!("split into {} chunks", chunks);
+    Ok(chunks)
+}
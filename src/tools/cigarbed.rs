@@ -0,0 +1,48 @@
+use crate::{
+    errors::WGAError,
+    parser::{common::AlignRecord, paf::PafRecord},
+};
+use std::io::Write;
+
+/// Header of CIGAR BED, 0-based half-open coordinates as PAF:
+///
+/// 1. target name
+/// 2. target start
+/// 3. target end
+/// 4. query name
+/// 5. query start
+/// 6. query end
+/// 7. strand of query
+/// 8. CIGAR without `cg:Z:` prefix
+pub const CIGAR_BED_HEADER: &str =
+    "#target_name\ttarget_start\ttarget_end\tquery_name\tquery_start\tquery_end\tstrand\tcigar";
+
+// main function of CIGAR BED, one line per record
+// t1  0  30  q1  5  35  +  10M2I2D18M
+pub fn cigar_bed<I>(records: I, writer: &mut dyn Write, header: bool) -> Result<(), WGAError>
+where
+    I: Iterator<Item = Result<PafRecord, WGAError>>,
+{
+    if header {
+        writeln!(writer, "{}", CIGAR_BED_HEADER)?;
+    }
+    for rec in records {
+        let rec = rec?;
+        let cigar = rec.get_cigar_str()?;
+        let cigar = cigar.trim_start_matches("cg:Z:");
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            rec.target_name,
+            rec.target_start,
+            rec.target_end,
+            rec.query_name,
+            rec.query_start,
+            rec.query_end,
+            rec.strand,
+            cigar
+        )?;
+    }
+    writer.flush()?;
+    Ok(())
+}
@@ -0,0 +1,130 @@
+use crate::{
+    errors::WGAError,
+    parser::{common::AlignRecord, maf::MAFReader},
+    tools::interval::{self, Interval},
+    utils::TraceExt,
+};
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+};
+
+// main function of target coverage, BED sorted by target name (natord) and start
+// t1 0 10          t1 0 10          t1 0 5  1
+// t1 5 20  merge=> t1 5 20  depth=> t1 5 10 2
+// t1 30 40         t1 30 40         t1 10 20 1
+//                                   t1 30 40 1
+// with both merge and depth, depth is the count of alignments in merged interval
+pub fn coverage<T, I>(
+    records: I,
+    writer: &mut dyn Write,
+    merge: bool,
+    depth: bool,
+) -> Result<(), WGAError>
+where
+    T: AlignRecord,
+    I: Iterator<Item = Result<T, WGAError>>,
+{
+    for (name, mut ivs) in target_intervals(records)? {
+        ivs.sort_unstable();
+        match (merge, depth) {
+            (false, false) => {
+                for (start, end) in ivs {
+                    writeln!(writer, "{}\t{}\t{}", name, start, end)?;
+                }
+            }
+            (true, _) => {
+                for (start, end, count) in merge_intervals(&ivs) {
+                    match depth {
+                        true => writeln!(writer, "{}\t{}\t{}\t{}", name, start, end, count)?,
+                        false => writeln!(writer, "{}\t{}\t{}", name, start, end)?,
+                    }
+                }
+            }
+            (false, true) => {
+                for (start, end, depth) in depth_sweep(&ivs) {
+                    writeln!(writer, "{}\t{}\t{}\t{}", name, start, end, depth)?;
+                }
+            }
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+// main function of maf depth, bedGraph of blocks covering each reference base
+// t1 0 10                t1 0  5  1
+// t1 5 20  min-depth=1=> t1 5  10 2
+//                        t1 10 20 1
+// only intervals of blocks are kept, no per-base array for long references
+pub fn maf_depth<R: Read + Send>(
+    mut reader: MAFReader<R>,
+    writer: &mut dyn Write,
+    min_depth: usize,
+) -> Result<(), WGAError> {
+    for (name, ivs) in target_intervals(reader.records().traced())? {
+        for (start, end, depth) in depth_sweep(&ivs) {
+            if depth >= min_depth {
+                writeln!(writer, "{}\t{}\t{}\t{}", name, start, end, depth)?;
+            }
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+type TargetIntervals = Vec<(String, Vec<(u64, u64)>)>;
+
+// target intervals of records grouped by target name, in natural order of names
+fn target_intervals<T, I>(records: I) -> Result<TargetIntervals, WGAError>
+where
+    T: AlignRecord,
+    I: Iterator<Item = Result<T, WGAError>>,
+{
+    let mut intervals: HashMap<String, Vec<(u64, u64)>> = HashMap::new();
+    for rec in records {
+        let rec = rec?;
+        intervals
+            .entry(rec.target_name().to_string())
+            .or_default()
+            .push((rec.target_start(), rec.target_end()));
+    }
+    let mut intervals = intervals.into_iter().collect::<Vec<_>>();
+    intervals.sort_by(|a, b| natord::compare(&a.0, &b.0));
+    Ok(intervals)
+}
+
+/// Merge sorted intervals which overlap or are book-ended,
+/// return (start, end, count of merged intervals)
+pub fn merge_intervals(sorted: &[(u64, u64)]) -> Vec<(u64, u64, usize)> {
+    interval::merge(sorted.iter().map(|&iv| Interval::from(iv)))
+        .into_iter()
+        .map(|(iv, count)| (iv.start, iv.end, count))
+        .collect()
+}
+
+/// Sweep start/end events of intervals to get segments of constant depth,
+/// segments with zero depth are skipped and adjacent ones of same depth are joined
+pub fn depth_sweep(intervals: &[(u64, u64)]) -> Vec<(u64, u64, usize)> {
+    // end before start at the same position, so book-ended intervals don't stack
+    let mut events = intervals
+        .iter()
+        .flat_map(|&(start, end)| [(start, 1i64), (end, -1i64)])
+        .collect::<Vec<_>>();
+    events.sort_unstable();
+
+    let mut segments: Vec<(u64, u64, usize)> = Vec::new();
+    let mut depth = 0i64;
+    let mut last_pos = 0;
+    for (pos, delta) in events {
+        if pos > last_pos && depth > 0 {
+            match segments.last_mut() {
+                Some(last) if last.1 == last_pos && last.2 == depth as usize => last.1 = pos,
+                _ => segments.push((last_pos, pos, depth as usize)),
+            }
+        }
+        depth += delta;
+        last_pos = pos;
+    }
+    segments
+}
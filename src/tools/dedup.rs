@@ -0,0 +1,103 @@
+use crate::{
+    errors::WGAError,
+    parser::common::{AlignRecord, IdentityMode, Strand},
+};
+use log::info;
+use std::collections::{hash_map::Entry, HashMap, HashSet};
+
+// key fields of a record, `content` is None when deduplicated by coordinates only
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct DedupKey {
+    target_name: String,
+    target_start: u64,
+    target_end: u64,
+    target_strand: Strand,
+    query_name: String,
+    query_start: u64,
+    query_end: u64,
+    query_strand: Strand,
+    content: Option<(String, String, String)>,
+}
+
+impl DedupKey {
+    fn new<T: AlignRecord>(rec: &T, by_coords: bool) -> Self {
+        DedupKey {
+            target_name: rec.target_name().to_string(),
+            target_start: rec.target_start(),
+            target_end: rec.target_end(),
+            target_strand: rec.target_strand(),
+            query_name: rec.query_name().to_string(),
+            query_start: rec.query_start(),
+            query_end: rec.query_end(),
+            query_strand: rec.query_strand(),
+            // sequences of MAF or CIGAR of PAF and Chain
+            content: (!by_coords).then(|| {
+                (
+                    rec.get_cigar_string(),
+                    rec.target_seq().to_string(),
+                    rec.query_seq().to_string(),
+                )
+            }),
+        }
+    }
+}
+
+// main function of dedup, records are passed to `write` in input order
+// t1 0 10 q1 0 10 + 10M          t1 0 10 q1 0 10 + 10M
+// t1 0 10 q1 0 10 + 10M  dedup=> t1 0 10 q1 0 10 + 5M1I4M
+// t1 0 10 q1 0 10 + 5M1I4M
+// keys are coordinates on target and query, with sequences or CIGAR unless `by_coords`,
+// `keep_best` keeps the record of highest identity among coordinate-duplicates,
+// otherwise the first one is kept
+pub fn dedup<T, I, F>(
+    records: I,
+    by_coords: bool,
+    keep_best: bool,
+    mut write: F,
+) -> Result<(), WGAError>
+where
+    T: AlignRecord,
+    I: Iterator<Item = Result<T, WGAError>>,
+    F: FnMut(&T) -> Result<(), WGAError>,
+{
+    let mut total = 0;
+    let mut kept = 0;
+    if keep_best {
+        // best record of each key, in order of first occurrence
+        let mut best: Vec<(T, f64)> = Vec::new();
+        let mut slots: HashMap<DedupKey, usize> = HashMap::new();
+        for rec in records {
+            let rec = rec?;
+            total += 1;
+            let identity = rec.identity(IdentityMode::Target)?;
+            match slots.entry(DedupKey::new(&rec, true)) {
+                Entry::Occupied(slot) => {
+                    let slot = &mut best[*slot.get()];
+                    if identity > slot.1 {
+                        *slot = (rec, identity);
+                    }
+                }
+                Entry::Vacant(slot) => {
+                    slot.insert(best.len());
+                    best.push((rec, identity));
+                }
+            }
+        }
+        for (rec, _) in &best {
+            write(rec)?;
+        }
+        kept = best.len();
+    } else {
+        let mut seen = HashSet::new();
+        for rec in records {
+            let rec = rec?;
+            total += 1;
+            if seen.insert(DedupKey::new(&rec, by_coords)) {
+                write(&rec)?;
+                kept += 1;
+            }
+        }
+    }
+    info!("{} duplicate records removed, {} kept", total - kept, kept);
+    Ok(())
+}
@@ -2,7 +2,7 @@ use crate::{
     errors::WGAError,
     parser::{
         cigar::{parse_cigar_to_base_plotdata, parse_maf_to_base_plotdata},
-        common::{AlignRecord, DotplotMode, DotplotoutFormat, FileFormat, Strand},
+        common::{AlignRecord, DotplotMode, DotplotoutFormat, FileFormat, IdentityMode, Strand},
         maf::MAFReader,
         paf::PAFReader,
     },
@@ -149,6 +149,42 @@ pub struct BasePlotdata {
     pub query_chro: String,
 }
 
+/// Header of dotplot segments TSV, coordinates are taken from `AlignRecord` as is
+pub const SEGMENT_HEADER: &str =
+    "target_name\ttarget_start\ttarget_end\tquery_name\tquery_start\tquery_end\tstrand";
+
+/// Records filter applied before generating dotplot data
+#[derive(Debug, Default)]
+pub struct SegmentFilter {
+    pub min_length: u64,
+    pub target: Option<String>,
+    pub query: Option<String>,
+}
+
+impl SegmentFilter {
+    /// if a record should be plotted, min length is checked on target aligned size
+    pub fn keep<T: AlignRecord>(&self, rec: &T) -> bool {
+        rec.target_end() - rec.target_start() >= self.min_length
+            && self
+                .target
+                .as_ref()
+                .is_none_or(|name| rec.target_name() == name)
+            && self
+                .query
+                .as_ref()
+                .is_none_or(|name| rec.query_name() == name)
+    }
+
+    // keep errors to be raised by the consumer
+    fn keep_result<T: AlignRecord>(&self, rec: &Result<T, WGAError>) -> bool {
+        match rec {
+            Ok(rec) => self.keep(rec),
+            Err(_) => true,
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn dotplot(
     reader: Box<dyn BufRead + Send>,
     writer: &mut dyn Write,
@@ -157,7 +193,19 @@ pub fn dotplot(
     mode: DotplotMode,
     no_identity: bool,
     skip_cutoff: usize,
+    filter: &SegmentFilter,
 ) -> Result<(), WGAError> {
+    // segments TSV for external tools, one line per record
+    if let DotplotoutFormat::Tsv = out_format {
+        return match format {
+            FileFormat::Maf => write_segments(MAFReader::new(reader)?.records(), writer, filter),
+            FileFormat::Paf => write_segments(PAFReader::new(reader).records(), writer, filter),
+            _ => Err(WGAError::Other(anyhow::anyhow!(
+                "Only support MAF and PAF format"
+            ))),
+        };
+    }
+
     // init vega spec
     let mut vega_spec: Value = serde_json::from_str(DOTPLOT_SPEC)?;
 
@@ -165,8 +213,8 @@ pub fn dotplot(
     match mode {
         DotplotMode::Overview => {
             let pair_stat_vec = match format {
-                FileFormat::Maf => generate_maf_data(MAFReader::new(reader)?, no_identity)?,
-                FileFormat::Paf => generate_paf_data(PAFReader::new(reader), no_identity)?,
+                FileFormat::Maf => generate_maf_data(MAFReader::new(reader)?, no_identity, filter)?,
+                FileFormat::Paf => generate_paf_data(PAFReader::new(reader), no_identity, filter)?,
                 _ => {
                     return Err(WGAError::Other(anyhow::anyhow!(
                         "Only support MAF and PAF format"
@@ -177,8 +225,12 @@ pub fn dotplot(
         }
         DotplotMode::BaseLevel => {
             let pair_base_plot_vec = match format {
-                FileFormat::Maf => generate_maf_basedata(MAFReader::new(reader)?, skip_cutoff)?,
-                FileFormat::Paf => generate_paf_basedata(PAFReader::new(reader), skip_cutoff)?,
+                FileFormat::Maf => {
+                    generate_maf_basedata(MAFReader::new(reader)?, skip_cutoff, filter)?
+                }
+                FileFormat::Paf => {
+                    generate_paf_basedata(PAFReader::new(reader), skip_cutoff, filter)?
+                }
                 _ => {
                     return Err(WGAError::Other(anyhow::anyhow!(
                         "Only support MAF and PAF format"
@@ -232,7 +284,39 @@ fn render_output<S: Serialize>(
             }
             wtr.flush()?;
         }
+        DotplotoutFormat::Tsv => unreachable!("TSV segments are written directly"),
+    }
+    Ok(())
+}
+
+/// write records as line segments TSV
+// target_name  target_start  target_end  query_name  query_start  query_end  strand
+// t1           0             30          q1          5            35         +
+fn write_segments<T, I>(
+    records: I,
+    writer: &mut dyn Write,
+    filter: &SegmentFilter,
+) -> Result<(), WGAError>
+where
+    T: AlignRecord,
+    I: Iterator<Item = Result<T, WGAError>>,
+{
+    writeln!(writer, "{}", SEGMENT_HEADER)?;
+    for rec in records.filter(|rec| filter.keep_result(rec)) {
+        let rec = rec?;
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            rec.target_name(),
+            rec.target_start(),
+            rec.target_end(),
+            rec.query_name(),
+            rec.query_start(),
+            rec.query_end(),
+            rec.query_strand()
+        )?;
     }
+    writer.flush()?;
     Ok(())
 }
 
@@ -240,9 +324,11 @@ fn render_output<S: Serialize>(
 fn generate_maf_data<R: Read + Send>(
     mut reader: MAFReader<R>,
     no_identity: bool,
+    filter: &SegmentFilter,
 ) -> Result<Vec<AllPlotdata>, WGAError> {
     let pair_stat_vec = reader
         .records()
+        .filter(|rec| filter.keep_result(rec))
         .par_bridge()
         .try_fold(Vec::new, |mut acc, rec| {
             acc.push(rec_dot_data(&rec?, no_identity)?);
@@ -259,9 +345,11 @@ fn generate_maf_data<R: Read + Send>(
 fn generate_paf_data<R: Read + Send>(
     mut reader: PAFReader<R>,
     no_identity: bool,
+    filter: &SegmentFilter,
 ) -> Result<Vec<AllPlotdata>, WGAError> {
     let pair_stat_vec = reader
         .records()
+        .filter(|rec| filter.keep_result(rec))
         .par_bridge()
         .try_fold(Vec::new, |mut acc, rec| {
             acc.push(rec_dot_data(&rec?, no_identity)?);
@@ -278,9 +366,11 @@ fn generate_paf_data<R: Read + Send>(
 fn generate_paf_basedata<R: Read + Send>(
     mut reader: PAFReader<R>,
     cutoff: usize,
+    filter: &SegmentFilter,
 ) -> Result<Vec<Vec<BasePlotdata>>, WGAError> {
     let pair_stat_vec = reader
         .records()
+        .filter(|rec| filter.keep_result(rec))
         .par_bridge()
         .try_fold(Vec::new, |mut acc, rec| {
             acc.push(parse_cigar_to_base_plotdata(&rec?, cutoff)?);
@@ -298,9 +388,11 @@ fn generate_paf_basedata<R: Read + Send>(
 fn generate_maf_basedata<R: Read + Send>(
     mut reader: MAFReader<R>,
     cutoff: usize,
+    filter: &SegmentFilter,
 ) -> Result<Vec<Vec<BasePlotdata>>, WGAError> {
     let pair_stat_vec = reader
         .records()
+        .filter(|rec| filter.keep_result(rec))
         .par_bridge()
         .try_fold(Vec::new, |mut acc, rec| {
             acc.push(parse_maf_to_base_plotdata(&rec?, cutoff)?);
@@ -324,7 +416,7 @@ fn rec_dot_data<T: AlignRecord>(rec: &T, no_identity: bool) -> Result<AllPlotdat
     let identity = if no_identity {
         1.0
     } else {
-        calculate_identity(rec)?
+        rec.identity(IdentityMode::Target)?
     };
     let ref_chro = rec.target_name().to_string();
     let query_chro = rec.query_name().to_string();
@@ -346,12 +438,3 @@ fn rec_dot_data<T: AlignRecord>(rec: &T, no_identity: bool) -> Result<AllPlotdat
         query_chro,
     })
 }
-
-// calculate identity for a record
-fn calculate_identity<T: AlignRecord>(rec: &T) -> Result<f64, WGAError> {
-    let aligned_size = rec.target_align_size();
-    let rec_stat = rec.get_stat()?;
-    let matched = rec_stat.matched;
-    let identity = matched as f64 / aligned_size as f64;
-    Ok(identity)
-}
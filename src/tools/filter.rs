@@ -2,37 +2,70 @@ use crate::{
     errors::WGAError,
     parser::{
         chain::ChainReader,
-        common::AlignRecord,
+        cigar::max_gap_len,
+        common::{AlignRecord, IdentityMode},
         maf::{MAFReader, MAFWriter},
         paf::PAFReader,
     },
+    tools::interval::Interval,
+    utils::TraceExt,
 };
+use log::warn;
 use rayon::prelude::*;
 use std::{
     collections::HashMap,
     io::{Read, Write},
 };
 
+/// Thresholds of filter, a record failing any of them is dropped
+#[derive(Debug, Default, Clone)]
+pub struct FilterOpts {
+    pub min_block_size: u64,
+    pub min_query_size: u64,
+    pub min_identity: Option<f64>,
+    pub identity_mode: IdentityMode,
+    // only PAF has MAPQ
+    pub min_mapq: Option<u64>,
+    pub max_gap: Option<u64>,
+    // drop self alignments on the diagonal, with tolerance of `--keep-diagonal-within`
+    pub drop_self: Option<u64>,
+}
+
+impl FilterOpts {
+    // MAPQ filter is a no-op for formats without MAPQ
+    fn warn_no_mapq(&self, format: &str) {
+        if self.min_mapq.is_some() {
+            warn!("{} has no MAPQ, `min_mapq` will be ignored", format);
+        }
+    }
+}
+
+/// If the record aligns a region to itself: same sequence and strand, target and query
+/// intervals overlap or are less than `within` bp apart, so inversions and paralogs off
+/// the diagonal are not self alignments
+pub fn is_self_alignment<T: AlignRecord>(rec: &T, within: u64) -> bool {
+    if rec.target_name() != rec.query_name() || rec.target_strand() != rec.query_strand() {
+        return false;
+    }
+    let target = rec.target_interval();
+    let near = Interval::new(target.start.saturating_sub(within), target.end + within);
+    near.overlaps(&rec.query_interval())
+}
+
 // filter chain
 pub fn filter_chain<R: Read + Send>(
     mut reader: ChainReader<R>,
     writer: &mut dyn Write,
-    min_block_size: u64,
-    min_query_size: u64,
+    opts: &FilterOpts,
 ) -> Result<(), WGAError> {
-    for rec in reader.records()? {
+    opts.warn_no_mapq("Chain");
+    for rec in reader.records()?.traced() {
         let rec = rec?;
-        let rec = filter_alignrec(&rec, min_block_size, min_query_size)?;
+        let rec = filter_alignrec(&rec, opts)?;
         // just write the record
         if let Some(rec) = rec {
-            let chainheader = &rec.header;
-            let chainblocks = &rec.lines;
-            writer.write_all(format!("{}", chainheader).as_bytes())?;
-            for dataline in chainblocks {
-                writer.write_all(format!("{}", dataline).as_bytes())?;
-            }
             // additional newline for standard chain format
-            writer.write_all(b"\n\n")?;
+            write!(writer, "{}\n\n", rec)?;
         }
     }
     Ok(())
@@ -42,17 +75,19 @@ pub fn filter_chain<R: Read + Send>(
 pub fn filter_paf<R: Read + Send>(
     mut reader: PAFReader<R>,
     writer: &mut dyn Write,
-    min_block_size: u64,
-    min_query_size: u64,
+    opts: &FilterOpts,
 ) -> Result<(), WGAError> {
     let mut pafwtr = csv::WriterBuilder::new()
         .flexible(true)
         .delimiter(b'\t')
         .has_headers(false)
         .from_writer(writer);
-    for rec in reader.records() {
+    for rec in reader.records().traced() {
         let rec = rec?;
-        let rec = filter_alignrec(&rec, min_block_size, min_query_size)?;
+        if opts.min_mapq.is_some_and(|min_mapq| rec.mapq < min_mapq) {
+            continue;
+        }
+        let rec = filter_alignrec(&rec, opts)?;
         // just write the record
         if let Some(rec) = rec {
             pafwtr.serialize(rec)?;
@@ -65,42 +100,87 @@ pub fn filter_paf<R: Read + Send>(
 pub fn filter_maf<R: Read + Send>(
     mut reader: MAFReader<R>,
     writer: &mut dyn Write,
-    min_block_size: u64,
-    min_query_size: u64,
+    opts: &FilterOpts,
 ) -> Result<(), WGAError> {
+    opts.warn_no_mapq("MAF");
     // init a MAFWriter
     let mut mafwtr = MAFWriter::new(writer);
     // write header
-    let header = format!(
+    let mut header = format!(
         "#maf version=1.6 filter=blocksize>={} querysize>={}",
-        min_block_size, min_query_size
+        opts.min_block_size, opts.min_query_size
     );
-    mafwtr.write_header(header)?;
-    for rec in reader.records() {
+    if let Some(min_identity) = opts.min_identity {
+        header.push_str(&format!(
+            " identity>={} identity_mode={:?}",
+            min_identity, opts.identity_mode
+        ));
+    }
+    if let Some(max_gap) = opts.max_gap {
+        header.push_str(&format!(" gap<={}", max_gap));
+    }
+    if let Some(within) = opts.drop_self {
+        header.push_str(&format!(" drop_self_within={}", within));
+    }
+    mafwtr.write_header(&reader.header_with(header))?;
+    for rec in reader.records().traced() {
         let rec = rec?;
-        let rec = filter_alignrec(&rec, min_block_size, min_query_size)?;
+        let rec = filter_alignrec(&rec, opts)?;
         // just write the record
         if let Some(rec) = rec {
             mafwtr.write_record(rec)?;
         }
     }
+    mafwtr.finish()?;
     Ok(())
 }
 
 // filter record, return Option
-fn filter_alignrec<T: AlignRecord>(
-    rec: &T,
-    min_block_size: u64,
-    min_query_size: u64,
-) -> Result<Option<&T>, WGAError> {
+fn filter_alignrec<'a, T: AlignRecord>(
+    rec: &'a T,
+    opts: &FilterOpts,
+) -> Result<Option<&'a T>, WGAError> {
+    if opts
+        .drop_self
+        .is_some_and(|within| is_self_alignment(rec, within))
+    {
+        return Ok(None);
+    }
+
     let query_length = rec.query_length();
     let block_length = rec.target_align_size();
 
     // if in condition, return None
-    if (block_length < min_block_size) | (query_length < min_query_size) {
+    if (block_length < opts.min_block_size) | (query_length < opts.min_query_size) {
         return Ok(None);
     }
 
+    // identity is same as `stat` with the same mode
+    if let Some(min_identity) = opts.min_identity {
+        if rec.identity(opts.identity_mode)? < min_identity {
+            return Ok(None);
+        }
+    }
+
+    // longest single insertion or deletion
+    if let Some(max_gap) = opts.max_gap {
+        let cigar = rec.get_cigar_string();
+        if cigar == "*" {
+            return Err(WGAError::CigarRequired(format!(
+                "{}:{}-{} {}:{}-{}",
+                rec.query_name(),
+                rec.query_start(),
+                rec.query_end(),
+                rec.target_name(),
+                rec.target_start(),
+                rec.target_end()
+            )));
+        }
+        if max_gap_len(&cigar)? > max_gap {
+            return Ok(None);
+        }
+    }
+
     Ok(Some(rec))
 }
 
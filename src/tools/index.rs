@@ -1,95 +1,394 @@
 use crate::{
     errors::WGAError,
-    parser::{common::Strand, maf::MAFReader},
+    parser::{
+        common::Strand,
+        maf::{next_record, read_header, skip_to_block, MAFReader, MAFRecord},
+    },
 };
 use anyhow::anyhow;
 use itertools::enumerate;
+use log::warn;
+use noodles::bgzf::{self, VirtualPosition};
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
     fs::File,
-    io::{Seek, Write},
+    io::{BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    path::Path,
 };
 
+// minimal byte size of a chunk scanned by one thread
+const MIN_CHUNK_SIZE: u64 = 1 << 20;
+
+/// Build index of a plain MAF file, offsets are byte offsets of `a` lines of blocks
 pub fn build_index(
     mafreader: &mut MAFReader<File>,
     idx_wtr: Box<dyn Write>,
+    validate: bool,
+    binary: bool,
 ) -> Result<(), WGAError> {
     // init a MAfIndex2 struct
     let mut idx: MafIndex = HashMap::new();
 
     loop {
+        skip_to_block(&mut mafreader.inner)?;
         let offset = mafreader.inner.stream_position()?;
-        let record = mafreader.records().next();
-        let record = match record {
+        // read blocks directly, an index should cover all of them whatever `--max-records`
+        let record = match next_record(&mut mafreader.inner) {
             Some(r) => r?,
             None => break,
         };
+        add_record(&mut idx, record, offset, validate)?;
+    }
+    write_index(idx, OffsetKind::Plain, idx_wtr, binary)
+}
+
+/// Build index of a plain MAF file by multiple threads, the file is split into byte ranges
+/// and each range indexes blocks whose `a` line starts in it, offsets are absolute byte
+/// offsets of `a` lines, partial indexes are merged in file order
+pub fn build_index_parallel<P: AsRef<Path>>(
+    path: P,
+    idx_wtr: Box<dyn Write>,
+    validate: bool,
+    binary: bool,
+) -> Result<(), WGAError> {
+    let path = path.as_ref();
+    let file_size = std::fs::metadata(path)?.len();
+    let chunk_size = (file_size / (rayon::current_num_threads() as u64 * 4)).max(MIN_CHUNK_SIZE);
+    let chunks = (0..file_size)
+        .step_by(chunk_size as usize)
+        .map(|lo| (lo, (lo + chunk_size).min(file_size)))
+        .collect::<Vec<_>>();
+    let parts = chunks
+        .par_iter()
+        .map(|&(lo, hi)| index_chunk(path, lo, hi, validate))
+        .collect::<Result<Vec<_>, WGAError>>()?;
+
+    let mut idx: MafIndex = HashMap::new();
+    for part in parts {
+        merge_index(&mut idx, part)?;
+    }
+    write_index(idx, OffsetKind::Plain, idx_wtr, binary)
+}
+
+// index blocks whose `a` line starts in [lo, hi), a block may run over `hi`
+fn index_chunk(path: &Path, lo: u64, hi: u64, validate: bool) -> Result<MafIndex, WGAError> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut pos = lo;
+    let mut line = Vec::new();
+    // move to the start of the first whole line in the chunk
+    if lo > 0 {
+        reader.seek(SeekFrom::Start(lo - 1))?;
+        pos = lo - 1 + reader.read_until(b'\n', &mut line)? as u64;
+    }
 
-        let mut name_vec = Vec::new();
-        for (ord, sline) in enumerate(record.slines) {
-            let name = sline.name;
-            if !name_vec.contains(&name) {
-                name_vec.push(name.clone());
-            } else {
-                return Err(WGAError::DuplicateName(name));
+    let mut idx: MafIndex = HashMap::new();
+    // a-line of next block which is already read
+    let mut next_block = None;
+    loop {
+        let (offset, mut block) = match next_block.take() {
+            Some(block) => block,
+            None => {
+                line.clear();
+                let len = reader.read_until(b'\n', &mut line)? as u64;
+                if len == 0 {
+                    break;
+                }
+                pos += len;
+                if !line.trim_ascii_start().starts_with(b"a") {
+                    continue;
+                }
+                (pos - len, line.clone())
             }
-            let start = sline.start;
-            let end = sline.start + sline.align_size;
-            let size = sline.size;
-            let strand = sline.strand;
-
-            // idx.entry(name.clone()).or_insert(MafIndexItem {
-            //     ivls: Vec::new(),
-            //     size,
-            //     ord,
-            // });
-
-            if !idx.contains_key(&name) {
-                idx.insert(
-                    name.clone(),
-                    MafIndexItem {
-                        ivls: Vec::new(),
-                        size,
-                        ord,
-                    },
-                );
-            } else {
-                // compare ord if same
-                if idx
-                    .get(&name)
-                    .ok_or(WGAError::Other(anyhow!("not excepted")))?
-                    .ord
-                    != ord
-                {
-                    return Err(WGAError::Other(anyhow!(
-                        "There is a different order between Records!"
-                    )));
+        };
+        if offset >= hi {
+            break;
+        }
+        // lines of the block until an empty line, the next a-line or EOF
+        loop {
+            line.clear();
+            let len = reader.read_until(b'\n', &mut line)? as u64;
+            if len == 0 || line.trim_ascii().is_empty() {
+                pos += len;
+                break;
+            }
+            if line.trim_ascii_start().starts_with(b"a") {
+                next_block = Some((pos, line.clone()));
+                pos += len;
+                break;
+            }
+            pos += len;
+            block.extend_from_slice(&line);
+        }
+        if let Some(record) = next_record(&mut block.as_slice()) {
+            add_record(&mut idx, record?, offset, validate)?;
+        }
+    }
+    Ok(idx)
+}
+
+// merge index of a later chunk into `idx`, orders of a sequence should be the same
+fn merge_index(idx: &mut MafIndex, part: MafIndex) -> Result<(), WGAError> {
+    for (name, item) in part {
+        match idx.get_mut(&name) {
+            Some(merged) => {
+                if merged.ord != item.ord {
+                    return Err(order_mismatch());
                 }
+                merged.ivls.extend(item.ivls);
             }
+            None => {
+                idx.insert(name, item);
+            }
+        }
+    }
+    Ok(())
+}
 
-            idx.get_mut(&name)
+// error of a sequence with different orders in blocks
+fn order_mismatch() -> WGAError {
+    WGAError::Other(anyhow!("There is a different order between Records!"))
+}
+
+/// Build index of a bgzipped MAF file, offsets are bgzip virtual offsets
+/// (coffset << 16 | uoffset) of `a` lines of blocks, so it can be seeked without
+/// decompressing all
+pub fn build_index_bgzf(
+    reader: &mut bgzf::Reader<File>,
+    idx_wtr: Box<dyn Write>,
+    validate: bool,
+    binary: bool,
+) -> Result<(), WGAError> {
+    let mut idx: MafIndex = HashMap::new();
+    // bgzf reader is buffered itself, read from it directly to keep offsets exact
+    read_header(reader)?;
+    loop {
+        skip_to_block(reader)?;
+        let offset = u64::from(reader.virtual_position());
+        let record = match next_record(reader) {
+            Some(r) => r?,
+            None => break,
+        };
+        add_record(&mut idx, record, offset, validate)?;
+    }
+    write_index(idx, OffsetKind::Bgzf, idx_wtr, binary)
+}
+
+// add s-lines of a block at `offset` into index
+fn add_record(
+    idx: &mut MafIndex,
+    record: MAFRecord,
+    offset: u64,
+    validate: bool,
+) -> Result<(), WGAError> {
+    if validate {
+        record.validate()?;
+    }
+
+    let mut name_vec = Vec::new();
+    for (ord, sline) in enumerate(record.slines) {
+        let name = sline.name;
+        if !name_vec.contains(&name) {
+            name_vec.push(name.clone());
+        } else {
+            return Err(WGAError::DuplicateName(name));
+        }
+        let start = sline.start;
+        let end = sline.start + sline.align_size;
+        let size = sline.size;
+        let strand = sline.strand;
+
+        if !idx.contains_key(&name) {
+            idx.insert(
+                name.clone(),
+                MafIndexItem {
+                    ivls: Vec::new(),
+                    size,
+                    ord,
+                },
+            );
+        } else {
+            // compare ord if same
+            if idx
+                .get(&name)
                 .ok_or(WGAError::Other(anyhow!("not excepted")))?
-                .ivls
-                .push(IvP {
-                    start,
-                    end,
-                    strand,
-                    offset,
-                });
+                .ord
+                != ord
+            {
+                return Err(order_mismatch());
+            }
         }
+
+        idx.get_mut(&name)
+            .ok_or(WGAError::Other(anyhow!("not excepted")))?
+            .ivls
+            .push(IvP {
+                start,
+                end,
+                strand,
+                offset,
+            });
     }
-    // write index to file if not empty
-    if !idx.is_empty() {
-        serde_json::to_writer(idx_wtr, &idx)?
+    Ok(())
+}
+
+// prefix of a binary index, followed by a version byte and bincode of `MafIndexFile`
+const BINARY_MAGIC: &[u8; 6] = b"WGAIDX";
+const BINARY_VERSION: u8 = 1;
+
+// write index to file as JSON or binary, an empty MAF has a valid empty index
+fn write_index(
+    idx: MafIndex,
+    offset_kind: OffsetKind,
+    mut idx_wtr: Box<dyn Write>,
+    binary: bool,
+) -> Result<(), WGAError> {
+    if idx.is_empty() {
+        warn!("no blocks in MAF, the index is empty");
+    }
+    let index_file = MafIndexFile {
+        offset_kind,
+        index: idx,
+    };
+    if binary {
+        idx_wtr.write_all(BINARY_MAGIC)?;
+        idx_wtr.write_all(&[BINARY_VERSION])?;
+        idx_wtr.write_all(&bincode::serialize(&index_file)?)?;
+        idx_wtr.flush()?;
     } else {
-        return Err(WGAError::EmptyRecord);
+        serde_json::to_writer(idx_wtr, &index_file)?;
     }
     Ok(())
 }
 
+// read a JSON or binary index, told apart by the magic prefix
+fn read_index(path: &Path, file: File) -> Result<MafIndexFile, WGAError> {
+    let mut reader = BufReader::new(file);
+    if !reader.fill_buf()?.starts_with(BINARY_MAGIC) {
+        return Ok(match serde_json::from_reader(reader)? {
+            IndexFormat::Tagged(index_file) => index_file,
+            IndexFormat::Legacy(index) => MafIndexFile {
+                offset_kind: OffsetKind::Plain,
+                index,
+            },
+        });
+    }
+    let corrupted = |reason: String| WGAError::IndexCorrupted(path.to_path_buf(), reason);
+    reader.consume(BINARY_MAGIC.len());
+    let mut version = [0; 1];
+    reader.read_exact(&mut version)?;
+    if version[0] != BINARY_VERSION {
+        return Err(corrupted(format!(
+            "binary version {} is not supported, expect {}",
+            version[0], BINARY_VERSION
+        )));
+    }
+    bincode::deserialize_from(reader).map_err(|e| corrupted(e.to_string()))
+}
+
+/// Load a MAF index written by `build_index` in JSON or binary, a corrupted index is
+/// returned as error:
+/// offsets of each sequence should be increasing, and s-lines of one block
+/// (same offset) should have distinct orders. Old indexes without offset kind are plain
+pub fn load_index<P: AsRef<Path>>(path: P) -> Result<MafIndexFile, WGAError> {
+    let path = path.as_ref();
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(WGAError::FileNotExist(path.to_path_buf()))
+        }
+        Err(e) => return Err(WGAError::Io(e)),
+    };
+    let index_file = read_index(path, file)?;
+    let idx = &index_file.index;
+    let corrupted = |reason: String| WGAError::IndexCorrupted(path.to_path_buf(), reason);
+
+    // offset => orders of s-lines in that block
+    let mut block_ords: HashMap<u64, Vec<usize>> = HashMap::new();
+    for (name, item) in idx {
+        for pair in item.ivls.windows(2) {
+            if pair[1].offset <= pair[0].offset {
+                return Err(corrupted(format!(
+                    "offsets of `{}` are not increasing: {} then {}",
+                    name, pair[0].offset, pair[1].offset
+                )));
+            }
+        }
+        for ivl in &item.ivls {
+            if ivl.start > ivl.end || ivl.end > item.size {
+                return Err(corrupted(format!(
+                    "interval `{}:{}-{}` is out of size {}",
+                    name, ivl.start, ivl.end, item.size
+                )));
+            }
+            block_ords.entry(ivl.offset).or_default().push(item.ord);
+        }
+    }
+    for (offset, mut ords) in block_ords {
+        ords.sort_unstable();
+        // orders of a block are 0..n with reference at 0
+        if ords.iter().enumerate().any(|(i, ord)| i != *ord) {
+            return Err(corrupted(format!(
+                "orders {:?} of block at offset {} are inconsistent",
+                ords, offset
+            )));
+        }
+    }
+    Ok(index_file)
+}
+
 pub type MafIndex = HashMap<String, MafIndexItem>;
 
+/// Scheme of `IvP.offset`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OffsetKind {
+    /// byte offset of a plain MAF file
+    #[default]
+    Plain,
+    /// bgzip virtual offset of a bgzipped MAF file
+    Bgzf,
+}
+
+/// Serialized MAF index, index of each sequence with its offset scheme
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MafIndexFile {
+    pub offset_kind: OffsetKind,
+    pub index: MafIndex,
+}
+
+// index file written by new or old (just the map) versions
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum IndexFormat {
+    Tagged(MafIndexFile),
+    Legacy(MafIndex),
+}
+
+/// A bgzf reader seekable by `SeekFrom::Start(virtual offset)`, so it can be used by
+/// `MAFReader` as a plain file for extracting blocks
+pub struct VirtualSeek<R: Read>(pub bgzf::Reader<R>);
+
+impl<R: Read> Read for VirtualSeek<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl<R: Read + Seek> Seek for VirtualSeek<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        match pos {
+            SeekFrom::Start(offset) => Ok(u64::from(self.0.seek(VirtualPosition::from(offset))?)),
+            SeekFrom::Current(0) => Ok(u64::from(self.0.virtual_position())),
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "bgzf reader only seeks to a virtual offset",
+            )),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct MafIndexItem {
     pub ivls: Vec<IvP>,
@@ -0,0 +1,74 @@
+use std::fmt;
+
+/// Half-open interval `[start, end)` of 0-based coordinates, as BED and PAF
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Interval {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl Interval {
+    pub fn new(start: u64, end: u64) -> Self {
+        Interval { start, end }
+    }
+
+    pub fn len(&self) -> u64 {
+        self.end.saturating_sub(self.start)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.end <= self.start
+    }
+
+    /// If the intervals share any base, book-ended `[0, 10)` and `[10, 20)` don't overlap,
+    /// nor does an empty interval
+    pub fn overlaps(&self, other: &Interval) -> bool {
+        self.start.max(other.start) < self.end.min(other.end)
+    }
+
+    /// If `other` is fully inside the interval
+    pub fn contains(&self, other: &Interval) -> bool {
+        self.start <= other.start && other.end <= self.end
+    }
+
+    /// Shared part of the intervals, None if they don't overlap
+    pub fn intersect(&self, other: &Interval) -> Option<Interval> {
+        let start = self.start.max(other.start);
+        let end = self.end.min(other.end);
+        (start < end).then_some(Interval { start, end })
+    }
+}
+
+impl From<(u64, u64)> for Interval {
+    fn from((start, end): (u64, u64)) -> Self {
+        Interval { start, end }
+    }
+}
+
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
+
+// sweep of sorted intervals, overlapping or book-ended ones are merged
+// [0, 10)            [0, 20)  2
+// [5, 20)   merge=>  [30, 40) 1
+// [30, 40)
+// each merged interval comes with the count of intervals in it
+pub fn merge<I>(sorted: I) -> Vec<(Interval, usize)>
+where
+    I: IntoIterator<Item = Interval>,
+{
+    let mut merged: Vec<(Interval, usize)> = Vec::new();
+    for iv in sorted {
+        match merged.last_mut() {
+            Some((last, count)) if iv.start <= last.end => {
+                last.end = last.end.max(iv.end);
+                *count += 1;
+            }
+            _ => merged.push((iv, 1)),
+        }
+    }
+    merged
+}
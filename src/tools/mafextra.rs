@@ -1,6 +1,7 @@
 use crate::errors::{ParseGenomeRegionErrKind, WGAError};
 use crate::parser::maf::{MAFReader, MAFWriter};
 use crate::tools::index::{IvP, MafIndex};
+use crate::tools::interval;
 use crate::utils::parse_str2u64;
 use csv::ReaderBuilder;
 use regex::Regex;
@@ -32,13 +33,49 @@ pub fn maf_extract_idx<R: Read + Send + Seek>(
     let input_regions = get_input_regions(regions, region_file)?;
     let mut sub_maf_wtr = MAFWriter::new(writer);
     let header = "#maf version=1.6 cmd=maf_extract";
-    sub_maf_wtr.write_header(header.to_owned())?;
+    sub_maf_wtr.write_header(&mafreader.header_with(header.to_owned()))?;
     let failed_regions =
         extract_sub_blocks_with_idx(mafindex, input_regions, mafreader, &mut sub_maf_wtr)?;
+    sub_maf_wtr.finish()?;
     Ok(failed_regions)
 }
 
-fn get_input_regions(
+/// Slice MAF blocks into `region` of the s-line named `seq` without index,
+/// blocks lacking `seq` or out of region are skipped
+pub fn maf_slice<R: Read + Send>(
+    mut mafreader: MAFReader<R>,
+    region: &GenomeRegion,
+    seq: &str,
+    writer: &mut dyn Write,
+) -> Result<(), WGAError> {
+    let mut mafwtr = MAFWriter::new(writer);
+    let header = format!(
+        "#maf version=1.6 cmd=maf_slice region={} seq={}",
+        region, seq
+    );
+    mafwtr.write_header(&mafreader.header_with(header))?;
+    for rec in mafreader.records() {
+        let mut rec = rec?;
+        let ord = match rec.slines.iter().position(|sline| sline.name == seq) {
+            Some(ord) => ord,
+            None => continue,
+        };
+        let b_start = rec.slines[ord].start;
+        let b_end = b_start + rec.slines[ord].align_size;
+        if !region.overlaps(b_start, b_end) {
+            continue;
+        }
+        // fast path: contained block is written unchanged, only straddling block is sliced
+        if !region.contains(b_start, b_end) {
+            rec.slice_block(max(b_start, region.start), min(b_end, region.end), ord)?;
+        }
+        mafwtr.write_record(&rec)?;
+    }
+    mafwtr.finish()?;
+    Ok(())
+}
+
+pub(crate) fn get_input_regions(
     regions: &Option<Vec<String>>,
     region_file: &Option<String>,
 ) -> Result<Vec<GenomeRegion>, WGAError> {
@@ -70,9 +107,21 @@ fn get_input_regions(
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct GenomeRegion {
-    name: String,
-    start: u64,
-    end: u64,
+    pub name: String,
+    pub start: u64,
+    pub end: u64,
+}
+
+impl GenomeRegion {
+    /// If block `[start, end)` is fully inside the region, it needs no slicing
+    pub fn contains(&self, start: u64, end: u64) -> bool {
+        self.start <= start && end <= self.end
+    }
+
+    /// If block `[start, end)` overlaps the region
+    pub fn overlaps(&self, start: u64, end: u64) -> bool {
+        interval::Interval::new(self.start, self.end).overlaps(&interval::Interval::new(start, end))
+    }
 }
 
 impl TryFrom<String> for GenomeRegion {
@@ -176,18 +225,16 @@ fn extract_sub_blocks_with_idx<R: Read + Send + Seek, W: Write>(
                             let b_start = block.start;
                             let b_end = block.stop;
 
-                            let g_start = givl.start;
-                            let g_end = givl.end;
-
-                            if g_start <= b_start && g_end >= b_end {
+                            // fast path: contained block is written unchanged
+                            if givl.contains(b_start, b_end) {
                                 mafwriter.write_record(&mafrec)?;
                                 continue;
                             }
 
-                            let r_start = max(b_start, g_start);
-                            let r_end = min(b_end, g_end);
+                            let r_start = max(b_start, givl.start);
+                            let r_end = min(b_end, givl.end);
 
-                            mafrec.slice_block(r_start, r_end, ord);
+                            mafrec.slice_block(r_start, r_end, ord)?;
 
                             mafwriter.write_record(&mafrec)?;
                         }
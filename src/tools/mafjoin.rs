@@ -0,0 +1,199 @@
+use crate::{
+    errors::WGAError,
+    parser::{
+        common::{AlignRecord, Strand},
+        maf::{MAFReader, MAFRecord, MAFSLine, MAFWriter},
+    },
+};
+use log::warn;
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+};
+
+// blocks of second MAF on one reference sequence, sorted by start
+struct RefBlocks {
+    blocks: Vec<MAFRecord>,
+    // running max of block ends, monotonic for binary search
+    max_ends: Vec<u64>,
+}
+
+impl RefBlocks {
+    fn new(mut blocks: Vec<MAFRecord>) -> Self {
+        blocks.sort_by_key(|rec| rec.target_start());
+        let mut max_end = 0;
+        let max_ends = blocks
+            .iter()
+            .map(|rec| {
+                max_end = max_end.max(rec.target_end());
+                max_end
+            })
+            .collect();
+        RefBlocks { blocks, max_ends }
+    }
+
+    // blocks overlapping [start, end)
+    fn overlaps(&self, start: u64, end: u64) -> impl Iterator<Item = &MAFRecord> {
+        let first = self.max_ends.partition_point(|&max_end| max_end <= start);
+        self.blocks[first..]
+            .iter()
+            .take_while(move |rec| rec.target_start() < end)
+            .filter(move |rec| rec.target_end() > start)
+    }
+}
+
+// main function of maf join, the first s-line of each block is the shared reference
+// first:                  second:
+// ref 0 6 + AC-GTTA       ref 2 6 + GT-TACA
+// A   0 7 + ACTGTTA       B   0 7 + GTTTACA
+//
+// joined block on ref [2, 6):
+// ref 2 4 + GT-TA
+// A   3 4 + GT-TA
+// B   0 5 + GTTTA
+pub fn maf_join<R1: Read + Send, R2: Read + Send>(
+    mut first: MAFReader<R1>,
+    mut second: MAFReader<R2>,
+    writer: &mut dyn Write,
+) -> Result<(), WGAError> {
+    // load the second MAF and group by reference name
+    let mut grouped: HashMap<String, Vec<MAFRecord>> = HashMap::new();
+    for rec in second.records() {
+        let rec = rec?;
+        if !is_plus_ref(&rec) {
+            continue;
+        }
+        grouped
+            .entry(rec.target_name().to_string())
+            .or_default()
+            .push(rec);
+    }
+    let grouped = grouped
+        .into_iter()
+        .map(|(name, blocks)| (name, RefBlocks::new(blocks)))
+        .collect::<HashMap<_, _>>();
+
+    let mut mafwtr = MAFWriter::new(writer);
+    let header = "#maf version=1.6 cmd=maf_join".to_string();
+    mafwtr.write_header(&first.header_with(header))?;
+
+    for rec in first.records() {
+        let rec = rec?;
+        if !is_plus_ref(&rec) {
+            continue;
+        }
+        let ref_blocks = match grouped.get(rec.target_name()) {
+            Some(ref_blocks) => ref_blocks,
+            None => continue,
+        };
+        for other in ref_blocks.overlaps(rec.target_start(), rec.target_end()) {
+            let start = rec.target_start().max(other.target_start());
+            let end = rec.target_end().min(other.target_end());
+            let joined = join_blocks(&rec, other, start, end)?;
+            mafwtr.write_record(&joined)?;
+        }
+    }
+    mafwtr.finish()?;
+    Ok(())
+}
+
+// reference of join should be on '+' strand
+fn is_plus_ref(rec: &MAFRecord) -> bool {
+    match rec.slines.first() {
+        Some(sline) if sline.strand == Strand::Positive => true,
+        Some(sline) => {
+            warn!(
+                "reference {}:{} is on '-' strand, skip the block",
+                sline.name, sline.start
+            );
+            false
+        }
+        None => false,
+    }
+}
+
+/// Join two blocks sharing the reference (first s-line) on `[start, end)` of reference,
+/// columns inserted relative to reference in one block are gaps in the other block
+pub fn join_blocks(
+    first: &MAFRecord,
+    second: &MAFRecord,
+    start: u64,
+    end: u64,
+) -> Result<MAFRecord, WGAError> {
+    let mut first = first.clone();
+    let mut second = second.clone();
+    first.slice_block(start, end, 0)?;
+    second.slice_block(start, end, 0)?;
+
+    let first_ref = first.slines[0].seq.as_bytes();
+    let second_ref = second.slines[0].seq.as_bytes();
+    // column index of first and second in each joined column, None for an inserted gap
+    let mut first_cols = Vec::with_capacity(first_ref.len().max(second_ref.len()));
+    let mut second_cols = Vec::with_capacity(first_cols.capacity());
+    let (mut i, mut j) = (0, 0);
+    while i < first_ref.len() || j < second_ref.len() {
+        let first_ins = i < first_ref.len() && first_ref[i] == b'-';
+        let second_ins = j < second_ref.len() && second_ref[j] == b'-';
+        if first_ins || j >= second_ref.len() {
+            first_cols.push(Some(i));
+            second_cols.push(None);
+            i += 1;
+        } else if second_ins || i >= first_ref.len() {
+            first_cols.push(None);
+            second_cols.push(Some(j));
+            j += 1;
+        } else {
+            // same reference base
+            first_cols.push(Some(i));
+            second_cols.push(Some(j));
+            i += 1;
+            j += 1;
+        }
+    }
+
+    let project = |sline: &MAFSLine, cols: &[Option<usize>]| {
+        let seq = sline.seq.as_bytes();
+        let seq = cols
+            .iter()
+            .map(|col| col.map_or('-', |idx| seq[idx] as char))
+            .collect::<String>();
+        MAFSLine {
+            seq,
+            ..sline.clone()
+        }
+    };
+    let mut slines = Vec::with_capacity(first.slines.len() + second.slines.len() - 1);
+    for sline in &first.slines {
+        slines.push(project(sline, &first_cols));
+    }
+    for sline in second.slines.iter().skip(1) {
+        slines.push(project(sline, &second_cols));
+    }
+
+    let mut joined = MAFRecord {
+        // score of the joined block is unknown
+        score: 0,
+        slines,
+    };
+    drop_gap_columns(&mut joined);
+    Ok(joined)
+}
+
+// drop columns which are gaps in all s-lines
+fn drop_gap_columns(rec: &mut MAFRecord) {
+    let width = rec.slines.first().map_or(0, |s| s.seq.len());
+    let keep = (0..width)
+        .map(|col| rec.slines.iter().any(|s| s.seq.as_bytes()[col] != b'-'))
+        .collect::<Vec<_>>();
+    if keep.iter().all(|k| *k) {
+        return;
+    }
+    for sline in rec.slines.iter_mut() {
+        sline.seq = sline
+            .seq
+            .chars()
+            .zip(keep.iter())
+            .filter_map(|(c, k)| k.then_some(c))
+            .collect();
+    }
+}
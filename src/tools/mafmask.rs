@@ -0,0 +1,168 @@
+use crate::{
+    errors::WGAError,
+    parser::{
+        common::{AlignRecord, Strand},
+        maf::{MAFReader, MAFRecord, MAFWriter},
+    },
+    tools::{
+        interval::{self, Interval},
+        mafextra::GenomeRegion,
+    },
+    utils::TraceExt,
+};
+use log::info;
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+};
+
+// main function of maf mask, regions are on forward strand of reference (first s-line)
+// ref 0 7 + ACG-TTAC   mask ref:2-5   ref 0 7 + ACN-NNAC
+// q   0 8 + ACGATTAC       ===>       q   0 8 + ACNNNNAC
+// with `as_gap` masked columns are cut out, the block is split into unmasked pieces
+// so coordinates of all s-lines stay valid:
+// ref 0 2 + AC         ref 5 2 + AC
+// q   0 2 + AC         q   6 2 + AC
+pub fn maf_mask<R: Read + Send>(
+    mut reader: MAFReader<R>,
+    writer: &mut dyn Write,
+    regions: Vec<GenomeRegion>,
+    as_gap: bool,
+) -> Result<(), WGAError> {
+    let masks = merge_regions(regions);
+    let mut mafwtr = MAFWriter::new(writer);
+    let header = format!("#maf version=1.6 cmd=maf_mask as_gap={}", as_gap);
+    mafwtr.write_header(&reader.header_with(header))?;
+
+    let mut masked = 0;
+    for rec in reader.records().traced() {
+        let mut rec = rec?;
+        let ivs = block_masks(&rec, &masks);
+        if ivs.is_empty() {
+            mafwtr.write_record(&rec)?;
+            continue;
+        }
+        masked += 1;
+        if as_gap {
+            for piece in unmasked_pieces(&rec, &ivs)? {
+                mafwtr.write_record(&piece)?;
+            }
+        } else {
+            mask_block(&mut rec, &ivs)?;
+            mafwtr.write_record(&rec)?;
+        }
+    }
+    mafwtr.finish()?;
+    info!("{} blocks masked", masked);
+    Ok(())
+}
+
+// regions grouped by name, overlapping and book-ended ones merged
+fn merge_regions(regions: Vec<GenomeRegion>) -> HashMap<String, Vec<Interval>> {
+    let mut grouped: HashMap<String, Vec<Interval>> = HashMap::new();
+    for region in regions {
+        grouped
+            .entry(region.name)
+            .or_default()
+            .push(Interval::new(region.start, region.end));
+    }
+    grouped
+        .into_iter()
+        .map(|(name, mut ivs)| {
+            ivs.sort_unstable();
+            let merged = interval::merge(ivs).into_iter().map(|(iv, _)| iv);
+            (name, merged.filter(|iv| !iv.is_empty()).collect())
+        })
+        .collect()
+}
+
+// masks clipped to the block, on the strand of reference s-line, sorted by start
+fn block_masks(rec: &MAFRecord, masks: &HashMap<String, Vec<Interval>>) -> Vec<Interval> {
+    let Some(ivs) = rec.slines.first().and_then(|s| masks.get(&s.name)) else {
+        return Vec::new();
+    };
+    let reference = &rec.slines[0];
+    let block = rec.target_interval();
+    // masks are on '+', so the block is flipped to '+' to look them up
+    let flip = |iv: Interval| Interval::new(reference.size - iv.end, reference.size - iv.start);
+    let forward = match reference.strand {
+        Strand::Positive => block,
+        Strand::Negative => flip(block),
+    };
+    // merged masks don't overlap, so their ends are sorted
+    let first = ivs.partition_point(|iv| iv.end <= forward.start);
+    let mut clipped = ivs[first..]
+        .iter()
+        .take_while(|iv| iv.start < forward.end)
+        .filter_map(|iv| iv.intersect(&forward))
+        .map(|iv| match reference.strand {
+            Strand::Positive => iv,
+            Strand::Negative => flip(iv),
+        })
+        .collect::<Vec<_>>();
+    clipped.sort_unstable();
+    clipped
+}
+
+// columns of masks in the block, [first base, last base + 1) of reference
+fn mask_columns(rec: &MAFRecord, ivs: &[Interval]) -> Result<Vec<(usize, usize)>, WGAError> {
+    let reference = &rec.slines[0];
+    let out_of_block = |iv: &Interval| {
+        WGAError::RegionOutOfBlock(
+            reference.name.clone(),
+            iv.start,
+            iv.end,
+            reference.start,
+            reference.start + reference.align_size,
+        )
+    };
+    ivs.iter()
+        .map(|iv| {
+            let start = reference.get_col_coord(iv.start - reference.start);
+            let end = reference.get_col_coord(iv.end - reference.start - 1);
+            match (start, end) {
+                (Some(start), Some(end)) => Ok((start as usize, end as usize + 1)),
+                _ => Err(out_of_block(iv)),
+            }
+        })
+        .collect()
+}
+
+// replace bases in masked columns of all s-lines with N, gaps are kept
+fn mask_block(rec: &mut MAFRecord, ivs: &[Interval]) -> Result<(), WGAError> {
+    let cols = mask_columns(rec, ivs)?;
+    let is_masked = |col: usize| cols.iter().any(|&(start, end)| start <= col && col < end);
+    for sline in rec.slines.iter_mut() {
+        sline.seq = sline
+            .seq
+            .bytes()
+            .enumerate()
+            .map(|(col, c)| match c != b'-' && is_masked(col) {
+                true => 'N',
+                false => c as char,
+            })
+            .collect();
+    }
+    Ok(())
+}
+
+// pieces of the block between masks, sliced on reference coordinates
+fn unmasked_pieces(rec: &MAFRecord, ivs: &[Interval]) -> Result<Vec<MAFRecord>, WGAError> {
+    let block = rec.target_interval();
+    let mut pieces = Vec::new();
+    let mut push_piece = |start: u64, end: u64| -> Result<(), WGAError> {
+        if start < end {
+            let mut piece = rec.clone();
+            piece.slice_block(start, end, 0)?;
+            pieces.push(piece);
+        }
+        Ok(())
+    };
+    let mut start = block.start;
+    for iv in ivs {
+        push_piece(start, iv.start)?;
+        start = iv.end;
+    }
+    push_piece(start, block.end)?;
+    Ok(pieces)
+}
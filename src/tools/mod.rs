@@ -1,12 +1,26 @@
 pub mod caller;
+pub mod chainstat;
 pub mod chunk;
+pub mod cigarbed;
+pub mod coverage;
+pub mod dedup;
 pub mod dotplot;
 pub mod filter;
 pub mod index;
+pub mod interval;
 pub mod mafextra;
+pub mod mafjoin;
+pub mod mafmask;
+pub mod namefilter;
+pub mod normalize;
 pub mod pafcov;
+pub mod pafindex;
+pub mod pafsplit;
+pub mod primary;
 pub mod pseudomaf;
 pub mod rename;
+pub mod sort;
 pub mod stat;
+pub mod trimedges;
 pub mod trimovp;
 pub mod tview;
@@ -0,0 +1,71 @@
+use crate::{
+    errors::WGAError,
+    parser::maf::{is_species, MAFReader, MAFRecord, MAFWriter},
+    utils::TraceExt,
+};
+use std::io::{BufRead, Read, Write};
+
+/// Names to keep or drop MAF blocks, a name matches s-line `name` or `name.contig`
+#[derive(Debug, Default, Clone)]
+pub struct NameFilter {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    /// all of `include` should be in a block, instead of any
+    pub require_all: bool,
+}
+
+impl NameFilter {
+    /// Read names of a file, one per line, blank lines and lines start with `#` are skipped
+    pub fn read_names<R: BufRead>(reader: R) -> Result<Vec<String>, WGAError> {
+        let mut names = Vec::new();
+        for line in reader.lines() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            names.push(line.to_string());
+        }
+        Ok(names)
+    }
+
+    /// If the block is kept, any excluded name drops it
+    pub fn keep(&self, rec: &MAFRecord) -> bool {
+        let contains = |name: &String| rec.slines.iter().any(|s| is_species(&s.name, name));
+        if self.exclude.iter().any(contains) {
+            return false;
+        }
+        match (self.include.is_empty(), self.require_all) {
+            (true, _) => true,
+            (false, true) => self.include.iter().all(contains),
+            (false, false) => self.include.iter().any(contains),
+        }
+    }
+}
+
+// main function of maf filter by name
+// a.chr1 b.chr1        include a,b    a.chr1 b.chr1
+// a.chr1 c.chr2  ==>   exclude c  ==>
+// b.chr3 d.chr1        any            b.chr3 d.chr1
+pub fn filter_maf_by_name<R: Read + Send>(
+    mut reader: MAFReader<R>,
+    writer: &mut dyn Write,
+    filter: &NameFilter,
+) -> Result<(), WGAError> {
+    let mut mafwtr = MAFWriter::new(writer);
+    let header = format!(
+        "#maf version=1.6 cmd=maf_filter_by_name include={} exclude={} require={}",
+        filter.include.join(","),
+        filter.exclude.join(","),
+        if filter.require_all { "all" } else { "any" }
+    );
+    mafwtr.write_header(&reader.header_with(header))?;
+    for rec in reader.records().traced() {
+        let rec = rec?;
+        if filter.keep(&rec) {
+            mafwtr.write_record(&rec)?;
+        }
+    }
+    mafwtr.finish()?;
+    Ok(())
+}
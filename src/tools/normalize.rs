@@ -0,0 +1,77 @@
+use crate::{
+    errors::WGAError,
+    parser::maf::{MAFReader, MAFRecord, MAFWriter},
+};
+use std::io::{Read, Write};
+
+// main function of normalize maf, merge adjacent blocks
+// A 0 3 + 10 AC-G      A 3 2 + 10 TT
+// B 5 4 + 20 ACTG  +   B 9 2 + 20 T-  =>
+//
+// A 0 5 + 10 AC-GTT
+// B 5 5 + 20 ACTGT-
+//
+// trim_gaps removes leading and trailing all-gap columns of each block before merging,
+// flip_to_target_plus reverse complements blocks whose target is on '-' strand
+pub fn normalize_maf<R: Read + Send>(
+    mut reader: MAFReader<R>,
+    writer: &mut dyn Write,
+    trim_gaps: bool,
+    flip_to_target_plus: bool,
+) -> Result<(), WGAError> {
+    // init a MAFWriter
+    let mut mafwtr = MAFWriter::new(writer);
+    // write header
+    let mut header = "#maf version=1.6 normalize=merge".to_string();
+    if flip_to_target_plus {
+        header.push_str(" flip=target_plus");
+    }
+    mafwtr.write_header(&reader.header_with(header))?;
+
+    let mut current: Option<MAFRecord> = None;
+    for rec in reader.records() {
+        let mut rec = rec?;
+        if flip_to_target_plus {
+            rec.flip_to_target_plus();
+        }
+        if trim_gaps {
+            rec.trim_gap_columns();
+        }
+        current = match current {
+            Some(mut prev) if is_adjacent(&prev, &rec) => {
+                merge_blocks(&mut prev, rec);
+                Some(prev)
+            }
+            Some(prev) => {
+                mafwtr.write_record(&prev)?;
+                Some(rec)
+            }
+            None => Some(rec),
+        };
+    }
+    if let Some(prev) = current {
+        mafwtr.write_record(&prev)?;
+    }
+    mafwtr.finish()?;
+    Ok(())
+}
+
+/// If `next` block follows `prev` in all s-lines: same names, strands and sizes in
+/// same order, and each s-line starts right at the end of previous one
+pub fn is_adjacent(prev: &MAFRecord, next: &MAFRecord) -> bool {
+    prev.slines.len() == next.slines.len()
+        && prev.slines.iter().zip(next.slines.iter()).all(|(p, n)| {
+            p.name == n.name
+                && p.strand == n.strand
+                && p.size == n.size
+                && p.start + p.align_size == n.start
+        })
+}
+
+/// Concatenate s-lines of `next` into `prev`, gaps are preserved
+pub fn merge_blocks(prev: &mut MAFRecord, next: MAFRecord) {
+    for (p, n) in prev.slines.iter_mut().zip(next.slines) {
+        p.set_align_size(p.align_size + n.align_size);
+        p.seq.push_str(&n.seq);
+    }
+}
@@ -36,7 +36,7 @@ pub fn pafcov<R: Read + Send>(
             let cov_vec = acc.entry(target_name).or_insert(vec![0; target_length]);
             let cigar = rec.get_cigar_str()?;
             let start = rec.target_start() as usize;
-            update_cov_vec(cov_vec, cigar, start)?;
+            update_cov_vec(cov_vec, &cigar, start)?;
             Ok::<HashMap<String, Vec<usize>>, WGAError>(acc)
         })
         .try_reduce(HashMap::new, |mut acc, mut map| {
@@ -0,0 +1,180 @@
+use crate::{
+    errors::WGAError,
+    parser::{
+        paf::PafRecord,
+        writer::{PafWriter, RecordWriter},
+    },
+    tools::mafextra::{get_input_regions, GenomeRegion},
+};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+pub type PafIndex = HashMap<String, PafIndexItem>;
+
+/// Byte range of the records of one target in a PAF grouped by target
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PafIndexItem {
+    /// length of target
+    pub size: u64,
+    /// byte offset of the first record
+    pub offset: u64,
+    /// byte offset after the last record
+    pub end: u64,
+    /// if records are sorted by target start, so a scan stops at the region end
+    pub sorted: bool,
+}
+
+/// Serialized PAF index, `format` tells it from a MAF index
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PafIndexFile {
+    pub format: String,
+    pub index: PafIndex,
+}
+
+// read the next record line of PAF, blank and `#` lines are skipped,
+// returns the record and the byte length of lines read
+fn next_paf_line<R: BufRead>(
+    reader: &mut R,
+    line: &mut Vec<u8>,
+) -> Result<Option<(PafRecord, u64)>, WGAError> {
+    let mut read = 0;
+    loop {
+        line.clear();
+        let len = reader.read_until(b'\n', line)? as u64;
+        if len == 0 {
+            return Ok(None);
+        }
+        read += len;
+        let text = String::from_utf8_lossy(line);
+        let text = text.trim_end();
+        if text.is_empty() || text.starts_with('#') {
+            continue;
+        }
+        return Ok(Some((text.parse()?, read)));
+    }
+}
+
+// main function of paf index, records of a target should be adjacent
+// t1 0 10 ...  offset 0           t1: [0, 240), sorted
+// t1 5 20 ...           ==>       t2: [240, 360), sorted
+// t2 0 30 ...  offset 240
+// a target seen again after others is an error, unsorted starts are warned,
+// then extract scans all records of the target
+pub fn build_paf_index<R: BufRead>(mut reader: R, idx_wtr: Box<dyn Write>) -> Result<(), WGAError> {
+    let mut idx: PafIndex = HashMap::new();
+    // target, its item and start of the last record
+    let mut current: Option<(String, PafIndexItem, u64)> = None;
+    let mut pos = 0;
+    let mut line = Vec::new();
+    while let Some((rec, len)) = next_paf_line(&mut reader, &mut line)? {
+        let offset = pos + len - line.len() as u64;
+        pos += len;
+        match current.as_mut() {
+            Some((name, item, last_start)) if *name == rec.target_name => {
+                if rec.target_start < *last_start && item.sorted {
+                    warn!(
+                        "records of `{}` are not sorted by start, extract will scan all of them",
+                        name
+                    );
+                    item.sorted = false;
+                }
+                *last_start = rec.target_start;
+                item.end = pos;
+            }
+            _ => {
+                if idx.contains_key(&rec.target_name) {
+                    return Err(WGAError::UnsortedGroup(rec.target_name));
+                }
+                let item = PafIndexItem {
+                    size: rec.target_length,
+                    offset,
+                    end: pos,
+                    sorted: true,
+                };
+                let start = rec.target_start;
+                if let Some((name, item, _)) = current.replace((rec.target_name, item, start)) {
+                    idx.insert(name, item);
+                }
+            }
+        }
+    }
+    if let Some((name, item, _)) = current {
+        idx.insert(name, item);
+    }
+    if idx.is_empty() {
+        warn!("no records in PAF, the index is empty");
+    }
+    let index_file = PafIndexFile {
+        format: "paf".to_string(),
+        index: idx,
+    };
+    serde_json::to_writer(idx_wtr, &index_file)?;
+    Ok(())
+}
+
+/// Load a PAF index written by `build_paf_index`
+pub fn load_paf_index<P: AsRef<Path>>(path: P) -> Result<PafIndexFile, WGAError> {
+    let path = path.as_ref();
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(WGAError::FileNotExist(path.to_path_buf()))
+        }
+        Err(e) => return Err(WGAError::Io(e)),
+    };
+    let index_file: PafIndexFile = serde_json::from_reader(BufReader::new(file))?;
+    if index_file.format != "paf" {
+        return Err(WGAError::IndexCorrupted(
+            path.to_path_buf(),
+            format!("format `{}` is not `paf`", index_file.format),
+        ));
+    }
+    Ok(index_file)
+}
+
+/// Extract PAF records overlapping target regions by index, in order of regions,
+/// regions of targets not in the index are returned
+pub fn paf_extract_idx<R: Read + Seek>(
+    regions: &Option<Vec<String>>,
+    region_file: &Option<String>,
+    reader: R,
+    pafindex: &PafIndex,
+    writer: &mut dyn Write,
+) -> Result<Vec<GenomeRegion>, WGAError> {
+    let mut reader = BufReader::new(reader);
+    let mut pafwtr = PafWriter::new(writer);
+    let mut failed_regions = Vec::new();
+    let mut line = Vec::new();
+    for region in get_input_regions(regions, region_file)? {
+        let item = match pafindex.get(&region.name) {
+            Some(item) => item,
+            None => {
+                failed_regions.push(region);
+                continue;
+            }
+        };
+        reader.seek(SeekFrom::Start(item.offset))?;
+        let mut pos = item.offset;
+        while pos < item.end {
+            let (rec, len) = match next_paf_line(&mut reader, &mut line)? {
+                Some(next) => next,
+                None => break,
+            };
+            pos += len;
+            if item.sorted && rec.target_start >= region.end {
+                break;
+            }
+            if region.overlaps(rec.target_start, rec.target_end) {
+                pafwtr.write_record(&rec)?;
+            }
+        }
+    }
+    pafwtr.flush()?;
+    Ok(failed_regions)
+}
@@ -0,0 +1,156 @@
+use crate::{
+    errors::WGAError,
+    parser::{
+        cigar::cigar_units,
+        common::{AlignRecord, Strand},
+        paf::PafRecord,
+        writer::{PafWriter, RecordWriter},
+    },
+};
+use log::{info, warn};
+use std::io::Write;
+
+// tags describing the whole alignment, they are stale for a fragment
+const STALE_TAGS: [&str; 6] = ["cg:Z:", "cs:Z:", "NM:i:", "AS:i:", "de:f:", "dv:f:"];
+
+// one fragment of a CIGAR, offsets are from the start of the alignment
+pub(crate) struct Fragment {
+    pub(crate) target_offset: u64,
+    pub(crate) query_offset: u64,
+    pub(crate) units: Vec<(char, u64)>,
+}
+
+/// Split a PAF record at each insertion or deletion longer than `max_gap`,
+/// gaps at both ends of a fragment are trimmed, a record without such gaps is kept as is
+pub fn split_paf_record(rec: &PafRecord, max_gap: u64) -> Result<Vec<PafRecord>, WGAError> {
+    let units = cigar_units(&rec.get_cigar_str()?)?;
+    let mut fragments = Vec::new();
+    let mut current: Option<Fragment> = None;
+    let (mut target_offset, mut query_offset) = (0, 0);
+    for &(op, len) in &units {
+        let (target_len, query_len) = match op {
+            'M' | '=' | 'X' => (len, len),
+            'D' => (len, 0),
+            'I' => (0, len),
+            _ => return Err(WGAError::CigarOpInvalid(op.to_string())),
+        };
+        let is_gap = query_len == 0 || target_len == 0;
+        if is_gap && len > max_gap {
+            fragments.extend(current.take());
+        } else if !is_gap || current.is_some() {
+            // leading gaps of a fragment are skipped
+            current
+                .get_or_insert_with(|| Fragment {
+                    target_offset,
+                    query_offset,
+                    units: Vec::new(),
+                })
+                .units
+                .push((op, len));
+        }
+        target_offset += target_len;
+        query_offset += query_len;
+    }
+    fragments.extend(current);
+
+    // trailing gaps of a fragment
+    for fragment in fragments.iter_mut() {
+        while matches!(fragment.units.last(), Some(('I' | 'D', _))) {
+            fragment.units.pop();
+        }
+    }
+    if let [fragment] = fragments.as_slice() {
+        if fragment.units.len() == units.len() {
+            return Ok(vec![rec.clone()]);
+        }
+    }
+    Ok(fragments
+        .iter()
+        .map(|fragment| fragment_record(rec, fragment))
+        .collect())
+}
+
+// record of a fragment, CIGAR walks target forward and query backward on '-' strand
+pub(crate) fn fragment_record(rec: &PafRecord, fragment: &Fragment) -> PafRecord {
+    let (mut target_span, mut query_span, mut matches, mut block_length) = (0, 0, 0, 0);
+    for &(op, len) in &fragment.units {
+        match op {
+            'M' | '=' => {
+                matches += len;
+                target_span += len;
+                query_span += len;
+            }
+            'X' => {
+                target_span += len;
+                query_span += len;
+            }
+            'D' => target_span += len,
+            _ => query_span += len,
+        }
+        block_length += len;
+    }
+    let (query_start, query_end) = match rec.strand {
+        Strand::Positive => {
+            let start = rec.query_start + fragment.query_offset;
+            (start, start + query_span)
+        }
+        Strand::Negative => {
+            let end = rec.query_end - fragment.query_offset;
+            (end - query_span, end)
+        }
+    };
+    let mut tags = rec
+        .tags
+        .iter()
+        .filter(|tag| !STALE_TAGS.iter().any(|prefix| tag.starts_with(prefix)))
+        .cloned()
+        .collect::<Vec<_>>();
+    tags.push(format!(
+        "cg:Z:{}",
+        fragment
+            .units
+            .iter()
+            .map(|(op, len)| format!("{}{}", len, op))
+            .collect::<String>()
+    ));
+    PafRecord {
+        query_start,
+        query_end,
+        target_start: rec.target_start + fragment.target_offset,
+        target_end: rec.target_start + fragment.target_offset + target_span,
+        matches,
+        block_length,
+        tags,
+        ..rec.clone()
+    }
+}
+
+// main function of paf split, max gap is 5
+// t1 0 28 q1 0 20 + 10M10D2I8M  ==>  t1 0 10 q1 0 10 + 10M
+//                                    t1 20 28 q1 12 20 + 8M
+// the 2I at the start of second fragment is trimmed
+pub fn paf_split<I>(records: I, writer: &mut dyn Write, max_gap: u64) -> Result<(), WGAError>
+where
+    I: Iterator<Item = Result<PafRecord, WGAError>>,
+{
+    let mut pafwtr = PafWriter::new(writer);
+    let (mut total, mut written) = (0, 0);
+    for rec in records {
+        let rec = rec?;
+        total += 1;
+        let fragments = split_paf_record(&rec, max_gap)?;
+        if fragments.is_empty() {
+            warn!(
+                "{}:{}-{} has no aligned bases, skip the record",
+                rec.query_name, rec.query_start, rec.query_end
+            );
+        }
+        for fragment in &fragments {
+            pafwtr.write_record(fragment)?;
+        }
+        written += fragments.len();
+    }
+    pafwtr.flush()?;
+    info!("{} records are split into {} records", total, written);
+    Ok(())
+}
@@ -0,0 +1,48 @@
+use crate::{
+    errors::WGAError,
+    parser::common::{AlignRecord, IdentityMode, PrimaryBy},
+};
+use log::info;
+
+// key of a record to compare, larger is better
+fn primary_key<T: AlignRecord>(rec: &T, by: PrimaryBy) -> Result<f64, WGAError> {
+    Ok(match by {
+        PrimaryBy::Identity => rec.identity(IdentityMode::Blast)?,
+        PrimaryBy::Length => (rec.query_end() - rec.query_start()) as f64,
+        PrimaryBy::Score => rec.align_score(),
+    })
+}
+
+// main function of primary, the best record of each query group is primary
+// q1 t1 0 100 (len 100)          q1 t1 0 100  primary
+// q1 t2 0 80  (len 80)   ==>     q1 t2 0 80   secondary
+// q2 t1 0 50  (len 50)  length   q2 t1 0 50   primary
+// the first record wins ties, records are passed to `write` in group order
+pub fn primary<T, I, F>(groups: I, by: PrimaryBy, mut write: F) -> Result<(), WGAError>
+where
+    T: AlignRecord,
+    I: Iterator<Item = Result<(String, Vec<T>), WGAError>>,
+    F: FnMut(T, bool) -> Result<(), WGAError>,
+{
+    let mut queries = 0;
+    let mut total = 0;
+    for group in groups {
+        let (_, records) = group?;
+        let mut best = 0;
+        let mut best_key = f64::MIN;
+        for (idx, rec) in records.iter().enumerate() {
+            let key = primary_key(rec, by)?;
+            if key > best_key {
+                best = idx;
+                best_key = key;
+            }
+        }
+        queries += 1;
+        total += records.len();
+        for (idx, rec) in records.into_iter().enumerate() {
+            write(rec, idx == best)?;
+        }
+    }
+    info!("{} primary records, {} secondary", queries, total - queries);
+    Ok(())
+}
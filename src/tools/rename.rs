@@ -1,8 +1,16 @@
 use crate::{
     errors::WGAError,
-    parser::maf::{MAFReader, MAFWriter},
+    parser::{
+        chain::ChainReader,
+        common::AlignRecord,
+        maf::{MAFReader, MAFWriter},
+        paf::PAFReader,
+    },
+};
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    io::{BufRead, Read, Write},
 };
-use std::io::{Read, Write};
 // filter maf
 pub fn rename_maf<R: Read + Send>(
     mut reader: MAFReader<R>,
@@ -13,11 +21,203 @@ pub fn rename_maf<R: Read + Send>(
     let mut mafwtr = MAFWriter::new(writer);
     // write header
     let header = format!("#maf version=1.6 rename={}", prefixs.join(";"));
-    mafwtr.write_header(header)?;
+    mafwtr.write_header(&reader.header_with(header))?;
     for rec in reader.records() {
         let mut rec = rec?;
         rec.rename(&prefixs)?;
         mafwtr.write_record(&rec)?;
     }
+    mafwtr.finish()?;
+    Ok(())
+}
+
+/// Name map from a two-column TSV of old and new names,
+/// blank lines and lines start with `#` are skipped
+pub struct NameMap {
+    map: HashMap<String, String>,
+    strict: bool,
+}
+
+impl NameMap {
+    /// Read a name map, error on unmapped names later if `strict`
+    pub fn from_reader<R: BufRead>(reader: R, strict: bool) -> Result<Self, WGAError> {
+        let mut map = HashMap::new();
+        for line in reader.lines() {
+            let line = line?;
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (old, new) = match line.split_once('\t') {
+                Some((old, new)) if !old.is_empty() && !new.is_empty() => (old, new),
+                _ => {
+                    return Err(WGAError::Other(anyhow::anyhow!(
+                        "invalid name map line `{}`, expect `old\\tnew`",
+                        line
+                    )))
+                }
+            };
+            if map.insert(old.to_string(), new.to_string()).is_some() {
+                return Err(WGAError::Other(anyhow::anyhow!(
+                    "duplicate name `{}` in name map",
+                    old
+                )));
+            }
+        }
+        Ok(NameMap { map, strict })
+    }
+
+    /// Get new name, unmapped name is unchanged or an error if `strict`
+    pub fn get(&self, name: &str) -> Result<String, WGAError> {
+        match self.map.get(name) {
+            Some(new) => Ok(new.clone()),
+            None if self.strict => Err(WGAError::NameNotMapped(name.to_string())),
+            None => Ok(name.to_string()),
+        }
+    }
+}
+
+/// New name of a target or query sequence
+pub trait Renamer {
+    fn rename(&mut self, name: &str, is_target: bool) -> Result<String, WGAError>;
+}
+
+impl Renamer for NameMap {
+    fn rename(&mut self, name: &str, _is_target: bool) -> Result<String, WGAError> {
+        self.get(name)
+    }
+}
+
+/// Rename PanSN names like `sample#haplotype#contig` split by `sep`:
+/// strip the prefix before the last `sep`, then add `target_prefix` or `query_prefix`,
+/// renamed names are kept to write a reverse name map for `rename --map`
+pub struct PanSN {
+    pub sep: String,
+    pub strip: bool,
+    pub target_prefix: Option<String>,
+    pub query_prefix: Option<String>,
+    // new name -> old name
+    renamed: HashMap<String, String>,
+}
+
+impl PanSN {
+    pub fn new(
+        sep: String,
+        strip: bool,
+        target_prefix: Option<String>,
+        query_prefix: Option<String>,
+    ) -> Self {
+        PanSN {
+            sep,
+            strip,
+            target_prefix,
+            query_prefix,
+            renamed: HashMap::new(),
+        }
+    }
+
+    /// Write `new\told` lines in natural order of new names, input of `rename --map`
+    pub fn write_map(&self, writer: &mut dyn Write) -> Result<(), WGAError> {
+        let mut names = self.renamed.iter().collect::<Vec<_>>();
+        names.sort_by(|a, b| natord::compare(a.0, b.0));
+        for (new, old) in names {
+            writeln!(writer, "{}\t{}", new, old)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+impl Renamer for PanSN {
+    fn rename(&mut self, name: &str, is_target: bool) -> Result<String, WGAError> {
+        let mut new = name;
+        if self.strip {
+            new = name
+                .rsplit_once(&self.sep)
+                .map_or(name, |(_, contig)| contig);
+        }
+        let prefix = match is_target {
+            true => &self.target_prefix,
+            false => &self.query_prefix,
+        };
+        let new = match prefix {
+            Some(prefix) => format!("{}{}{}", prefix, self.sep, new),
+            None => new.to_string(),
+        };
+        match self.renamed.entry(new.clone()) {
+            Entry::Occupied(old) if old.get() != name => {
+                return Err(WGAError::Other(anyhow::anyhow!(
+                    "`{}` and `{}` are both renamed to `{}`, can't be reversed",
+                    old.get(),
+                    name,
+                    new
+                )))
+            }
+            Entry::Occupied(_) => {}
+            Entry::Vacant(slot) => {
+                slot.insert(name.to_string());
+            }
+        }
+        Ok(new)
+    }
+}
+
+// rename s-lines of maf by name map, the first s-line is target
+pub fn rename_maf_by_map<R: Read + Send>(
+    mut reader: MAFReader<R>,
+    writer: &mut dyn Write,
+    name_map: &mut impl Renamer,
+) -> Result<(), WGAError> {
+    let mut mafwtr = MAFWriter::new(writer);
+    mafwtr.write_header(&reader.header_with("#maf version=1.6 rename=map".to_string()))?;
+    for rec in reader.records() {
+        let mut rec = rec?;
+        for (idx, sline) in rec.slines.iter_mut().enumerate() {
+            let new_name = name_map.rename(&sline.name, idx == 0)?;
+            sline.set_name(new_name);
+        }
+        mafwtr.write_record(&rec)?;
+    }
+    mafwtr.finish()?;
+    Ok(())
+}
+
+// rename query and target of paf by name map
+pub fn rename_paf_by_map<R: Read + Send>(
+    mut reader: PAFReader<R>,
+    writer: &mut dyn Write,
+    name_map: &mut impl Renamer,
+) -> Result<(), WGAError> {
+    let mut pafwtr = csv::WriterBuilder::new()
+        .flexible(true)
+        .delimiter(b'\t')
+        .has_headers(false)
+        .from_writer(writer);
+    for rec in reader.records() {
+        let mut rec = rec?;
+        rec.query_name = name_map.rename(&rec.query_name, false)?;
+        rec.target_name = name_map.rename(&rec.target_name, true)?;
+        pafwtr.serialize(rec)?;
+    }
+    pafwtr.flush()?;
+    Ok(())
+}
+
+// rename tName and qName of chain by name map
+pub fn rename_chain_by_map<R: Read + Send>(
+    mut reader: ChainReader<R>,
+    writer: &mut dyn Write,
+    name_map: &mut impl Renamer,
+) -> Result<(), WGAError> {
+    for rec in reader.records()? {
+        let mut rec = rec?;
+        let target_name = name_map.rename(rec.target_name(), true)?;
+        let query_name = name_map.rename(rec.query_name(), false)?;
+        rec.header.set_target_name(target_name);
+        rec.header.set_query_name(query_name);
+        // additional newline for standard chain format
+        write!(writer, "{}\n\n", rec)?;
+    }
+    writer.flush()?;
     Ok(())
 }
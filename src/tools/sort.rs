@@ -0,0 +1,170 @@
+use crate::{
+    errors::WGAError,
+    parser::{
+        maf::{MAFReader, MAFRecord, MAFWriter},
+        paf::PafRecord,
+    },
+    utils::TraceExt,
+};
+use log::debug;
+use rayon::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Default approximate memory of records before spilling a sorted run into temp file
+pub const SORT_BUFFER_SIZE: u64 = 1 << 30;
+
+// make temp file names unique between sorters in a process
+static SORTER_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Sort records by `Ord`, fall back to an external merge sort when records over
+/// `buffer_size` bytes, runs are spilled into bincode temp files
+pub struct ExternalSorter<T> {
+    buf: Vec<T>,
+    buf_bytes: u64,
+    buffer_size: u64,
+    // temp file and record count of each sorted run
+    runs: Vec<(PathBuf, usize)>,
+    id: usize,
+}
+
+/// Sort PafRecords by target name (natural order) and target start
+pub type PafSorter = ExternalSorter<PafRecord>;
+
+/// Sort MAFRecords by target name (natural order) and target start
+pub type MafSorter = ExternalSorter<MAFRecord>;
+
+impl<T> ExternalSorter<T>
+where
+    T: Ord + Send + Serialize + DeserializeOwned,
+{
+    /// Create a new ExternalSorter
+    pub fn new(buffer_size: u64) -> Self {
+        ExternalSorter {
+            buf: Vec::new(),
+            buf_bytes: 0,
+            buffer_size: buffer_size.max(1),
+            runs: Vec::new(),
+            id: SORTER_ID.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+
+    /// Push a record, spill a sorted run if buffer is full
+    pub fn push(&mut self, rec: T) -> Result<(), WGAError> {
+        // encoded size is close to heap size of names and sequences
+        self.buf_bytes += bincode::serialized_size(&rec)? + size_of::<T>() as u64;
+        self.buf.push(rec);
+        if self.buf_bytes >= self.buffer_size {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    // sort the buffer and write it into a temp file
+    fn spill(&mut self) -> Result<(), WGAError> {
+        let path = std::env::temp_dir().join(format!(
+            "wgatools.{}.{}.{}.bin",
+            std::process::id(),
+            self.id,
+            self.runs.len()
+        ));
+        debug!("spill sorted run into `{}`", path.display());
+        // push first, make sure the file will be removed even if writing failed
+        self.runs.push((path.clone(), self.buf.len()));
+
+        self.buf.par_sort();
+        let mut wtr = BufWriter::new(File::create(&path)?);
+        for rec in self.buf.drain(..) {
+            bincode::serialize_into(&mut wtr, &rec)?;
+        }
+        wtr.flush()?;
+        self.buf_bytes = 0;
+        Ok(())
+    }
+
+    /// Pass all records to `write` in sorted order
+    pub fn write_sorted<F>(mut self, mut write: F) -> Result<(), WGAError>
+    where
+        F: FnMut(T) -> Result<(), WGAError>,
+    {
+        // all records in memory
+        if self.runs.is_empty() {
+            self.buf.par_sort();
+            for rec in self.buf.drain(..) {
+                write(rec)?;
+            }
+            return Ok(());
+        }
+
+        if !self.buf.is_empty() {
+            self.spill()?;
+        }
+        // k-way merge, run index makes ties stable
+        let mut readers = self
+            .runs
+            .iter()
+            .map(|(path, count)| Ok((BufReader::new(File::open(path)?), *count)))
+            .collect::<Result<Vec<_>, WGAError>>()?;
+        let mut next = |idx: usize| -> Result<Option<T>, WGAError> {
+            let (rdr, left) = &mut readers[idx];
+            if *left == 0 {
+                return Ok(None);
+            }
+            *left -= 1;
+            Ok(Some(bincode::deserialize_from(rdr)?))
+        };
+        let mut heap = BinaryHeap::new();
+        for idx in 0..self.runs.len() {
+            if let Some(rec) = next(idx)? {
+                heap.push(Reverse((rec, idx)));
+            }
+        }
+        while let Some(Reverse((rec, idx))) = heap.pop() {
+            write(rec)?;
+            if let Some(rec) = next(idx)? {
+                heap.push(Reverse((rec, idx)));
+            }
+        }
+        Ok(())
+    }
+}
+
+// remove temp files
+impl<T> Drop for ExternalSorter<T> {
+    fn drop(&mut self) {
+        for (path, _) in &self.runs {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+// main function of maf sort, blocks of all inputs are sorted into one MAF,
+// header of the first input is kept
+pub fn maf_sort<R: Read + Send>(
+    readers: Vec<MAFReader<R>>,
+    writer: &mut dyn Write,
+    buffer_size: u64,
+) -> Result<(), WGAError> {
+    let mut wtr = MAFWriter::new(writer);
+    let header = "#maf version=1.6 cmd=maf_sort".to_string();
+    match readers.first() {
+        Some(first) => wtr.write_header(&first.header_with(header))?,
+        None => return Ok(()),
+    }
+    let mut sorter = MafSorter::new(buffer_size);
+    for mut reader in readers {
+        for rec in reader.records().traced() {
+            sorter.push(rec?)?;
+        }
+    }
+    sorter.write_sorted(|rec| wtr.write_record(&rec))?;
+    wtr.finish()?;
+    Ok(())
+}
@@ -1,10 +1,12 @@
 use crate::{
     errors::WGAError,
     parser::{
-        common::{AlignRecord, RecStat},
+        chain::ChainReader,
+        common::{AlignRecord, IdentityMode, RecStat},
         maf::MAFReader,
         paf::PAFReader,
     },
+    utils::TraceExt,
 };
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
@@ -35,18 +37,497 @@ pub struct Statistic {
     pub unaligned_size: u64,
     pub identity: f32,
     pub similarity: f32,
-    pub matched: usize,       // agg
-    pub mismatched: usize,    // agg
-    pub ins_event: usize,     // agg
-    pub del_event: usize,     // agg
-    pub ins_size: usize,      // agg
-    pub del_size: usize,      // agg
-    pub inv_event: usize,     // agg
-    pub inv_size: f32,        // agg
-    pub inv_ins_event: usize, // agg
-    pub inv_ins_size: usize,  // agg
-    pub inv_del_event: usize, // agg
-    pub inv_del_size: usize,  // agg
+    pub gap_compressed_identity: f32, // matched / (matched + mismatched + gap events)
+    pub block_identity: f32,          // matched / all alignment columns
+    pub matched: usize,               // agg
+    pub mismatched: usize,            // agg
+    pub ins_event: usize,             // agg
+    pub del_event: usize,             // agg
+    pub ins_size: usize,              // agg
+    pub del_size: usize,              // agg
+    pub inv_event: usize,             // agg
+    pub inv_size: f32,                // agg
+    pub inv_ins_event: usize,         // agg
+    pub inv_ins_size: usize,          // agg
+    pub inv_del_event: usize,         // agg
+    pub inv_del_size: usize,          // agg
+}
+
+/// Statistic of a target sequence, Serialize for output
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct TargetStatistic {
+    pub target_name: String,
+    pub target_size: u64,
+    pub records: usize,
+    pub aligned_bases: u64, // sum of target aligned size, overlaps counted repeatedly
+    pub covered_bases: u64, // merged target intervals
+    pub covered_fraction: f64,
+    pub identity: Option<f32>, // matched / aligned columns of all records, see `IdentityMode`
+}
+
+// accumulator of a target sequence
+#[derive(Default)]
+struct TargetAcc {
+    size: u64,
+    records: usize,
+    aligned_bases: u64,
+    rec_stat: RecStat,
+    ivls: Vec<(u64, u64)>,
+}
+
+impl TargetAcc {
+    fn merge(&mut self, mut other: TargetAcc) {
+        self.size = self.size.max(other.size);
+        self.records += other.records;
+        self.aligned_bases += other.aligned_bases;
+        add_rec_stat(&mut self.rec_stat, &other.rec_stat);
+        self.ivls.append(&mut other.ivls);
+    }
+
+    // length of the union of intervals
+    fn covered(&mut self) -> u64 {
+        self.ivls.sort_unstable();
+        let mut covered = 0;
+        let mut current: Option<(u64, u64)> = None;
+        for &(start, end) in &self.ivls {
+            current = match current {
+                Some((s, e)) if start <= e => Some((s, e.max(end))),
+                Some((s, e)) => {
+                    covered += e - s;
+                    Some((start, end))
+                }
+                None => Some((start, end)),
+            };
+        }
+        if let Some((s, e)) = current {
+            covered += e - s;
+        }
+        covered
+    }
+}
+
+// aggregate a RecStat into another
+fn add_rec_stat(acc: &mut RecStat, rec_stat: &RecStat) {
+    acc.aligned_size += rec_stat.aligned_size;
+    acc.matched += rec_stat.matched;
+    acc.mismatched += rec_stat.mismatched;
+    acc.ins_event += rec_stat.ins_event;
+    acc.del_event += rec_stat.del_event;
+    acc.ins_size += rec_stat.ins_size;
+    acc.del_size += rec_stat.del_size;
+    acc.inv_ins_event += rec_stat.inv_ins_event;
+    acc.inv_ins_size += rec_stat.inv_ins_size;
+    acc.inv_del_event += rec_stat.inv_del_event;
+    acc.inv_del_size += rec_stat.inv_del_size;
+    acc.inv_event += rec_stat.inv_event;
+    acc.inv_size += rec_stat.inv_size;
+    acc.update_identities();
+}
+
+type TargetAccMap = HashMap<String, TargetAcc>;
+
+// streaming accumulation keyed by target name, `fast` skips CIGAR of records
+fn acc_by_target<T, I>(records: I, fast: bool) -> Result<TargetAccMap, WGAError>
+where
+    T: AlignRecord + Send,
+    I: Iterator<Item = Result<T, WGAError>> + Send,
+{
+    records
+        .par_bridge()
+        .try_fold(TargetAccMap::new, |mut acc, rec| {
+            let rec = rec?;
+            let target = acc.entry(rec.target_name().to_string()).or_default();
+            target.size = rec.target_length();
+            target.records += 1;
+            target.aligned_bases += rec.target_end() - rec.target_start();
+            if !fast {
+                add_rec_stat(&mut target.rec_stat, &rec.get_stat()?);
+            }
+            target.ivls.push((rec.target_start(), rec.target_end()));
+            Ok::<TargetAccMap, WGAError>(acc)
+        })
+        .try_reduce(TargetAccMap::new, |mut acc, map| {
+            for (name, target) in map {
+                acc.entry(name).or_default().merge(target);
+            }
+            Ok(acc)
+        })
+}
+
+// finalize accumulations into natural ordered TargetStatistic, no identity if `fast`
+fn finalize_by_target(
+    acc_map: TargetAccMap,
+    mode: IdentityMode,
+    fast: bool,
+) -> Vec<TargetStatistic> {
+    let mut final_stat = acc_map
+        .into_iter()
+        .map(|(target_name, mut target)| {
+            let covered_bases = target.covered();
+            TargetStatistic {
+                target_name,
+                target_size: target.size,
+                records: target.records,
+                aligned_bases: target.aligned_bases,
+                covered_bases,
+                covered_fraction: covered_bases as f64 / target.size as f64,
+                identity: (!fast).then(|| target.rec_stat.identity(mode) as f32),
+            }
+        })
+        .collect::<Vec<_>>();
+    final_stat.sort_by(|a, b| natord::compare(&a.target_name, &b.target_name));
+    final_stat
+}
+
+// stat by target sequence, for all AlignRecord iterators,
+// `fast` leaves identity empty, as CIGAR of records is not computed
+pub fn stat_by_target<T, I>(
+    records: I,
+    writer: &mut dyn Write,
+    mode: IdentityMode,
+    fast: bool,
+) -> Result<(), WGAError>
+where
+    T: AlignRecord + Send,
+    I: Iterator<Item = Result<T, WGAError>> + Send,
+{
+    let final_stat = finalize_by_target(acc_by_target(records, fast)?, mode, fast);
+
+    let mut wtr = csv::WriterBuilder::new()
+        .delimiter(b'\t')
+        .has_headers(true)
+        .from_writer(writer);
+    for stat in final_stat {
+        wtr.serialize(stat)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// Statistic of a target or query sequence, Serialize for output
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct SequenceStatistic {
+    pub kind: String, // target or query
+    pub name: String,
+    pub size: u64,
+    pub blocks: usize,
+    pub aligned_bases: u64, // sum of aligned size on the sequence
+    pub identity: f32,      // matched / aligned columns of all blocks, see `IdentityMode`
+    pub span_start: u64,    // min start of blocks, forward strand for query
+    pub span_end: u64,      // max end of blocks
+}
+
+// accumulator of a target or query sequence
+struct SequenceAcc {
+    size: u64,
+    blocks: usize,
+    aligned_bases: u64,
+    rec_stat: RecStat,
+    span: (u64, u64),
+}
+
+impl Default for SequenceAcc {
+    fn default() -> Self {
+        SequenceAcc {
+            size: 0,
+            blocks: 0,
+            aligned_bases: 0,
+            rec_stat: RecStat::default(),
+            span: (u64::MAX, 0),
+        }
+    }
+}
+
+impl SequenceAcc {
+    fn add(&mut self, size: u64, start: u64, end: u64, rec_stat: &RecStat) {
+        self.size = size;
+        self.blocks += 1;
+        self.aligned_bases += end - start;
+        add_rec_stat(&mut self.rec_stat, rec_stat);
+        self.span = (self.span.0.min(start), self.span.1.max(end));
+    }
+
+    fn merge(&mut self, other: SequenceAcc) {
+        self.size = self.size.max(other.size);
+        self.blocks += other.blocks;
+        self.aligned_bases += other.aligned_bases;
+        add_rec_stat(&mut self.rec_stat, &other.rec_stat);
+        self.span = (self.span.0.min(other.span.0), self.span.1.max(other.span.1));
+    }
+}
+
+// keyed by (is query, name), so targets are before queries
+type SequenceAccMap = HashMap<(bool, String), SequenceAcc>;
+
+// stat of each target and query sequence, for all AlignRecord iterators
+// kind    name  size  blocks  aligned_bases  identity  span_start  span_end
+// target  chr1  1000  2       300            0.98      100         600
+// query   ctg1  500   1       150            0.99      0           150
+pub fn stat_per_sequence<T, I>(
+    records: I,
+    writer: &mut dyn Write,
+    mode: IdentityMode,
+) -> Result<(), WGAError>
+where
+    T: AlignRecord + Send,
+    I: Iterator<Item = Result<T, WGAError>> + Send,
+{
+    let acc_map = records
+        .par_bridge()
+        .try_fold(SequenceAccMap::new, |mut acc, rec| {
+            let rec = rec?;
+            let rec_stat = rec.get_stat()?;
+            acc.entry((false, rec.target_name().to_string()))
+                .or_default()
+                .add(
+                    rec.target_length(),
+                    rec.target_start(),
+                    rec.target_end(),
+                    &rec_stat,
+                );
+            let (query_start, query_end) = rec.query_forward();
+            acc.entry((true, rec.query_name().to_string()))
+                .or_default()
+                .add(rec.query_length(), query_start, query_end, &rec_stat);
+            Ok::<SequenceAccMap, WGAError>(acc)
+        })
+        .try_reduce(SequenceAccMap::new, |mut acc, map| {
+            for (key, seq) in map {
+                acc.entry(key).or_default().merge(seq);
+            }
+            Ok(acc)
+        })?;
+
+    let mut final_stat = acc_map.into_iter().collect::<Vec<_>>();
+    final_stat.sort_by(|((a_query, a), _), ((b_query, b), _)| {
+        a_query.cmp(b_query).then_with(|| natord::compare(a, b))
+    });
+    let mut wtr = csv::WriterBuilder::new()
+        .delimiter(b'\t')
+        .has_headers(true)
+        .from_writer(writer);
+    for ((is_query, name), seq) in final_stat {
+        wtr.serialize(SequenceStatistic {
+            kind: if is_query { "query" } else { "target" }.to_string(),
+            name,
+            size: seq.size,
+            blocks: seq.blocks,
+            aligned_bases: seq.aligned_bases,
+            identity: seq.rec_stat.identity(mode) as f32,
+            span_start: seq.span.0,
+            span_end: seq.span.1,
+        })?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// Summary of a whole alignment file and coverage of each target sequence,
+/// print a human-readable table or a machine-readable TSV if `tsv`,
+/// `fast` keeps only counts and coverage, metrics of CIGAR are omitted
+pub fn stat_summary<T, I>(
+    records: I,
+    writer: &mut dyn Write,
+    tsv: bool,
+    mode: IdentityMode,
+    fast: bool,
+) -> Result<(), WGAError>
+where
+    T: AlignRecord + Send,
+    I: Iterator<Item = Result<T, WGAError>> + Send,
+{
+    let mut acc_map = acc_by_target(records, fast)?;
+    let mut total = TargetAcc::default();
+    for target in acc_map.values() {
+        total.records += target.records;
+        total.aligned_bases += target.aligned_bases;
+        add_rec_stat(&mut total.rec_stat, &target.rec_stat);
+    }
+    // skip targets without size to avoid NaN coverage
+    acc_map.retain(|_, target| target.size > 0);
+    let final_stat = finalize_by_target(acc_map, mode, fast);
+
+    let rec_stat = &total.rec_stat;
+    let identity = rec_stat.identity(mode) * 100.0;
+    let mut summary = vec![
+        ("blocks", total.records.to_string()),
+        ("aligned_bases", total.aligned_bases.to_string()),
+        ("matched", rec_stat.matched.to_string()),
+        ("mismatched", rec_stat.mismatched.to_string()),
+        (
+            "ins_event",
+            (rec_stat.ins_event + rec_stat.inv_ins_event).to_string(),
+        ),
+        (
+            "ins_size",
+            (rec_stat.ins_size + rec_stat.inv_ins_size).to_string(),
+        ),
+        (
+            "del_event",
+            (rec_stat.del_event + rec_stat.inv_del_event).to_string(),
+        ),
+        (
+            "del_size",
+            (rec_stat.del_size + rec_stat.inv_del_size).to_string(),
+        ),
+        ("identity(%)", format!("{:.2}", identity)),
+        (
+            "gap_compressed_identity(%)",
+            format!("{:.2}", rec_stat.gap_compressed_identity * 100.0),
+        ),
+        (
+            "block_identity(%)",
+            format!("{:.2}", rec_stat.block_identity * 100.0),
+        ),
+    ];
+    if fast {
+        summary.truncate(2);
+    }
+
+    if tsv {
+        writeln!(writer, "#metric\tvalue")?;
+        for (k, v) in summary.iter() {
+            writeln!(writer, "{}\t{}", k, v)?;
+        }
+        writeln!(
+            writer,
+            "#target_name\ttarget_size\tcovered_bases\tcoverage(%)"
+        )?;
+        for stat in final_stat {
+            writeln!(
+                writer,
+                "{}\t{}\t{}\t{:.2}",
+                stat.target_name,
+                stat.target_size,
+                stat.covered_bases,
+                stat.covered_fraction * 100.0
+            )?;
+        }
+    } else {
+        let width = summary.iter().map(|(k, _)| k.len()).max().unwrap_or(0);
+        for (k, v) in summary.iter() {
+            writeln!(writer, "{:<width$}  {}", k, v, width = width)?;
+        }
+        writeln!(writer)?;
+        let name_width = final_stat
+            .iter()
+            .map(|stat| stat.target_name.len())
+            .chain(std::iter::once("target".len()))
+            .max()
+            .unwrap_or(0);
+        writeln!(
+            writer,
+            "{:<name_width$}  {:>12}  {:>12}  {:>11}",
+            "target",
+            "size",
+            "covered",
+            "coverage(%)",
+            name_width = name_width
+        )?;
+        for stat in final_stat {
+            writeln!(
+                writer,
+                "{:<name_width$}  {:>12}  {:>12}  {:>11.2}",
+                stat.target_name,
+                stat.target_size,
+                stat.covered_bases,
+                stat.covered_fraction * 100.0,
+                name_width = name_width
+            )?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// GC content of a species, Serialize for output
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct GCStatistic {
+    pub species: String,
+    pub aligned_bases: u64, // all non-gap bases
+    pub gc: u64,            // G/C/S
+    pub at: u64,            // A/T/U/W
+    pub ambiguous: u64,     // N and other IUPAC codes, not counted in gc_fraction
+    pub gc_fraction: f64,
+}
+
+// accumulator of a species
+#[derive(Default, Clone, Copy)]
+struct GCAcc {
+    gc: u64,
+    at: u64,
+    ambiguous: u64,
+}
+
+impl GCAcc {
+    fn add_seq(&mut self, seq: &str) {
+        for b in seq.bytes() {
+            match b.to_ascii_uppercase() {
+                b'-' | b'.' => {}
+                b'G' | b'C' | b'S' => self.gc += 1,
+                b'A' | b'T' | b'U' | b'W' => self.at += 1,
+                _ => self.ambiguous += 1,
+            }
+        }
+    }
+}
+
+/// Species of a MAF src name, `hg38.chr1` => `hg38`, a name without `.` is itself
+pub fn species_of(name: &str) -> &str {
+    name.split_once('.').map_or(name, |(species, _)| species)
+}
+
+/// GC content of each species over aligned (ungapped) bases of all blocks
+pub fn stat_gc<R: Read + Send>(
+    mut mafreader: MAFReader<R>,
+    writer: &mut dyn Write,
+) -> Result<(), WGAError> {
+    let acc_map = mafreader
+        .records()
+        .traced()
+        .par_bridge()
+        .try_fold(HashMap::<String, GCAcc>::new, |mut acc, rec| {
+            for sline in rec?.slines {
+                let species = species_of(&sline.name).to_string();
+                acc.entry(species).or_default().add_seq(&sline.seq);
+            }
+            Ok::<_, WGAError>(acc)
+        })
+        .try_reduce(HashMap::new, |mut acc, map| {
+            for (species, other) in map {
+                let gc_acc = acc.entry(species).or_default();
+                gc_acc.gc += other.gc;
+                gc_acc.at += other.at;
+                gc_acc.ambiguous += other.ambiguous;
+            }
+            Ok(acc)
+        })?;
+
+    let mut final_stat = acc_map
+        .into_iter()
+        .map(|(species, acc)| GCStatistic {
+            species,
+            aligned_bases: acc.gc + acc.at + acc.ambiguous,
+            gc: acc.gc,
+            at: acc.at,
+            ambiguous: acc.ambiguous,
+            gc_fraction: match acc.gc + acc.at {
+                0 => 0.0,
+                n => acc.gc as f64 / n as f64,
+            },
+        })
+        .collect::<Vec<_>>();
+    final_stat.sort_by(|a, b| natord::compare(&a.species, &b.species));
+
+    let mut wtr = csv::WriterBuilder::new()
+        .delimiter(b'\t')
+        .has_headers(true)
+        .from_writer(writer);
+    for stat in final_stat {
+        wtr.serialize(stat)?;
+    }
+    wtr.flush()?;
+    Ok(())
 }
 
 // define a type for pair_stat
@@ -62,9 +543,11 @@ pub fn stat_maf<R: Read + Send>(
     mut reader: MAFReader<R>,
     writer: &mut dyn Write,
     each: bool,
+    mode: IdentityMode,
 ) -> Result<(), WGAError> {
     let pair_stat_vec = reader
         .records()
+        .traced()
         .par_bridge()
         .try_fold(Vec::new, |mut acc, rec| {
             acc.push(stat_rec(&rec?)?);
@@ -75,7 +558,7 @@ pub fn stat_maf<R: Read + Send>(
             Ok(acc)
         })?;
 
-    write_style_result(pair_stat_vec, writer, each)
+    write_style_result(pair_stat_vec, writer, each, mode)
 }
 
 // stat for paf
@@ -83,9 +566,34 @@ pub fn stat_paf<R: Read + Send>(
     mut reader: PAFReader<R>,
     writer: &mut dyn Write,
     each: bool,
+    mode: IdentityMode,
 ) -> Result<(), WGAError> {
     let pair_stat_vec = reader
         .records()
+        .traced()
+        .par_bridge()
+        .try_fold(Vec::new, |mut acc, rec| {
+            acc.push(stat_rec(&rec?)?);
+            Ok::<Vec<PairStat>, WGAError>(acc)
+        })
+        .try_reduce(Vec::new, |mut acc, mut vec| {
+            acc.append(&mut vec);
+            Ok(acc)
+        })?;
+
+    write_style_result(pair_stat_vec, writer, each, mode)
+}
+
+// stat for chain
+pub fn stat_chain<R: Read + Send>(
+    mut reader: ChainReader<R>,
+    writer: &mut dyn Write,
+    each: bool,
+    mode: IdentityMode,
+) -> Result<(), WGAError> {
+    let pair_stat_vec = reader
+        .records()?
+        .traced()
         .par_bridge()
         .try_fold(Vec::new, |mut acc, rec| {
             acc.push(stat_rec(&rec?)?);
@@ -96,17 +604,18 @@ pub fn stat_paf<R: Read + Send>(
             Ok(acc)
         })?;
 
-    write_style_result(pair_stat_vec, writer, each)
+    write_style_result(pair_stat_vec, writer, each, mode)
 }
 
 fn write_style_result(
     pair_stat_vec: Vec<PairStat>,
     writer: &mut dyn Write,
     each: bool,
+    mode: IdentityMode,
 ) -> Result<(), WGAError> {
     let mut final_stat = match each {
-        true => split_final(pair_stat_vec),
-        false => merge_final_from_pair(pair_stat_vec),
+        true => split_final(pair_stat_vec, mode),
+        false => merge_final_from_pair(pair_stat_vec, mode),
     };
     final_stat.sort_by(|a, b| natord::compare(&a.ref_name, &b.ref_name));
     let mut wtr = csv::WriterBuilder::new()
@@ -121,7 +630,7 @@ fn write_style_result(
 }
 
 // not merge
-fn split_final(pair_stat_vec: Vec<PairStat>) -> Vec<Statistic> {
+fn split_final(pair_stat_vec: Vec<PairStat>, mode: IdentityMode) -> Vec<Statistic> {
     // init final_stat
     let mut final_stat = Vec::new();
     for pair_stat in pair_stat_vec {
@@ -150,8 +659,10 @@ fn split_final(pair_stat_vec: Vec<PairStat>) -> Vec<Statistic> {
         stat.inv_del_size = rec_stat.inv_del_size;
         stat.inv_event = rec_stat.inv_event;
         stat.inv_size = rec_stat.inv_size;
-        stat.identity = stat.matched as f32 / stat.aligned_size as f32;
+        stat.identity = rec_stat.identity(mode) as f32;
         stat.similarity = (stat.matched + stat.mismatched) as f32 / stat.aligned_size as f32;
+        stat.gap_compressed_identity = rec_stat.gap_compressed_identity as f32;
+        stat.block_identity = rec_stat.block_identity as f32;
         // push to final_stat
         final_stat.push(stat);
     }
@@ -159,7 +670,7 @@ fn split_final(pair_stat_vec: Vec<PairStat>) -> Vec<Statistic> {
 }
 
 // merge blocks in aggregation by ref_name
-fn merge_final_from_pair(pair_stat_vec: Vec<PairStat>) -> Vec<Statistic> {
+fn merge_final_from_pair(pair_stat_vec: Vec<PairStat>, mode: IdentityMode) -> Vec<Statistic> {
     // init final_stat
     let mut final_stat = Vec::new();
 
@@ -185,7 +696,9 @@ fn merge_final_from_pair(pair_stat_vec: Vec<PairStat>) -> Vec<Statistic> {
             ..Default::default()
         };
         // aggregate by each record
+        let mut total = RecStat::default();
         for (rec_stat, r_s, q_s) in rec_stats {
+            add_rec_stat(&mut total, &rec_stat);
             stat.aligned_size += rec_stat.aligned_size;
             stat.matched += rec_stat.matched;
             stat.mismatched += rec_stat.mismatched;
@@ -209,8 +722,10 @@ fn merge_final_from_pair(pair_stat_vec: Vec<PairStat>) -> Vec<Statistic> {
         }
         // calculate the identity and similarity
         stat.unaligned_size = stat.ref_size - stat.aligned_size as u64;
-        stat.identity = stat.matched as f32 / stat.aligned_size as f32;
+        stat.identity = total.identity(mode) as f32;
         stat.similarity = (stat.matched + stat.mismatched) as f32 / stat.aligned_size as f32;
+        stat.gap_compressed_identity = total.gap_compressed_identity as f32;
+        stat.block_identity = total.block_identity as f32;
         // push to final_stat
         final_stat.push(stat);
     }
@@ -0,0 +1,79 @@
+use crate::{
+    errors::WGAError,
+    parser::{
+        common::recount_align_size,
+        maf::{MAFReader, MAFRecord, MAFWriter},
+    },
+};
+use std::io::{Read, Write};
+
+// main function of trim ragged edges
+// A 0 5  + 5  ---ATCGT--
+// B 0 8  + 8  GGGATCGTAA
+// C 0 5  + 5  ---ATCGT--
+
+// min species = 2
+
+// A 0 5 + 5 ATCGT
+// B 3 5 + 8 ATCGT
+// C 0 5 + 5 ATCGT
+pub fn trim_edges_maf<R: Read + Send>(
+    mut reader: MAFReader<R>,
+    writer: &mut dyn Write,
+    min_species: usize,
+) -> Result<(), WGAError> {
+    // init a MAFWriter
+    let mut mafwtr = MAFWriter::new(writer);
+    // write header
+    let header = format!("#maf version=1.6 trim_edges={}", min_species);
+    mafwtr.write_header(&reader.header_with(header))?;
+
+    for rec in reader.records() {
+        let mut rec = rec?;
+        // drop the block if no column has enough species
+        if trim_edges(&mut rec, min_species) {
+            mafwtr.write_record(&rec)?;
+        }
+    }
+    mafwtr.finish()?;
+    Ok(())
+}
+
+/// Trim columns from both ends of a block until at least `min_species`
+/// s-lines have non-gap bases, return false if no column is left
+pub fn trim_edges(rec: &mut MAFRecord, min_species: usize) -> bool {
+    let block_length = match rec.slines.first() {
+        Some(sline) => sline.seq.len(),
+        None => return false,
+    };
+    let enough = |col: usize| {
+        rec.slines
+            .iter()
+            .filter(|sline| sline.seq.as_bytes()[col] != b'-')
+            .count()
+            >= min_species
+    };
+    let first = match (0..block_length).find(|&col| enough(col)) {
+        Some(first) => first,
+        None => return false,
+    };
+    // `first` is found, so `last` always exists
+    let last = (first..block_length)
+        .rev()
+        .find(|&col| enough(col))
+        .unwrap_or(first);
+    if first == 0 && last == block_length - 1 {
+        return true;
+    }
+
+    for sline in rec.slines.iter_mut() {
+        // coordinates are on the strand of s-line, just move start by clipped bases
+        let (head_size, _) = recount_align_size(&sline.seq[..first]);
+        let seq = sline.seq[first..=last].to_string();
+        let (align_size, _) = recount_align_size(&seq);
+        sline.set_start(sline.start + head_size);
+        sline.set_align_size(align_size);
+        sline.seq = seq;
+    }
+    true
+}
@@ -1,85 +1,174 @@
-// use crate::{
-//     errors::WGAError,
-//     parser::{
-//         common::AlignRecord,
-//         paf::{PAFReader, PafRecord},
-//     },
-// };
-// use std::{
-//     collections::HashMap,
-//     io::{Read, Write},
-// };
+use crate::{
+    errors::WGAError,
+    parser::{
+        cigar::cigar_units,
+        common::AlignRecord,
+        paf::PafRecord,
+        writer::{PafWriter, RecordWriter},
+    },
+    tools::{
+        interval::Interval,
+        pafsplit::{fragment_record, Fragment},
+    },
+};
+use log::info;
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::Write,
+};
 
-// // main function of generate pesudo MAF from PAF
-// pub fn trim_ovp<R: Read + Send>(
-//     mut reader: PAFReader<R>,
-//     writer: &mut dyn Write,
-// ) -> Result<(), WGAError> {
-//     // 1. gourp by target
-//     let mut target_groupby_map: HashMap<String, Vec<PafRecord>> = HashMap::new();
-//     // TODO: could be parallel
-//     for rec in reader.records() {
-//         let rec = rec?;
-//         let target_name = rec.target_name().to_string();
-//         let rec_vec = target_groupby_map.entry(target_name).or_default();
-//         rec_vec.push(rec);
-//     }
-//     // TODO: could be parallel
-//     for (_, rec_vec) in target_groupby_map {
-//         trim_query(writer, rec_vec)?;
-//     }
+/// Slice a PAF record to target `[start, end)` by cutting its CIGAR at target offsets,
+/// gaps at both ends of the slice are trimmed, so the target span of the result may be
+/// shorter, None if no aligned base is left
+pub fn slice_paf_target(
+    rec: &PafRecord,
+    start: u64,
+    end: u64,
+) -> Result<Option<PafRecord>, WGAError> {
+    let units = cigar_units(&rec.get_cigar_str()?)?;
+    let mut fragment: Option<Fragment> = None;
+    let (mut target_offset, mut query_offset) = (0, 0);
+    for &(op, len) in &units {
+        let pos = rec.target_start + target_offset;
+        match op {
+            'M' | '=' | 'X' | 'D' => {
+                let (from, to) = (pos.max(start), (pos + len).min(end));
+                // leading deletions are skipped
+                if from < to && (op != 'D' || fragment.is_some()) {
+                    let skipped = from - pos;
+                    let query_skipped = if op == 'D' { 0 } else { skipped };
+                    fragment
+                        .get_or_insert_with(|| Fragment {
+                            target_offset: target_offset + skipped,
+                            query_offset: query_offset + query_skipped,
+                            units: Vec::new(),
+                        })
+                        .units
+                        .push((op, to - from));
+                }
+                target_offset += len;
+                if op != 'D' {
+                    query_offset += len;
+                }
+            }
+            'I' => {
+                // only insertions between two bases of the slice are kept
+                if let Some(fragment) = fragment.as_mut().filter(|_| pos < end) {
+                    fragment.units.push((op, len));
+                }
+                query_offset += len;
+            }
+            _ => return Err(WGAError::CigarOpInvalid(op.to_string())),
+        }
+    }
+    let Some(mut fragment) = fragment else {
+        return Ok(None);
+    };
+    while matches!(fragment.units.last(), Some(('I' | 'D', _))) {
+        fragment.units.pop();
+    }
+    if fragment.units.is_empty() {
+        return Ok(None);
+    }
+    if fragment.units == units {
+        return Ok(Some(rec.clone()));
+    }
+    Ok(Some(fragment_record(rec, &fragment)))
+}
 
-//     Ok(())
-// }
+// main function of trim overlaps, records of each target are placed in order of
+// 1. higher score, `AS:i:` or MAPQ if absent
+// 2. longer target span
+// 3. earlier in input
+// a record is trimmed to target bases not covered by placed ones, and split if a placed
+// one is inside it, pieces shorter than `min_length` on target are dropped
+// t1 0  100 q1 0  100 + 100M   score 60        t1 0   100 q1 0   100 + 100M
+// t1 80 200 q2 0  120 + 120M   score 30  ==>   t1 100 200 q2 20  120 + 100M
+// output is sorted by target name and start
+pub fn trim_overlaps<I>(records: I, writer: &mut dyn Write, min_length: u64) -> Result<(), WGAError>
+where
+    I: Iterator<Item = Result<PafRecord, WGAError>>,
+{
+    let mut by_target: HashMap<String, Vec<PafRecord>> = HashMap::new();
+    let mut total = 0;
+    for rec in records {
+        let rec = rec?;
+        total += 1;
+        by_target
+            .entry(rec.target_name.clone())
+            .or_default()
+            .push(rec);
+    }
+    let mut targets = by_target.into_iter().collect::<Vec<_>>();
+    targets.sort_by(|a, b| natord::compare(&a.0, &b.0));
 
-// // filter for each query
-// fn trim_query(writer: &mut dyn Write, rec_vec: Vec<PafRecord>) -> Result<(), WGAError> {
-//     // groupby query name and sort by target start
-//     // [A,B,C,D1,D2,E] => {A:[A],B:[B],C:[C],D:[D1,D2],E:E}
-//     let mut query_groupby_map: HashMap<String, Vec<PafRecord>> = HashMap::new();
-//     // TODO: could be parallel
-//     for rec in rec_vec {
-//         let query_name = rec.query_name().to_string();
-//         let query_rec_vec = query_groupby_map.entry(query_name).or_default();
-//         // sort by query start
-//         let idx = query_rec_vec
-//             .binary_search_by(|probe| probe.target_start().cmp(&rec.target_start()))
-//             .unwrap_or_else(|e| e);
-//         query_rec_vec.insert(idx, rec);
-//     }
-//     let mut pafwtr = csv::WriterBuilder::new()
-//         .delimiter(b'\t')
-//         .has_headers(false)
-//         .from_writer(writer);
-//     // let mut final_rec_vec = Vec::new();
-//     // TODO: could be parallel
-//     for (_, rec_vec) in query_groupby_map {
-//         // remove overlap by target_start and target_end; keep the longest
-//         let mut rec_vec_iter = rec_vec.into_iter();
-//         let mut last_rec = rec_vec_iter.next().unwrap();
-//         for rec in rec_vec_iter {
-//             let align_size = rec.target_end() - rec.target_start();
-//             let last_align_size = last_rec.target_end() - last_rec.target_start();
-//             // remove overlap by target_start and target_end; keep the longest
-//             if rec.target_start() >= last_rec.target_end() {
-//                 // no overlap
-//                 // final_rec_vec.push(last_rec);
-//                 pafwtr.serialize(last_rec)?;
-//                 last_rec = rec;
-//             } else if align_size > last_align_size {
-//                 // overlap, but longer
-//                 last_rec = rec;
-//             } else {
-//                 // overlap, but shorter
-//                 continue;
-//             }
-//         }
-//         // final_rec_vec.push(last_rec);
-//         pafwtr.serialize(last_rec)?;
-//     }
+    let mut pafwtr = PafWriter::new(writer);
+    let mut written = 0;
+    for (_, recs) in targets {
+        let mut kept = trim_target(recs, min_length)?;
+        kept.sort_by(|a, b| a.align_cmp(b));
+        for rec in &kept {
+            pafwtr.write_record(rec)?;
+        }
+        written += kept.len();
+    }
+    pafwtr.flush()?;
+    info!("{} records are trimmed into {} records", total, written);
+    Ok(())
+}
 
-//     // for rec in final_rec_vec {
-//     //     pafwtr.serialize(rec)?;
-//     // }
-//     Ok(())
-// }
+// resolve overlaps of records on the same target
+fn trim_target(mut recs: Vec<PafRecord>, min_length: u64) -> Result<Vec<PafRecord>, WGAError> {
+    // stable sort keeps the input order of ties
+    recs.sort_by(|a, b| {
+        b.align_score()
+            .total_cmp(&a.align_score())
+            .then_with(|| b.target_interval().len().cmp(&a.target_interval().len()))
+    });
+    // covered target intervals by start, they never overlap
+    let mut covered: BTreeMap<u64, u64> = BTreeMap::new();
+    let mut kept = Vec::new();
+    for rec in recs {
+        for free in uncovered(&covered, rec.target_interval()) {
+            let piece = match free == rec.target_interval() {
+                true => Some(rec.clone()),
+                false => slice_paf_target(&rec, free.start, free.end)?,
+            };
+            let Some(piece) = piece else {
+                continue;
+            };
+            let span = piece.target_interval();
+            if span.len() < min_length {
+                continue;
+            }
+            covered.insert(span.start, span.end);
+            kept.push(piece);
+        }
+    }
+    Ok(kept)
+}
+
+// parts of `iv` not in `covered`
+fn uncovered(covered: &BTreeMap<u64, u64>, iv: Interval) -> Vec<Interval> {
+    // ends grow with starts as intervals don't overlap
+    let mut overlaps = covered
+        .range(..iv.end)
+        .rev()
+        .take_while(|(_, &end)| end > iv.start)
+        .map(|(&start, &end)| Interval::new(start, end))
+        .collect::<Vec<_>>();
+    overlaps.reverse();
+
+    let mut free = Vec::new();
+    let mut start = iv.start;
+    for other in overlaps {
+        if start < other.start {
+            free.push(Interval::new(start, other.start));
+        }
+        start = start.max(other.end);
+    }
+    if start < iv.end {
+        free.push(Interval::new(start, iv.end));
+    }
+    free
+}
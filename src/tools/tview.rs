@@ -1,4 +1,4 @@
-use crate::tools::index::MafIndex;
+use crate::tools::index::{load_index, MafIndex};
 use crate::{errors::WGAError, parser::maf::MAFReader};
 use crossterm::{
     event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
@@ -9,10 +9,9 @@ use itertools::enumerate;
 use ratatui::{prelude::*, widgets::*};
 use regex::Regex;
 use rust_lapper::{Interval, Lapper};
-use std::path::PathBuf;
 use std::{
     fs::File,
-    io::{self, BufReader, Read, Seek},
+    io::{self, Read, Seek},
     rc::Rc,
     time::{Duration, Instant},
 };
@@ -207,17 +206,12 @@ impl MafViewApp<'_, File> {
 
     fn new(input: &String) -> Result<Self, WGAError> {
         // creat reader
-        let mut mafreader = MAFReader::from_path(input)?;
+        let mut mafreader = MAFReader::from_path_raw(input)?;
         // init scroll, fixed
         let mut scroll = Scroll::default();
         let mut fixed = vec![Line::from("pos:"), Line::from("|")];
         // read index
-        let index_file_path = &format!("{}.index", input);
-        let index_file = match File::open(index_file_path) {
-            Ok(index_file) => index_file,
-            Err(_) => return Err(WGAError::FileNotExist(PathBuf::from(index_file_path))),
-        };
-        let mafindex: MafIndex = serde_json::from_reader(BufReader::new(index_file))?;
+        let mafindex = load_index(format!("{}.index", input))?.index;
         // create navigation
         let mut navigation = Self::gen_navigation(mafindex);
 
@@ -400,10 +394,8 @@ fn run_app<B: Backend>(
                     KeyCode::Down => {
                         app.navigation.select_down();
                     }
-                    KeyCode::Esc => {
-                        if app.navigation.show {
-                            app.navigation.show = false;
-                        }
+                    KeyCode::Esc if app.navigation.show => {
+                        app.navigation.show = false;
                     }
                     KeyCode::Char(input_char) => {
                         if app.navigation.show {
@@ -414,20 +406,14 @@ fn run_app<B: Backend>(
                             app.navigation.show = true;
                         }
                     }
-                    KeyCode::Backspace => {
-                        if app.navigation.show {
-                            app.navigation.delete_char();
-                        }
+                    KeyCode::Backspace if app.navigation.show => {
+                        app.navigation.delete_char();
                     }
-                    KeyCode::Tab => {
-                        if app.navigation.show {
-                            app.navigation.select_region = !app.navigation.select_region;
-                        }
+                    KeyCode::Tab if app.navigation.show => {
+                        app.navigation.select_region = !app.navigation.select_region;
                     }
-                    KeyCode::Enter => {
-                        if app.navigation.show && app.navigation.input_valid {
-                            app.update()?;
-                        }
+                    KeyCode::Enter if app.navigation.show && app.navigation.input_valid => {
+                        app.update()?;
                     }
                     _ => {}
                 }
@@ -1,38 +1,198 @@
 use crate::{
     cli::Cli,
-    converter::{chain2maf, chain2paf, maf2chain, maf2paf, maf2sam, paf2chain, paf2maf},
+    converter::{
+        chain2maf, chain2paf, delta2maf, maf2chain, maf2delta, maf2fasta, maf2paf, maf2sam,
+        paf2chain, paf2delta, paf2maf, paf2sam,
+    },
     errors::WGAError,
     parser::{
         chain::ChainReader,
-        common::{DotplotMode, DotplotoutFormat, FileFormat},
-        maf::MAFReader,
+        common::{
+            groups_buffered, max_records, AlignRecord, CigarStyle, DotplotMode, DotplotoutFormat,
+            FileFormat, GroupBy, Groups, IdentityMode, PrimaryBy,
+        },
+        delta::DeltaReader,
+        maf::{MAFReader, MAFWriter},
         paf::PAFReader,
+        writer::{ChainWriter, DeltaWriter, PafWriter, RecordWriter},
     },
     tools::{
-        caller::call_var_maf,
-        chunk::chunk_maf,
-        dotplot::dotplot,
-        filter::{filter_chain, filter_maf, filter_paf, filter_paf_align_pair},
-        index::{build_index, MafIndex},
-        mafextra::maf_extract_idx,
+        caller::{call_var_maf, maf2vcf},
+        chainstat::chain_stat,
+        chunk::{chunk_maf, split_maf},
+        cigarbed::cigar_bed,
+        coverage::{coverage, maf_depth},
+        dedup::dedup,
+        dotplot::{dotplot, SegmentFilter},
+        filter::{
+            filter_chain, filter_maf, filter_paf, filter_paf_align_pair, is_self_alignment,
+            FilterOpts,
+        },
+        index::{
+            build_index, build_index_bgzf, build_index_parallel, load_index, OffsetKind,
+            VirtualSeek,
+        },
+        mafextra::{get_input_regions, maf_extract_idx, maf_slice, GenomeRegion},
+        mafjoin::maf_join,
+        mafmask::maf_mask,
+        namefilter::{filter_maf_by_name, NameFilter},
+        normalize::normalize_maf,
         pafcov::pafcov,
+        pafindex::{build_paf_index, load_paf_index, paf_extract_idx},
+        pafsplit::paf_split,
+        primary::primary,
         pseudomaf::generate_pesudo_maf,
-        rename::rename_maf,
-        stat::{stat_maf, stat_paf}, // trimovp::trim_ovp,
+        rename::{
+            rename_chain_by_map, rename_maf, rename_maf_by_map, rename_paf_by_map, NameMap, PanSN,
+            Renamer,
+        },
+        sort::maf_sort,
+        stat::{
+            stat_by_target, stat_chain, stat_gc, stat_maf, stat_paf, stat_per_sequence,
+            stat_summary,
+        },
+        trimedges::trim_edges_maf,
+        trimovp::trim_overlaps,
     },
 };
 use clap::CommandFactory;
 use clap_complete::{generate, Shell};
-use log::{info, warn};
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use log::{debug, info, log_enabled, trace, warn, Level};
+use noodles::bgzf;
 use std::io::{stdin, stdout, BufRead, BufReader, BufWriter, Read, Stdin, Write};
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
 use std::{fs::File, path::PathBuf};
 
+// number parsers are shared with the core record model
+pub use crate::record::{parse_str2f64, parse_str2u64};
+
 // TODO : define a pub type WResult = Result<(), WGAError>;
 
+// global switch of `--no-decompress`, read input files as they are
+static NO_DECOMPRESS: AtomicBool = AtomicBool::new(false);
+
+/// Disable transparent decompression of input files
+pub fn set_no_decompress(no_decompress: bool) {
+    NO_DECOMPRESS.store(no_decompress, Ordering::Relaxed);
+}
+
+// if show progress of reading input
+static PROGRESS: AtomicBool = AtomicBool::new(false);
+// all progress bars on stderr, one for each input file
+static PROGRESS_BARS: OnceLock<MultiProgress> = OnceLock::new();
+// spinner of STDIN or bar of `--max-records`, records are counted by `Traced`
+static SPINNER: OnceLock<ProgressBar> = OnceLock::new();
+
+/// Show progress of reading input on stderr, disabled by `--quiet`
+pub fn set_progress(progress: bool) {
+    PROGRESS.store(progress, Ordering::Relaxed);
+}
+
+/// Clear all progress bars, so they don't mix with messages at exit
+pub fn finish_progress() {
+    if let Some(bars) = PROGRESS_BARS.get() {
+        let _ = bars.clear();
+    }
+}
+
+fn progress_bars() -> &'static MultiProgress {
+    PROGRESS_BARS.get_or_init(|| MultiProgress::with_draw_target(ProgressDrawTarget::stderr()))
+}
+
+// a bar of raw bytes consumed against file size, so compressed input works too
+fn file_progress(path: &str, file: &File) -> Option<ProgressBar> {
+    if !PROGRESS.load(Ordering::Relaxed) {
+        return None;
+    }
+    // reading stops at the limit, so the total is records instead of file size
+    if let Some(max) = max_records() {
+        limit_progress(max);
+        return None;
+    }
+    let len = file.metadata().ok()?.len();
+    let style = ProgressStyle::with_template(
+        "{prefix} [{elapsed_precise}] {wide_bar} {bytes}/{total_bytes} ({eta})",
+    )
+    .unwrap_or_else(|_| ProgressStyle::default_bar());
+    let bar = progress_bars().add(ProgressBar::new(len).with_style(style));
+    bar.set_prefix(path.to_string());
+    Some(bar)
+}
+
+// a bar of records read against `--max-records`, shared by all inputs,
+// each of them adds `max` to the total
+fn limit_progress(max: u64) {
+    if let Some(bar) = SPINNER.get() {
+        bar.inc_length(max);
+        return;
+    }
+    let style = ProgressStyle::with_template(
+        "[{elapsed_precise}] {wide_bar} {human_pos}/{human_len} records",
+    )
+    .unwrap_or_else(|_| ProgressStyle::default_bar());
+    SPINNER.get_or_init(|| progress_bars().add(ProgressBar::new(max).with_style(style)));
+}
+
+// a spinner with record counter for STDIN without known size
+fn stdin_progress() -> Option<ProgressBar> {
+    if !PROGRESS.load(Ordering::Relaxed) {
+        return None;
+    }
+    if let Some(max) = max_records() {
+        limit_progress(max);
+        return None;
+    }
+    let style = ProgressStyle::with_template("{spinner} [{elapsed_precise}] {human_pos} records")
+        .unwrap_or_else(|_| ProgressStyle::default_spinner());
+    let spinner = SPINNER.get_or_init(|| {
+        let spinner = progress_bars().add(ProgressBar::new_spinner().with_style(style));
+        spinner.enable_steady_tick(Duration::from_millis(100));
+        spinner
+    });
+    Some(spinner.clone())
+}
+
+// a reader moving progress bar by raw bytes, the bar is cleared at EOF
+struct ProgressReader<R> {
+    inner: R,
+    bar: ProgressBar,
+}
+
+impl<R: Read> Read for ProgressReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if n == 0 {
+            self.bar.finish_and_clear();
+        } else if self.bar.length().is_some() {
+            self.bar.inc(n as u64);
+        }
+        Ok(n)
+    }
+}
+
+// wrap raw input with progress bar and byte counter if enabled
+fn raw_reader<R: Read + Send + 'static>(
+    reader: R,
+    bar: Option<ProgressBar>,
+) -> Box<dyn Read + Send> {
+    let reader: Box<dyn Read + Send> = match bar {
+        Some(bar) => Box::new(ProgressReader { inner: reader, bar }),
+        None => Box::new(reader),
+    };
+    // count raw bytes for throughput only at trace level
+    match log_enabled!(Level::Trace) {
+        true => Box::new(CountingReader(reader)),
+        false => reader,
+    }
+}
+
 const BUFFER_SIZE: usize = 32 * 1024;
 
-const MAGIC_MAX_LEN: usize = 6;
+const MAGIC_MAX_LEN: usize = 14;
 // compressed file magic number, ref: https://docs.rs/infer/latest/infer/archive/index.html
 const GZ_MAGIC: [u8; 3] = [0x1f, 0x8b, 0x08];
 const BZ_MAGIC: [u8; 3] = [0x42, 0x5a, 0x68];
@@ -64,18 +224,25 @@ fn prepare_rdr_wtr(
     Ok((reader, writer))
 }
 
-pub fn parse_str2u64(s: &str) -> Result<u64, WGAError> {
-    match s.parse::<u64>() {
-        Ok(n) => Ok(n),
-        Err(_) => Err(WGAError::ParseIntError(s.to_string())),
-    }
-}
-
-pub fn parse_str2f64(s: &str) -> Result<f64, WGAError> {
-    match s.parse::<f64>() {
-        Ok(n) => Ok(n),
-        Err(_) => Err(WGAError::ParseFloatError(s.to_string())),
-    }
+// parse human readable size like `500K`, `100M`, `1G` into bytes
+pub fn parse_size(s: &str) -> Result<u64, WGAError> {
+    let s = s.trim();
+    let (num, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => s.split_at(i),
+        None => (s, ""),
+    };
+    let scale: u64 = match unit.to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" => 1 << 10,
+        "M" | "MB" => 1 << 20,
+        "G" | "GB" => 1 << 30,
+        "T" | "TB" => 1 << 40,
+        _ => return Err(WGAError::ParseSizeError(s.to_string())),
+    };
+    num.parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(scale))
+        .ok_or_else(|| WGAError::ParseSizeError(s.to_string()))
 }
 
 pub fn reverse_complement(input: &str) -> Result<String, WGAError> {
@@ -112,6 +279,17 @@ fn is_gzipped(path: &str) -> Result<bool, WGAError> {
     Ok(gz_or_not || Path::new(path).extension().is_some_and(|ext| ext == "gz"))
 }
 
+/// Check if a file is bgzipped: gzip with extra field `BC` (BGZF block size)
+pub fn is_bgzipped(path: &str) -> Result<bool, WGAError> {
+    let buffer = get_magic_num(path)?;
+    Ok(buffer[0] == GZ_MAGIC[0]
+        && buffer[1] == GZ_MAGIC[1]
+        && buffer[2] == GZ_MAGIC[2]
+        && buffer[3] & 0x04 != 0
+        && buffer[12] == b'B'
+        && buffer[13] == b'C')
+}
+
 fn is_bzipped(path: &str) -> Result<bool, WGAError> {
     let buffer = get_magic_num(path)?;
     let bz_or_not =
@@ -134,38 +312,148 @@ pub fn get_input_reader(input: &Option<String>) -> Result<Box<dyn BufRead + Send
     let reader: Box<dyn BufRead + Send> = if let Some(path) = input {
         match File::open(path) {
             Ok(file) => {
-                if is_xz(path)? {
-                    // decode xz compressed file
-                    Box::new(BufReader::with_capacity(
-                        BUFFER_SIZE,
-                        xz2::read::XzDecoder::new_multi_decoder(file),
-                    ))
-                } else if is_gzipped(path)? {
-                    // decode gzip compressed file
-                    Box::new(BufReader::with_capacity(
-                        BUFFER_SIZE,
-                        flate2::read::MultiGzDecoder::new(file),
-                    ))
-                } else if is_bzipped(path)? {
-                    // decode bzip2 compressed file
-                    Box::new(BufReader::with_capacity(
-                        BUFFER_SIZE,
-                        bzip2::read::MultiBzDecoder::new(file),
-                    ))
-                } else {
-                    // stdin flag "-" covered
-                    Box::new(BufReader::with_capacity(BUFFER_SIZE, file))
-                }
+                let bar = file_progress(path, &file);
+                decompress_reader(path, raw_reader(file, bar))?
             }
             Err(_) => return Err(WGAError::FileNotExist(PathBuf::from(path))),
         }
     } else {
-        Box::new(BufReader::with_capacity(BUFFER_SIZE, stdin_reader()?))
+        let reader = raw_reader(stdin_reader()?, stdin_progress());
+        Box::new(BufReader::with_capacity(BUFFER_SIZE, reader))
     };
 
     Ok(reader)
 }
 
+// wrap a opened file with decoder by its magic number
+fn decompress_reader<R: Read + Send + 'static>(
+    path: &str,
+    file: R,
+) -> Result<Box<dyn BufRead + Send>, WGAError> {
+    let reader: Box<dyn BufRead + Send> = if NO_DECOMPRESS.load(Ordering::Relaxed) {
+        Box::new(BufReader::with_capacity(BUFFER_SIZE, file))
+    } else if is_xz(path)? {
+        // decode xz compressed file
+        Box::new(BufReader::with_capacity(
+            BUFFER_SIZE,
+            xz2::read::XzDecoder::new_multi_decoder(file),
+        ))
+    } else if is_gzipped(path)? {
+        // decode gzip compressed file
+        Box::new(BufReader::with_capacity(
+            BUFFER_SIZE,
+            flate2::read::MultiGzDecoder::new(file),
+        ))
+    } else if is_bzipped(path)? {
+        // decode bzip2 compressed file
+        Box::new(BufReader::with_capacity(
+            BUFFER_SIZE,
+            bzip2::read::MultiBzDecoder::new(file),
+        ))
+    } else {
+        // stdin flag "-" covered
+        Box::new(BufReader::with_capacity(BUFFER_SIZE, file))
+    };
+    Ok(reader)
+}
+
+// raw bytes read from all inputs, only counted at trace level
+static READ_BYTES: AtomicU64 = AtomicU64::new(0);
+
+// a reader counting raw bytes into `READ_BYTES`
+struct CountingReader<R>(R);
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.0.read(buf)?;
+        READ_BYTES.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+}
+
+// records between two throughput logs
+const TRACE_EVERY: u64 = 100_000;
+
+/// An iterator adaptor logging throughput every `TRACE_EVERY` records at trace level,
+/// count of all records at info level, and counting records for the STDIN spinner,
+/// it just forwards items if none is enabled
+pub struct Traced<I> {
+    inner: I,
+    enabled: bool,
+    trace: bool,
+    count: u64,
+    last: Instant,
+    last_bytes: u64,
+    spinner: Option<ProgressBar>,
+}
+
+impl<I: Iterator> Traced<I> {
+    pub fn new(inner: I) -> Self {
+        Traced {
+            inner,
+            enabled: log_enabled!(Level::Info),
+            trace: log_enabled!(Level::Trace),
+            count: 0,
+            last: Instant::now(),
+            last_bytes: READ_BYTES.load(Ordering::Relaxed),
+            spinner: SPINNER.get().cloned(),
+        }
+    }
+
+    // log throughput since last log
+    fn log(&mut self, records: u64) {
+        let secs = self.last.elapsed().as_secs_f64().max(f64::EPSILON);
+        let bytes = READ_BYTES.load(Ordering::Relaxed);
+        trace!(
+            "{} records, {:.0} records/sec, {:.2} MB/sec",
+            self.count,
+            records as f64 / secs,
+            (bytes - self.last_bytes) as f64 / secs / 1024.0 / 1024.0
+        );
+        self.last = Instant::now();
+        self.last_bytes = bytes;
+    }
+}
+
+impl<I: Iterator> Iterator for Traced<I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.inner.next();
+        if let (Some(spinner), Some(_)) = (&self.spinner, &item) {
+            spinner.inc(1);
+        }
+        if self.enabled {
+            match item {
+                Some(_) => {
+                    self.count += 1;
+                    if self.trace && self.count.is_multiple_of(TRACE_EVERY) {
+                        self.log(TRACE_EVERY);
+                    }
+                }
+                None => {
+                    // log the rest records only once
+                    self.enabled = false;
+                    if self.trace {
+                        self.log(self.count % TRACE_EVERY);
+                    }
+                    info!("{} records read", self.count);
+                }
+            }
+        }
+        item
+    }
+}
+
+/// Extension to wrap any iterator into `Traced`
+pub trait TraceExt: Iterator + Sized {
+    fn traced(self) -> Traced<Self> {
+        Traced::new(self)
+    }
+}
+
+impl<I: Iterator> TraceExt for I {}
+
 /// rational stdin reader: if stdin is empty, exit with error
 fn stdin_reader() -> Result<Stdin, WGAError> {
     // check if stdin is empty
@@ -179,6 +467,11 @@ fn stdin_reader() -> Result<Stdin, WGAError> {
 fn get_output_writer(outputpath: &str, rewrite: bool) -> Result<Box<dyn Write>, WGAError> {
     check_outfile(outputpath, rewrite)?;
 
+    // stdout flag "-"
+    if outputpath == "-" {
+        return Ok(Box::new(BufWriter::with_capacity(BUFFER_SIZE, stdout())));
+    }
+
     let file = File::create(outputpath)?;
     let compression_level: u32 = 6;
 
@@ -210,7 +503,7 @@ fn get_output_writer(outputpath: &str, rewrite: bool) -> Result<Box<dyn Write>,
             bzip2::write::BzEncoder::new(file, bzip2::Compression::new(compression_level)),
         ))
     } else {
-        Box::new(BufWriter::new(stdout()))
+        Box::new(BufWriter::with_capacity(BUFFER_SIZE, file))
     };
 
     Ok(writer)
@@ -235,11 +528,50 @@ fn check_outfile(output_file: &str, rewrite: bool) -> Result<(), WGAError> {
 }
 
 /// Command: maf2paf
-pub fn wrap_maf2paf(input: &Option<String>, output: &str, rewrite: bool) -> Result<(), WGAError> {
+#[allow(clippy::too_many_arguments)]
+pub fn wrap_maf2paf(
+    input: &Option<String>,
+    output: &str,
+    rewrite: bool,
+    sort: bool,
+    species: &Option<String>,
+    target_name: &Option<String>,
+    cigar_style: CigarStyle,
+    verify: bool,
+    check: bool,
+    with_divergence: bool,
+    tag_primary: Option<PrimaryBy>,
+) -> Result<(), WGAError> {
+    // prepare reader and writer
+    let (reader, mut writer) = prepare_rdr_wtr(input, output, rewrite)?;
+    let mut mafrdr = MAFReader::new(reader)?;
+    maf2paf(
+        &mut mafrdr,
+        &mut writer,
+        sort,
+        species.as_deref(),
+        target_name.as_deref(),
+        cigar_style,
+        verify,
+        check,
+        with_divergence,
+        tag_primary,
+    )?;
+    Ok(())
+}
+
+/// Command: maf2delta
+pub fn wrap_maf2delta(
+    input: &Option<String>,
+    output: &str,
+    rewrite: bool,
+    target: &str,
+    query: &str,
+) -> Result<(), WGAError> {
     // prepare reader and writer
     let (reader, mut writer) = prepare_rdr_wtr(input, output, rewrite)?;
     let mut mafrdr = MAFReader::new(reader)?;
-    maf2paf(&mut mafrdr, &mut writer)?;
+    maf2delta(&mut mafrdr, &mut writer, target, query)?;
     Ok(())
 }
 
@@ -252,6 +584,30 @@ pub fn wrap_maf2chain(input: &Option<String>, output: &str, rewrite: bool) -> Re
     Ok(())
 }
 
+/// Command: maf2fasta
+pub fn wrap_maf2fasta(
+    input: &Option<String>,
+    output: &str,
+    rewrite: bool,
+    block: Option<usize>,
+    names: &Option<Vec<String>>,
+    consensus: bool,
+    remove_empty_columns: bool,
+) -> Result<(), WGAError> {
+    // prepare reader and writer
+    let (reader, mut writer) = prepare_rdr_wtr(input, output, rewrite)?;
+    let mut mafrdr = MAFReader::new(reader)?;
+    maf2fasta(
+        &mut mafrdr,
+        &mut writer,
+        block,
+        names.as_deref(),
+        consensus,
+        remove_empty_columns,
+    )?;
+    Ok(())
+}
+
 /// Command: maf2sam
 pub fn wrap_maf2sam(input: &Option<String>, output: &str, rewrite: bool) -> Result<(), WGAError> {
     // prepare reader and writer
@@ -261,27 +617,85 @@ pub fn wrap_maf2sam(input: &Option<String>, output: &str, rewrite: bool) -> Resu
     Ok(())
 }
 
+/// Command: maf2vcf
+pub fn wrap_maf2vcf(input: &Option<String>, output: &str, rewrite: bool) -> Result<(), WGAError> {
+    // prepare reader and writer
+    let (reader, mut writer) = prepare_rdr_wtr(input, output, rewrite)?;
+    let mut mafrdr = MAFReader::new(reader)?;
+    maf2vcf(&mut mafrdr, &mut writer)?;
+    Ok(())
+}
+
+/// Command: paf2delta
+pub fn wrap_paf2delta(
+    input: &Option<String>,
+    output: &str,
+    rewrite: bool,
+    target: &str,
+    query: &str,
+    require_cigar: bool,
+) -> Result<(), WGAError> {
+    // prepare reader and writer
+    let (reader, mut writer) = prepare_rdr_wtr(input, output, rewrite)?;
+    let mut pafrdr = PAFReader::new(reader);
+    paf2delta(&mut pafrdr, &mut writer, target, query, require_cigar)?;
+    Ok(())
+}
+
 /// Command: paf2chain
-pub fn wrap_paf2chain(input: &Option<String>, output: &str, rewrite: bool) -> Result<(), WGAError> {
+pub fn wrap_paf2chain(
+    input: &Option<String>,
+    output: &str,
+    rewrite: bool,
+    require_cigar: bool,
+) -> Result<(), WGAError> {
+    // prepare reader and writer
+    let (reader, mut writer) = prepare_rdr_wtr(input, output, rewrite)?;
+    let mut pafrdr = PAFReader::new(reader);
+    paf2chain(&mut pafrdr, &mut writer, require_cigar)?;
+    Ok(())
+}
+
+/// Command: paf2sam and paf2bam
+pub fn wrap_paf2sam(
+    input: &Option<String>,
+    output: &str,
+    rewrite: bool,
+    require_cigar: bool,
+    soft_clip: bool,
+    bam: bool,
+) -> Result<(), WGAError> {
     // prepare reader and writer
     let (reader, mut writer) = prepare_rdr_wtr(input, output, rewrite)?;
     let mut pafrdr = PAFReader::new(reader);
-    paf2chain(&mut pafrdr, &mut writer)?;
+    paf2sam(&mut pafrdr, &mut writer, require_cigar, soft_clip, bam)?;
     Ok(())
 }
 
 /// Command: paf2maf
+#[allow(clippy::too_many_arguments)]
 pub fn wrap_paf2maf(
     input: &Option<String>,
     output: &str,
     target_fa_path: &str,
     query_fa_path: &str,
     rewrite: bool,
+    coordinate_check: bool,
+    require_cigar: bool,
+    uppercase: bool,
 ) -> Result<(), WGAError> {
     // prepare reader and writer
     let (reader, mut writer) = prepare_rdr_wtr(input, output, rewrite)?;
     let mut pafrdr = PAFReader::new(reader);
-    paf2maf(&mut pafrdr, &mut writer, target_fa_path, query_fa_path)?;
+    paf2maf(
+        &mut pafrdr,
+        &mut writer,
+        target_fa_path,
+        query_fa_path,
+        coordinate_check,
+        require_cigar,
+        uppercase,
+    )?;
     Ok(())
 }
 
@@ -292,25 +706,424 @@ pub fn wrap_chain2maf(
     target_fa_path: &str,
     query_fa_path: &str,
     rewrite: bool,
+    uppercase: bool,
 ) -> Result<(), WGAError> {
     // prepare reader and writer
     let (reader, mut writer) = prepare_rdr_wtr(input, output, rewrite)?;
     let mut chainrdr = ChainReader::new(reader);
-    chain2maf(&mut chainrdr, &mut writer, target_fa_path, query_fa_path)?;
+    chain2maf(
+        &mut chainrdr,
+        &mut writer,
+        target_fa_path,
+        query_fa_path,
+        uppercase,
+    )?;
     Ok(())
 }
 
 /// Command: chain2paf
-pub fn wrap_chain2paf(input: &Option<String>, output: &str, rewrite: bool) -> Result<(), WGAError> {
+pub fn wrap_chain2paf(
+    input: &Option<String>,
+    output: &str,
+    rewrite: bool,
+    sort: bool,
+) -> Result<(), WGAError> {
     // prepare reader and writer
     let (reader, mut writer) = prepare_rdr_wtr(input, output, rewrite)?;
     let mut chainrdr = ChainReader::new(reader);
-    chain2paf(&mut chainrdr, &mut writer)?;
+    chain2paf(&mut chainrdr, &mut writer, sort)?;
+    Ok(())
+}
+
+/// Format of input alignments, in order of precedence:
+/// 1. explicit `--format`/`--input-format` flag
+/// 2. extension of input file, compression suffix is ignored, see `FileFormat::from_path`
+/// 3. content sniffed by `detect_format`
+pub fn resolve_format<R: BufRead + ?Sized>(
+    format: Option<FileFormat>,
+    input: &Option<String>,
+    reader: &mut R,
+) -> Result<FileFormat, WGAError> {
+    if let Some(format) = format {
+        return Ok(format);
+    }
+    if let Some(format) = input.as_deref().and_then(FileFormat::from_path) {
+        info!("input format by extension: {:?}", format);
+        return Ok(format);
+    }
+    let format = detect_format(reader)?;
+    info!("detected input format: {:?}", format);
+    Ok(format)
+}
+
+/// Detect alignment format by peeking the buffered head of input, nothing is consumed
+pub fn detect_format<R: BufRead + ?Sized>(reader: &mut R) -> Result<FileFormat, WGAError> {
+    let buf = reader.fill_buf()?;
+    let text = String::from_utf8_lossy(buf);
+    // delta starts with FASTA paths, then the program name
+    if matches!(
+        text.lines().nth(1).map(str::trim),
+        Some("NUCMER" | "PROMER")
+    ) {
+        return Ok(FileFormat::Delta);
+    }
+    // the last line may be truncated by buffer, but it's enough to check the first record
+    for line in text.lines() {
+        let line = line.trim_start_matches('\u{feff}').trim();
+        if line.is_empty() {
+            continue;
+        }
+        // a MAF without header may start with an s-line
+        if line.starts_with("##maf")
+            || line == "a"
+            || line.starts_with("a ")
+            || line.starts_with("s ")
+            || line.starts_with("s\t")
+        {
+            return Ok(FileFormat::Maf);
+        }
+        if line.starts_with("chain") {
+            return Ok(FileFormat::Chain);
+        }
+        // UCSC track lines may precede the MAF header
+        if line.starts_with('#') || line.starts_with("track") {
+            continue;
+        }
+        // lengths and coordinates of query are numeric
+        let columns = line.split('\t').collect::<Vec<_>>();
+        if columns.len() >= 12 && columns[1..4].iter().all(|c| c.parse::<u64>().is_ok()) {
+            return Ok(FileFormat::Paf);
+        }
+        return Err(WGAError::UnknownFormat);
+    }
+    // empty or comments only, PAF has no header so the output is empty too
+    info!("no records in input, treated as PAF");
+    Ok(FileFormat::Paf)
+}
+
+// only blank and `#` comment lines in the buffered head of input, as `detect_format`
+fn no_records<R: BufRead + ?Sized>(reader: &mut R) -> Result<bool, WGAError> {
+    let buf = reader.fill_buf()?;
+    Ok(String::from_utf8_lossy(buf).lines().all(|line| {
+        let line = line.trim_start_matches('\u{feff}').trim();
+        line.is_empty() || line.starts_with('#')
+    }))
+}
+
+/// Command: convert, detect input format and dispatch to converters,
+/// write each record into its own numbered file in `per_block_dir` if set
+#[allow(clippy::too_many_arguments)]
+pub fn wrap_convert(
+    input: &Option<String>,
+    output: &str,
+    rewrite: bool,
+    from: Option<FileFormat>,
+    to: FileFormat,
+    target: &Option<String>,
+    query: &Option<String>,
+    sort: bool,
+    per_block_dir: &Option<String>,
+    drop_self: Option<u64>,
+) -> Result<(), WGAError> {
+    let dir = match per_block_dir {
+        Some(dir) => dir,
+        None => {
+            // prepare reader and writer
+            let (mut reader, mut writer) = prepare_rdr_wtr(input, output, rewrite)?;
+            let from = resolve_format(from, input, &mut reader)?;
+            // nothing to convert, a header-only MAF needs no FASTA files
+            if from != FileFormat::Maf && to == FileFormat::Maf && no_records(&mut reader)? {
+                let mut mafwtr = MAFWriter::new(&mut writer);
+                mafwtr.write_header(&["#maf version=1.6 cmd=convert"])?;
+                return mafwtr.finish();
+            }
+            let mut convert =
+                |reader| convert_stream(from, to, reader, &mut writer, target, query, sort);
+            return match drop_self {
+                Some(within) => convert_without_self(from, reader, within, convert),
+                None => convert(reader),
+            };
+        }
+    };
+
+    let mut reader = get_input_reader(input)?;
+    let from = resolve_format(from, input, &mut reader)?;
+    let ext = match to {
+        FileFormat::Maf => "maf",
+        FileFormat::Paf => "paf",
+        FileFormat::Chain => "chain",
+        FileFormat::Sam => "sam",
+        FileFormat::Delta => "delta",
+        to => return Err(WGAError::ConvertNotSupported(from, to)),
+    };
+    let dir = Path::new(dir);
+    if dir.exists() && !dir.is_dir() {
+        return Err(WGAError::NotDir(dir.to_path_buf()));
+    }
+    std::fs::create_dir_all(dir)?;
+    info!("start write files into dir: `{}`", dir.display());
+
+    // only one output file is opened at a time
+    let write_block = |idx: usize, block: Vec<u8>| -> Result<(), WGAError> {
+        let path = dir.join(format!("{:06}.{}", idx, ext));
+        let mut writer = get_output_writer(&path.to_string_lossy(), rewrite)?;
+        let block: Box<dyn BufRead + Send> = Box::new(std::io::Cursor::new(block));
+        convert_stream(from, to, block, &mut writer, target, query, sort)?;
+        writer.flush()?;
+        Ok(())
+    };
+
+    // re-serialize each record into a standalone input
+    match from {
+        FileFormat::Maf => {
+            let mut mafrdr = MAFReader::new(reader)?;
+            // header of input is kept in each block, a version line if input has none
+            let header = match mafrdr.header.is_empty() {
+                true => vec!["##maf version=1".to_string()],
+                false => mafrdr.header.clone(),
+            };
+            for (idx, rec) in mafrdr.records().filter(not_self(drop_self)).enumerate() {
+                let mut block = Vec::new();
+                let mut mafwtr = MAFWriter::new(&mut block).with_eof(false);
+                mafwtr.write_header(&header)?;
+                mafwtr.write_record(&rec?)?;
+                mafwtr.flush()?;
+                drop(mafwtr);
+                write_block(idx, block)?;
+            }
+        }
+        FileFormat::Paf => {
+            let mut pafrdr = PAFReader::new(reader);
+            for (idx, rec) in pafrdr.records().filter(not_self(drop_self)).enumerate() {
+                let mut wtr = csv::WriterBuilder::new()
+                    .delimiter(b'\t')
+                    .has_headers(false)
+                    .from_writer(Vec::new());
+                wtr.serialize(rec?)?;
+                let block = wtr.into_inner().map_err(|e| WGAError::Io(e.into_error()))?;
+                write_block(idx, block)?;
+            }
+        }
+        FileFormat::Chain => {
+            let mut chainrdr = ChainReader::new(reader);
+            for (idx, rec) in chainrdr.records()?.filter(not_self(drop_self)).enumerate() {
+                let block = format!("{}\n\n", rec?).into_bytes();
+                write_block(idx, block)?;
+            }
+        }
+        FileFormat::Delta => {
+            let mut deltardr = DeltaReader::new(reader)?;
+            let (target_path, query_path) =
+                (deltardr.target_path.clone(), deltardr.query_path.clone());
+            for (idx, rec) in deltardr.records().filter(not_self(drop_self)).enumerate() {
+                let mut block = Vec::new();
+                let mut deltawtr = DeltaWriter::new(&mut block, &target_path, &query_path);
+                deltawtr.write_record(&rec?)?;
+                deltawtr.flush()?;
+                drop(deltawtr);
+                write_block(idx, block)?;
+            }
+        }
+        _ => return Err(WGAError::UnknownFormat),
+    }
+    Ok(())
+}
+
+// predicate of records kept by `--drop-self-alignments`, errors are kept to be returned
+fn not_self<T: AlignRecord>(drop_self: Option<u64>) -> impl FnMut(&Result<T, WGAError>) -> bool {
+    move |rec| match (rec, drop_self) {
+        (Ok(rec), Some(within)) => !is_self_alignment(rec, within),
+        _ => true,
+    }
+}
+
+// convert records without self alignments, they are dropped by `filter` in a thread
+// writing into a pipe, so any converter reads them as a plain input
+fn convert_without_self<F>(
+    from: FileFormat,
+    reader: Box<dyn BufRead + Send>,
+    within: u64,
+    convert: F,
+) -> Result<(), WGAError>
+where
+    F: FnOnce(Box<dyn BufRead + Send>) -> Result<(), WGAError>,
+{
+    if !matches!(from, FileFormat::Maf | FileFormat::Paf | FileFormat::Chain) {
+        return Err(WGAError::NotImplemented);
+    }
+    let (pipe_rdr, pipe_wtr) = std::io::pipe()?;
+    let opts = FilterOpts {
+        drop_self: Some(within),
+        ..Default::default()
+    };
+    let filter = std::thread::spawn(move || -> Result<(), WGAError> {
+        let mut writer = BufWriter::new(pipe_wtr);
+        match from {
+            FileFormat::Maf => filter_maf(MAFReader::new(reader)?, &mut writer, &opts)?,
+            FileFormat::Paf => filter_paf(PAFReader::new(reader), &mut writer, &opts)?,
+            _ => filter_chain(ChainReader::new(reader), &mut writer, &opts)?,
+        }
+        writer.flush()?;
+        Ok(())
+    });
+    let converted = convert(Box::new(BufReader::new(pipe_rdr)));
+    let filtered = filter
+        .join()
+        .map_err(|_| WGAError::Other(anyhow::anyhow!("filter of self alignments panicked")))?;
+    // a failed converter closes the pipe and breaks the filter, so its error goes first
+    converted.and(filtered)
+}
+
+// dispatch a stream of `from` format to the converter of `to` format
+fn convert_stream(
+    from: FileFormat,
+    to: FileFormat,
+    reader: Box<dyn BufRead + Send>,
+    writer: &mut Box<dyn Write>,
+    target: &Option<String>,
+    query: &Option<String>,
+    sort: bool,
+) -> Result<(), WGAError> {
+    debug!("convert {:?} to {:?}", from, to);
+    // FASTA files are only required when convert to MAF
+    let fasta = || -> Result<(&str, &str), WGAError> {
+        let target = target
+            .as_deref()
+            .ok_or(WGAError::FastaRequired("target".to_string()))?;
+        let query = query
+            .as_deref()
+            .ok_or(WGAError::FastaRequired("query".to_string()))?;
+        Ok((target, query))
+    };
+
+    match (from, to) {
+        (FileFormat::Maf, FileFormat::Paf) => {
+            let mut mafrdr = MAFReader::new(reader)?;
+            maf2paf(
+                &mut mafrdr,
+                writer,
+                sort,
+                None,
+                None,
+                CigarStyle::default(),
+                false,
+                false,
+                false,
+                None,
+            )?
+        }
+        (FileFormat::Maf, FileFormat::Chain) => {
+            let mut mafrdr = MAFReader::new(reader)?;
+            maf2chain(&mut mafrdr, writer)?
+        }
+        (FileFormat::Maf, FileFormat::Sam) => {
+            let mut mafrdr = MAFReader::new(reader)?;
+            maf2sam(&mut mafrdr, writer)?
+        }
+        (FileFormat::Paf, FileFormat::Chain) => {
+            let mut pafrdr = PAFReader::new(reader);
+            paf2chain(&mut pafrdr, writer, false)?
+        }
+        (FileFormat::Paf, FileFormat::Maf) => {
+            let (target, query) = fasta()?;
+            let mut pafrdr = PAFReader::new(reader);
+            paf2maf(&mut pafrdr, writer, target, query, false, false, false)?
+        }
+        (FileFormat::Chain, FileFormat::Paf) => {
+            let mut chainrdr = ChainReader::new(reader);
+            chain2paf(&mut chainrdr, writer, sort)?
+        }
+        (FileFormat::Chain, FileFormat::Maf) => {
+            let (target, query) = fasta()?;
+            let mut chainrdr = ChainReader::new(reader);
+            chain2maf(&mut chainrdr, writer, target, query, false)?
+        }
+        // re-write records of the same format, e.g. renumber chain ids
+        (FileFormat::Maf, FileFormat::Maf) => {
+            let mut mafrdr = MAFReader::new(reader)?;
+            let mut mafwtr = MAFWriter::new(writer);
+            let header = "#maf version=1.6 cmd=convert".to_string();
+            mafwtr.write_header(&mafrdr.header_with(header))?;
+            write_records(mafrdr.records().traced(), &mut mafwtr)?
+        }
+        (FileFormat::Paf, FileFormat::Paf) => {
+            let mut pafrdr = PAFReader::new(reader);
+            write_records(pafrdr.records().traced(), &mut PafWriter::new(writer))?
+        }
+        (FileFormat::Chain, FileFormat::Chain) => {
+            let mut chainrdr = ChainReader::new(reader);
+            write_records(chainrdr.records()?.traced(), &mut ChainWriter::new(writer))?
+        }
+        (FileFormat::Delta, FileFormat::Paf) => {
+            let mut deltardr = DeltaReader::new(reader)?;
+            write_records(deltardr.records().traced(), &mut PafWriter::new(writer))?
+        }
+        (FileFormat::Delta, FileFormat::Chain) => {
+            let mut deltardr = DeltaReader::new(reader)?;
+            write_records(deltardr.records().traced(), &mut ChainWriter::new(writer))?
+        }
+        (FileFormat::Delta, FileFormat::Maf) => {
+            let mut deltardr = DeltaReader::new(reader)?;
+            // FASTA paths of nucmer in the delta header if not set
+            let (target, query) = match (target, query) {
+                (Some(target), Some(query)) => (target.clone(), query.clone()),
+                _ => (deltardr.target_path.clone(), deltardr.query_path.clone()),
+            };
+            delta2maf(&mut deltardr, writer, &target, &query, false)?
+        }
+        (FileFormat::Delta, FileFormat::Delta) => {
+            let mut deltardr = DeltaReader::new(reader)?;
+            let mut deltawtr =
+                DeltaWriter::new(writer, &deltardr.target_path, &deltardr.query_path);
+            write_records(deltardr.records().traced(), &mut deltawtr)?
+        }
+        (from, FileFormat::Delta) => {
+            // FASTA paths are only written in the delta header
+            let target = target.as_deref().unwrap_or("target.fa");
+            let query = query.as_deref().unwrap_or("query.fa");
+            match from {
+                FileFormat::Maf => {
+                    let mut mafrdr = MAFReader::new(reader)?;
+                    maf2delta(&mut mafrdr, writer, target, query)?
+                }
+                FileFormat::Paf => {
+                    let mut pafrdr = PAFReader::new(reader);
+                    paf2delta(&mut pafrdr, writer, target, query, false)?
+                }
+                FileFormat::Chain => {
+                    let mut chainrdr = ChainReader::new(reader);
+                    let mut deltawtr = DeltaWriter::new(writer, target, query);
+                    write_records(chainrdr.records()?.traced(), &mut deltawtr)?
+                }
+                from => return Err(WGAError::ConvertNotSupported(from, FileFormat::Delta)),
+            }
+        }
+        (from, to) => return Err(WGAError::ConvertNotSupported(from, to)),
+    }
     Ok(())
 }
 
+// write records of any format by a RecordWriter of the output format
+fn write_records<T, I, W>(records: I, wtr: &mut W) -> Result<(), WGAError>
+where
+    T: AlignRecord,
+    I: Iterator<Item = Result<T, WGAError>>,
+    W: RecordWriter,
+{
+    for rec in records {
+        RecordWriter::write_record(wtr, &rec?)?;
+    }
+    RecordWriter::finish(wtr)
+}
+
 /// Command: build maf index
-pub fn wrap_build_index(input: &String, outputpath: &str) -> Result<(), WGAError> {
+pub fn wrap_build_index(
+    input: &String,
+    outputpath: &str,
+    validate: bool,
+    threads: usize,
+    binary: bool,
+) -> Result<(), WGAError> {
     let outputpath = match outputpath {
         "-" => {
             // add .idx suffix to input file
@@ -321,11 +1134,26 @@ pub fn wrap_build_index(input: &String, outputpath: &str) -> Result<(), WGAError
         path => path.to_owned(),
     };
 
-    let mut mafreader = MAFReader::from_path(input)?;
-
     // NOTE: new index file will always overwrite old one
-    let idx_wtr = get_output_writer(&outputpath, true)?;
-    build_index(&mut mafreader, idx_wtr)
+    match is_bgzipped(input) {
+        // bgzipped MAF is indexed by virtual offsets
+        Ok(true) => {
+            let mut reader = bgzf::Reader::new(File::open(input)?);
+            let idx_wtr = get_output_writer(&outputpath, true)?;
+            build_index_bgzf(&mut reader, idx_wtr, validate, binary)
+        }
+        // plain MAF is split into byte ranges with multiple threads
+        Ok(false) if threads > 1 => {
+            let idx_wtr = get_output_writer(&outputpath, true)?;
+            build_index_parallel(input, idx_wtr, validate, binary)
+        }
+        Ok(false) => {
+            let mut mafreader = MAFReader::from_path_raw(input)?;
+            let idx_wtr = get_output_writer(&outputpath, true)?;
+            build_index(&mut mafreader, idx_wtr, validate, binary)
+        }
+        Err(_) => Err(WGAError::FileNotExist(PathBuf::from(input))),
+    }
 }
 
 /// Command: maf extract
@@ -355,12 +1183,19 @@ pub fn wrap_maf_extract(
             if path == "-" {
                 return Err(WGAError::StdinNotAllowed);
             }
-            let mut mafreader = MAFReader::from_path(path)?;
-            let index_path = format!("{}.index", path);
-            let index_rdr = BufReader::new(File::open(index_path)?);
-            let mafindex: MafIndex = serde_json::from_reader(index_rdr)?;
-            let failed_regions =
-                maf_extract_idx(regions, region_file, &mut mafreader, mafindex, &mut writer)?;
+            let index_file = load_index(format!("{}.index", path))?;
+            let mafindex = index_file.index;
+            let failed_regions = match index_file.offset_kind {
+                OffsetKind::Plain => {
+                    let mut mafreader = MAFReader::from_path_raw(path)?;
+                    maf_extract_idx(regions, region_file, &mut mafreader, mafindex, &mut writer)?
+                }
+                OffsetKind::Bgzf => {
+                    let reader = VirtualSeek(bgzf::Reader::new(File::open(path)?));
+                    let mut mafreader = MAFReader::new(reader)?;
+                    maf_extract_idx(regions, region_file, &mut mafreader, mafindex, &mut writer)?
+                }
+            };
             for region in failed_regions {
                 let err = WGAError::FailedRegion(region);
                 warn!("{}", err);
@@ -372,15 +1207,58 @@ pub fn wrap_maf_extract(
     }
 }
 
-/// Command: maf call
-pub fn wrap_maf_call(
-    input: &Option<String>,
-    output: &str,
-    rewrite: bool,
-    snp: bool,
-    svlen: u64,
-    between: bool,
-    sample: Option<&str>,
+/// Command: build paf index
+pub fn wrap_paf_index(input: &String, outputpath: &str) -> Result<(), WGAError> {
+    let outputpath = match outputpath {
+        "-" => format!("{}.index", input),
+        path => path.to_owned(),
+    };
+    // byte offsets are only valid for a plain file
+    if is_gzipped(input)? || is_bzipped(input)? || is_xz(input)? {
+        return Err(WGAError::CompressedPaf(PathBuf::from(input)));
+    }
+    let reader = BufReader::new(File::open(input)?);
+    // NOTE: new index file will always overwrite old one
+    let idx_wtr = get_output_writer(&outputpath, true)?;
+    build_paf_index(reader, idx_wtr)
+}
+
+/// Command: paf extract
+pub fn wrap_paf_extract(
+    input: &str,
+    regions: &Option<Vec<String>>,
+    region_file: &Option<String>,
+    output: &str,
+    rewrite: bool,
+) -> Result<(), WGAError> {
+    if input == "-" {
+        return Err(WGAError::StdinNotAllowed);
+    }
+    let index_file = load_paf_index(format!("{}.index", input))?;
+    let mut writer = get_output_writer(output, rewrite)?;
+    let failed_regions = paf_extract_idx(
+        regions,
+        region_file,
+        File::open(input)?,
+        &index_file.index,
+        &mut writer,
+    )?;
+    for region in failed_regions {
+        let err = WGAError::FailedRegion(region);
+        warn!("{}", err);
+    }
+    Ok(())
+}
+
+/// Command: maf call
+pub fn wrap_maf_call(
+    input: &Option<String>,
+    output: &str,
+    rewrite: bool,
+    snp: bool,
+    svlen: u64,
+    between: bool,
+    sample: Option<&str>,
 ) -> Result<(), WGAError> {
     // prepare reader and writer
     let (reader, mut writer) = prepare_rdr_wtr(input, output, rewrite)?;
@@ -392,13 +1270,9 @@ pub fn wrap_maf_call(
                 None
             } else {
                 let index_path = format!("{}.index", path);
-                match File::open(index_path) {
-                    Err(_) => None,
-                    Ok(index_file) => {
-                        let index_rdr = BufReader::new(index_file);
-                        let mafindex: MafIndex = serde_json::from_reader(index_rdr)?;
-                        Some(mafindex)
-                    }
+                match Path::new(&index_path).exists() {
+                    false => None,
+                    true => Some(load_index(&index_path)?.index),
                 }
             }
         }
@@ -423,26 +1297,360 @@ pub fn wrap_maf_call(
     Ok(())
 }
 
-/// A wrapper for stat sub-cmd, match format and call `stat_{maf,paf}`
+/// Command: paf-split, MAF and Chain are converted to PAF first
+pub fn wrap_paf_split(
+    format: Option<FileFormat>,
+    input: &Option<String>,
+    output: &str,
+    rewrite: bool,
+    max_gap: u64,
+) -> Result<(), WGAError> {
+    // prepare reader and writer
+    let (mut reader, mut writer) = prepare_rdr_wtr(input, output, rewrite)?;
+    let format = resolve_format(format, input, &mut reader)?;
+
+    match format {
+        FileFormat::Paf => {
+            let mut pafrdr = PAFReader::new(reader);
+            paf_split(pafrdr.records(), &mut writer, max_gap)?
+        }
+        FileFormat::Maf => {
+            let mut mafrdr = MAFReader::new(reader)?;
+            let records = mafrdr.records().map(|rec| rec?.convert2paf());
+            paf_split(records, &mut writer, max_gap)?
+        }
+        FileFormat::Chain => {
+            let mut chainrdr = ChainReader::new(reader);
+            let records = chainrdr.records()?.map(|rec| rec?.convert2paf());
+            paf_split(records, &mut writer, max_gap)?
+        }
+        _ => {
+            return Err(WGAError::NotImplemented);
+        }
+    }
+    Ok(())
+}
+
+/// Command: chain stat
+pub fn wrap_chain_stat(
+    input: &Option<String>,
+    output: &str,
+    rewrite: bool,
+    bin_width: u64,
+) -> Result<(), WGAError> {
+    let (reader, mut writer) = prepare_rdr_wtr(input, output, rewrite)?;
+    let mut chainrdr = ChainReader::new(reader);
+    chain_stat(chainrdr.records()?.traced(), &mut writer, bin_width)
+}
+
+/// A wrapper for cigar-bed sub-cmd, MAF and Chain are converted to PAF first
+pub fn wrap_cigar_bed(
+    format: Option<FileFormat>,
+    input: &Option<String>,
+    output: &str,
+    rewrite: bool,
+    header: bool,
+) -> Result<(), WGAError> {
+    // prepare reader and writer
+    let (mut reader, mut writer) = prepare_rdr_wtr(input, output, rewrite)?;
+    let format = resolve_format(format, input, &mut reader)?;
+
+    match format {
+        FileFormat::Paf => {
+            let mut pafrdr = PAFReader::new(reader);
+            cigar_bed(pafrdr.records(), &mut writer, header)?
+        }
+        FileFormat::Maf => {
+            let mut mafrdr = MAFReader::new(reader)?;
+            let records = mafrdr.records().map(|rec| rec?.convert2paf());
+            cigar_bed(records, &mut writer, header)?
+        }
+        FileFormat::Chain => {
+            let mut chainrdr = ChainReader::new(reader);
+            let records = chainrdr.records()?.map(|rec| rec?.convert2paf());
+            cigar_bed(records, &mut writer, header)?
+        }
+        _ => {
+            return Err(WGAError::NotImplemented);
+        }
+    }
+    Ok(())
+}
+
+/// Command: dedup
+pub fn wrap_dedup(
+    format: Option<FileFormat>,
+    input: &Option<String>,
+    output: &str,
+    rewrite: bool,
+    by_coords: bool,
+    keep_best: bool,
+) -> Result<(), WGAError> {
+    // prepare reader and writer
+    let (mut reader, mut writer) = prepare_rdr_wtr(input, output, rewrite)?;
+    let format = resolve_format(format, input, &mut reader)?;
+
+    match format {
+        FileFormat::Maf => {
+            let mut mafrdr = MAFReader::new(reader)?;
+            let mut mafwtr = MAFWriter::new(&mut writer);
+            let header = "#maf version=1.6 cmd=dedup".to_string();
+            mafwtr.write_header(&mafrdr.header_with(header))?;
+            dedup(mafrdr.records().traced(), by_coords, keep_best, |rec| {
+                mafwtr.write_record(rec)
+            })?;
+            mafwtr.finish()?;
+        }
+        FileFormat::Paf => {
+            let mut pafrdr = PAFReader::new(reader);
+            let mut pafwtr = csv::WriterBuilder::new()
+                .flexible(true)
+                .delimiter(b'\t')
+                .has_headers(false)
+                .from_writer(&mut writer);
+            dedup(pafrdr.records().traced(), by_coords, keep_best, |rec| {
+                Ok(pafwtr.serialize(rec)?)
+            })?;
+            pafwtr.flush()?;
+        }
+        FileFormat::Chain => {
+            let mut chainrdr = ChainReader::new(reader);
+            dedup(chainrdr.records()?.traced(), by_coords, keep_best, |rec| {
+                // additional newline for standard chain format
+                Ok(write!(writer, "{}\n\n", rec)?)
+            })?;
+        }
+        _ => {
+            return Err(WGAError::NotImplemented);
+        }
+    }
+    Ok(())
+}
+
+type RecordGroups<'a, T> = Box<dyn Iterator<Item = Result<(String, Vec<T>), WGAError>> + 'a>;
+
+// lazy groups of query-sorted input, or all records buffered if `unsorted`
+fn query_groups<'a, T, I>(records: I, unsorted: bool) -> Result<RecordGroups<'a, T>, WGAError>
+where
+    T: AlignRecord + 'a,
+    I: Iterator<Item = Result<T, WGAError>> + 'a,
+{
+    if unsorted {
+        let groups = groups_buffered(records, GroupBy::Query)?;
+        Ok(Box::new(groups.into_iter().map(Ok)))
+    } else {
+        Ok(Box::new(Groups::new(records, GroupBy::Query)))
+    }
+}
+
+/// Command: primary
+pub fn wrap_primary(
+    format: Option<FileFormat>,
+    input: &Option<String>,
+    output: &str,
+    rewrite: bool,
+    by: PrimaryBy,
+    mark_secondary: bool,
+    unsorted: bool,
+) -> Result<(), WGAError> {
+    // prepare reader and writer
+    let (mut reader, mut writer) = prepare_rdr_wtr(input, output, rewrite)?;
+    let format = resolve_format(format, input, &mut reader)?;
+
+    match format {
+        FileFormat::Maf => {
+            let mut mafrdr = MAFReader::new(reader)?;
+            let mut mafwtr = MAFWriter::new(&mut writer);
+            let header = format!("#maf version=1.6 cmd=primary by={:?}", by);
+            mafwtr.write_header(&mafrdr.header_with(header))?;
+            primary(
+                query_groups(mafrdr.records().traced(), unsorted)?,
+                by,
+                |rec, is_primary| {
+                    if is_primary {
+                        mafwtr.write_record(&rec)?;
+                    }
+                    Ok(())
+                },
+            )?;
+            mafwtr.finish()?;
+        }
+        FileFormat::Paf => {
+            let mut pafrdr = PAFReader::new(reader);
+            let mut pafwtr = csv::WriterBuilder::new()
+                .flexible(true)
+                .delimiter(b'\t')
+                .has_headers(false)
+                .from_writer(&mut writer);
+            primary(
+                query_groups(pafrdr.records().traced(), unsorted)?,
+                by,
+                |mut rec, is_primary| {
+                    if mark_secondary {
+                        rec.set_primary(is_primary);
+                    } else if !is_primary {
+                        return Ok(());
+                    }
+                    Ok(pafwtr.serialize(rec)?)
+                },
+            )?;
+            pafwtr.flush()?;
+        }
+        FileFormat::Chain => {
+            let mut chainrdr = ChainReader::new(reader);
+            primary(
+                query_groups(chainrdr.records()?.traced(), unsorted)?,
+                by,
+                |rec, is_primary| {
+                    if is_primary {
+                        // additional newline for standard chain format
+                        write!(writer, "{}\n\n", rec)?;
+                    }
+                    Ok(())
+                },
+            )?;
+        }
+        _ => {
+            return Err(WGAError::NotImplemented);
+        }
+    }
+    Ok(())
+}
+
+/// Command: coverage
+pub fn wrap_coverage(
+    format: Option<FileFormat>,
+    input: &Option<String>,
+    output: &str,
+    rewrite: bool,
+    merge: bool,
+    depth: bool,
+) -> Result<(), WGAError> {
+    // prepare reader and writer
+    let (mut reader, mut writer) = prepare_rdr_wtr(input, output, rewrite)?;
+    let format = resolve_format(format, input, &mut reader)?;
+
+    match format {
+        FileFormat::Paf => {
+            let mut pafrdr = PAFReader::new(reader);
+            coverage(pafrdr.records().traced(), &mut writer, merge, depth)?
+        }
+        FileFormat::Maf => {
+            let mut mafrdr = MAFReader::new(reader)?;
+            coverage(mafrdr.records().traced(), &mut writer, merge, depth)?
+        }
+        FileFormat::Chain => {
+            let mut chainrdr = ChainReader::new(reader);
+            coverage(chainrdr.records()?.traced(), &mut writer, merge, depth)?
+        }
+        _ => {
+            return Err(WGAError::NotImplemented);
+        }
+    }
+    Ok(())
+}
+
+/// Command: maf-depth
+pub fn wrap_maf_depth(
+    input: &Option<String>,
+    output: &str,
+    rewrite: bool,
+    min_depth: usize,
+) -> Result<(), WGAError> {
+    let (reader, mut writer) = prepare_rdr_wtr(input, output, rewrite)?;
+    let mafrdr = MAFReader::new(reader)?;
+    maf_depth(mafrdr, &mut writer, min_depth)?;
+    Ok(())
+}
+
+/// A wrapper for stat sub-cmd, detect or match format and call `stat_{maf,paf,chain}`
+#[allow(clippy::too_many_arguments)]
 pub fn wrap_stat(
-    format: FileFormat,
+    format: Option<FileFormat>,
     input: &Option<String>,
     output: &str,
     rewrite: bool,
     each: bool,
+    by_target: bool,
+    summary: bool,
+    per_sequence: bool,
+    tsv: bool,
+    gc: bool,
+    identity_mode: IdentityMode,
+    fast: bool,
 ) -> Result<(), WGAError> {
     // prepare reader and writer
-    let (reader, mut writer) = prepare_rdr_wtr(input, output, rewrite)?;
+    let (mut reader, mut writer) = prepare_rdr_wtr(input, output, rewrite)?;
+    let format = resolve_format(format, input, &mut reader)?;
+
+    if fast {
+        if !(summary || by_target) {
+            return Err(WGAError::Other(anyhow::anyhow!(
+                "`--fast` is only supported with `--summary` or `--by-target`"
+            )));
+        }
+        warn!("`--fast` skips CIGAR of records, identity and gap metrics are omitted");
+    }
 
     // match format and call stat
     match format {
+        FileFormat::Maf if gc => {
+            let mafrdr = MAFReader::new(reader)?;
+            stat_gc(mafrdr, &mut writer)?
+        }
+        _ if gc => {
+            return Err(WGAError::Other(anyhow::anyhow!(
+                "`--gc` is only supported for MAF, but input is {:?}",
+                format
+            )))
+        }
+        FileFormat::Maf if summary => {
+            let mut mafrdr = MAFReader::new(reader)?;
+            stat_summary(mafrdr.records(), &mut writer, tsv, identity_mode, fast)?
+        }
+        FileFormat::Paf if summary => {
+            let mut pafrdr = PAFReader::new(reader);
+            stat_summary(pafrdr.records(), &mut writer, tsv, identity_mode, fast)?
+        }
+        FileFormat::Chain if summary => {
+            let mut chainrdr = ChainReader::new(reader);
+            stat_summary(chainrdr.records()?, &mut writer, tsv, identity_mode, fast)?
+        }
+        FileFormat::Maf if per_sequence => {
+            let mut mafrdr = MAFReader::new(reader)?;
+            stat_per_sequence(mafrdr.records(), &mut writer, identity_mode)?
+        }
+        FileFormat::Paf if per_sequence => {
+            let mut pafrdr = PAFReader::new(reader);
+            stat_per_sequence(pafrdr.records(), &mut writer, identity_mode)?
+        }
+        FileFormat::Chain if per_sequence => {
+            let mut chainrdr = ChainReader::new(reader);
+            stat_per_sequence(chainrdr.records()?, &mut writer, identity_mode)?
+        }
+        FileFormat::Maf if by_target => {
+            let mut mafrdr = MAFReader::new(reader)?;
+            stat_by_target(mafrdr.records(), &mut writer, identity_mode, fast)?
+        }
+        FileFormat::Paf if by_target => {
+            let mut pafrdr = PAFReader::new(reader);
+            stat_by_target(pafrdr.records(), &mut writer, identity_mode, fast)?
+        }
+        FileFormat::Chain if by_target => {
+            let mut chainrdr = ChainReader::new(reader);
+            stat_by_target(chainrdr.records()?, &mut writer, identity_mode, fast)?
+        }
         FileFormat::Maf => {
             let mafrdr = MAFReader::new(reader)?;
-            stat_maf(mafrdr, &mut writer, each)?
+            stat_maf(mafrdr, &mut writer, each, identity_mode)?
         }
         FileFormat::Paf => {
             let pafrdr = PAFReader::new(reader);
-            stat_paf(pafrdr, &mut writer, each)?
+            stat_paf(pafrdr, &mut writer, each, identity_mode)?
+        }
+        FileFormat::Chain => {
+            let chainrdr = ChainReader::new(reader);
+            stat_chain(chainrdr, &mut writer, each, identity_mode)?
         }
         _ => {
             return Err(WGAError::NotImplemented);
@@ -453,35 +1661,42 @@ pub fn wrap_stat(
 
 /// A wrapper for filter sub-cmd, match format and call `filter_{maf,paf}`
 pub fn wrap_filter(
-    format: FileFormat,
+    format: Option<FileFormat>,
     input: &Option<String>,
     output: &str,
     rewrite: bool,
-    min_block_size: u64,
-    min_query_size: u64,
+    opts: &FilterOpts,
     min_align_size: Option<u64>,
 ) -> Result<(), WGAError> {
+    // check min_identity in [0, 1]
+    if opts.min_identity.is_some_and(|i| !(0.0..=1.0).contains(&i)) {
+        return Err(WGAError::Other(anyhow::anyhow!(
+            "`min_identity` should be in [0, 1]"
+        )));
+    }
+
     // prepare reader and writer
-    let (reader, mut writer) = prepare_rdr_wtr(input, output, rewrite)?;
+    let (mut reader, mut writer) = prepare_rdr_wtr(input, output, rewrite)?;
+    let format = resolve_format(format, input, &mut reader)?;
 
     match format {
         FileFormat::Maf => {
             let mafrdr = MAFReader::new(reader)?;
-            filter_maf(mafrdr, &mut writer, min_block_size, min_query_size)?
+            filter_maf(mafrdr, &mut writer, opts)?
         }
         FileFormat::Paf => {
             let pafrdr = PAFReader::new(reader);
             match min_align_size {
                 Some(min_align_size) => {
-                    warn!("`min_align_size` is set, will not filter paf `min_block_size` and `min_query_size`");
+                    warn!("`min_align_size` is set, will not filter paf by other thresholds");
                     filter_paf_align_pair(pafrdr, &mut writer, min_align_size)?
                 }
-                None => filter_paf(pafrdr, &mut writer, min_block_size, min_query_size)?,
+                None => filter_paf(pafrdr, &mut writer, opts)?,
             }
         }
         FileFormat::Chain => {
             let chainrdr = ChainReader::new(reader);
-            filter_chain(chainrdr, &mut writer, min_block_size, min_query_size)?
+            filter_chain(chainrdr, &mut writer, opts)?
         }
         _ => {
             return Err(WGAError::NotImplemented);
@@ -505,6 +1720,79 @@ pub fn wrap_rename_maf(
     Ok(())
 }
 
+/// A wrapper for rename sub-cmd with a name map, detect or match format
+pub fn wrap_rename_by_map(
+    format: Option<FileFormat>,
+    input: &Option<String>,
+    output: &str,
+    rewrite: bool,
+    map: &str,
+    strict: bool,
+) -> Result<(), WGAError> {
+    let map_file = File::open(map).map_err(|_| WGAError::FileNotExist(PathBuf::from(map)))?;
+    let mut name_map = NameMap::from_reader(BufReader::new(map_file), strict)?;
+    rename_by(format, input, output, rewrite, &mut name_map)
+}
+
+/// Command: rename PanSN names, the reverse name map is written into `map_out` if set
+#[allow(clippy::too_many_arguments)]
+pub fn wrap_rename_pansn(
+    format: Option<FileFormat>,
+    input: &Option<String>,
+    output: &str,
+    rewrite: bool,
+    sep: &str,
+    strip: bool,
+    target_prefix: &Option<String>,
+    query_prefix: &Option<String>,
+    map_out: &Option<String>,
+) -> Result<(), WGAError> {
+    let mut pansn = PanSN::new(
+        sep.to_string(),
+        strip,
+        target_prefix.clone(),
+        query_prefix.clone(),
+    );
+    rename_by(format, input, output, rewrite, &mut pansn)?;
+    if let Some(map_out) = map_out {
+        let mut writer = get_output_writer(map_out, rewrite)?;
+        pansn.write_map(&mut writer)?;
+    }
+    Ok(())
+}
+
+// rename target and query of any format by `renamer`
+fn rename_by(
+    format: Option<FileFormat>,
+    input: &Option<String>,
+    output: &str,
+    rewrite: bool,
+    renamer: &mut impl Renamer,
+) -> Result<(), WGAError> {
+    // prepare reader and writer
+    let (mut reader, mut writer) = prepare_rdr_wtr(input, output, rewrite)?;
+    let format = resolve_format(format, input, &mut reader)?;
+
+    match format {
+        FileFormat::Maf => {
+            let mafrdr = MAFReader::new(reader)?;
+            rename_maf_by_map(mafrdr, &mut writer, renamer)?
+        }
+        FileFormat::Paf => {
+            let pafrdr = PAFReader::new(reader);
+            rename_paf_by_map(pafrdr, &mut writer, renamer)?
+        }
+        FileFormat::Chain => {
+            let chainrdr = ChainReader::new(reader);
+            rename_chain_by_map(chainrdr, &mut writer, renamer)?
+        }
+        _ => {
+            return Err(WGAError::NotImplemented);
+        }
+    }
+    Ok(())
+}
+
 /// A wrapper for PAF Converage count
 pub fn wrap_paf_cov(input: &Option<String>, output: &str, rewrite: bool) -> Result<(), WGAError> {
     let (reader, mut writer) = prepare_rdr_wtr(input, output, rewrite)?;
@@ -591,6 +1879,184 @@ pub fn wrap_chunk(
     Ok(())
 }
 
+/// Command: maf-slice
+pub fn wrap_maf_slice(
+    input: &Option<String>,
+    output: &str,
+    rewrite: bool,
+    region: &str,
+    seq: &Option<String>,
+) -> Result<(), WGAError> {
+    let region = GenomeRegion::try_from(region.to_string())?;
+    let seq = seq.as_deref().unwrap_or(&region.name);
+
+    // prepare reader and writer
+    let (reader, mut writer) = prepare_rdr_wtr(input, output, rewrite)?;
+    let mafrdr = MAFReader::new(reader)?;
+    maf_slice(mafrdr, &region, seq, &mut writer)?;
+    Ok(())
+}
+
+/// Command: trim-overlaps
+pub fn wrap_trim_overlaps(
+    input: &Option<String>,
+    output: &str,
+    rewrite: bool,
+    min_length: u64,
+) -> Result<(), WGAError> {
+    let (reader, mut writer) = prepare_rdr_wtr(input, output, rewrite)?;
+    let mut pafrdr = PAFReader::new(reader);
+    trim_overlaps(pafrdr.records(), &mut writer, min_length)?;
+    Ok(())
+}
+
+/// Command: maf-mask
+pub fn wrap_maf_mask(
+    input: &Option<String>,
+    output: &str,
+    rewrite: bool,
+    bed: &str,
+    as_gap: bool,
+) -> Result<(), WGAError> {
+    let regions = get_input_regions(&None, &Some(bed.to_string()))?;
+    let (reader, mut writer) = prepare_rdr_wtr(input, output, rewrite)?;
+    let mafrdr = MAFReader::new(reader)?;
+    maf_mask(mafrdr, &mut writer, regions, as_gap)?;
+    Ok(())
+}
+
+/// Command: maf-normalize
+pub fn wrap_normalize(
+    input: &Option<String>,
+    output: &str,
+    rewrite: bool,
+    trim_gaps: bool,
+    flip_to_target_plus: bool,
+) -> Result<(), WGAError> {
+    // prepare reader and writer
+    let (reader, mut writer) = prepare_rdr_wtr(input, output, rewrite)?;
+    let mafrdr = MAFReader::new(reader)?;
+    normalize_maf(mafrdr, &mut writer, trim_gaps, flip_to_target_plus)?;
+    Ok(())
+}
+
+/// Command: maf-chunk
+pub fn wrap_maf_chunk(
+    input: &Option<String>,
+    rewrite: bool,
+    blocks: Option<u64>,
+    size: &Option<String>,
+    prefix: &str,
+) -> Result<(), WGAError> {
+    // check blocks/size > 0
+    let size = size.as_deref().map(parse_size).transpose()?;
+    if blocks == Some(0) || size == Some(0) {
+        return Err(WGAError::Other(anyhow::anyhow!(
+            "`blocks` and `size` should be greater than 0"
+        )));
+    }
+
+    let input_name = input.as_deref().unwrap_or("stdin");
+    info!("start read file: `{}`", input_name);
+    let mafrdr = MAFReader::new(get_input_reader(input)?)?;
+
+    split_maf(mafrdr, blocks, size, |idx| {
+        let path = format!("{}.{:03}.maf", prefix, idx);
+        info!("start write file: `{}`", path);
+        get_output_writer(&path, rewrite)
+    })?;
+    Ok(())
+}
+
+/// Command: maf join
+pub fn wrap_maf_join(
+    first: &str,
+    second: &str,
+    output: &str,
+    rewrite: bool,
+) -> Result<(), WGAError> {
+    let first = MAFReader::from_path(first)?;
+    let second = MAFReader::from_path(second)?;
+    let mut writer = get_output_writer(output, rewrite)?;
+    maf_join(first, second, &mut writer)
+}
+
+/// Command: maf-filter-by-name, names are from args and a file
+#[allow(clippy::too_many_arguments)]
+pub fn wrap_maf_filter_by_name(
+    input: &Option<String>,
+    output: &str,
+    rewrite: bool,
+    include: &Option<Vec<String>>,
+    exclude: &Option<Vec<String>>,
+    include_file: &Option<String>,
+    exclude_file: &Option<String>,
+    require_all: bool,
+) -> Result<(), WGAError> {
+    let collect_names = |names: &Option<Vec<String>>, file: &Option<String>| {
+        let mut names = names.clone().unwrap_or_default();
+        if file.is_some() {
+            names.extend(NameFilter::read_names(get_input_reader(file)?)?);
+        }
+        Ok::<_, WGAError>(names)
+    };
+    let filter = NameFilter {
+        include: collect_names(include, include_file)?,
+        exclude: collect_names(exclude, exclude_file)?,
+        require_all,
+    };
+    if filter.include.is_empty() && filter.exclude.is_empty() {
+        return Err(WGAError::Other(anyhow::anyhow!(
+            "no names to include or exclude, please set `--include` or `--exclude`"
+        )));
+    }
+
+    // prepare reader and writer
+    let (reader, mut writer) = prepare_rdr_wtr(input, output, rewrite)?;
+    let mafrdr = MAFReader::new(reader)?;
+    filter_maf_by_name(mafrdr, &mut writer, &filter)
+}
+
+/// Command: maf-sort
+pub fn wrap_maf_sort(
+    inputs: &[String],
+    output: &str,
+    rewrite: bool,
+    buffer_size: &str,
+) -> Result<(), WGAError> {
+    let buffer_size = parse_size(buffer_size)?;
+    let readers = match inputs.is_empty() {
+        true => vec![MAFReader::new(get_input_reader(&None)?)?],
+        false => inputs
+            .iter()
+            .map(MAFReader::from_path)
+            .collect::<Result<Vec<_>, WGAError>>()?,
+    };
+    let mut writer = get_output_writer(output, rewrite)?;
+    maf_sort(readers, &mut writer, buffer_size)
+}
+
+/// Command: maf-trim-edges
+pub fn wrap_trim_edges(
+    input: &Option<String>,
+    output: &str,
+    rewrite: bool,
+    min_species: usize,
+) -> Result<(), WGAError> {
+    // check min_species > 0
+    if min_species == 0 {
+        return Err(WGAError::Other(anyhow::anyhow!(
+            "`min_species` should be greater than 0"
+        )));
+    }
+
+    // prepare reader and writer
+    let (reader, mut writer) = prepare_rdr_wtr(input, output, rewrite)?;
+    let mafrdr = MAFReader::new(reader)?;
+    trim_edges_maf(mafrdr, &mut writer, min_species)?;
+    Ok(())
+}
+
 /// A wrapper for dotplot sub-cmd
 #[allow(clippy::too_many_arguments)]
 pub fn wrap_dotplot(
@@ -600,6 +2066,7 @@ pub fn wrap_dotplot(
     mode: DotplotMode,
     no_identity: bool,
     cutoff: Option<usize>,
+    filter: &SegmentFilter,
     output: &str,
     rewrite: bool,
 ) -> Result<(), WGAError> {
@@ -630,6 +2097,7 @@ pub fn wrap_dotplot(
         mode,
         no_identity,
         cutoff,
+        filter,
     )?;
     Ok(())
 }
@@ -0,0 +1,321 @@
+mod common;
+
+use common::{random_seq, write_fasta, TempFile};
+use std::fs::File;
+use std::io::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use wgalib::converter::{chain2maf, chain2paf, maf2chain, paf2chain};
+use wgalib::parser::chain::{ChainReader, ChainRecord};
+use wgalib::parser::common::Strand;
+use wgalib::parser::maf::MAFReader;
+use wgalib::parser::paf::PAFReader;
+
+// output of a converter writing to `Box<dyn Write>`, by a temp file of each call
+fn boxed_output(name: &str, convert: impl FnOnce(&mut Box<dyn Write>)) -> String {
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+    let out = TempFile::new(&format!(
+        "{}.{}",
+        CALLS.fetch_add(1, Ordering::Relaxed),
+        name
+    ));
+    let mut writer: Box<dyn Write> = Box::new(File::create(&out.0).unwrap());
+    convert(&mut writer);
+    drop(writer);
+    std::fs::read_to_string(&out.0).unwrap()
+}
+
+// chain text of MAF by `maf2chain`
+fn chain_text(maf: &str) -> String {
+    let mut mafreader = MAFReader::new(maf.as_bytes()).unwrap();
+    boxed_output("maf2chain.chain", |writer| {
+        maf2chain(&mut mafreader, writer).unwrap()
+    })
+}
+
+// chain text of PAF by `paf2chain`
+fn paf_to_chain(paf: &str) -> String {
+    let mut pafreader = PAFReader::new(paf.as_bytes());
+    boxed_output("paf2chain.chain", |writer| {
+        paf2chain(&mut pafreader, writer, false).unwrap()
+    })
+}
+
+fn chain_to_paf(chain: &str) -> String {
+    let mut chainreader = ChainReader::new(chain.as_bytes());
+    let mut out = Vec::new();
+    chain2paf(&mut chainreader, &mut out, false).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+fn parse_chains(chain: &str) -> Vec<ChainRecord> {
+    ChainReader::new(chain.as_bytes())
+        .records()
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap()
+}
+
+#[test]
+fn gap_columns_of_other_species_are_skipped() {
+    // `AC-GTAC-G` vs `AC--TACTG`, the third column is only aligned in oth.chr1
+    let maf = "\
+##maf version=1
+a score=30
+s ref.chr1 40 7 + 100 AC-GTAC-G
+s qry.chr2 0 7 + 20 AC--TACTG
+s oth.chr1 2 8 + 60 ACTGTA-CG
+
+";
+    let chain = chain_text(maf);
+    assert_eq!(
+        chain,
+        "chain\t30\tref.chr1\t100\t+\t40\t47\tqry.chr2\t20\t+\t0\t7\t0\n2\t1\t0\n3\t0\t1\n1\n\n"
+    );
+
+    let chains = parse_chains(&chain);
+    assert_eq!(chains.len(), 1);
+    let blocks = chains[0].blocks().collect::<Vec<_>>();
+    let (t_size, q_size) = blocks.iter().fold((0, 0), |(t, q), (size, dt, dq)| {
+        (t + size + dt, q + size + dq)
+    });
+    assert_eq!((t_size, q_size), (7, 7));
+}
+
+#[test]
+fn minus_strand_paf_roundtrip_by_chain() {
+    // query [10, 30) of length 50 on '-' is [20, 40) on the reverse complement in chain
+    let paf = "qry.chr1\t50\t10\t30\t-\tref.chr1\t100\t20\t39\t18\t21\t255\tcg:Z:5M1D8M2I5M\n";
+    let chain = paf_to_chain(paf);
+    assert_eq!(
+        chain,
+        "chain\t255\tref.chr1\t100\t+\t20\t39\tqry.chr1\t50\t-\t20\t40\t0\n5\t1\t0\n8\t0\t2\n5\n\n"
+    );
+    assert_eq!(chain_to_paf(&chain), paf);
+}
+
+#[test]
+fn minus_strand_head_insertion_is_trimmed_at_query_end() {
+    // the leading insertion in target order is at the end of forward query
+    let paf = "qry.chr1\t50\t10\t30\t-\tref.chr1\t100\t20\t39\t18\t21\t255\tcg:Z:2I5M1D13M\n";
+    let chain = paf_to_chain(paf);
+    let header = chain.lines().next().unwrap();
+    assert_eq!(
+        header,
+        "chain\t255\tref.chr1\t100\t+\t20\t39\tqry.chr1\t50\t-\t22\t40\t0"
+    );
+    assert_eq!(
+        chain_to_paf(&chain),
+        "qry.chr1\t50\t10\t28\t-\tref.chr1\t100\t20\t39\t18\t19\t255\tcg:Z:5M1D13M\n"
+    );
+}
+
+// chain example of UCSC with a second chain on '+', separated by spaces
+const UCSC_CHAIN: &str = "\
+#comment line of liftOver chain
+chain 4900 chrY 58368225 + 25985403 25985638 chr5 151006098 - 43257292 43257528 1
+9 1 0
+10 0 5
+61 4 0
+16 0 4
+42 3 0
+16 0 8
+14 1 0
+3 7 0
+48
+
+chain 3400.5 chrY 58368225 + 100 120 chr5 151006098 + 200 221 2
+12 0 1
+8
+
+";
+
+#[test]
+fn chain_roundtrip_is_byte_stable() {
+    let chains = parse_chains(UCSC_CHAIN);
+    assert_eq!(chains.len(), 2);
+    let written = chains
+        .iter()
+        .map(|chain| format!("{}\n\n", chain))
+        .collect::<String>();
+    // same as input except the comment and separators written as tabs
+    let expected = UCSC_CHAIN
+        .lines()
+        .skip(1)
+        .map(|line| line.split(' ').collect::<Vec<_>>().join("\t") + "\n")
+        .collect::<String>();
+    assert_eq!(written, expected);
+
+    // parse-then-write of the output gives the same bytes
+    let rewritten = parse_chains(&written)
+        .iter()
+        .map(|chain| format!("{}\n\n", chain))
+        .collect::<String>();
+    assert_eq!(rewritten, written);
+}
+
+#[test]
+fn chain_maf_chain_roundtrip() {
+    let (t_seq, q_seq) = (random_seq(100, 5), random_seq(50, 6));
+    let t_fa = TempFile::new("chain2maf.target.fa");
+    let q_fa = TempFile::new("chain2maf.query.fa");
+    write_fasta(&t_fa, "ref.chr1", &t_seq);
+    write_fasta(&q_fa, "qry.chr1", &q_seq);
+    let _fai = (
+        TempFile::new("chain2maf.target.fa.fai"),
+        TempFile::new("chain2maf.query.fa.fai"),
+    );
+
+    // gaps in both, query [5, 36) on '-' is [14, 45) of the forward strand
+    let chain = "\
+chain\t1000\tref.chr1\t100\t+\t10\t40\tqry.chr1\t50\t-\t5\t36\t0
+10\t2\t0
+8\t0\t3
+10
+
+chain\t500\tref.chr1\t100\t+\t50\t70\tqry.chr1\t50\t+\t0\t21\t1
+12\t0\t1
+8
+
+";
+    let mut chainreader = ChainReader::new(chain.as_bytes());
+    let mut maf = Vec::new();
+    chain2maf(
+        &mut chainreader,
+        &mut maf,
+        &t_fa.0.to_string_lossy(),
+        &q_fa.0.to_string_lossy(),
+        false,
+    )
+    .unwrap();
+    let maf = String::from_utf8(maf).unwrap();
+
+    let blocks = MAFReader::new(maf.as_bytes())
+        .unwrap()
+        .records()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(blocks.len(), 2);
+    let (target, query) = (&blocks[0].slines[0], &blocks[0].slines[1]);
+    assert_eq!(target.seq.len(), query.seq.len());
+    assert_eq!(
+        (target.start, target.align_size, target.size),
+        (10, 30, 100)
+    );
+    assert_eq!((query.start, query.align_size, query.size), (5, 31, 50));
+    let ungapped = |seq: &str| seq.bytes().filter(|b| *b != b'-').collect::<Vec<_>>();
+    assert_eq!(ungapped(&target.seq), &t_seq[10..40]);
+    assert_eq!(ungapped(&query.seq), Strand::complement_seq(&q_seq[14..45]));
+    // 2 target bases after the first block are gaps in query, then 3 query bases
+    assert_eq!(&query.seq[10..12], "--");
+    assert_eq!(&target.seq[20..23], "---");
+
+    assert_eq!(chain_text(&maf), chain);
+}
+
+#[test]
+fn maf_score_roundtrip_by_chain() {
+    let (t_seq, q_seq) = (random_seq(40, 7), random_seq(30, 8));
+    let t_fa = TempFile::new("score.target.fa");
+    let q_fa = TempFile::new("score.query.fa");
+    write_fasta(&t_fa, "ref.chr1", &t_seq);
+    write_fasta(&q_fa, "qry.chr1", &q_seq);
+    let _fai = (
+        TempFile::new("score.target.fa.fai"),
+        TempFile::new("score.query.fa.fai"),
+    );
+
+    // the second block has no score
+    let seq = |seq: &[u8], start: usize| String::from_utf8(seq[start..start + 8].to_vec()).unwrap();
+    let maf = format!(
+        "##maf version=1\na score=3400\ns ref.chr1 0 8 + 40 {}\ns qry.chr1 0 8 + 30 {}\n\na\ns ref.chr1 20 8 + 40 {}\ns qry.chr1 10 8 + 30 {}\n\n",
+        seq(&t_seq, 0),
+        seq(&q_seq, 0),
+        seq(&t_seq, 20),
+        seq(&q_seq, 10),
+    );
+    let chain = chain_text(&maf);
+    let scores = parse_chains(&chain)
+        .iter()
+        .map(|chain| chain.header.score())
+        .collect::<Vec<_>>();
+    assert_eq!(scores, [3400.0, 255.0]);
+
+    let mut chainreader = ChainReader::new(chain.as_bytes());
+    let mut out = Vec::new();
+    chain2maf(
+        &mut chainreader,
+        &mut out,
+        &t_fa.0.to_string_lossy(),
+        &q_fa.0.to_string_lossy(),
+        false,
+    )
+    .unwrap();
+    let blocks = MAFReader::new(out.as_slice())
+        .unwrap()
+        .records()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(
+        blocks.iter().map(|block| block.score).collect::<Vec<_>>(),
+        [3400, 255]
+    );
+    assert!(String::from_utf8(out).unwrap().contains("a score=3400\n"));
+
+    // float score of chain is rounded in MAF
+    let chain = chain.replacen("\t3400\t", "\t3400.6\t", 1);
+    let mut chainreader = ChainReader::new(chain.as_bytes());
+    let mut out = Vec::new();
+    chain2maf(
+        &mut chainreader,
+        &mut out,
+        &t_fa.0.to_string_lossy(),
+        &q_fa.0.to_string_lossy(),
+        false,
+    )
+    .unwrap();
+    let first = MAFReader::new(out.as_slice()).unwrap().records().next();
+    assert_eq!(first.unwrap().unwrap().score, 3401);
+}
+
+#[test]
+fn softmask_is_kept_across_strand_flip() {
+    let t_seq = random_seq(40, 9);
+    let t_fa = TempFile::new("softmask.chain.target.fa");
+    let q_fa = TempFile::new("softmask.chain.query.fa");
+    write_fasta(&t_fa, "ref.chr1", &t_seq);
+    // [10, 18) of query is soft-masked
+    write_fasta(&q_fa, "qry.chr1", b"ACGTACGTACacgttgcaGGCCAATT");
+    let _fai = (
+        TempFile::new("softmask.chain.target.fa.fai"),
+        TempFile::new("softmask.chain.query.fa.fai"),
+    );
+
+    // forward query [6, 14) is [12, 20) of the reverse complement
+    let chain = "\
+chain\t10\tref.chr1\t40\t+\t0\t8\tqry.chr1\t26\t+\t6\t14\t1
+8
+
+chain\t10\tref.chr1\t40\t+\t20\t28\tqry.chr1\t26\t-\t12\t20\t2
+8
+
+";
+    let query_seqs = |uppercase: bool| {
+        let mut chainreader = ChainReader::new(chain.as_bytes());
+        let mut out = Vec::new();
+        chain2maf(
+            &mut chainreader,
+            &mut out,
+            &t_fa.0.to_string_lossy(),
+            &q_fa.0.to_string_lossy(),
+            uppercase,
+        )
+        .unwrap();
+        MAFReader::new(out.as_slice())
+            .unwrap()
+            .records()
+            .map(|rec| rec.unwrap().slines[1].seq.clone())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(query_seqs(false), ["GTACacgt", "acgtGTAC"]);
+    assert_eq!(query_seqs(true), ["GTACACGT", "ACGTGTAC"]);
+}
@@ -0,0 +1,58 @@
+use wgalib::parser::paf::PAFReader;
+use wgalib::tools::cigarbed::{cigar_bed, CIGAR_BED_HEADER};
+
+// bases of target and query consumed by a CIGAR
+fn consumed(cigar: &str) -> (u64, u64) {
+    let (mut target, mut query, mut len) = (0, 0, 0);
+    for c in cigar.chars() {
+        match c {
+            '0'..='9' => len = len * 10 + c.to_digit(10).unwrap() as u64,
+            'M' | '=' | 'X' => {
+                target += len;
+                query += len;
+                len = 0
+            }
+            'D' | 'N' => {
+                target += len;
+                len = 0
+            }
+            'I' => {
+                query += len;
+                len = 0
+            }
+            _ => panic!("unexpected CIGAR op {}", c),
+        }
+    }
+    (target, query)
+}
+
+#[test]
+fn cigar_bed_lines_are_paftools_layout() {
+    let paf = "\
+q1\t100\t5\t35\t+\tt1\t200\t0\t30\t28\t32\t60\tNM:i:4\tcg:Z:10=2I2D18=
+q2\t50\t10\t30\t-\tt1\t200\t40\t59\t18\t21\t60\tcg:Z:5M1D8M2I5M
+";
+    let mut out = Vec::new();
+    let mut pafreader = PAFReader::new(paf.as_bytes());
+    cigar_bed(pafreader.records(), &mut out, true).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert_eq!(
+        out,
+        format!(
+            "{}\nt1\t0\t30\tq1\t5\t35\t+\t10=2I2D18=\nt1\t40\t59\tq2\t10\t30\t-\t5M1D8M2I5M\n",
+            CIGAR_BED_HEADER
+        )
+    );
+
+    // parsed by the documented column order, as paftools reads it
+    assert_eq!(CIGAR_BED_HEADER.split('\t').count(), 8);
+    for line in out.lines().filter(|line| !line.starts_with('#')) {
+        let fields = line.split('\t').collect::<Vec<_>>();
+        assert_eq!(fields.len(), 8, "{}", line);
+        let coord = |i: usize| fields[i].parse::<u64>().unwrap();
+        let (t_start, t_end, q_start, q_end) = (coord(1), coord(2), coord(4), coord(5));
+        assert!(["+", "-"].contains(&fields[6]));
+        assert!(!fields[7].starts_with("cg:Z:"));
+        assert_eq!(consumed(fields[7]), (t_end - t_start, q_end - q_start));
+    }
+}
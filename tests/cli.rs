@@ -0,0 +1,292 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+// blocks of two and three species, one with a '-' strand query
+const MAF: &str = "\
+##maf version=1
+a score=10
+s ref.chr1 0 10 + 100 ACGTACGTAC
+s qry.chr1 5 10 + 50 ACGAACGTAC
+
+a score=20
+s ref.chr1 20 8 + 100 ACG--TACGT
+s qry.chr1 30 9 - 50 ACGTTT-ACG
+
+a score=30
+s ref.chr1 40 7 + 100 AC-GTAC-G
+s qry.chr2 0 7 - 20 AC--TACTG
+s oth.chr1 2 8 + 60 ACTGTA-CG
+
+";
+
+// run wgatools with `input` as stdin, which is written in another thread so a large
+// output never blocks it, and not read at all if arguments are rejected
+fn wgatools(args: &[&str], input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_wgatools"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    let input = input.to_string();
+    let feeder = std::thread::spawn(move || {
+        let _ = stdin.write_all(input.as_bytes());
+    });
+    let output = child.wait_with_output().unwrap();
+    feeder.join().unwrap();
+    output
+}
+
+// stdout of a successful run
+fn stdout_of(args: &[&str], input: &str) -> String {
+    let output = wgatools(args, input);
+    assert!(
+        output.status.success(),
+        "{:?}: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn convert_to_paf_equals_maf2paf() {
+    let maf2paf = stdout_of(&["maf2paf"], MAF);
+    assert_eq!(maf2paf.lines().count(), 4);
+    // MAF input is detected by content
+    assert_eq!(stdout_of(&["convert", "--to", "paf"], MAF), maf2paf);
+    assert_eq!(
+        stdout_of(&["convert", "--input-format", "maf", "--to", "paf"], MAF),
+        maf2paf
+    );
+    assert_eq!(
+        stdout_of(&["convert", "--to", "paf", "--sort"], MAF),
+        stdout_of(&["maf2paf", "--sort"], MAF)
+    );
+}
+
+// MAF of `blocks` pairwise blocks
+fn many_blocks(blocks: usize) -> String {
+    let mut maf = String::from("##maf version=1\n");
+    for i in 0..blocks {
+        maf += &format!(
+            "a score={}\ns ref.chr1 {} 8 + 100000000 ACGT-ACGT\ns qry.chr{} {} 9 - 100000000 ACGTTACGT\n\n",
+            i,
+            i * 10,
+            i % 7,
+            i * 10
+        );
+    }
+    maf
+}
+
+#[test]
+fn record_counter_with_one_thread() {
+    // several batches of the parallel converter
+    let maf = many_blocks(3000);
+    let serial = wgatools(&["maf2paf", "--threads", "1", "--log-level", "info"], &maf);
+    assert!(serial.status.success());
+    let stderr = String::from_utf8_lossy(&serial.stderr);
+    assert!(stderr.contains("INFO Threads: 1\n"), "{}", stderr);
+    assert!(stderr.contains("INFO 3000 records read\n"), "{}", stderr);
+
+    // same records and order as a pool of 4 threads
+    let parallel = wgatools(&["maf2paf", "--threads", "4", "--log-level", "info"], &maf);
+    let stderr = String::from_utf8_lossy(&parallel.stderr);
+    assert!(stderr.contains("INFO Threads: 4\n"), "{}", stderr);
+    assert!(stderr.contains("INFO 3000 records read\n"), "{}", stderr);
+    assert_eq!(serial.stdout, parallel.stdout);
+    assert_eq!(
+        String::from_utf8_lossy(&serial.stdout).lines().count(),
+        3000
+    );
+
+    assert!(!wgatools(&["maf2paf", "--threads", "0"], &maf)
+        .status
+        .success());
+}
+
+// a dir in the temp dir, removed with its files when dropped
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("wgatools-{}-{}", std::process::id(), name));
+        TempDir(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+#[test]
+fn per_block_files_keep_header() {
+    let maf = "\
+##maf version=1 scoring=tba
+# made by test
+a score=1
+s ref.chr1 0 4 + 10 ACGT
+s qry.chr1 0 4 + 10 ACGT
+
+a score=2
+s ref.chr1 4 4 + 10 ACGT
+s qry.chr1 4 4 - 10 ACGT
+
+";
+    for to in ["maf", "paf"] {
+        let dir = TempDir::new(&format!("per-block-{}", to));
+        let dir_arg = dir.0.to_string_lossy().to_string();
+        stdout_of(&["convert", "--to", to, "--per-block-dir", &dir_arg], maf);
+        let mut files = std::fs::read_dir(&dir.0)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        files.sort();
+        assert_eq!(files, [format!("000000.{}", to), format!("000001.{}", to)]);
+
+        for (idx, file) in files.iter().enumerate() {
+            let content = std::fs::read_to_string(dir.0.join(file)).unwrap();
+            if to == "maf" {
+                assert!(
+                    content.starts_with("##maf version=1 scoring=tba\n# made by test\n"),
+                    "{}",
+                    content
+                );
+                let blocks = content.matches("\na score=").count();
+                assert_eq!(blocks, 1);
+                assert!(content.contains(&format!("a score={}\n", idx + 1)));
+            } else {
+                assert_eq!(content.lines().count(), 1);
+                assert!(content.contains(&format!("\tref.chr1\t10\t{}\t", idx * 4)));
+            }
+        }
+    }
+}
+
+#[test]
+fn throughput_is_logged_at_trace_level() {
+    let is_trace = |line: &&str| line.contains(" TRACE ") && line.contains(" records/sec, ");
+    for args in [
+        &["maf2paf", "-vvv"][..],
+        &["maf2paf", "--log-level", "trace"],
+    ] {
+        let output = wgatools(args, MAF);
+        assert!(output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let traces = stderr.lines().filter(is_trace).collect::<Vec<_>>();
+        // the rest of records are logged when input ends
+        assert_eq!(traces.len(), 1, "{}", stderr);
+        assert!(traces[0].contains(" 3 records, "), "{}", traces[0]);
+    }
+    for args in [&["maf2paf", "-vv"][..], &["maf2paf", "--log-level", "info"]] {
+        let output = wgatools(args, MAF);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            !stderr.lines().any(|line| line.contains(" TRACE ")),
+            "{}",
+            stderr
+        );
+    }
+
+    // logged every 100000 records
+    let output = wgatools(&["maf2paf", "-vvv"], &many_blocks(100_001));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let traces = stderr.lines().filter(is_trace).collect::<Vec<_>>();
+    assert_eq!(traces.len(), 2, "{}", stderr);
+    assert!(traces[0].contains(" 100000 records, "), "{}", traces[0]);
+    assert!(traces[1].contains(" 100001 records, "), "{}", traces[1]);
+    assert!(traces[0].ends_with(" MB/sec"), "{}", traces[0]);
+}
+
+#[test]
+fn empty_and_header_only_inputs_give_empty_outputs() {
+    // header-only input of each format, PAF has no header but comments are skipped
+    let maf_header = "##maf version=1 scoring=none\n";
+    let paf_header = "# no records\n";
+    let chain_header = "#comment line of chain\n";
+    // lines of an empty output only start with the header prefix of its format
+    let cases: &[(&[&str], &str, &str)] = &[
+        (&["maf2paf"], maf_header, "#"),
+        (&["maf2chain"], maf_header, "#"),
+        (&["maf2fasta"], maf_header, ">"),
+        (&["maf2sam"], maf_header, "@"),
+        (&["maf2vcf"], maf_header, "#"),
+        (&["maf-normalize"], maf_header, "#"),
+        (&["maf-sort"], maf_header, "#"),
+        (&["maf-trim-edges"], maf_header, "#"),
+        (&["maf-depth"], maf_header, "#"),
+        (&["paf2chain"], paf_header, "#"),
+        (&["paf2sam"], paf_header, "@"),
+        (&["cigar-bed"], paf_header, "#"),
+        (&["trim-overlaps"], paf_header, "#"),
+        (&["chain2paf"], chain_header, "#"),
+        (&["stat"], maf_header, "#"),
+        (&["coverage"], paf_header, "#"),
+        (&["dedup"], maf_header, "#"),
+        (&["primary"], maf_header, "#"),
+        (&["filter"], chain_header, "#"),
+        (&["convert", "--to", "paf"], maf_header, "#"),
+        (&["convert", "--to", "maf"], paf_header, "#"),
+        (&["convert", "--to", "chain"], maf_header, "#"),
+        // empty input is detected as PAF, which is not converted to SAM
+        (
+            &["convert", "--input-format", "maf", "--to", "sam"],
+            maf_header,
+            "@",
+        ),
+    ];
+    for (args, header, prefix) in cases {
+        for input in ["", header] {
+            let out = stdout_of(args, input);
+            assert!(
+                out.lines().all(|line| line.starts_with(prefix)),
+                "{:?} of {:?}: {}",
+                args,
+                input,
+                out
+            );
+        }
+    }
+
+    // headers of MAF outputs are kept
+    let out = stdout_of(&["maf-sort"], maf_header);
+    assert!(out.starts_with(maf_header), "{}", out);
+    let out = stdout_of(&["convert", "--to", "maf"], "");
+    assert_eq!(out.lines().count(), 1);
+    // headers of VCF and delta are written without records
+    let out = stdout_of(&["maf2vcf"], "");
+    assert!(
+        out.lines().last().unwrap().starts_with("#CHROM\t"),
+        "{}",
+        out
+    );
+    for args in [
+        &["maf2delta"][..],
+        &["paf2delta"],
+        &["convert", "--to", "delta"],
+    ] {
+        assert_eq!(
+            stdout_of(args, ""),
+            "target.fa query.fa\nNUCMER\n",
+            "{:?}",
+            args
+        );
+    }
+    let out = stdout_of(&["chain-stat"], "");
+    assert!(out.contains("\nchains\t0\n"), "{}", out);
+
+    // an empty index of an empty MAF
+    let dir = TempDir::new("empty-index");
+    std::fs::create_dir_all(&dir.0).unwrap();
+    let maf = dir.0.join("empty.maf");
+    std::fs::write(&maf, "").unwrap();
+    stdout_of(&["maf-index", &maf.to_string_lossy()], "");
+    let index = std::fs::read_to_string(dir.0.join("empty.maf.index")).unwrap();
+    assert_eq!(index, "{\"offset_kind\":\"plain\",\"index\":{}}");
+}
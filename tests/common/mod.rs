@@ -0,0 +1,43 @@
+// helpers shared by test crates, not all of them are used by each one
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+
+// a file in the temp dir, removed when dropped
+pub struct TempFile(pub PathBuf);
+
+impl TempFile {
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("wgatools-{}-{}", std::process::id(), name));
+        TempFile(path)
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+// a sequence of `len` bases by a linear congruential generator
+pub fn random_seq(len: usize, seed: u64) -> Vec<u8> {
+    let mut state = seed;
+    (0..len)
+        .map(|_| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            b"ACGT"[(state >> 62) as usize]
+        })
+        .collect()
+}
+
+// FASTA of one sequence wrapped at 60 columns
+pub fn write_fasta(file: &TempFile, name: &str, seq: &[u8]) {
+    let mut fasta = format!(">{}\n", name);
+    for line in seq.chunks(60) {
+        fasta += std::str::from_utf8(line).unwrap();
+        fasta += "\n";
+    }
+    std::fs::write(&file.0, fasta).unwrap();
+}
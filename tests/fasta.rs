@@ -0,0 +1,118 @@
+use std::io::Cursor;
+use wgalib::errors::WGAError;
+use wgalib::parser::common::Strand;
+use wgalib::parser::fasta::{build_fai, FaiRecord, FastaIndex};
+
+// `len` bases cycling through a pattern with soft-masked bases
+fn make_seq(len: usize) -> Vec<u8> {
+    b"ACGTTGCAacgtN".iter().cycle().take(len).copied().collect()
+}
+
+fn wrap(name: &str, seq: &[u8], width: usize) -> String {
+    let mut fasta = format!(">{} description\n", name);
+    for line in seq.chunks(width) {
+        fasta += std::str::from_utf8(line).unwrap();
+        fasta += "\n";
+    }
+    fasta
+}
+
+fn fasta_index(fasta: &str) -> FastaIndex<Cursor<Vec<u8>>> {
+    let records = build_fai(fasta.as_bytes()).unwrap();
+    FastaIndex::new(Cursor::new(fasta.as_bytes().to_vec()), records)
+}
+
+#[test]
+fn fetch_from_records_of_different_widths() {
+    let (seq1, seq2) = (make_seq(200), make_seq(155));
+    let fasta = wrap("chr1", &seq1, 60) + &wrap("chr2", &seq2, 70);
+
+    let records = build_fai(fasta.as_bytes()).unwrap();
+    let offset2 = fasta.find(">chr2").unwrap() as u64 + ">chr2 description\n".len() as u64;
+    assert_eq!(
+        records,
+        [
+            FaiRecord {
+                name: "chr1".to_string(),
+                length: 200,
+                offset: 18,
+                linebases: 60,
+                linewidth: 61,
+            },
+            FaiRecord {
+                name: "chr2".to_string(),
+                length: 155,
+                offset: offset2,
+                linebases: 70,
+                linewidth: 71,
+            },
+        ]
+    );
+
+    let mut fasta = fasta_index(&fasta);
+    // inside a line, across line breaks, at ends of records
+    for (start, end) in [(0, 10), (55, 125), (59, 61), (120, 200), (199, 200), (7, 7)] {
+        let got = fasta.fetch("chr1", start, end, Strand::Positive).unwrap();
+        assert_eq!(got, &seq1[start as usize..end as usize]);
+    }
+    for (start, end) in [(0, 70), (65, 145), (69, 71), (140, 155)] {
+        let got = fasta.fetch("chr2", start, end, Strand::Positive).unwrap();
+        assert_eq!(got, &seq2[start as usize..end as usize]);
+        let got = fasta.fetch("chr2", start, end, Strand::Negative).unwrap();
+        assert_eq!(
+            got,
+            Strand::complement_seq(&seq2[start as usize..end as usize])
+        );
+    }
+    assert!(matches!(
+        fasta.fetch("chr2", 150, 156, Strand::Positive),
+        Err(WGAError::FetchOutOfRange(..))
+    ));
+    assert!(matches!(
+        fasta.fetch("chr3", 0, 1, Strand::Positive),
+        Err(WGAError::SeqNotFound(..))
+    ));
+}
+
+#[test]
+fn full_last_line_without_final_newline() {
+    let (seq1, seq2) = (make_seq(120), make_seq(140));
+    // both records end with a full line, the file has no final line break
+    let fasta = wrap("chr1", &seq1, 60) + wrap("chr2", &seq2, 70).trim_end();
+
+    let records = build_fai(fasta.as_bytes()).unwrap();
+    assert_eq!((records[1].length, records[1].linebases), (140, 70));
+    assert_eq!(records[1].linewidth, 71);
+
+    let mut fasta = fasta_index(&fasta);
+    let got = fasta.fetch("chr2", 60, 140, Strand::Positive).unwrap();
+    assert_eq!(got, &seq2[60..]);
+    let got = fasta.fetch("chr1", 0, 120, Strand::Positive).unwrap();
+    assert_eq!(got, seq1);
+}
+
+#[test]
+fn inconsistent_line_widths_are_rejected() {
+    let seq = make_seq(100);
+    // a short line in the middle of a record
+    let fasta = format!(
+        ">chr1\n{}\n{}\n{}\n",
+        std::str::from_utf8(&seq[..60]).unwrap(),
+        std::str::from_utf8(&seq[60..80]).unwrap(),
+        std::str::from_utf8(&seq[80..]).unwrap()
+    );
+    assert!(matches!(
+        build_fai(fasta.as_bytes()),
+        Err(WGAError::FaiInconsistentLine(name)) if name == "chr1"
+    ));
+    // a line longer than the first one
+    let fasta = format!(
+        ">chr1\n{}\n{}\n",
+        std::str::from_utf8(&seq[..40]).unwrap(),
+        std::str::from_utf8(&seq[40..]).unwrap()
+    );
+    assert!(matches!(
+        build_fai(fasta.as_bytes()),
+        Err(WGAError::FaiInconsistentLine(_))
+    ));
+}
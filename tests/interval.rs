@@ -0,0 +1,60 @@
+use wgalib::parser::common::AlignRecord;
+use wgalib::parser::paf::PafRecord;
+use wgalib::tools::interval::{merge, Interval};
+
+#[test]
+fn touching_intervals_do_not_overlap() {
+    let (left, right) = (Interval::new(0, 10), Interval::new(10, 20));
+    assert!(!left.overlaps(&right));
+    assert!(!right.overlaps(&left));
+    assert_eq!(left.intersect(&right), None);
+
+    // one shared base is an overlap
+    let shifted = Interval::new(9, 20);
+    assert!(left.overlaps(&shifted) && shifted.overlaps(&left));
+    assert_eq!(left.intersect(&shifted), Some(Interval::new(9, 10)));
+    assert_eq!(
+        Interval::new(0, 20).intersect(&Interval::new(5, 8)),
+        Some(Interval::new(5, 8))
+    );
+
+    // an empty interval overlaps nothing, even inside another one
+    let empty = Interval::new(5, 5);
+    assert!(empty.is_empty());
+    assert_eq!(Interval::new(10, 5).len(), 0);
+    assert!(!empty.overlaps(&left) && !left.overlaps(&empty));
+    assert!(left.contains(&empty));
+    assert!(left.contains(&left) && !left.contains(&shifted));
+    assert_eq!((left.len(), right.to_string()), (10, "10-20".to_string()));
+}
+
+#[test]
+fn sorted_intervals_are_merged() {
+    let ivs = [(0, 10), (5, 20), (20, 25), (30, 40), (32, 35), (41, 50)];
+    let merged = merge(ivs.iter().copied().map(Interval::from));
+    // book-ended intervals are merged, a gap of one base is kept
+    assert_eq!(
+        merged,
+        [
+            (Interval::new(0, 25), 3),
+            (Interval::new(30, 40), 2),
+            (Interval::new(41, 50), 1),
+        ]
+    );
+    assert!(merge(Vec::new()).is_empty());
+}
+
+#[test]
+fn records_overlap_on_the_same_target() {
+    let record = |line: &str| line.parse::<PafRecord>().unwrap();
+    let a = record("q1\t100\t0\t10\t+\tchr1\t100\t0\t10\t10\t10\t60");
+    let b = record("q2\t100\t90\t100\t-\tchr1\t100\t10\t20\t10\t10\t60");
+    let c = record("q3\t100\t0\t20\t+\tchr1\t100\t5\t25\t20\t20\t60");
+    let d = record("q4\t100\t0\t10\t+\tchr2\t100\t0\t10\t10\t10\t60");
+    assert_eq!(a.target_interval(), Interval::new(0, 10));
+    assert_eq!(b.query_interval(), Interval::new(90, 100));
+    assert!(!a.overlaps(&b));
+    assert!(a.overlaps(&c) && c.overlaps(&b));
+    // same coordinates of another target
+    assert!(!a.overlaps(&d));
+}
@@ -0,0 +1,225 @@
+use wgalib::parser::common::FileFormat;
+use wgalib::parser::maf::MAFReader;
+use wgalib::parser::maf::MAFRecord;
+use wgalib::tools::mafextra::{maf_slice, GenomeRegion};
+use wgalib::tools::trimedges::trim_edges;
+use wgalib::utils::detect_format;
+
+fn block(maf: &str) -> MAFRecord {
+    maf.parse().unwrap()
+}
+
+// name, start, align size, strand and seq of s-lines
+fn slines(rec: &MAFRecord) -> Vec<(String, u64, u64, String, String)> {
+    rec.slines
+        .iter()
+        .map(|s| {
+            (
+                s.name.clone(),
+                s.start,
+                s.align_size,
+                s.strand.to_string(),
+                s.seq.clone(),
+            )
+        })
+        .collect()
+}
+
+fn expect(slines: [(&str, u64, u64, &str, &str); 3]) -> Vec<(String, u64, u64, String, String)> {
+    slines
+        .iter()
+        .map(|(name, start, size, strand, seq)| {
+            (
+                name.to_string(),
+                *start,
+                *size,
+                strand.to_string(),
+                seq.to_string(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn ragged_edges_are_trimmed() {
+    // only B has bases in the first 3 and the last column, A has none in the last 2
+    let maf = "\
+a score=1
+s A.chr1 10 5 + 100 ---ATCGT--
+s B.chr1 0 10 + 20 GGGATCGTAA
+s C.chr2 5 6 - 50 ---ATCGTA-
+";
+    let mut rec = block(maf);
+    assert!(trim_edges(&mut rec, 2));
+    assert_eq!(
+        slines(&rec),
+        expect([
+            ("A.chr1", 10, 5, "+", "ATCGT-"),
+            ("B.chr1", 3, 6, "+", "ATCGTA"),
+            ("C.chr2", 5, 6, "-", "ATCGTA"),
+        ])
+    );
+    assert_eq!(
+        rec.slines.iter().map(|s| s.size).collect::<Vec<_>>(),
+        [100, 20, 50]
+    );
+
+    // with K = 3 the column of B and C is clipped as well, starts are not moved
+    // by a clipped tail on either strand
+    let mut rec = block(maf);
+    assert!(trim_edges(&mut rec, 3));
+    assert_eq!(
+        slines(&rec),
+        expect([
+            ("A.chr1", 10, 5, "+", "ATCGT"),
+            ("B.chr1", 3, 5, "+", "ATCGT"),
+            ("C.chr2", 5, 5, "-", "ATCGT"),
+        ])
+    );
+
+    // nothing to trim if one species is enough
+    let mut rec = block(maf);
+    assert!(trim_edges(&mut rec, 1));
+    assert_eq!(slines(&rec), slines(&block(maf)));
+
+    // no column has 4 species, the block is dropped
+    let mut rec = block(maf);
+    assert!(!trim_edges(&mut rec, 4));
+}
+
+#[test]
+fn contained_block_is_unchanged_and_straddling_block_is_sliced() {
+    // blocks in output format of MAFWriter, the first one is in [10, 40) and starts
+    // with columns gapped in ref, which a slice to its own range would drop
+    let contained = "\
+a score=1
+s\tref.chr1\t20\t6\t+\t100\t--ACGTAC
+s\tqry.chr1\t0\t8\t+\t50\tTTACGTAC
+
+";
+    let maf = format!(
+        "##maf version=1\n{}{}",
+        contained,
+        "\
+a score=2
+s\tref.chr1\t35\t10\t+\t100\tACGTACGTAC
+s\tqry.chr2\t40\t10\t-\t60\tACGAACGTAC
+
+a score=3
+s\tref.chr1\t50\t5\t+\t100\tACGTA
+s\tqry.chr1\t20\t5\t+\t50\tACGTA
+
+a score=4
+s\toth.chr1\t20\t5\t+\t100\tACGTA
+s\tqry.chr1\t30\t5\t+\t50\tACGTA
+
+"
+    );
+    let region = GenomeRegion::try_from("ref.chr1:10-40".to_string()).unwrap();
+    let mut out = Vec::new();
+    maf_slice(
+        MAFReader::new(maf.as_bytes()).unwrap(),
+        &region,
+        "ref.chr1",
+        &mut out,
+    )
+    .unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains(&format!("\n{}", contained)), "{}", out);
+
+    let blocks = MAFReader::new(out.as_bytes())
+        .unwrap()
+        .records()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(blocks.len(), 2);
+    assert_eq!(blocks[0].slines[0].seq, "--ACGTAC");
+    // [35, 40) of ref, qry on '-' keeps its start as the tail is clipped
+    assert_eq!(
+        slines(&blocks[1]),
+        [
+            (
+                "ref.chr1".to_string(),
+                35,
+                5,
+                "+".to_string(),
+                "ACGTA".to_string()
+            ),
+            (
+                "qry.chr2".to_string(),
+                40,
+                5,
+                "-".to_string(),
+                "ACGAA".to_string()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn gap_only_columns_are_trimmed_at_both_ends() {
+    // first 2 and last 3 columns are gaps in every s-line, the inner one is kept
+    let maf = "\
+a score=1
+s ref.chr1 10 6 + 100 --ACG-TAC---
+s qry.chr1 20 5 - 50 --AC--TAC---
+s oth.chr1 0 6 + 80 --A-GTTAC---
+";
+    let mut rec = block(maf);
+    rec.trim_gap_columns();
+    assert_eq!(
+        slines(&rec),
+        expect([
+            ("ref.chr1", 10, 6, "+", "ACG-TAC"),
+            ("qry.chr1", 20, 5, "-", "AC--TAC"),
+            ("oth.chr1", 0, 6, "+", "A-GTTAC"),
+        ])
+    );
+    assert!(rec.validate().is_ok());
+
+    // nothing to trim
+    let before = slines(&rec);
+    rec.trim_gap_columns();
+    assert_eq!(slines(&rec), before);
+}
+
+#[test]
+fn crlf_and_bom_input_parses_as_lf() {
+    let maf = "\
+##maf version=1
+# made on Windows
+a score=10
+s ref.chr1 0 10 + 100 ACGTACGTAC
+s qry.chr1 5 10 + 50 ACGAACGTAC
+
+a score=20
+ s ref.chr1 20 8 + 100 ACG--TACGT
+s qry.chr1 30 9 - 50 ACGTTT-ACG
+
+";
+    let read = |maf: &str| {
+        let mut mafreader = MAFReader::new(maf.as_bytes()).unwrap();
+        let blocks = mafreader
+            .records()
+            .map(|rec| {
+                let rec = rec.unwrap();
+                (rec.score, slines(&rec))
+            })
+            .collect::<Vec<_>>();
+        (mafreader.header.clone(), blocks)
+    };
+    let expected = read(maf);
+    assert_eq!(expected.0, ["##maf version=1", "# made on Windows"]);
+    // the s-line with leading space is read
+    assert_eq!(expected.1.len(), 2);
+    assert_eq!(expected.1[1].1[0].4, "ACG--TACGT");
+
+    let crlf = maf.replace('\n', "\r\n");
+    let bom = format!("\u{feff}{}", maf);
+    let both = format!("\u{feff}{}", crlf);
+    for input in [&crlf, &bom, &both] {
+        assert_eq!(read(input), expected, "{:?}", input);
+        let mut reader = input.as_bytes();
+        assert_eq!(detect_format(&mut reader).unwrap(), FileFormat::Maf);
+    }
+}
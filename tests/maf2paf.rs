@@ -0,0 +1,421 @@
+mod common;
+
+use common::{random_seq, write_fasta, TempFile};
+use wgalib::converter::{maf2paf, paf2maf, project_species};
+use wgalib::errors::WGAError;
+use wgalib::parser::cigar::cigar_to_gapped_seqs;
+use wgalib::parser::common::{AlignRecord, CigarStyle, Strand};
+use wgalib::parser::maf::{MAFReader, MAFRecord};
+use wgalib::parser::paf::{parse_records, PAFReader, PafRecord};
+
+// blocks with mismatches, indels, a '-' strand query, a column gapped in both
+// s-lines of a pair and a block of three species
+const MAF: &str = "\
+##maf version=1
+a score=10
+s ref.chr1 0 10 + 100 ACGTACGTAC
+s qry.chr1 5 10 + 50 ACGAACGTAC
+
+a score=20
+s ref.chr1 20 8 + 100 ACG--TACGT
+s qry.chr1 30 9 - 50 ACGTTT-ACG
+
+a score=30
+s ref.chr1 40 7 + 100 AC-GTAC-G
+s qry.chr2 0 7 - 20 AC--TACTG
+s oth.chr1 2 8 + 60 ACTGTA-CG
+
+";
+
+// PAF of MAF by `maf2paf`, with the roundtrip check on
+fn paf_text(maf: &str, style: CigarStyle) -> String {
+    let mut mafreader = MAFReader::new(maf.as_bytes()).unwrap();
+    let mut out = Vec::new();
+    maf2paf(
+        &mut mafreader,
+        &mut out,
+        false,
+        None,
+        None,
+        style,
+        true,
+        true,
+        false,
+        None,
+    )
+    .unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+fn convert(maf: &str, style: CigarStyle) -> Vec<PafRecord> {
+    parse_records(&paf_text(maf, style))
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap()
+}
+
+// pairwise blocks of reference vs each other s-line, in the order of PAF records
+fn pairwise(maf: &str) -> Vec<MAFRecord> {
+    let mut mafreader = MAFReader::new(maf.as_bytes()).unwrap();
+    mafreader
+        .records()
+        .map(|rec| rec.unwrap())
+        .flat_map(|rec| rec.pairwise().collect::<Vec<_>>())
+        .collect()
+}
+
+fn ungapped(seq: &str) -> Vec<u8> {
+    seq.bytes().filter(|b| *b != b'-').collect()
+}
+
+#[test]
+fn cigar_rebuilds_maf_sequences() {
+    for style in [CigarStyle::Extended, CigarStyle::Match] {
+        let pafrecs = convert(MAF, style);
+        let blocks = pairwise(MAF);
+        assert_eq!(pafrecs.len(), blocks.len());
+        for (pafrec, block) in pafrecs.iter().zip(&blocks) {
+            let cigar = pafrec.get_cigar_str().unwrap();
+            let (target, query) = cigar_to_gapped_seqs(
+                &cigar,
+                &ungapped(block.target_seq()),
+                &ungapped(block.query_seq()),
+            )
+            .unwrap();
+            assert_eq!(target.as_bytes(), block.target_seq().as_bytes());
+            assert_eq!(query.as_bytes(), block.query_seq().as_bytes());
+        }
+    }
+}
+
+#[test]
+fn cigar_rebuilds_minus_strand_query() {
+    let pafrecs = convert(MAF, CigarStyle::Extended);
+    let blocks = pairwise(MAF);
+    let (pafrec, block) = pafrecs
+        .iter()
+        .zip(&blocks)
+        .find(|(pafrec, _)| pafrec.query_name == "qry.chr1" && pafrec.target_start == 20)
+        .unwrap();
+    assert_eq!(pafrec.strand.to_string(), "-");
+    let cigar = pafrec.get_cigar_str().unwrap();
+    let (target, query) = cigar_to_gapped_seqs(&cigar, b"ACGTACGT", b"ACGTTTACG").unwrap();
+    assert_eq!(target, "ACG--TACGT");
+    assert_eq!(query, "ACGTTT-ACG");
+    assert_eq!(query, block.query_seq());
+}
+
+#[test]
+fn cigar_of_other_sequences_is_rejected() {
+    // one base short of the query consumed by CIGAR
+    assert!(cigar_to_gapped_seqs("cg:Z:3=2I", b"ACG", b"ACGT").is_err());
+    let (target, query) = cigar_to_gapped_seqs("2=1I1D1X", b"ACGT", b"ATAG").unwrap();
+    assert_eq!((target.as_str(), query.as_str()), ("AC-GT", "ATA-G"));
+}
+
+#[test]
+fn strand_flip_and_complement() {
+    assert_eq!(Strand::Positive.flip(), Strand::Negative);
+    assert_eq!(Strand::Negative.flip().flip(), Strand::Negative);
+    // case and IUPAC codes are kept, gaps are only reversed, U is complemented as T
+    let seq = b"ACGTU-acgtn-RYKMBVDHSWN";
+    let rc = Strand::complement_seq(seq);
+    assert_eq!(rc, b"NWSDHBVKMRY-nacgt-AACGT");
+    assert_eq!(Strand::complement_seq(&rc), b"ACGTT-acgtn-RYKMBVDHSWN");
+}
+
+#[test]
+fn minus_strand_query_roundtrip() {
+    let (t_seq, q_seq) = (random_seq(100, 1), random_seq(50, 2));
+    let t_fa = TempFile::new("roundtrip.target.fa");
+    let q_fa = TempFile::new("roundtrip.query.fa");
+    write_fasta(&t_fa, "ref.chr1", &t_seq);
+    write_fasta(&q_fa, "qry.chr1", &q_seq);
+    // `.fai` written beside FASTA when it's loaded
+    let _fai = (
+        TempFile::new("roundtrip.target.fa.fai"),
+        TempFile::new("roundtrip.query.fa.fai"),
+    );
+
+    // query [20, 32) on '+' is aligned as its reverse complement, MAF start is 50 - 32
+    let target = std::str::from_utf8(&t_seq[10..22]).unwrap();
+    let rc = Strand::complement_seq(&q_seq[20..32]);
+    let query = std::str::from_utf8(&rc).unwrap();
+    let t_line = format!("{}-{}", &target[..5], &target[5..]);
+    let q_line = format!("{}-{}", &query[..8], &query[8..]);
+    let maf = format!(
+        "##maf version=1\na score=0\ns ref.chr1 10 12 + 100 {}\ns qry.chr1 18 12 - 50 {}\n\n",
+        t_line, q_line
+    );
+
+    let paf = paf_text(&maf, CigarStyle::Extended);
+    let pafrecs = parse_records(&paf).collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(pafrecs.len(), 1);
+    assert_eq!(pafrecs[0].strand, Strand::Negative);
+
+    // back to MAF by bases of FASTA
+    let mut pafreader = PAFReader::new(paf.as_bytes());
+    let mut out = Vec::new();
+    paf2maf(
+        &mut pafreader,
+        &mut out,
+        &t_fa.0.to_string_lossy(),
+        &q_fa.0.to_string_lossy(),
+        true,
+        true,
+        false,
+    )
+    .unwrap();
+    let mut mafreader = MAFReader::new(out.as_slice()).unwrap();
+    let blocks = mafreader.records().collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(blocks.len(), 1);
+    let (t_sline, q_sline) = (&blocks[0].slines[0], &blocks[0].slines[1]);
+    assert_eq!((t_sline.start, t_sline.align_size), (10, 12));
+    assert_eq!(t_sline.seq, t_line);
+    assert_eq!(q_sline.strand, Strand::Negative);
+    assert_eq!(
+        (q_sline.start, q_sline.align_size, q_sline.size),
+        (18, 12, 50)
+    );
+    assert_eq!(q_sline.seq, q_line);
+    assert_eq!(
+        ungapped(&q_sline.seq),
+        Strand::complement_seq(&q_seq[20..32])
+    );
+}
+
+#[test]
+fn minus_strand_query_coordinates() {
+    // size=50, start=20, align_size=8 on '-' => [50-20-8, 50-20) on '+'
+    let block: MAFRecord =
+        "a score=1\ns ref.chr1 10 8 + 100 ACGTACGT\ns qry.chr1 20 8 - 50 ACGTACGT\n"
+            .parse()
+            .unwrap();
+    assert_eq!((block.query_start(), block.query_end()), (22, 30));
+    let pafrec = block.convert2paf().unwrap();
+    assert_eq!(pafrec.strand.to_string(), "-");
+    assert_eq!((pafrec.query_start, pafrec.query_end), (22, 30));
+    assert_eq!((pafrec.target_start, pafrec.target_end), (10, 18));
+
+    // the whole query, and a block at the end of the reverse complement
+    for (start, size, expect) in [(0, 8, (0, 8)), (42, 50, (0, 8)), (0, 50, (42, 50))] {
+        let block: MAFRecord = format!(
+            "a score=1\ns ref.chr1 0 8 + 100 ACGTACGT\ns qry.chr1 {} 8 - {} ACGTACGT\n",
+            start, size
+        )
+        .parse()
+        .unwrap();
+        let pafrec = block.convert2paf().unwrap();
+        assert_eq!(pafrec.strand, Strand::Negative);
+        assert!(pafrec.query_start < pafrec.query_end);
+        assert_eq!((pafrec.query_start, pafrec.query_end), expect);
+        assert!(pafrec.query_end <= pafrec.query_length);
+    }
+}
+
+#[test]
+fn minus_strand_blocks_in_maf2paf() {
+    let pafrecs = convert(MAF, CigarStyle::Extended);
+    let minus = pafrecs
+        .iter()
+        .filter(|rec| rec.strand == Strand::Negative)
+        .map(|rec| (rec.query_name.as_str(), rec.query_start, rec.query_end))
+        .collect::<Vec<_>>();
+    // qry.chr1 30 9 - 50 and qry.chr2 0 7 - 20
+    assert_eq!(minus, [("qry.chr1", 11, 20), ("qry.chr2", 13, 20)]);
+}
+
+#[test]
+fn project_one_species() {
+    let all = convert(MAF, CigarStyle::Extended);
+    // a block of another species but no qry
+    let maf = format!(
+        "{}a score=40\ns ref.chr2 0 6 + 80 ACG-TAC\ns oth.chr9 3 6 + 60 ACGT-AC\ns far.chr1 0 6 + 90 ACG-TAC\n\n",
+        MAF
+    );
+    for (species, names) in [
+        ("qry", vec!["qry.chr1", "qry.chr1", "qry.chr2"]),
+        ("qry.chr2", vec!["qry.chr2"]),
+        ("oth", vec!["oth.chr1", "oth.chr9"]),
+        ("far", vec!["far.chr1"]),
+        ("ref", vec![]),
+        ("missing", vec![]),
+    ] {
+        let mut mafreader = MAFReader::new(maf.as_bytes()).unwrap();
+        let projected = project_species(&mut mafreader, species)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let got = projected
+            .iter()
+            .map(|rec| rec.query_name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(got, names, "{}", species);
+        assert!(projected
+            .iter()
+            .all(|rec| rec.target_name.starts_with("ref.")));
+
+        // same records as maf2paf of all pairs
+        for rec in projected.iter().filter(|rec| rec.target_name == "ref.chr1") {
+            assert!(all.contains(rec), "{:?}", rec);
+        }
+
+        // `maf2paf --species`
+        let mut mafreader = MAFReader::new(maf.as_bytes()).unwrap();
+        let mut out = Vec::new();
+        maf2paf(
+            &mut mafreader,
+            &mut out,
+            false,
+            Some(species),
+            None,
+            CigarStyle::Extended,
+            false,
+            false,
+            false,
+            None,
+        )
+        .unwrap();
+        let from_cmd = parse_records(std::str::from_utf8(&out).unwrap())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(from_cmd, projected);
+    }
+}
+
+#[test]
+fn length_mismatch_with_fasta_is_flagged() {
+    let (t_seq, q_seq) = (random_seq(100, 3), random_seq(50, 4));
+    let t_fa = TempFile::new("check.target.fa");
+    let q_fa = TempFile::new("check.query.fa");
+    write_fasta(&t_fa, "ref.chr1", &t_seq);
+    write_fasta(&q_fa, "qry.chr1", &q_seq);
+    let _fai = (
+        TempFile::new("check.target.fa.fai"),
+        TempFile::new("check.query.fa.fai"),
+    );
+    let run = |paf: &str, coordinate_check: bool| {
+        let mut pafreader = PAFReader::new(paf.as_bytes());
+        let mut out = Vec::new();
+        paf2maf(
+            &mut pafreader,
+            &mut out,
+            &t_fa.0.to_string_lossy(),
+            &q_fa.0.to_string_lossy(),
+            coordinate_check,
+            true,
+            false,
+        )
+    };
+
+    let paf = "qry.chr1\t50\t0\t10\t+\tref.chr1\t100\t0\t10\t10\t10\t60\tcg:Z:10M\n";
+    assert!(run(paf, true).is_ok());
+
+    // target declared as 120 bases, but 100 in FASTA
+    let paf = "qry.chr1\t50\t0\t10\t+\tref.chr1\t120\t0\t10\t10\t10\t60\tcg:Z:10M\n";
+    let err = run(paf, true).unwrap_err();
+    assert!(
+        matches!(&err, WGAError::SeqLengthNotMatch(name, 120, 100) if name == "ref.chr1"),
+        "{:?}",
+        err
+    );
+    let msg = err.to_string();
+    assert!(msg.contains("ref.chr1") && msg.contains("120") && msg.contains("100"));
+    // not checked without the flag
+    assert!(run(paf, false).is_ok());
+
+    // query declared shorter than FASTA
+    let paf = "qry.chr1\t40\t0\t10\t+\tref.chr1\t100\t0\t10\t10\t10\t60\tcg:Z:10M\n";
+    assert!(matches!(
+        run(paf, true),
+        Err(WGAError::SeqLengthNotMatch(name, 40, 50)) if name == "qry.chr1"
+    ));
+
+    // end is beyond the sequence
+    let paf = "qry.chr1\t50\t45\t55\t+\tref.chr1\t100\t0\t10\t10\t10\t60\tcg:Z:10M\n";
+    assert!(matches!(
+        run(paf, true),
+        Err(WGAError::FetchOutOfRange(name, _, 55, 50)) if name == "qry.chr1"
+    ));
+}
+
+#[test]
+fn nm_and_block_length_are_same_in_both_styles() {
+    let tag = |rec: &PafRecord, prefix: &str| {
+        rec.tags
+            .iter()
+            .find(|tag| tag.starts_with(prefix))
+            .cloned()
+            .unwrap()
+    };
+    let extended = convert(MAF, CigarStyle::Extended);
+    let matched = convert(MAF, CigarStyle::Match);
+    assert_eq!(extended.len(), matched.len());
+    for (ext, m) in extended.iter().zip(&matched) {
+        assert_eq!(tag(ext, "NM:i:"), tag(m, "NM:i:"));
+        assert_eq!((ext.matches, ext.block_length), (m.matches, m.block_length));
+
+        // same record except the CIGAR, and the same columns of it
+        let (ext_cigar, m_cigar) = (tag(ext, "cg:Z:"), tag(m, "cg:Z:"));
+        assert!(!m_cigar.contains(['=', 'X']), "{}", m_cigar);
+        let without_cigar = |rec: &PafRecord| {
+            let mut rec = rec.clone();
+            rec.tags.retain(|tag| !tag.starts_with("cg:Z:"));
+            rec
+        };
+        assert_eq!(without_cigar(ext), without_cigar(m));
+        let columns = |cigar: &str| {
+            cigar
+                .trim_start_matches("cg:Z:")
+                .split(|c: char| !c.is_ascii_digit())
+                .filter(|len| !len.is_empty())
+                .map(|len| len.parse::<u64>().unwrap())
+                .sum::<u64>()
+        };
+        assert_eq!(columns(&ext_cigar), ext.block_length);
+        assert_eq!(columns(&m_cigar), m.block_length);
+    }
+    // mismatches, insertions and deletions of the second block
+    assert_eq!(tag(&extended[1], "NM:i:"), "NM:i:6");
+    assert_eq!(tag(&matched[1], "cg:Z:"), "cg:Z:3M2I1M1D3M");
+}
+
+#[test]
+fn softmask_is_kept_across_strand_flip() {
+    let t_seq = random_seq(40, 9);
+    let t_fa = TempFile::new("softmask.target.fa");
+    let q_fa = TempFile::new("softmask.query.fa");
+    write_fasta(&t_fa, "ref.chr1", &t_seq);
+    // [10, 18) of query is soft-masked
+    write_fasta(&q_fa, "qry.chr1", b"ACGTACGTACacgttgcaGGCCAATT");
+    let _fai = (
+        TempFile::new("softmask.target.fa.fai"),
+        TempFile::new("softmask.query.fa.fai"),
+    );
+
+    // query [6, 14) crosses the start of the mask, on both strands
+    let paf = "\
+qry.chr1\t26\t6\t14\t+\tref.chr1\t40\t0\t8\t8\t8\t60\tcg:Z:8M
+qry.chr1\t26\t6\t14\t-\tref.chr1\t40\t20\t28\t8\t8\t60\tcg:Z:8M
+";
+    let query_seqs = |uppercase: bool| {
+        let mut pafreader = PAFReader::new(paf.as_bytes());
+        let mut out = Vec::new();
+        paf2maf(
+            &mut pafreader,
+            &mut out,
+            &t_fa.0.to_string_lossy(),
+            &q_fa.0.to_string_lossy(),
+            true,
+            true,
+            uppercase,
+        )
+        .unwrap();
+        let mut mafreader = MAFReader::new(out.as_slice()).unwrap();
+        mafreader
+            .records()
+            .map(|rec| rec.unwrap().slines[1].seq.clone())
+            .collect::<Vec<_>>()
+    };
+    // complement of `a` is `t`, the masked bases are at the head of reverse complement
+    assert_eq!(query_seqs(false), ["GTACacgt", "acgtGTAC"]);
+    assert_eq!(query_seqs(true), ["GTACACGT", "ACGTGTAC"]);
+}
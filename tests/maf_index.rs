@@ -0,0 +1,203 @@
+mod common;
+
+use common::TempFile;
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::path::Path;
+use wgalib::errors::{ParseMafErrKind, WGAError};
+use wgalib::parser::common::Strand;
+use wgalib::parser::maf::{next_record, MAFReader};
+use wgalib::tools::index::{
+    build_index, build_index_parallel, load_index, MafIndexFile, OffsetKind,
+};
+use wgalib::tools::mafextra::maf_extract_idx;
+
+// headerless MAF with CRLF line endings, comment and track lines between blocks,
+// return the content and offsets of all a-lines
+fn make_maf(blocks: usize) -> (String, BTreeSet<u64>) {
+    let mut maf = String::new();
+    let mut offsets = BTreeSet::new();
+    for i in 0..blocks {
+        if i % 3 == 1 {
+            maf += &format!("# comment of block {}\r\n", i);
+        }
+        if i % 5 == 2 {
+            maf += &format!("track name=block{}\r\n", i);
+        }
+        offsets.insert(maf.len() as u64);
+        maf += &format!("a score={}\r\n", i);
+        maf += &format!("s ref.chr1 {} 8 + 100000000 ACGT-ACGT\r\n", i * 10);
+        maf += &format!("s qry.chr{} {} 8 - 100000000 ACGTTACG-\r\n", i % 4, i * 10);
+        maf += "\r\n";
+    }
+    (maf, offsets)
+}
+
+// every offset of the index is at the a-line of a block containing that interval
+fn check_offsets(path: &Path, index: &MafIndexFile, expected: &BTreeSet<u64>) {
+    let mut file = BufReader::new(File::open(path).unwrap());
+    let mut offsets = BTreeSet::new();
+    for (name, item) in &index.index {
+        for ivl in &item.ivls {
+            file.seek(SeekFrom::Start(ivl.offset)).unwrap();
+            let mut line = String::new();
+            file.read_line(&mut line).unwrap();
+            assert!(
+                line.starts_with("a score="),
+                "offset {}: {:?}",
+                ivl.offset,
+                line
+            );
+
+            file.seek(SeekFrom::Start(ivl.offset)).unwrap();
+            let block = next_record(&mut file).unwrap().unwrap();
+            let sline = &block.slines[item.ord];
+            assert_eq!(&sline.name, name);
+            assert_eq!(sline.start, ivl.start);
+            offsets.insert(ivl.offset);
+        }
+    }
+    assert_eq!(&offsets, expected);
+}
+
+#[test]
+fn index_offsets_are_a_lines() {
+    let (maf, expected) = make_maf(20);
+    let maf_path = TempFile::new("small.maf");
+    let idx_path = TempFile::new("small.maf.index");
+    std::fs::write(&maf_path.0, maf).unwrap();
+
+    let mut mafreader = MAFReader::from_path_raw(&maf_path.0).unwrap();
+    let idx_wtr = Box::new(File::create(&idx_path.0).unwrap());
+    build_index(&mut mafreader, idx_wtr, true, false).unwrap();
+    check_offsets(&maf_path.0, &load_index(&idx_path.0).unwrap(), &expected);
+}
+
+#[test]
+fn parallel_index_offsets_are_a_lines() {
+    // several times of the 1M minimal chunk, so the file is split into more than one chunk
+    let (maf, expected) = make_maf(60000);
+    assert!(maf.len() > 3 << 20);
+    let maf_path = TempFile::new("large.maf");
+    let idx_path = TempFile::new("large.maf.index");
+    let serial_path = TempFile::new("large.serial.index");
+    std::fs::write(&maf_path.0, maf).unwrap();
+
+    let idx_wtr = Box::new(File::create(&idx_path.0).unwrap());
+    build_index_parallel(&maf_path.0, idx_wtr, true, true).unwrap();
+    let parallel = load_index(&idx_path.0).unwrap();
+    check_offsets(&maf_path.0, &parallel, &expected);
+
+    // same intervals and offsets as the single thread index
+    let mut mafreader = MAFReader::from_path_raw(&maf_path.0).unwrap();
+    let idx_wtr = Box::new(File::create(&serial_path.0).unwrap());
+    build_index(&mut mafreader, idx_wtr, true, true).unwrap();
+    let serial = load_index(&serial_path.0).unwrap();
+    for (name, item) in &serial.index {
+        let ivls = |item: &wgalib::tools::index::MafIndexItem| {
+            item.ivls
+                .iter()
+                .map(|ivl| (ivl.start, ivl.end, ivl.offset))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ivls(item), ivls(&parallel.index[name]), "{}", name);
+    }
+    assert_eq!(serial.index.len(), parallel.index.len());
+}
+
+#[test]
+fn align_size_mismatch_is_an_error() {
+    // qry.chr1 has 7 bases but align size 8
+    let maf = "##maf version=1\na score=0\ns ref.chr1 0 8 + 100 ACGTACGT\ns qry.chr1 0 8 + 100 ACG-ACGT\n\n";
+    let block = MAFReader::new(maf.as_bytes())
+        .unwrap()
+        .records()
+        .next()
+        .unwrap()
+        .unwrap();
+    match block.validate() {
+        Err(WGAError::ParseMaf(ParseMafErrKind::AlignSizeNotMatch(name, size, count))) => {
+            assert_eq!((name.as_str(), size, count), ("qry.chr1", 8, 7))
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+
+    let maf_path = TempFile::new("mismatch.maf");
+    let idx_path = TempFile::new("mismatch.maf.index");
+    std::fs::write(&maf_path.0, maf).unwrap();
+    for (validate, ok) in [(true, false), (false, true)] {
+        let mut mafreader = MAFReader::from_path_raw(&maf_path.0).unwrap();
+        let idx_wtr = Box::new(File::create(&idx_path.0).unwrap());
+        let res = build_index(&mut mafreader, idx_wtr, validate, false);
+        assert_eq!(res.is_ok(), ok, "{:?}", res);
+        let idx_wtr = Box::new(File::create(&idx_path.0).unwrap());
+        let res = build_index_parallel(&maf_path.0, idx_wtr, validate, false);
+        assert_eq!(res.is_ok(), ok, "{:?}", res);
+    }
+}
+
+#[test]
+fn index_of_old_format_still_loads() {
+    let (maf, expected) = make_maf(20);
+    let maf_path = TempFile::new("legacy.maf");
+    let idx_path = TempFile::new("legacy.maf.index");
+    let legacy_path = TempFile::new("legacy.old.index");
+    std::fs::write(&maf_path.0, maf).unwrap();
+    let mut mafreader = MAFReader::from_path_raw(&maf_path.0).unwrap();
+    let idx_wtr = Box::new(File::create(&idx_path.0).unwrap());
+    build_index(&mut mafreader, idx_wtr, true, false).unwrap();
+
+    // strands are written as in MAF
+    let json = std::fs::read_to_string(&idx_path.0).unwrap();
+    assert!(json.contains("\"strand\":\"-\""), "{}", json);
+    assert!(!json.contains("Positive") && !json.contains("Negative"));
+
+    // an old index is the bare map with variant names of strand
+    let value = serde_json::from_str::<serde_json::Value>(&json).unwrap();
+    let legacy = serde_json::to_string(&value["index"])
+        .unwrap()
+        .replace("\"strand\":\"+\"", "\"strand\":\"Positive\"")
+        .replace("\"strand\":\"-\"", "\"strand\":\"Negative\"");
+    assert!(legacy.contains("\"strand\":\"Negative\""));
+    std::fs::write(&legacy_path.0, legacy).unwrap();
+
+    let current = load_index(&idx_path.0).unwrap();
+    let old = load_index(&legacy_path.0).unwrap();
+    assert_eq!(old.offset_kind, OffsetKind::Plain);
+    check_offsets(&maf_path.0, &old, &expected);
+    assert_eq!(old.index.len(), current.index.len());
+    for (name, item) in &current.index {
+        let ivls = |item: &wgalib::tools::index::MafIndexItem| {
+            item.ivls
+                .iter()
+                .map(|ivl| (ivl.start, ivl.end, ivl.strand, ivl.offset))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ivls(&old.index[name]), ivls(item), "{}", name);
+        assert_eq!(
+            (old.index[name].size, old.index[name].ord),
+            (item.size, item.ord)
+        );
+    }
+    assert!(old.index["qry.chr1"]
+        .ivls
+        .iter()
+        .all(|ivl| ivl.strand == Strand::Negative));
+
+    // blocks extracted by the old index are the same
+    let extract = |index: MafIndexFile| {
+        let mut mafreader = MAFReader::from_path_raw(&maf_path.0).unwrap();
+        let mut out = Vec::new();
+        let regions = Some(vec!["ref.chr1:30-80".to_string()]);
+        maf_extract_idx(&regions, &None, &mut mafreader, index.index, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    };
+    let extracted = extract(current);
+    assert!(
+        extracted.contains("\ns\tqry.chr3\t30\t8\t-\t"),
+        "{}",
+        extracted
+    );
+    assert_eq!(extract(old), extracted);
+}
@@ -0,0 +1,95 @@
+use wgalib::errors::WGAError;
+use wgalib::parser::common::{AlignRecord, RecStat};
+use wgalib::parser::paf::{parse_records, PAFReader, PafRecord};
+
+fn record(line: &str) -> PafRecord {
+    let mut pafreader = PAFReader::new(line.as_bytes());
+    pafreader.records().next().unwrap().unwrap()
+}
+
+fn stat(line: &str) -> RecStat {
+    record(line).get_stat().unwrap()
+}
+
+#[test]
+fn cs_only_record_has_same_stat_as_cigar() {
+    let fields = "qry.chr1\t20\t2\t11\t+\tref.chr1\t30\t5\t13\t6\t10\t60";
+    let cg = stat(&format!("{}\tcg:Z:4=1X2I1D2=", fields));
+    assert_eq!(
+        (cg.matched, cg.mismatched, cg.ins_size, cg.del_size),
+        (6, 1, 2, 1)
+    );
+
+    // short and long forms of matches
+    for cs in ["cs:Z::4*ag+cc-t:2", "cs:Z:=ACGT*ag+cc-t=AC"] {
+        let line = format!("{}\t{}", fields, cs);
+        assert_eq!(stat(&line), cg, "{}", cs);
+        assert_eq!(record(&line).get_cigar_string(), "4=1X2I1D2=");
+    }
+
+    // same on '-' strand of query
+    let minus = fields.replace("\t+\t", "\t-\t");
+    assert_eq!(
+        stat(&format!("{}\tcs:Z::4*ag+cc-t:2", minus)),
+        stat(&format!("{}\tcg:Z:4=1X2I1D2=", minus))
+    );
+
+    // `cg:Z:` is preferred when both are present
+    let both = record(&format!("{}\tcs:Z::9\tcg:Z:4=1X2I1D2=", fields));
+    assert_eq!(both.get_stat().unwrap(), cg);
+
+    // neither of tags
+    assert!(record(fields).get_stat().is_err());
+}
+
+#[test]
+fn truncated_and_garbage_lines_are_rejected() {
+    let good = "qry.chr1\t20\t2\t11\t+\tref.chr1\t30\t5\t13\t6\t10\t60\tcg:Z:4=1X2I1D2=";
+    let errors = |paf: &str| {
+        let mut pafreader = PAFReader::new(paf.as_bytes());
+        pafreader
+            .records()
+            .filter_map(|rec| rec.err())
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>()
+    };
+
+    // the mapq column is missing on line 2
+    let truncated = "qry.chr1\t20\t2\t11\t+\tref.chr1\t30\t5\t13\t6\t10";
+    let paf = format!("{}\n{}\n", good, truncated);
+    assert_eq!(
+        errors(&paf),
+        ["PAF line 2 has 11 columns, at least 12 are required"]
+    );
+    let line = parse_records(&paf).nth(1).unwrap();
+    assert!(matches!(line, Err(WGAError::PafTooFewColumns(2, 11))));
+    assert!(matches!(
+        truncated.parse::<PafRecord>(),
+        Err(WGAError::PafTooFewColumns(0, 11))
+    ));
+
+    // a column after the 12th which is not a tag
+    let paf = format!("{}\n{}\tgarbage\n", good, good);
+    assert_eq!(
+        errors(&paf),
+        ["PAF line 2 column 14 `tag` is invalid: `garbage`"]
+    );
+    assert!(matches!(
+        parse_records(&paf).nth(1).unwrap(),
+        Err(WGAError::PafInvalidColumn(2, 14, name, value)) if name == "tag" && value == "garbage"
+    ));
+
+    // mandatory columns of wrong types are named
+    for (column, name, bad) in [(3, "query_start", "2.5"), (5, "strand", "x")] {
+        let mut fields = good.split('\t').collect::<Vec<_>>();
+        fields[column - 1] = bad;
+        assert_eq!(
+            errors(&fields.join("\t")),
+            [format!(
+                "PAF line 1 column {} `{}` is invalid: `{}`",
+                column, name, bad
+            )]
+        );
+    }
+    assert!(good.parse::<PafRecord>().is_ok());
+}
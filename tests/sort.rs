@@ -0,0 +1,101 @@
+use wgalib::parser::maf::MAFReader;
+use wgalib::parser::paf::{parse_records, PafRecord};
+use wgalib::tools::sort::{maf_sort, PafSorter, SORT_BUFFER_SIZE};
+use wgalib::utils::parse_size;
+
+// records of shuffled targets and starts, some of them with the same target and start
+fn shuffled_paf(records: u64) -> String {
+    let mut state = 7_u64;
+    let mut paf = String::new();
+    for i in 0..records {
+        state = state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        let (chr, start) = ((state >> 60) % 12 + 1, (state >> 40) % 50 * 10);
+        paf += &format!(
+            "qry.{}\t1000\t0\t10\t+\tref.chr{}\t1000\t{}\t{}\t10\t10\t60\tcg:Z:10=\n",
+            i,
+            chr,
+            start,
+            start + 10
+        );
+    }
+    paf
+}
+
+fn sorted(recs: &[PafRecord], buffer_size: u64) -> Vec<PafRecord> {
+    let mut sorter = PafSorter::new(buffer_size);
+    for rec in recs {
+        sorter.push(rec.clone()).unwrap();
+    }
+    let mut out = Vec::new();
+    sorter
+        .write_sorted(|rec| {
+            out.push(rec);
+            Ok(())
+        })
+        .unwrap();
+    out
+}
+
+#[test]
+fn tiny_buffer_sorts_as_in_memory() {
+    let recs = parse_records(&shuffled_paf(500))
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    let in_memory = sorted(&recs, SORT_BUFFER_SIZE);
+    let mut expected = recs.clone();
+    expected.sort();
+    assert_eq!(in_memory, expected);
+
+    // a run of each record, and runs of a few records
+    for buffer_size in [1, 1000, 10000] {
+        assert_eq!(sorted(&recs, buffer_size), in_memory, "{}", buffer_size);
+    }
+
+    // natural order of target names
+    let targets = in_memory
+        .iter()
+        .map(|rec| rec.target_name.as_str())
+        .collect::<Vec<_>>();
+    let first = targets
+        .iter()
+        .position(|name| *name == "ref.chr10")
+        .unwrap();
+    assert!(targets[..first].contains(&"ref.chr9"));
+}
+
+#[test]
+fn tiny_buffer_sorts_maf_as_in_memory() {
+    let mut maf = String::from("##maf version=1\n");
+    for (i, start) in [50, 10, 30, 10, 0, 40, 20].iter().enumerate() {
+        maf += &format!(
+            "a score={}\ns ref.chr{} {} 4 + 100 ACGT\ns qry.chr1 {} 4 + 100 ACGT\n\n",
+            i,
+            i % 2 + 1,
+            start,
+            i * 10
+        );
+    }
+    let sort = |buffer_size: u64| {
+        let mut out = Vec::new();
+        let readers = vec![MAFReader::new(maf.as_bytes()).unwrap()];
+        maf_sort(readers, &mut out, buffer_size).unwrap();
+        String::from_utf8(out).unwrap()
+    };
+    let in_memory = sort(SORT_BUFFER_SIZE);
+    assert_eq!(in_memory.matches("\na score=").count(), 7);
+    assert_eq!(sort(1), in_memory);
+    assert_eq!(sort(300), in_memory);
+}
+
+#[test]
+fn buffer_size_is_human_readable() {
+    assert_eq!(parse_size("512M").unwrap(), 512 << 20);
+    assert_eq!(parse_size("2G").unwrap(), 2 << 30);
+    assert_eq!(parse_size("64kb").unwrap(), 64 << 10);
+    assert_eq!(parse_size("1000").unwrap(), 1000);
+    assert!(parse_size("2X").is_err());
+    assert!(parse_size("G").is_err());
+    assert!(parse_size("99999999999T").is_err());
+}
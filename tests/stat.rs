@@ -0,0 +1,86 @@
+use wgalib::parser::common::IdentityMode;
+use wgalib::parser::maf::MAFReader;
+use wgalib::parser::paf::PAFReader;
+use wgalib::tools::stat::{stat_by_target, stat_gc, GCStatistic, TargetStatistic};
+
+// rows of a TSV with header
+fn read_rows<T: serde::de::DeserializeOwned>(tsv: &[u8]) -> Vec<T> {
+    csv::ReaderBuilder::new()
+        .delimiter(b'\t')
+        .from_reader(tsv)
+        .deserialize()
+        .collect::<Result<Vec<T>, _>>()
+        .unwrap()
+}
+
+#[test]
+fn by_target_gives_a_row_per_target() {
+    // records of chr2 overlap in [40, 50), chr10 is the first in input
+    let paf = "\
+qry2\t50\t0\t28\t+\tchr10\t200\t10\t40\t28\t30\t60\tcg:Z:10=2D18=
+qry1\t100\t0\t50\t+\tchr2\t100\t0\t50\t45\t50\t60\tcg:Z:45=5X
+qry1\t100\t50\t70\t+\tchr2\t100\t40\t60\t20\t20\t60\tcg:Z:20=
+";
+    for fast in [false, true] {
+        let mut pafreader = PAFReader::new(paf.as_bytes());
+        let mut out = Vec::new();
+        stat_by_target(pafreader.records(), &mut out, IdentityMode::Target, fast).unwrap();
+        let rows = read_rows::<TargetStatistic>(&out);
+        assert_eq!(rows.len(), 2);
+
+        // natural order of target names
+        let (chr2, chr10) = (&rows[0], &rows[1]);
+        assert_eq!(chr2.target_name, "chr2");
+        assert_eq!((chr2.target_size, chr2.records), (100, 2));
+        assert_eq!((chr2.aligned_bases, chr2.covered_bases), (70, 60));
+        assert_eq!(chr2.covered_fraction, 0.6);
+
+        assert_eq!(chr10.target_name, "chr10");
+        assert_eq!((chr10.target_size, chr10.records), (200, 1));
+        assert_eq!((chr10.aligned_bases, chr10.covered_bases), (30, 30));
+        assert_eq!(chr10.covered_fraction, 0.15);
+
+        if fast {
+            assert_eq!((chr2.identity, chr10.identity), (None, None));
+        } else {
+            // matched of target aligned size, the deletion of chr10 counted
+            assert_eq!(chr2.identity, Some((65.0 / 70.0) as f32));
+            assert_eq!(chr10.identity, Some((28.0 / 30.0) as f32));
+        }
+    }
+}
+
+#[test]
+fn gc_fraction_of_species() {
+    let maf = "\
+##maf version=1
+a score=1
+s hg38.chr1 0 12 + 100 ACGTacgt--GCNn
+s mm10.chr2 0 14 + 100 AAAAttttSSWWRY
+
+a score=2
+s hg38.chr5 10 4 + 100 GGCC
+s mm10.chr2 20 2 + 100 --uU
+
+";
+    let mut out = Vec::new();
+    stat_gc(MAFReader::new(maf.as_bytes()).unwrap(), &mut out).unwrap();
+    let rows = read_rows::<GCStatistic>(&out);
+    let got = rows
+        .iter()
+        .map(|row| {
+            (
+                row.species.as_str(),
+                row.aligned_bases,
+                row.gc,
+                row.at,
+                row.ambiguous,
+            )
+        })
+        .collect::<Vec<_>>();
+    // lowercase counted as uppercase, S is G/C, W and U are A/T, N/R/Y are ambiguous
+    assert_eq!(got, [("hg38", 16, 10, 4, 2), ("mm10", 16, 2, 12, 2)]);
+    // ambiguous bases are not in the fraction
+    assert_eq!(rows[0].gc_fraction, 10.0 / 14.0);
+    assert_eq!(rows[1].gc_fraction, 2.0 / 14.0);
+}
@@ -0,0 +1,50 @@
+use wgalib::parser::maf::MAFReader;
+use wgalib::tools::caller::maf2vcf;
+
+#[test]
+fn multi_allelic_sites_of_several_queries() {
+    // SNPs of A and B at the same site, a deletion shared by B and C, a second
+    // s-line of A which is ignored, and a block without B and C
+    let maf = "\
+##maf version=1
+a score=1
+s ref.chr1 10 8 + 100 ACGTACGT
+s A.chr1 0 8 + 50 ATGTACGT
+s B.chr1 0 6 + 50 AGG--CGT
+s C.chr3 5 6 - 50 ACG--CGT
+s A.chr9 0 8 + 50 ACGTACGA
+
+a score=2
+s ref.chr1 30 4 + 100 ACGT
+s A.chr2 0 4 + 50 ACCT
+
+";
+    let mut mafreader = MAFReader::new(maf.as_bytes()).unwrap();
+    let mut out = Vec::new();
+    maf2vcf(&mut mafreader, &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+
+    assert!(out.starts_with("##fileformat=VCFv4"), "{}", out);
+    assert!(
+        out.contains("\n##contig=<ID=ref.chr1,length=100>\n"),
+        "{}",
+        out
+    );
+    let (header, records): (Vec<_>, Vec<_>) = out
+        .lines()
+        .filter(|line| !line.starts_with("##"))
+        .partition(|line| line.starts_with('#'));
+    assert_eq!(
+        header,
+        ["#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\tA\tB\tC"]
+    );
+    // ALT are in order of samples, absent samples are `.`
+    assert_eq!(
+        records,
+        [
+            "ref.chr1\t12\t.\tC\tT,G\t.\t.\t.\tGT\t1\t2\t0",
+            "ref.chr1\t13\t.\tGTA\tG\t.\t.\t.\tGT\t0\t1\t1",
+            "ref.chr1\t33\t.\tG\tC\t.\t.\t.\tGT\t1\t.\t.",
+        ]
+    );
+}